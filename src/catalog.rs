@@ -0,0 +1,298 @@
+//! The catalog of a data directory, a file alongside the tables keeping the types declared for their columns
+//! and the saved queries, so that they survive restarts without declaring them again in every session.
+//! It is a JSON object like `{"types": {"books": {"isbn": "text"}}, "queries": {"by_author": "SELECT ..."}}`.
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::{collections::BTreeMap, error::Error, io::Write};
+
+use nom::{
+    Finish, Parser,
+    branch::alt,
+    bytes::complete::{is_not, tag, take_while_m_n},
+    character::complete::{char, multispace0, one_of},
+    combinator::{all_consuming, map, map_opt, value},
+    multi::{fold_many0, separated_list0},
+    sequence::{delimited, preceded, separated_pair},
+};
+
+use crate::{db::Database, select::write_json_str, session::SavedQueries, table::ColumnType};
+
+/// The name of the catalog file in a data directory, hidden so that it is not loaded as a table
+#[cfg(feature = "fs")]
+pub(crate) const CATALOG_FILE: &str = ".bogosql_catalog.json";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Catalog {
+    /// The types declared for the columns by the names of the tables and the columns
+    pub types: BTreeMap<String, BTreeMap<String, ColumnType>>,
+    /// The source text of the saved queries by their names
+    pub queries: BTreeMap<String, String>,
+}
+
+impl Catalog {
+    /// Parses the JSON of the catalog, which has only objects and strings.
+    pub fn parse(json: &str) -> Result<Self, Box<dyn Error>> {
+        let (_, root) = all_consuming(delimited(multispace0, object, multispace0))
+            .parse(json)
+            .finish()
+            .map_err(|e| {
+                let line = json[..json.len() - e.input.len()].lines().count().max(1);
+                format!("Invalid JSON in the catalog at line {line}")
+            })?;
+        let mut catalog = Self::default();
+        for (key, val) in root {
+            match (key.as_str(), val) {
+                ("types", Json::Object(tables)) => {
+                    for (table, cols) in tables {
+                        let Json::Object(cols) = cols else {
+                            return Err(format!("The types of {table} must be an object").into());
+                        };
+                        let types = catalog.types.entry(table.clone()).or_default();
+                        for (col, ty) in cols {
+                            let Json::String(ty) = ty else {
+                                return Err(
+                                    format!("The type of {table}.{col} must be a string").into()
+                                );
+                            };
+                            types.insert(col, ty.parse()?);
+                        }
+                    }
+                }
+                ("queries", Json::Object(queries)) => {
+                    for (name, sql) in queries {
+                        let Json::String(sql) = sql else {
+                            return Err(format!("The query {name} must be a string").into());
+                        };
+                        catalog.queries.insert(name, sql);
+                    }
+                }
+                _ => return Err(format!("Unknown entry {key} in the catalog").into()),
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Writes the catalog as JSON that [`Catalog::parse`] reads, with an entry on each line so that the file
+    /// can be edited by hand.
+    pub fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "{{")?;
+        write!(out, "  \"types\": {{")?;
+        for (i, (table, types)) in self.types.iter().enumerate() {
+            write!(out, "{}\n    ", if i == 0 { "" } else { "," })?;
+            write_json_str(out, table)?;
+            write!(out, ": {{")?;
+            for (j, (col, ty)) in types.iter().enumerate() {
+                write!(out, "{}\n      ", if j == 0 { "" } else { "," })?;
+                write_json_str(out, col)?;
+                write!(out, ": \"{ty}\"")?;
+            }
+            write!(out, "\n    }}")?;
+        }
+        let end = |empty: bool| if empty { "}" } else { "\n  }" };
+        writeln!(out, "{},", end(self.types.is_empty()))?;
+        write!(out, "  \"queries\": {{")?;
+        for (i, (name, sql)) in self.queries.iter().enumerate() {
+            write!(out, "{}\n    ", if i == 0 { "" } else { "," })?;
+            write_json_str(out, name)?;
+            write!(out, ": ")?;
+            write_json_str(out, sql)?;
+        }
+        writeln!(out, "{}", end(self.queries.is_empty()))?;
+        writeln!(out, "}}")
+    }
+
+    /// Declares the types of the columns of the tables in the database. A table or a column missing in the
+    /// database is an error, since the catalog is stale then.
+    pub fn declare_types(&self, db: &mut Database) -> Result<(), Box<dyn Error>> {
+        for (name, types) in &self.types {
+            let table = db
+                .get_mut(name)
+                .ok_or_else(|| format!("Table {name} in the catalog is not loaded"))?;
+            for (col, ty) in types {
+                table
+                    .find_col(col, false)
+                    .and_then(|i| table.declare_type(i, *ty))
+                    .ok_or_else(|| format!("Column {col} in the catalog is not in table {name}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the queries to be run by name, replacing the queries saved under the same names.
+    pub fn save_queries(&self, saved: &mut SavedQueries) -> Result<(), Box<dyn Error>> {
+        for (name, sql) in &self.queries {
+            saved
+                .save(name, sql)
+                .map_err(|e| format!("Query {name} in the catalog: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// Reads the catalog file in the directory, or returns `None` if there is none.
+    #[cfg(feature = "fs")]
+    pub fn load(dir: impl AsRef<Path>) -> Result<Option<Self>, Box<dyn Error>> {
+        let path = dir.as_ref().join(CATALOG_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(json) => {
+                Ok(Some(Self::parse(&json).map_err(|e| {
+                    format!("error reading catalog {path:?}: {e}")
+                })?))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("error reading catalog {path:?}: {e}").into()),
+        }
+    }
+
+    /// Writes the catalog file in the directory, replacing the file if any.
+    #[cfg(feature = "fs")]
+    pub fn store(&self, dir: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let path = dir.as_ref().join(CATALOG_FILE);
+        let mut buf = vec![];
+        self.write(&mut buf)?;
+        std::fs::write(&path, buf).map_err(|e| format!("error writing catalog {path:?}: {e}"))?;
+        Ok(())
+    }
+}
+
+/// The subset of JSON in the catalog
+#[derive(Debug, Clone)]
+enum Json {
+    String(String),
+    Object(Vec<(String, Json)>),
+}
+
+fn json_value(i: &str) -> nom::IResult<&str, Json> {
+    alt((map(string, Json::String), map(object, Json::Object))).parse(i)
+}
+
+fn object(i: &str) -> nom::IResult<&str, Vec<(String, Json)>> {
+    let entry = separated_pair(
+        delimited(multispace0, string, multispace0),
+        char(':'),
+        delimited(multispace0, json_value, multispace0),
+    );
+    delimited(
+        char('{'),
+        delimited(multispace0, separated_list0(char(','), entry), multispace0),
+        char('}'),
+    )
+    .parse(i)
+}
+
+/// A string with the escapes of JSON, except the surrogate pairs that the catalog never writes.
+fn string(i: &str) -> nom::IResult<&str, String> {
+    let escape = preceded(
+        char('\\'),
+        alt((
+            one_of("\"\\/"),
+            value('\n', char('n')),
+            value('\r', char('r')),
+            value('\t', char('t')),
+            value('\u{8}', char('b')),
+            value('\u{c}', char('f')),
+            map_opt(
+                preceded(
+                    char('u'),
+                    take_while_m_n(4, 4, |c: char| c.is_ascii_hexdigit()),
+                ),
+                |hex| char::from_u32(u32::from_str_radix(hex, 16).ok()?),
+            ),
+        )),
+    );
+    let fragment = alt((
+        map(is_not("\"\\"), str::to_string),
+        map(escape, String::from),
+    ));
+    delimited(
+        tag("\""),
+        fold_many0(fragment, String::new, |mut acc, s| {
+            acc.push_str(&s);
+            acc
+        }),
+        tag("\""),
+    )
+    .parse(i)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Session, StatementOutput, test_util::db_from_csv};
+
+    #[test]
+    fn test_catalog_round_trip() {
+        let json = r#"{
+  "types": {
+    "t": {
+      "zip": "text"
+    }
+  },
+  "queries": {
+    "by_zip": "SELECT name AS \"n\" FROM t WHERE zip = :zip AND name <> '\u00e9'"
+  }
+}
+"#;
+        let catalog = Catalog::parse(json).unwrap();
+        assert_eq!(catalog.types["t"]["zip"], ColumnType::Text);
+        assert_eq!(
+            catalog.queries["by_zip"],
+            "SELECT name AS \"n\" FROM t WHERE zip = :zip AND name <> 'é'"
+        );
+        let mut buf = vec![];
+        catalog.write(&mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            json.replace("\\u00e9", "é")
+        );
+        assert_eq!(
+            Catalog::parse(&json.replace("\"text\"", "\"txt\""))
+                .unwrap_err()
+                .to_string(),
+            "Unknown column type txt, expected integer, number, date, timestamp or text"
+        );
+        assert_eq!(
+            Catalog::parse("{\"types\": {}\n\"queries\": {}}")
+                .unwrap_err()
+                .to_string(),
+            "Invalid JSON in the catalog at line 1"
+        );
+        let mut buf = vec![];
+        Catalog::default().write(&mut buf).unwrap();
+        let json = String::from_utf8(buf).unwrap();
+        assert_eq!(json, "{\n  \"types\": {},\n  \"queries\": {}\n}\n");
+        assert_eq!(Catalog::parse(&json).unwrap(), Catalog::default());
+    }
+
+    #[test]
+    fn test_catalog_declare_types() {
+        let mut db = db_from_csv(&[("t", "zip,name\n10, a\n9, b\n")]);
+        let catalog = Catalog::parse(r#"{"types": {"t": {"zip": "text"}}}"#).unwrap();
+        catalog.declare_types(&mut db).unwrap();
+        // The declared type is kept when the cells change
+        let table = db.get_mut("t").unwrap();
+        table.set(0, 0, "11".to_string());
+        assert_eq!(table.column_type(0), ColumnType::Text);
+        assert_eq!(table.column_type(1), ColumnType::Text);
+
+        let mut session = Session::new(&db);
+        let catalog =
+            Catalog::parse(r#"{"queries": {"by_zip": "SELECT name FROM t ORDER BY zip"}}"#)
+                .unwrap();
+        catalog.save_queries(&mut session.saved).unwrap();
+        assert_eq!(
+            session.run_saved("by_zip", &Default::default()).unwrap(),
+            StatementOutput::Rows(vec![
+                vec!["name".to_string()],
+                vec!["a".to_string()],
+                vec!["b".to_string()]
+            ])
+        );
+
+        let catalog = Catalog::parse(r#"{"types": {"t": {"id": "integer"}}}"#).unwrap();
+        assert_eq!(
+            catalog.declare_types(&mut db).unwrap_err().to_string(),
+            "Column id in the catalog is not in table t"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! SELECT statements combined with set operators, like `SELECT a FROM t UNION ALL SELECT b FROM u`.
+//! Each SELECT is executed on its own and the results are concatenated, so ORDER BY and LIMIT apply to
+//! the SELECT they are written in, not to the whole result.
+
+use std::{collections::HashSet, error::Error, io::Write};
+
+use crate::{
+    db::Database,
+    limits::Limits,
+    options::ExecOptions,
+    select::{BufferOutput, LimitRows, QueryOutput, SelectStmt, TruncateCells, exec_select_with},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompoundStmt {
+    pub first: SelectStmt,
+    /// The following SELECT statements and the operators that combine them with the result so far,
+    /// evaluated from left to right.
+    pub rest: Vec<(SetOp, SelectStmt)>,
+}
+
+impl CompoundStmt {
+    pub(crate) fn selects_mut(&mut self) -> impl Iterator<Item = &mut SelectStmt> {
+        std::iter::once(&mut self.first).chain(self.rest.iter_mut().map(|(_, stmt)| stmt))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOp {
+    /// `UNION`, which removes duplicate rows
+    Union,
+    /// `UNION ALL`, which keeps all the rows
+    UnionAll,
+}
+
+pub fn exec_compound(
+    out: &mut impl QueryOutput,
+    db: &Database,
+    stmt: &CompoundStmt,
+    options: &ExecOptions,
+) -> Result<(), Box<dyn Error>> {
+    // The cells are truncated and the rows are counted after removing the duplicates, which may differ only in
+    // the parts truncated
+    let limits = Limits::new(options);
+    let max_cell_size = options.max_cell_size;
+    let options = &options.clone().with_max_cell_size(None).with_max_rows(None);
+    let mut buf = BufferOutput::default();
+    exec_select_with(&mut buf, db, &stmt.first, options, None)?;
+    let mut rows = buf.0;
+    let num_cols = rows.first().map_or(0, |header| header.len());
+
+    for (op, select) in &stmt.rest {
+        let mut buf = BufferOutput::default();
+        exec_select_with(&mut buf, db, select, options, None)?;
+        let header_len = buf.0.first().map_or(0, |header| header.len());
+        if header_len != num_cols {
+            return Err(format!(
+                "Each SELECT in a UNION must have the same number of columns: {num_cols} and {header_len}"
+            )
+            .into());
+        }
+        rows.extend(buf.0.into_iter().skip(1));
+        if *op == SetOp::Union {
+            // The header is in the first row, so it is never removed as a duplicate.
+            let mut seen = HashSet::new();
+            rows.retain(|row| seen.insert(row.clone()));
+        }
+    }
+
+    let mut out = LimitRows {
+        out,
+        limits: &limits,
+        header: true,
+    };
+    let mut rows = rows.iter();
+    if let Some(header) = rows.next() {
+        out.output(header)?;
+    }
+    let mut out = TruncateCells { out, max_cell_size };
+    for row in rows {
+        out.output(row)?;
+    }
+    Ok(())
+}
+
+pub fn format_compound(
+    out: &mut impl Write,
+    db: &Database,
+    stmt: &CompoundStmt,
+    options: &ExecOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut buf = BufferOutput::default();
+    exec_compound(&mut buf, db, stmt, options)?;
+    buf.format(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Statement, parser::statement, test_util::db_from_csv};
+
+    #[test]
+    fn test_union() {
+        let db = db_from_csv(&[("a", "id\n1\n2\n2\n"), ("b", "id,name\n2, x\n3, y\n")]);
+        let run = |sql| {
+            let Statement::Compound(stmt) = statement(sql).unwrap().1 else {
+                panic!("Not a compound statement");
+            };
+            let mut buf = BufferOutput::default();
+            exec_compound(&mut buf, &db, &stmt, &ExecOptions::default()).map(|_| buf.0)
+        };
+        assert_eq!(
+            run("SELECT id FROM a UNION ALL SELECT id FROM b").unwrap(),
+            vec![
+                vec!["id"],
+                vec!["1"],
+                vec!["2"],
+                vec!["2"],
+                vec!["2"],
+                vec!["3"]
+            ]
+        );
+        assert_eq!(
+            run("SELECT id FROM a UNION SELECT id FROM b").unwrap(),
+            vec![vec!["id"], vec!["1"], vec!["2"], vec!["3"]]
+        );
+        assert!(run("SELECT id FROM a UNION SELECT * FROM b").is_err());
+    }
+}
@@ -6,42 +6,85 @@
 //! so I wrote my own tiny parser for CSVs.
 
 use nom::{
-    Finish, IResult, Parser,
+    Parser,
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{multispace0, none_of},
-    combinator::recognize,
-    multi::{fold_many0, many0, many1},
-    sequence::{delimited, pair},
+    bytes::complete::{tag, take_while, take_while1},
+    character::complete::char,
+    combinator::value,
+    multi::{fold_many0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated},
 };
 
+type IResult<'a, O> = nom::IResult<&'a str, O>;
+
+/// The delimiters that [`detect_delimiter`] chooses from
+const DELIMITERS: [char; 3] = [',', '\t', ';'];
+
 pub fn parse_csv(src: &str) -> Result<Vec<Vec<String>>, String> {
-    csv(src)
-        .finish()
-        .map(|(_, res)| res)
-        .map_err(|e| e.to_string())
+    parse_csv_with(src, ',')
 }
 
-fn csv(i: &str) -> IResult<&str, Vec<Vec<String>>> {
-    let (r, first) = line(i)?;
-    let (r, res) = fold_many0(
-        pair(tag("\n"), line),
-        move || vec![first.clone()],
-        |mut acc, (_, row)| {
-            acc.push(row);
-            acc
-        },
-    )
-    .parse(r)?;
-    Ok((r, res))
+/// Same as [`parse_csv`], but the cells are separated by the delimiter like `'\t'` for TSV.
+/// The syntax is as RFC 4180: a quoted cell can have delimiters, line breaks and quotes written as `""`,
+/// and lines can end with CRLF. A blank line is an empty record.
+pub(crate) fn parse_csv_with(src: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let (rest, res) = csv(src, delimiter).map_err(|e| e.to_string())?;
+    if !rest.is_empty() {
+        let offset = src.len() - rest.len();
+        let line = src[..offset].matches('\n').count() + 1;
+        let found: String = rest.chars().take_while(|c| *c != '\n').take(20).collect();
+        return Err(format!(
+            "CSV has an unterminated quote or a quote in an unquoted cell at line {line}: {found}"
+        ));
+    }
+    Ok(res)
 }
 
-fn line(i: &str) -> IResult<&str, Vec<String>> {
-    let (r, first) = cell(i)?;
+/// Guesses the delimiter of the CSV from the header, which is the one of `,`, tab and `;` appearing the most
+/// outside quotes. Comma wins the ties, including when the header has a single column.
+pub(crate) fn detect_delimiter(src: &str) -> char {
+    let header = src.lines().next().unwrap_or("");
+    let mut counts = [0; DELIMITERS.len()];
+    let mut quoted = false;
+    for c in header.chars() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if let Some(i) = DELIMITERS.iter().position(|d| *d == c)
+            && !quoted
+        {
+            counts[i] += 1;
+        }
+    }
+    let mut best = 0;
+    for (i, count) in counts.iter().enumerate() {
+        if counts[best] < *count {
+            best = i;
+        }
+    }
+    DELIMITERS[best]
+}
+
+fn csv(i: &str, delimiter: char) -> IResult<'_, Vec<Vec<String>>> {
+    separated_list1(line_break, |i| line(i, delimiter)).parse(i)
+}
+
+fn line_break(i: &str) -> IResult<'_, &str> {
+    alt((tag("\r\n"), tag("\n"))).parse(i)
+}
+
+fn line(i: &str, delimiter: char) -> IResult<'_, Vec<String>> {
+    if i.is_empty() || line_break(i).is_ok() {
+        return Ok((i, vec![]));
+    }
+    // Spaces around the delimiter are skipped, unless it is the delimiter itself like a tab
+    let space = || take_while(move |c: char| (c == ' ' || c == '\t') && c != delimiter);
+    let (r, first) = cell(i, delimiter)?;
     let (r, res) = fold_many0(
-        pair(delimited(multispace0, tag(","), multispace0), cell),
+        preceded(delimited(space(), char(delimiter), space()), |i| {
+            cell(i, delimiter)
+        }),
         move || vec![first.clone()],
-        |mut acc, (_, token)| {
+        |mut acc, token| {
             acc.push(token);
             acc
         },
@@ -50,21 +93,50 @@ fn line(i: &str) -> IResult<&str, Vec<String>> {
     Ok((r, res))
 }
 
-fn cell(i: &str) -> IResult<&str, String> {
-    alt((quoted_cell, unquoted_cell)).parse(i)
+fn cell(i: &str, delimiter: char) -> IResult<'_, String> {
+    alt((quoted_cell, |i| unquoted_cell(i, delimiter))).parse(i)
 }
 
-fn unquoted_cell(i: &str) -> IResult<&str, String> {
-    let (r, val) = recognize(many1(none_of("\",\n"))).parse(i)?;
+/// An unquoted cell, which can be empty. Whitespace at either end is trimmed, unlike in a quoted cell.
+fn unquoted_cell(i: &str, delimiter: char) -> IResult<'_, String> {
+    let (r, val) =
+        take_while(|c: char| c != '"' && c != '\n' && c != '\r' && c != delimiter).parse(i)?;
 
-    Ok((r, val.to_string()))
+    Ok((r, val.trim().to_string()))
 }
 
-fn quoted_cell(i: &str) -> IResult<&str, String> {
-    let (r, _) = pair(multispace0, tag("\"")).parse(i)?;
-    let (r, val) = recognize(many0(none_of("\"\n"))).parse(r)?;
-    let (r, _) = tag("\"")(r)?;
-    Ok((r, val.to_string()))
+/// A quoted cell, where a quote is written as `""`
+fn quoted_cell(i: &str) -> IResult<'_, String> {
+    let space = || take_while(|c: char| c == ' ' || c == '\t');
+    delimited(
+        pair(space(), char('"')),
+        fold_many0(
+            alt((value("\"", tag("\"\"")), take_while1(|c: char| c != '"'))),
+            String::new,
+            |mut acc, s| {
+                acc.push_str(s);
+                acc
+            },
+        ),
+        terminated(char('"'), space()),
+    )
+    .parse(i)
+}
+
+/// Writes the cells as a line of CSV as RFC 4180, quoting the cells with commas, quotes, line breaks or
+/// whitespace at either end, so that [`parse_csv`] reads them back as they are.
+pub fn write_csv_row(out: &mut impl std::io::Write, row: &[String]) -> std::io::Result<()> {
+    for (i, cell) in row.iter().enumerate() {
+        if i != 0 {
+            write!(out, ",")?;
+        }
+        if cell.contains([',', '"', '\n', '\r']) || cell.trim() != cell {
+            write!(out, "\"{}\"", cell.replace('"', "\"\""))?;
+        } else {
+            write!(out, "{cell}")?;
+        }
+    }
+    writeln!(out)
 }
 
 #[cfg(test)]
@@ -91,4 +163,62 @@ mod test {
         let res = parse_csv(src).unwrap();
         assert_eq!(res, vec![vec!["1", "I, Robot", "2"]]);
     }
+
+    #[test]
+    fn test_delimiter() {
+        let src = "id\tname\n1\tI, Robot\n2\t\"\"";
+        assert_eq!(detect_delimiter(src), '\t');
+        let res = parse_csv_with(src, '\t').unwrap();
+        assert_eq!(
+            res,
+            vec![vec!["id", "name"], vec!["1", "I, Robot"], vec!["2", ""]]
+        );
+
+        let src = "id; name\n1; Robot, I";
+        assert_eq!(detect_delimiter(src), ';');
+        let res = parse_csv_with(src, ';').unwrap();
+        assert_eq!(res, vec![vec!["id", "name"], vec!["1", "Robot, I"]]);
+
+        assert_eq!(detect_delimiter("\"a;b\",c"), ',');
+        assert_eq!(detect_delimiter("id\n1"), ',');
+    }
+
+    #[test]
+    fn test_rfc4180() {
+        let src = "id,quote\r\n1,\"He said \"\"hi\"\"\"\r\n2,\"line 1\nline 2\"\r\n";
+        let res = parse_csv(src).unwrap();
+        assert_eq!(
+            res,
+            vec![
+                vec!["id", "quote"],
+                vec!["1", "He said \"hi\""],
+                vec!["2", "line 1\nline 2"],
+                vec![],
+            ]
+        );
+
+        let res = parse_csv("a,,c,\n\n,b").unwrap();
+        assert_eq!(res, vec![vec!["a", "", "c", ""], vec![], vec!["", "b"]]);
+
+        assert_eq!(
+            parse_csv("id,name\n1,\"x").unwrap_err(),
+            "CSV has an unterminated quote or a quote in an unquoted cell at line 2: \"x"
+        );
+    }
+
+    #[test]
+    fn test_write_csv_row() {
+        let row: Vec<_> = ["1", "I, Robot", "He said \"hi\"", "line 1\nline 2", ""]
+            .iter()
+            .map(|cell| cell.to_string())
+            .collect();
+        let mut buf = vec![];
+        write_csv_row(&mut buf, &row).unwrap();
+        let src = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            src,
+            "1,\"I, Robot\",\"He said \"\"hi\"\"\",\"line 1\nline 2\",\n"
+        );
+        assert_eq!(parse_csv(&src).unwrap()[0], row);
+    }
 }
@@ -0,0 +1,123 @@
+//! Common table expressions, the named subqueries of `WITH t AS (SELECT ...) SELECT * FROM t`.
+//! Each one is executed in order and its results are added as a table to a scoped copy of the database, where
+//! the following ones and the statement find it before the tables of the same name.
+
+use std::error::Error;
+
+use crate::{
+    db::Database,
+    options::ExecOptions,
+    select::{BufferOutput, SelectStmt, exec_select_impl},
+    table::{RowSchema, Table},
+};
+
+/// `name [(columns)] AS (SELECT ...)` in a `WITH` clause
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cte {
+    pub name: String,
+    /// The names of the columns, or empty to name them after the headers of the results
+    pub columns: Vec<String>,
+    pub stmt: SelectStmt,
+}
+
+impl std::fmt::Display for Cte {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.columns.is_empty() {
+            write!(f, "({})", self.columns.join(", "))?;
+        }
+        write!(f, " AS ({})", self.stmt)
+    }
+}
+
+/// A copy of the database with the results of the CTEs as tables, replacing the tables of the same names.
+pub(crate) fn materialize_ctes(
+    db: &Database,
+    ctes: &[Cte],
+    options: &ExecOptions,
+) -> Result<Database, Box<dyn Error>> {
+    // The rows of a CTE are not the output rows to trace back, truncate or count
+    let options = options
+        .clone()
+        .with_provenance(false)
+        .with_max_cell_size(None)
+        .with_max_rows(None);
+    let mut scoped = db.scoped();
+    for cte in ctes {
+        let mut buf = BufferOutput::default();
+        exec_select_impl(&mut buf, &scoped, &cte.stmt, &options, None)?;
+        let mut rows = buf.0.into_iter();
+        let header = rows.next().unwrap_or_default();
+        let names = if cte.columns.is_empty() {
+            header
+        } else if cte.columns.len() == header.len() {
+            cte.columns.clone()
+        } else {
+            return Err(format!(
+                "WITH {} has {} column names for {} columns",
+                cte.name,
+                cte.columns.len(),
+                header.len()
+            )
+            .into());
+        };
+        let schema = names.into_iter().map(RowSchema::new).collect();
+        let table = Table::new(cte.name.clone(), schema, rows.flatten().collect());
+        scoped.remove(&cte.name);
+        scoped.insert(cte.name.clone(), table);
+    }
+    Ok(scoped)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        Session, assert_query_results,
+        test_util::{db_from_tables, table_from_rows},
+    };
+
+    #[test]
+    fn test_with() {
+        let mut db = db_from_tables([
+            table_from_rows(
+                "books",
+                &["id", "author", "year"],
+                &[
+                    &["1", "1", "1990"],
+                    &["2", "1", "2005"],
+                    &["3", "2", "2010"],
+                ],
+            ),
+            table_from_rows("authors", &["id", "name"], &[&["1", "Ann"], &["2", "Bob"]]),
+        ]);
+        assert_query_results!(
+            db,
+            "WITH counts (author, n) AS (SELECT author, count(*) FROM books GROUP BY author), \
+                top AS (SELECT * FROM counts WHERE n > 1) \
+                SELECT name, n FROM top INNER JOIN authors ON author = authors.id",
+            [["name", "n"], ["Ann", "2"]]
+        );
+        // A CTE hides the table of the same name, also in the subqueries
+        assert_query_results!(
+            db,
+            "WITH books AS (SELECT * FROM books WHERE year > 2000) \
+                SELECT name FROM authors WHERE id IN (SELECT author FROM books) ORDER BY name",
+            [["name"], ["Ann"], ["Bob"]]
+        );
+        assert_query_results!(
+            db,
+            "WITH recent AS (SELECT id FROM books WHERE year > 2000) \
+                SELECT count(*) FROM recent",
+            [["count(*)"], ["2"]]
+        );
+
+        let err = Session::new(&db)
+            .execute("WITH t (a, b) AS (SELECT id FROM books) SELECT * FROM t")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "WITH t has 2 column names for 1 columns");
+
+        // The tables shared with the scoped copies are still owned by the database
+        assert!(db.get_mut("books").is_some());
+        assert_eq!(db.remove("books").unwrap().name, "books");
+    }
+}
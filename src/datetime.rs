@@ -0,0 +1,312 @@
+//! Dates and timestamps in cells and literals.
+//! Cells are strings, so values written in ISO-8601 like `2024-01-31` or `2024-01-31T09:00:00+09:00` are
+//! recognized as dates whenever they are compared or passed to the date functions.
+//! Timestamps without an offset are in the session time zone, and those with an offset are converted to it.
+
+use crate::{
+    eval::EvalError,
+    options::{DETERMINISTIC_NOW, ExecOptions, parse_utc_offset},
+};
+
+const SECS_PER_DAY: i64 = 86400;
+
+/// A date or a timestamp as the seconds since the Unix epoch in the session time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DateTime {
+    pub secs: i64,
+    /// False for a date without the time of day, which is formatted without it
+    pub has_time: bool,
+}
+
+impl DateTime {
+    /// Parses `YYYY-MM-DD`, optionally followed by ` HH:MM[:SS[.fff]]` or `THH:MM[:SS[.fff]]` and an offset
+    /// like `Z` or `+09:00`. Fractional seconds are truncated.
+    pub fn parse(s: &str, utc_offset: i32) -> Option<Self> {
+        let s = s.trim();
+        let (date, rest) = (s.get(..10)?, &s[10..]);
+        let (year, month, day) = (date.get(..4)?, date.get(5..7)?, date.get(8..)?);
+        if date.as_bytes()[4] != b'-' || date.as_bytes()[7] != b'-' {
+            return None;
+        }
+        let (year, month, day) = (digits(year)?, digits(month)?, digits(day)?);
+        if !(1..=12).contains(&month) || day < 1 || days_in_month(year, month) < day {
+            return None;
+        }
+        let days = days_from_civil(year, month, day);
+        if rest.is_empty() {
+            return Some(Self {
+                secs: days * SECS_PER_DAY,
+                has_time: false,
+            });
+        }
+
+        let rest = rest.strip_prefix([' ', 'T'])?;
+        let time_end = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+        let (time, offset) = rest.split_at(time_end);
+        let time = time.split_once('.').map_or(time, |(time, frac)| {
+            if frac.bytes().all(|b| b.is_ascii_digit()) {
+                time
+            } else {
+                ""
+            }
+        });
+        let mut fields = time.split(':');
+        let hour = digits(fields.next()?)?;
+        let minute = digits(fields.next()?)?;
+        let second = fields.next().map_or(Some(0), digits)?;
+        if fields.next().is_some() || 23 < hour || 59 < minute || 59 < second {
+            return None;
+        }
+        let offset = if offset.is_empty() {
+            utc_offset
+        } else {
+            parse_utc_offset(offset).ok()?
+        };
+        Some(Self {
+            secs: days * SECS_PER_DAY + hour * 3600 + minute * 60 + second
+                - (offset - utc_offset) as i64 * 60,
+            has_time: true,
+        })
+    }
+
+    /// The current time in the time zone of the options, or [`DETERMINISTIC_NOW`] in the deterministic mode.
+    pub fn now(options: &ExecOptions) -> Result<Self, EvalError> {
+        let secs = if options.deterministic {
+            DETERMINISTIC_NOW
+        } else {
+            // SystemTime::now() panics in wasm32-unknown-unknown
+            if cfg!(target_arch = "wasm32") {
+                return Err(EvalError::NoClock);
+            }
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_err(|_| EvalError::NoClock)?
+                .as_secs() as i64
+        };
+        Ok(Self {
+            secs: secs + options.utc_offset as i64 * 60,
+            has_time: true,
+        })
+    }
+
+    /// The date as year, month and day
+    fn civil(self) -> (i64, i64, i64) {
+        civil_from_days(self.secs.div_euclid(SECS_PER_DAY))
+    }
+
+    fn secs_of_day(self) -> i64 {
+        self.secs.rem_euclid(SECS_PER_DAY)
+    }
+
+    /// Formats the date as `YYYY-MM-DD`
+    pub fn date(self) -> String {
+        let (year, month, day) = self.civil();
+        format!("{year:04}-{month:02}-{day:02}")
+    }
+
+    /// Formats the time of day as `HH:MM:SS`
+    pub fn time(self) -> String {
+        let secs = self.secs_of_day();
+        format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+
+    /// A field of the date like `year` or `hour`, as in `date_part('year', col)`.
+    /// `dow` is the day of the week from 0 for Sunday, and `epoch` is the seconds since the Unix epoch.
+    pub fn part(self, field: &str) -> Result<i64, EvalError> {
+        let (year, month, day) = self.civil();
+        let secs = self.secs_of_day();
+        Ok(match field.to_ascii_lowercase().as_str() {
+            "year" => year,
+            "month" => month,
+            "day" => day,
+            "hour" => secs / 3600,
+            "minute" => secs / 60 % 60,
+            "second" => secs % 60,
+            // 1970-01-01 was a Thursday
+            "dow" => (self.secs.div_euclid(SECS_PER_DAY) + 4).rem_euclid(7),
+            "doy" => days_from_civil(year, month, day) - days_from_civil(year, 1, 1) + 1,
+            "epoch" => self.secs,
+            _ => return Err(EvalError::InvalidDateField(field.to_string())),
+        })
+    }
+
+    /// Adds `n` units like `day` or `month`. Adding months keeps the day, or clamps it to the end of the month,
+    /// e.g. a month after `2024-01-31` is `2024-02-29`. Units of the time of day make a date a timestamp.
+    pub fn add(self, n: i64, unit: &str) -> Result<Self, EvalError> {
+        let overflow = || EvalError::Overflow;
+        let add_secs = |unit_secs: i64, has_time| {
+            Ok(Self {
+                secs: n
+                    .checked_mul(unit_secs)
+                    .and_then(|secs| self.secs.checked_add(secs))
+                    .ok_or_else(overflow)?,
+                has_time,
+            })
+        };
+        match unit.to_ascii_lowercase().as_str() {
+            "second" => add_secs(1, true),
+            "minute" => add_secs(60, true),
+            "hour" => add_secs(3600, true),
+            "day" => add_secs(SECS_PER_DAY, self.has_time),
+            "week" => add_secs(7 * SECS_PER_DAY, self.has_time),
+            "month" | "year" => {
+                let months = if unit.eq_ignore_ascii_case("year") {
+                    n.checked_mul(12).ok_or_else(overflow)?
+                } else {
+                    n
+                };
+                let (year, month, day) = self.civil();
+                let total = (year * 12 + month - 1)
+                    .checked_add(months)
+                    .ok_or_else(overflow)?;
+                let (year, month) = (total.div_euclid(12), total.rem_euclid(12) + 1);
+                let day = day.min(days_in_month(year, month));
+                Ok(Self {
+                    secs: days_from_civil(year, month, day) * SECS_PER_DAY + self.secs_of_day(),
+                    has_time: self.has_time,
+                })
+            }
+            _ => Err(EvalError::InvalidDateField(unit.to_string())),
+        }
+    }
+
+    /// Truncates to the start of the unit like `month` or `hour`. Weeks start on Monday.
+    pub fn trunc(self, unit: &str) -> Result<Self, EvalError> {
+        let (year, month, _) = self.civil();
+        let days = self.secs.div_euclid(SECS_PER_DAY);
+        let truncated = |secs, has_time| Ok(Self { secs, has_time });
+        match unit.to_ascii_lowercase().as_str() {
+            "second" => truncated(self.secs, self.has_time),
+            "minute" => truncated(self.secs - self.secs.rem_euclid(60), self.has_time),
+            "hour" => truncated(self.secs - self.secs.rem_euclid(3600), self.has_time),
+            "day" => truncated(days * SECS_PER_DAY, false),
+            // 1970-01-05 was a Monday
+            "week" => truncated((days - (days - 4).rem_euclid(7)) * SECS_PER_DAY, false),
+            "month" => truncated(days_from_civil(year, month, 1) * SECS_PER_DAY, false),
+            "year" => truncated(days_from_civil(year, 1, 1) * SECS_PER_DAY, false),
+            _ => Err(EvalError::InvalidDateField(unit.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.has_time {
+            write!(f, "{} {}", self.date(), self.time())
+        } else {
+            f.write_str(&self.date())
+        }
+    }
+}
+
+fn digits(s: &str) -> Option<i64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the epoch from a civil date, by Howard Hinnant's algorithm
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Civil date from the days since the epoch, by Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(s: &str) -> DateTime {
+        DateTime::parse(s, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("1970-01-01").secs, 0);
+        // Leap day
+        let ts = parse("2000-02-29T12:34:56");
+        assert_eq!(ts.secs, 951827696);
+        assert_eq!(ts.to_string(), "2000-02-29 12:34:56");
+        assert_eq!(parse("2000-02-29 21:34:56.789+09:00"), ts);
+        assert_eq!(
+            DateTime::parse("2000-02-29 21:34:56", 540)
+                .unwrap()
+                .to_string(),
+            "2000-02-29 21:34:56"
+        );
+        assert_eq!(parse("1969-12-31 23:59").to_string(), "1969-12-31 23:59:00");
+        for invalid in [
+            "2001-02-29",
+            "2024-13-01",
+            "2024-1-1",
+            "20240101",
+            "2024-01-01 24:00",
+            "2024-01-01x",
+            "101",
+            "",
+        ] {
+            assert_eq!(DateTime::parse(invalid, 0), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let ts = parse("2024-01-31 13:45:30");
+        assert_eq!(ts.part("year").unwrap(), 2024);
+        assert_eq!(ts.part("doy").unwrap(), 31);
+        // A Wednesday
+        assert_eq!(ts.part("dow").unwrap(), 3);
+        assert!(ts.part("fortnight").is_err());
+        assert_eq!(
+            ts.add(1, "month").unwrap().to_string(),
+            "2024-02-29 13:45:30"
+        );
+        assert_eq!(
+            ts.add(-1, "year").unwrap().to_string(),
+            "2023-01-31 13:45:30"
+        );
+        assert_eq!(
+            parse("2024-12-31").add(1, "day").unwrap().to_string(),
+            "2025-01-01"
+        );
+        assert_eq!(
+            parse("2024-12-31").add(1, "hour").unwrap().to_string(),
+            "2024-12-31 01:00:00"
+        );
+        assert_eq!(ts.trunc("month").unwrap().to_string(), "2024-01-01");
+        assert_eq!(ts.trunc("week").unwrap().to_string(), "2024-01-29");
+        assert_eq!(ts.trunc("hour").unwrap().to_string(), "2024-01-31 13:00:00");
+    }
+}
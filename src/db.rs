@@ -1,10 +1,179 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
-use crate::{SelectStmt, Table};
+use crate::{CompoundStmt, ExplainStmt, SelectStmt, Table};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     Select(SelectStmt),
+    Explain(ExplainStmt),
+    /// SELECT statements combined with `UNION` or `UNION ALL`
+    Compound(CompoundStmt),
+    /// `SEARCH 'value'` for the cells with the value in all the tables
+    Search(String),
+    /// `COMPARE SCHEMA a, b` for the differences of the columns between two tables
+    CompareSchema(String, String),
+    /// `DESCRIBE table` or `SHOW COLUMNS FROM table` for the columns of a table and their types
+    Describe(String),
+    /// `SHOW TABLES` for the names of the tables in the database
+    ShowTables,
+    /// `SHOW QUERIES` for the queries saved in the session
+    ShowQueries,
+    /// `SHOW FUNCTIONS` for the functions that can be called in queries
+    ShowFunctions,
 }
 
-pub type Database = HashMap<String, Table>;
+impl Statement {
+    /// The SELECT statements that make up this statement, excluding subqueries.
+    pub fn selects_mut(&mut self) -> Vec<&mut SelectStmt> {
+        match self {
+            Statement::Select(select) => vec![select],
+            Statement::Explain(explain) => vec![&mut explain.stmt],
+            Statement::Compound(compound) => compound.selects_mut().collect(),
+            Statement::Search(_)
+            | Statement::CompareSchema(..)
+            | Statement::Describe(_)
+            | Statement::ShowTables
+            | Statement::ShowQueries
+            | Statement::ShowFunctions => vec![],
+        }
+    }
+}
+
+/// The tables to run statements on, keyed by their names.
+/// A table is looked up by its exact name, or ignoring ASCII case if no table has the exact name and just one
+/// table has the name in another case, so that `FROM Books` finds `books`.
+/// The tables are shared with [`Database::scoped`] copies, so that a statement can add its own tables cheaply.
+#[derive(Debug, Default)]
+pub struct Database {
+    tables: HashMap<String, Arc<Table>>,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the table with the name, returning the table that had the same name if any.
+    pub fn insert(&mut self, name: String, table: Table) -> Option<Table> {
+        self.tables.insert(name, Arc::new(table)).map(unshare)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Table> {
+        self.tables.get(self.resolve(name)?).map(|table| &**table)
+    }
+
+    /// The table to modify, which is copied first if a scoped database shares it.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Table> {
+        let name = self.resolve(name)?.to_string();
+        self.tables.get_mut(&name).map(Arc::make_mut)
+    }
+
+    /// Removes the table looked up by the name and returns it.
+    pub fn remove(&mut self, name: &str) -> Option<Table> {
+        let name = self.resolve(name)?.to_string();
+        self.tables.remove(&name).map(unshare)
+    }
+
+    /// A database with the same tables, which are shared rather than copied, to add or replace the tables of a
+    /// statement like the results of `WITH` without changing this one.
+    pub(crate) fn scoped(&self) -> Self {
+        Self {
+            tables: self.tables.clone(),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.resolve(name).is_some()
+    }
+
+    /// The names and the tables in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Table)> {
+        self.tables.iter().map(|(name, table)| (name, &**table))
+    }
+
+    /// The names of the tables in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.tables.keys()
+    }
+
+    /// The tables in no particular order.
+    pub fn tables(&self) -> impl Iterator<Item = &Table> {
+        self.tables.values().map(|table| &**table)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// The key of the table with the name, exact or unique ignoring ASCII case.
+    fn resolve<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        if self.tables.contains_key(name) {
+            return Some(name);
+        }
+        let mut candidates = self
+            .tables
+            .keys()
+            .filter(|key| key.eq_ignore_ascii_case(name));
+        match (candidates.next(), candidates.next()) {
+            (Some(key), None) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+/// The table out of the database, copied if a scoped database still shares it
+fn unshare(table: Arc<Table>) -> Table {
+    Arc::try_unwrap(table).unwrap_or_else(|table| (*table).clone())
+}
+
+impl FromIterator<(String, Table)> for Database {
+    fn from_iter<I: IntoIterator<Item = (String, Table)>>(iter: I) -> Self {
+        Self {
+            tables: iter
+                .into_iter()
+                .map(|(name, table)| (name, Arc::new(table)))
+                .collect(),
+        }
+    }
+}
+
+impl<const N: usize> From<[(String, Table); N]> for Database {
+    fn from(tables: [(String, Table); N]) -> Self {
+        tables.into_iter().collect()
+    }
+}
+
+impl Extend<(String, Table)> for Database {
+    fn extend<I: IntoIterator<Item = (String, Table)>>(&mut self, iter: I) {
+        self.tables.extend(
+            iter.into_iter()
+                .map(|(name, table)| (name, Arc::new(table))),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::make_table;
+
+    #[test]
+    fn test_table_lookup() {
+        let mut db: Database = ["books", "Ab", "AB"]
+            .into_iter()
+            .map(|name| (name.to_string(), make_table(name, "id\n1\n").unwrap()))
+            .collect();
+        assert_eq!(db.get("Books").map(|t| t.name.as_str()), Some("books"));
+        assert_eq!(db.get("AB").map(|t| t.name.as_str()), Some("AB"));
+        // Ambiguous ignoring case
+        assert!(db.get("ab").is_none());
+        assert!(db.remove("Ab").is_some());
+        assert_eq!(db.get("ab").map(|t| t.name.as_str()), Some("AB"));
+        assert!(db.remove("BOOKS").is_some());
+        assert_eq!(db.names().collect::<Vec<_>>(), ["AB"]);
+    }
+}
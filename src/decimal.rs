@@ -0,0 +1,211 @@
+//! Fixed-point decimal numbers for exact arithmetic on money-like values.
+//! Floating point arithmetic on prices produces artifacts like `0.1 + 0.2 = 0.30000000000000004`,
+//! so values written with a finite number of decimal places can be computed as scaled integers instead.
+
+/// Maximum number of decimal places, which keeps the mantissa of practical values within i128.
+const MAX_SCALE: u32 = 18;
+/// Minimum number of decimal places in a quotient, since a division rarely terminates.
+const DIV_SCALE: u32 = 6;
+
+/// A decimal number `mantissa * 10^-scale`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    /// Parses a plain decimal literal like `-12.50`. Exponents are not accepted.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (neg, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        if int.is_empty() && frac.is_empty()
+            || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit())
+            || MAX_SCALE < frac.len() as u32
+        {
+            return None;
+        }
+        let mut mantissa: i128 = 0;
+        for b in int.bytes().chain(frac.bytes()) {
+            mantissa = mantissa.checked_mul(10)?.checked_add((b - b'0') as i128)?;
+        }
+        Some(Self {
+            mantissa: if neg { -mantissa } else { mantissa },
+            scale: frac.len() as u32,
+        })
+    }
+
+    pub fn from_int(val: i128) -> Self {
+        Self {
+            mantissa: val,
+            scale: 0,
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.to_string().parse().unwrap_or(0.)
+    }
+
+    /// Increases the number of decimal places without changing the value.
+    fn rescale(self, scale: u32) -> Option<Self> {
+        Some(Self {
+            mantissa: self
+                .mantissa
+                .checked_mul(10i128.checked_pow(scale - self.scale)?)?,
+            scale,
+        })
+    }
+
+    /// Decreases the number of decimal places, rounding half away from zero.
+    pub fn round_to(self, scale: u32) -> Self {
+        if self.scale <= scale {
+            return self;
+        }
+        Self {
+            mantissa: div_round(self.mantissa, 10i128.pow(self.scale - scale)),
+            scale,
+        }
+    }
+
+    /// Removes trailing zeros, but keeps at least `min_scale` decimal places.
+    fn trim(mut self, min_scale: u32) -> Self {
+        while min_scale < self.scale && self.mantissa % 10 == 0 {
+            self.mantissa /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    fn align(self, rhs: Self) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(rhs.scale);
+        Some((
+            self.rescale(scale)?.mantissa,
+            rhs.rescale(scale)?.mantissa,
+            scale,
+        ))
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        let (lhs, rhs, scale) = self.align(rhs)?;
+        Some(Self {
+            mantissa: lhs.checked_add(rhs)?,
+            scale,
+        })
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        let (lhs, rhs, scale) = self.align(rhs)?;
+        Some(Self {
+            mantissa: lhs.checked_sub(rhs)?,
+            scale,
+        })
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let res = Self {
+            mantissa: self.mantissa.checked_mul(rhs.mantissa)?,
+            scale: self.scale + rhs.scale,
+        };
+        Some(res.round_to(MAX_SCALE))
+    }
+
+    /// The remainder with the sign of the dividend, like `%` on integers.
+    pub fn checked_rem(self, rhs: Self) -> Option<Self> {
+        let (lhs, rhs, scale) = self.align(rhs)?;
+        Some(Self {
+            mantissa: lhs.checked_rem(rhs)?,
+            scale,
+        })
+    }
+
+    /// Divides with at least [`DIV_SCALE`] decimal places, then trims trailing zeros down to the scale of the
+    /// operands, so that `1.00 / 2` yields `0.50`.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.mantissa == 0 {
+            return None;
+        }
+        let min_scale = self.scale.max(rhs.scale);
+        let scale = min_scale.max(DIV_SCALE);
+        // (a / 10^sa) / (b / 10^sb) = a * 10^(scale + sb - sa) / b / 10^scale
+        let num = self
+            .mantissa
+            .checked_mul(10i128.checked_pow(scale + rhs.scale - self.scale)?)?;
+        Some(
+            Self {
+                mantissa: div_round(num, rhs.mantissa),
+                scale,
+            }
+            .trim(min_scale),
+        )
+    }
+}
+
+/// Integer division rounding half away from zero.
+fn div_round(num: i128, den: i128) -> i128 {
+    let (quot, rem) = (num / den, num % den);
+    if den.unsigned_abs() - rem.unsigned_abs() <= rem.unsigned_abs() {
+        quot + num.signum() * den.signum()
+    } else {
+        quot
+    }
+}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let abs = self.mantissa.unsigned_abs();
+        let pow = 10u128.pow(self.scale);
+        if self.mantissa < 0 {
+            write!(f, "-")?;
+        }
+        if self.scale == 0 {
+            write!(f, "{abs}")
+        } else {
+            write!(
+                f,
+                "{}.{:0width$}",
+                abs / pow,
+                abs % pow,
+                width = self.scale as usize
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_decimal() {
+        assert_eq!(
+            dec("0.1").checked_add(dec("0.2")).unwrap().to_string(),
+            "0.3"
+        );
+        assert_eq!(
+            dec("19.90").checked_sub(dec("20")).unwrap().to_string(),
+            "-0.10"
+        );
+        assert_eq!(
+            dec("1.10").checked_mul(dec("3")).unwrap().to_string(),
+            "3.30"
+        );
+        assert_eq!(
+            dec("1.00").checked_div(dec("2")).unwrap().to_string(),
+            "0.50"
+        );
+        assert_eq!(
+            dec("2").checked_div(dec("3")).unwrap().to_string(),
+            "0.666667"
+        );
+        assert_eq!(dec("-.5").to_string(), "-0.5");
+        assert_eq!(Decimal::parse("1e3"), None);
+        assert_eq!(dec("1").checked_div(dec("0")), None);
+    }
+}
@@ -0,0 +1,48 @@
+//! `DESCRIBE table` or `SHOW COLUMNS FROM table`, which lists the columns of a table with the types inferred
+//! from the values, e.g. to see what a CSV file has before writing a query on it, and `SHOW TABLES`, which
+//! lists the tables.
+
+use std::error::Error;
+
+use crate::{db::Database, select::QueryOutput};
+
+/// Outputs a row for each column of the table with its position from 1, name and type.
+/// If only some of the columns of the file are loaded, all of them are listed with the positions in the file,
+/// and the type of the others is `not loaded`.
+pub fn exec_describe(
+    out: &mut impl QueryOutput,
+    db: &Database,
+    table: &str,
+) -> Result<(), Box<dyn Error>> {
+    let table = db
+        .get(table)
+        .ok_or_else(|| format!("Table {table} not found"))?;
+    out.output(&["position", "column", "type"].map(String::from))?;
+    let loaded: Vec<_> = table
+        .schema
+        .iter()
+        .map(|schema| schema.name.clone())
+        .collect();
+    let header = table.source_header.as_ref().unwrap_or(&loaded);
+    let mut loaded = table.schema.iter().enumerate().peekable();
+    for (pos, name) in header.iter().enumerate() {
+        // The loaded columns are in the order in the file
+        let ty = match loaded.next_if(|(_, schema)| schema.name == *name) {
+            Some((col, _)) => table.column_type(col).to_string(),
+            None => "not loaded".to_string(),
+        };
+        out.output(&[(pos + 1).to_string(), name.clone(), ty])?;
+    }
+    Ok(())
+}
+
+/// Outputs a row for each table in the database with its name, in the order of the names.
+pub fn exec_show_tables(out: &mut impl QueryOutput, db: &Database) -> Result<(), Box<dyn Error>> {
+    let mut names: Vec<_> = db.names().collect();
+    names.sort();
+    out.output(&["table".to_string()])?;
+    for name in names {
+        out.output(std::slice::from_ref(name))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,146 @@
+//! Errors as JSON objects for tools like editors and the playground, which can underline the offending part of
+//! the query instead of showing the message as it is.
+
+use std::{error::Error, io::Write};
+
+use crate::{
+    eval::EvalError,
+    parse_error::{ParseError, line_column, token_len},
+    select::write_json_str,
+};
+
+/// Formats an error of parsing or executing `sql` as a JSON object like
+/// `{"kind": "unknown_column", "message": "...", "span": {...}, "suggestions": ["name"]}`.
+///
+/// The kind is one of `parse`, `unknown_column`, `ambiguous_column` or `execution`.
+/// The span has the byte `offset` and `length`, and the `line` and `column` starting from 1, of the offending
+/// token, or is null if the error is not located in the query. A column is located by the first occurrence of
+/// its name outside string literals, since the statement does not keep the positions.
+/// The suggestions are what could be written instead, e.g. a column with a similar name.
+pub fn format_error_json(err: &(dyn Error + 'static), sql: &str) -> String {
+    let (kind, span, suggestions) = if let Some(err) = err.downcast_ref::<ParseError>() {
+        let length = token_len(&sql[err.offset..])
+            .max(sql[err.offset..].chars().next().map_or(0, char::len_utf8));
+        ("parse", Some((err.offset, length)), vec![])
+    } else {
+        match err.downcast_ref::<EvalError>() {
+            Some(EvalError::UnknownColumn {
+                column, suggestion, ..
+            }) => (
+                "unknown_column",
+                find_token(sql, column).map(|offset| (offset, column.len())),
+                suggestion.iter().cloned().collect(),
+            ),
+            Some(EvalError::AmbiguousColumn { column, tables }) => (
+                "ambiguous_column",
+                find_token(sql, column).map(|offset| (offset, column.len())),
+                tables
+                    .iter()
+                    .map(|table| format!("{table}.{column}"))
+                    .collect(),
+            ),
+            _ => ("execution", None, vec![]),
+        }
+    };
+
+    let mut buf = vec![];
+    write_error(&mut buf, kind, &err.to_string(), sql, span, &suggestions)
+        .expect("Writing to a Vec does not fail");
+    String::from_utf8(buf).expect("JSON is written from strings")
+}
+
+fn write_error(
+    f: &mut impl Write,
+    kind: &str,
+    message: &str,
+    sql: &str,
+    span: Option<(usize, usize)>,
+    suggestions: &[String],
+) -> std::io::Result<()> {
+    write!(f, "{{\"kind\": ")?;
+    write_json_str(f, kind)?;
+    write!(f, ", \"message\": ")?;
+    write_json_str(f, message)?;
+    write!(f, ", \"span\": ")?;
+    match span {
+        Some((offset, length)) => {
+            let (line, column) = line_column(sql, offset);
+            write!(
+                f,
+                "{{\"offset\": {offset}, \"length\": {length}, \"line\": {line}, \"column\": {column}}}"
+            )?;
+        }
+        None => write!(f, "null")?,
+    }
+    write!(f, ", \"suggestions\": [")?;
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        write_json_str(f, suggestion)?;
+    }
+    write!(f, "]}}")
+}
+
+/// The byte offset of the first occurrence of the token in the source text, which is not a part of a longer
+/// word or in a string literal.
+fn find_token(sql: &str, token: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut in_string = false;
+    for (offset, c) in sql.char_indices() {
+        if c == '\'' {
+            in_string = !in_string;
+        }
+        if in_string || !sql[offset..].starts_with(token) {
+            continue;
+        }
+        let before = sql[..offset].chars().next_back();
+        let after = sql[offset + token.len()..].chars().next();
+        if !before.is_some_and(|c| is_word(c) || c == '.') && !after.is_some_and(is_word) {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Database, exec_select, make_table, parser::parse_statement, select::BufferOutput};
+
+    fn error_json(sql: &str) -> String {
+        let mut db = Database::new();
+        db.insert("a".to_string(), make_table("a", "id,name\n1,x\n").unwrap());
+        db.insert("b".to_string(), make_table("b", "id,a_id\n1,1\n").unwrap());
+        let err: Box<dyn Error> = match parse_statement(sql) {
+            Ok(crate::Statement::Select(stmt)) => {
+                exec_select(&mut BufferOutput::default(), &db, &stmt).unwrap_err()
+            }
+            Ok(_) => panic!("Not a SELECT statement"),
+            Err(e) => e.into(),
+        };
+        format_error_json(&*err, sql)
+    }
+
+    #[test]
+    fn test_error_json() {
+        assert_eq!(
+            error_json("SELECT id\nFORM a"),
+            r#"{"kind": "parse", "message": "Parse error at line 2, column 1: expected FROM, found 'FORM'", "span": {"offset": 10, "length": 4, "line": 2, "column": 1}, "suggestions": []}"#
+        );
+        assert_eq!(
+            error_json("SELECT 'naem', naem FROM a"),
+            r#"{"kind": "unknown_column", "message": "Unknown column 'naem', did you mean 'name'? Available columns: id, name", "span": {"offset": 15, "length": 4, "line": 1, "column": 16}, "suggestions": ["name"]}"#
+        );
+        assert_eq!(
+            error_json("SELECT a.id, id FROM a INNER JOIN b ON a.id = a_id"),
+            r#"{"kind": "ambiguous_column", "message": "Column 'id' is ambiguous, qualify it with one of the tables: a, b", "span": {"offset": 13, "length": 2, "line": 1, "column": 14}, "suggestions": ["a.id", "b.id"]}"#
+        );
+        assert_eq!(
+            error_json("SELECT 9223372036854775807 + id FROM a")
+                .split(", \"message\"")
+                .next(),
+            Some(r#"{"kind": "execution""#)
+        );
+    }
+}
@@ -1,10 +1,38 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
 
-use crate::select::{BinOp, ColSpecifier, Expr, QueryContext, RowCursor, UniOp};
+use crate::{
+    datetime::DateTime,
+    functions::{FunctionKind, ResultType, find_function, is_aggregate},
+    number::Number,
+    options::{AggregateNulls, ExecOptions},
+    select::{BinOp, ColSpecifier, Expr, QueryContext, RowCursor, UniOp},
+    sketch::{HyperLogLog, TDigest},
+    table::ColumnType,
+};
 
 #[derive(Clone, Debug)]
 pub(crate) enum EvalError {
     ColNotFound(String),
+    /// A column referenced in the statement is not in any of its tables, found before execution
+    UnknownColumn {
+        column: String,
+        /// A column with a similar name, which the user might have meant
+        suggestion: Option<String>,
+        /// The first few columns in scope, to show what can be referenced
+        available: Vec<String>,
+        /// The number of the columns in scope not in `available`
+        more: usize,
+    },
+    /// An unqualified column is in more than one of the joined tables
+    AmbiguousColumn {
+        column: String,
+        tables: Vec<String>,
+    },
+    /// A qualifier names more than one of the joined tables, like `t.id` in a self-join of `t` without aliases
+    AmbiguousTable(String),
     RowNotFound(usize),
     CursorNone(usize),
     /// When an aggregate function like count is called in scalar context
@@ -15,12 +43,60 @@ pub(crate) enum EvalError {
     DisallowedWildcard(String),
     /// An argument is required, but not given to a function.
     InsufficientArg(&'static str),
+    /// A parameter placeholder is evaluated without binding a value.
+    UnboundParam(String),
+    /// The system clock is not available, e.g. in a wasm runtime.
+    NoClock,
+    /// An integer arithmetic overflowed.
+    Overflow,
+    /// A scalar subquery failed or did not yield a single value.
+    Subquery(String),
+    /// A window function is called outside of the output columns or QUALIFY
+    WindowCall(String),
+    UnknownWindowFn(String),
+    /// The quantile given to approx_quantile is not a number in [0, 1]
+    InvalidQuantile(String),
+    /// DISTINCT is given to a function other than count
+    DisallowedDistinct(String),
+    /// A LIKE pattern ends with the escape character, which has nothing to escape
+    InvalidPattern(String),
+    /// An unknown field or unit is given to a date function, e.g. `date_part('fortnight', d)`
+    InvalidDateField(String),
 }
 
 impl std::fmt::Display for EvalError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::ColNotFound(col) => write!(f, "Column {col} not found"),
+            Self::UnknownColumn {
+                column,
+                suggestion,
+                available,
+                more,
+            } => {
+                write!(f, "Unknown column '{column}'")?;
+                match suggestion {
+                    Some(suggestion) => write!(f, ", did you mean '{suggestion}'?")?,
+                    None if !available.is_empty() => write!(f, ".")?,
+                    None => {}
+                }
+                if !available.is_empty() {
+                    write!(f, " Available columns: {}", available.join(", "))?;
+                }
+                if 0 < *more {
+                    write!(f, " and {more} more")?;
+                }
+                Ok(())
+            }
+            Self::AmbiguousColumn { column, tables } => write!(
+                f,
+                "Column '{column}' is ambiguous, qualify it with one of the tables: {}",
+                tables.join(", ")
+            ),
+            Self::AmbiguousTable(table) => write!(
+                f,
+                "Table '{table}' is joined more than once, give each an alias with AS"
+            ),
             Self::RowNotFound(row) => write!(f, "Row {row} is out of bound"),
             Self::CursorNone(table) => write!(f, "Table index {table} has None cursor"),
             Self::AggregateCall(name) => {
@@ -33,12 +109,54 @@ impl std::fmt::Display for EvalError {
             Self::InsufficientArg(func) => {
                 write!(f, "{func} function requires an argument")
             }
+            Self::UnboundParam(name) => write!(f, "Parameter {name} is not bound"),
+            Self::NoClock => write!(f, "System clock is not available"),
+            Self::Overflow => write!(f, "Integer overflow"),
+            Self::InvalidQuantile(val) => {
+                write!(f, "Quantile must be a number between 0 and 1: {val}")
+            }
+            Self::DisallowedDistinct(name) => {
+                write!(f, "{name} function cannot have DISTINCT argument")
+            }
+            Self::InvalidPattern(pattern) => {
+                write!(
+                    f,
+                    "LIKE pattern must not end with the escape character: {pattern}"
+                )
+            }
+            Self::InvalidDateField(field) => write!(f, "Unknown date field or unit: {field}"),
+            Self::Subquery(msg) => write!(f, "Error in scalar subquery: {msg}"),
+            Self::WindowCall(ex) => write!(
+                f,
+                "Window function {ex} can only be used in columns or QUALIFY without aggregates"
+            ),
+            Self::UnknownWindowFn(ex) => write!(f, "Unknown window function {ex}"),
         }
     }
 }
 
 impl std::error::Error for EvalError {}
 
+/// Same as [`eval_expr`], but borrows the cell of a column from the table instead of copying it, so that a large
+/// cell like embedded JSON is not copied on each row only to be compared or truncated.
+pub(crate) fn eval_cell<'a>(
+    expr: &Expr,
+    cols: &[Expr],
+    ctx: &QueryContext<'a>,
+    row_cursor: &[RowCursor],
+    aggregates: &AggregateResult,
+) -> Result<Cow<'a, str>, EvalError> {
+    match expr {
+        Expr::Bound(_, id) => Ok(Cow::Borrowed(ctx.col_ref(*id).get(row_cursor)?)),
+        Expr::ColIdx(i) => match i.checked_sub(1).and_then(|i| cols.get(i)) {
+            Some(col) => eval_cell(col, cols, ctx, row_cursor, aggregates),
+            None => Err(EvalError::ColNotFound(format!("{i}"))),
+        },
+        Expr::Collate { expr, .. } => eval_cell(expr, cols, ctx, row_cursor, aggregates),
+        _ => eval_expr(expr, cols, ctx, row_cursor, aggregates).map(Cow::Owned),
+    }
+}
+
 pub(crate) fn eval_expr(
     expr: &Expr,
     cols: &[Expr],
@@ -47,11 +165,12 @@ pub(crate) fn eval_expr(
     aggregates: &AggregateResult,
 ) -> Result<String, EvalError> {
     match expr {
+        Expr::Bound(_, id) => ctx.col_ref(*id).get(row_cursor).cloned(),
         Expr::Column(col) => {
             let col = ctx
-                .find_col(col)
+                .find_col(col)?
                 .ok_or_else(|| EvalError::ColNotFound(col.column.clone()))?;
-            col.get(&row_cursor).cloned()
+            col.get(row_cursor).cloned()
         }
         Expr::ColIdx(i) => {
             let col = cols
@@ -62,107 +181,448 @@ pub(crate) fn eval_expr(
                 .ok_or_else(|| EvalError::ColNotFound(format!("{i}")))?;
             eval_expr(col, cols, ctx, row_cursor, aggregates)
         }
-        Expr::StrLiteral(lit) => Ok(lit.clone()),
-        Expr::Binary { op, lhs, rhs } => {
-            let lhs = eval_expr(lhs, cols, ctx, row_cursor, aggregates)?;
-            let rhs = eval_expr(rhs, cols, ctx, row_cursor, aggregates)?;
-            Ok(eval_bin_op(op, lhs, rhs)?)
+        Expr::SourceRow(joindex) => Ok(row_cursor
+            .get(*joindex)
+            .and_then(|cursor| cursor.row)
+            .map_or_else(String::new, |row| row.to_string())),
+        Expr::StrLiteral(lit) | Expr::NumLiteral(lit) => Ok(lit.clone()),
+        Expr::Param(name) => Err(EvalError::UnboundParam(name.clone())),
+        Expr::Subquery(stmt) => ctx.eval_subquery(expr, stmt),
+        Expr::InSubquery {
+            lhs,
+            subquery,
+            negated,
+        } => {
+            let val = match eval_expr(lhs, cols, ctx, row_cursor, aggregates) {
+                Ok(val) => val,
+                Err(EvalError::CursorNone(_)) => "".to_string(),
+                Err(e) => return Err(e),
+            };
+            ctx.eval_in_subquery(expr, subquery, &val, *negated)
+        }
+        Expr::Exists(stmt) => ctx.eval_exists(expr, stmt),
+        Expr::Like {
+            lhs,
+            pattern,
+            escape,
+            negated,
+        } => {
+            let val = eval_cell(lhs, cols, ctx, row_cursor, aggregates)?;
+            let pattern = eval_expr(pattern, cols, ctx, row_cursor, aggregates)?;
+            let pattern: Vec<_> = pattern.chars().collect();
+            let chars: Vec<_> = val.chars().collect();
+            Ok((like_match(&chars, &pattern, *escape)? != *negated).to_string())
+        }
+        Expr::Window { .. } => ctx
+            .window
+            .and_then(|window| window.value(expr))
+            .cloned()
+            .ok_or_else(|| EvalError::WindowCall(expr.to_string())),
+        Expr::Binary {
+            op: op @ (BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div),
+            lhs,
+            rhs,
+        } => {
+            let lhs = eval_number(lhs, cols, ctx, row_cursor, aggregates)?;
+            let rhs = eval_number(rhs, cols, ctx, row_cursor, aggregates)?;
+            eval_arith(op, lhs, rhs)
         }
+        Expr::Binary {
+            op,
+            lhs: lhs_ex,
+            rhs: rhs_ex,
+        } => {
+            let lhs = eval_cell(lhs_ex, cols, ctx, row_cursor, aggregates)?;
+            let rhs = eval_cell(rhs_ex, cols, ctx, row_cursor, aggregates)?;
+            let ty = comparison_type(lhs_ex, rhs_ex, cols, ctx);
+            let (lhs, rhs) = collate_operands(op, lhs_ex, rhs_ex, lhs, rhs);
+            Ok(eval_bin_op(op, &lhs, &rhs, ty, ctx)?)
+        }
+        Expr::Collate { expr, .. } => eval_expr(expr, cols, ctx, row_cursor, aggregates),
         Expr::Unary { op, operand } => {
             let val = eval_expr(operand, cols, ctx, row_cursor, aggregates)?;
-            let res = match op {
-                UniOp::Not => !coerce_bool(&val),
-            };
-            Ok((if res { "1" } else { "0" }).to_string())
+            eval_uni_op(op, val, ctx)
+        }
+        Expr::AggregateFn { name, distinct, .. }
+            if *distinct && !name.eq_ignore_ascii_case("count") =>
+        {
+            Err(EvalError::DisallowedDistinct(name.clone()))
         }
-        Expr::AggregateFn { name, args } => match name.to_ascii_lowercase().as_str() {
+        Expr::AggregateFn { name, args, .. } => match name.to_ascii_lowercase().as_str() {
             "length" => {
                 let arg = args
-                    .get(0)
-                    .ok_or_else(|| EvalError::InsufficientArg("length"))?
+                    .first()
+                    .ok_or(EvalError::InsufficientArg("length"))?
                     .as_expr()?;
-                let val = eval_expr(arg, cols, ctx, row_cursor, aggregates)?;
-                return Ok(val.len().to_string());
+                let val = eval_cell(arg, cols, ctx, row_cursor, aggregates)?;
+                Ok(val.chars().count().to_string())
             }
             "upper" => {
                 let arg = args
-                    .get(0)
-                    .ok_or_else(|| EvalError::InsufficientArg("upper"))?
+                    .first()
+                    .ok_or(EvalError::InsufficientArg("upper"))?
                     .as_expr()?;
                 let val = eval_expr(arg, cols, ctx, row_cursor, aggregates)?;
-                return Ok(val.to_uppercase());
+                Ok(val.to_uppercase())
             }
             "lower" => {
                 let arg = args
-                    .get(0)
-                    .ok_or_else(|| EvalError::InsufficientArg("lower"))?
+                    .first()
+                    .ok_or(EvalError::InsufficientArg("lower"))?
                     .as_expr()?;
                 let val = eval_expr(arg, cols, ctx, row_cursor, aggregates)?;
-                return Ok(val.to_lowercase());
+                Ok(val.to_lowercase())
+            }
+            "env" => {
+                let arg = args
+                    .first()
+                    .ok_or(EvalError::InsufficientArg("env"))?
+                    .as_expr()?;
+                let var = eval_expr(arg, cols, ctx, row_cursor, aggregates)?;
+                // An undefined variable is NULL
+                Ok(std::env::var(var).unwrap_or_default())
+            }
+            "substr" | "trim" | "ltrim" | "rtrim" | "replace" | "instr" | "lpad" | "rpad" => {
+                let vals = args
+                    .iter()
+                    .map(|arg| eval_expr(arg.as_expr()?, cols, ctx, row_cursor, aggregates))
+                    .collect::<Result<Vec<_>, _>>()?;
+                eval_string_fn(&name.to_ascii_lowercase(), &vals)
+            }
+            "round" | "abs" | "ceil" | "floor" | "mod" => {
+                let vals = args
+                    .iter()
+                    .map(|arg| eval_expr(arg.as_expr()?, cols, ctx, row_cursor, aggregates))
+                    .collect::<Result<Vec<_>, _>>()?;
+                eval_numeric_fn(&name.to_ascii_lowercase(), &vals, ctx.options.decimal)
+            }
+            "now" | "date_part" | "date_add" | "date_trunc" => {
+                let vals = args
+                    .iter()
+                    .map(|arg| eval_expr(arg.as_expr()?, cols, ctx, row_cursor, aggregates))
+                    .collect::<Result<Vec<_>, _>>()?;
+                eval_date_fn(&name.to_ascii_lowercase(), &vals, ctx.options)
+            }
+            "current_date" => Ok(DateTime::now(ctx.options)?.date()),
+            "current_time" => Ok(DateTime::now(ctx.options)?.time()),
+            fn_name if !aggregates.active && is_aggregate(fn_name) => {
+                Err(EvalError::AggregateCall(name.clone()))
             }
-            "count" => aggregates
+            "count" => Ok(aggregates
                 .count
                 .get(&(expr as *const _ as usize))
-                .map(|v| v.to_string()),
-            "sum" => aggregates
+                .map_or(0, |v| *v)
+                .to_string()),
+            "sum" => Ok(aggregates
                 .sum
                 .get(&(expr as *const _ as usize))
-                .map(|v| v.to_string()),
-            "avg" => aggregates
-                .avg
-                .get(&(expr as *const _ as usize))
-                .map(|entry| (entry.sum / entry.count as f64).to_string()),
-            "min" => aggregates
-                .min
+                .map_or_else(|| empty_aggregate(ctx), |v| format_number(*v, ctx))),
+            "avg" => match aggregates.avg.get(&(expr as *const _ as usize)) {
+                Some(entry) => Ok(format_number(entry.mean()?, ctx)),
+                None => Ok(empty_aggregate(ctx)),
+            },
+            fn_name @ ("min" | "max") => {
+                let extremes = if fn_name == "min" {
+                    &aggregates.min
+                } else {
+                    &aggregates.max
+                };
+                Ok(match extremes.get(&(expr as *const _ as usize)) {
+                    Some(Extreme::Number(val)) => format_number(*val, ctx),
+                    Some(Extreme::Value(val)) => val.clone(),
+                    // An extreme of values that are not numbers is NULL, like string_agg
+                    None if extreme_type(args, cols, ctx).is_some() => String::new(),
+                    None => empty_aggregate(ctx),
+                })
+            }
+            "approx_count_distinct" => Ok(aggregates
+                .distinct
                 .get(&(expr as *const _ as usize))
-                .map(|entry| entry.to_string()),
-            "max" => aggregates
-                .max
+                .map_or(0, |hll| hll.estimate())
+                .to_string()),
+            "approx_quantile" => {
+                let q = quantile_arg(args, cols, ctx, row_cursor, aggregates)?;
+                Ok(aggregates
+                    .quantile
+                    .get(&(expr as *const _ as usize))
+                    .and_then(|digest| digest.quantile(q))
+                    .map_or_else(
+                        || empty_aggregate(ctx),
+                        |val| format_number(Number::Float(val), ctx),
+                    ))
+            }
+            // Concatenating no values yields NULL even with --spreadsheet-nulls, since it is not a number
+            "string_agg" | "group_concat" => Ok(aggregates
+                .string_agg
                 .get(&(expr as *const _ as usize))
-                .map(|entry| entry.to_string()),
-            _ => return Err(EvalError::AggregateCall(name.clone())),
+                .cloned()
+                .unwrap_or_default()),
+            _ => Err(EvalError::AggregateCall(name.clone())),
+        },
+    }
+}
+
+/// The second argument of approx_quantile, like 0.5 for the median.
+fn quantile_arg(
+    args: &[ColSpecifier],
+    cols: &[Expr],
+    ctx: &QueryContext,
+    row_cursor: &[RowCursor],
+    aggregates: &AggregateResult,
+) -> Result<f64, EvalError> {
+    let arg = args
+        .get(1)
+        .ok_or(EvalError::InsufficientArg("approx_quantile"))?
+        .as_expr()?;
+    let val = eval_expr(arg, cols, ctx, row_cursor, aggregates)?;
+    val.parse()
+        .ok()
+        .filter(|q| (0. ..=1.).contains(q))
+        .ok_or(EvalError::InvalidQuantile(val))
+}
+
+/// The result of an aggregate function other than count over no values.
+fn empty_aggregate(ctx: &QueryContext) -> String {
+    match ctx.options.aggregate_nulls {
+        AggregateNulls::Standard => "".to_string(),
+        AggregateNulls::Spreadsheet => "0".to_string(),
+    }
+}
+
+/// Formats the result of an aggregate function with the precision in the options.
+/// Integers and decimals are printed as they are, since they are exact.
+fn format_number(val: Number, ctx: &QueryContext) -> String {
+    let Number::Float(val) = val else {
+        return val.to_string();
+    };
+    let Some(precision) = ctx.options.float_precision else {
+        return val.to_string();
+    };
+    let s = format!("{val:.precision$}");
+    let s = if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &s
+    };
+    // Tiny negative values are rounded to "-0"
+    if s == "-0" { "0" } else { s }.to_string()
+}
+
+fn eval_uni_op(op: &UniOp, val: String, ctx: &QueryContext) -> Result<String, EvalError> {
+    match op {
+        UniOp::Not => Ok((if coerce_bool(&val) { "0" } else { "1" }).to_string()),
+        UniOp::Neg => {
+            let val = Number::parse(&val, ctx.options.decimal).unwrap_or(Number::Float(0.));
+            Number::Int(0)
+                .checked_sub(val)
+                .map(|v| v.to_string())
+                .ok_or(EvalError::Overflow)
+        }
+    }
+}
+
+/// Applies the collation of either operand of a comparison to both, e.g. `name = 'alice' COLLATE NOCASE`
+/// matches "Alice".
+fn collate_operands<'a>(
+    op: &BinOp,
+    lhs_ex: &Expr,
+    rhs_ex: &Expr,
+    lhs: Cow<'a, str>,
+    rhs: Cow<'a, str>,
+) -> (Cow<'a, str>, Cow<'a, str>) {
+    use BinOp::*;
+    let collated = |ex: &Expr| matches!(ex, Expr::Collate { .. });
+    if !matches!(op, Eq | Ne | Lt | Gt | Le | Ge) || !(collated(lhs_ex) || collated(rhs_ex)) {
+        return (lhs, rhs);
+    }
+    let collate =
+        |val: Cow<str>| Cow::Owned(lhs_ex.collation_key(rhs_ex.collation_key(val.into_owned())));
+    (collate(lhs), collate(rhs))
+}
+
+/// The type of the values of the expression known before evaluating it, from the types of the columns
+/// inferred on loading. `None` if the values can be of any type, like those of a string literal.
+pub(crate) fn static_type(expr: &Expr, cols: &[Expr], ctx: &QueryContext) -> Option<ColumnType> {
+    match expr {
+        Expr::Column(_) | Expr::Bound(..) => Some(ctx.column_of(expr).ok()??.column_type()),
+        Expr::ColIdx(i) => static_type(cols.get(i.checked_sub(1)?)?, cols, ctx),
+        Expr::SourceRow(_) => Some(ColumnType::Integer),
+        Expr::NumLiteral(_)
+        | Expr::Binary {
+            op: BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div,
+            ..
         }
-        .ok_or_else(|| EvalError::AggregateCall(name.clone())),
+        | Expr::Unary { op: UniOp::Neg, .. } => Some(ColumnType::Number),
+        // Collated values are compared as strings
+        Expr::Collate { .. } => Some(ColumnType::Text),
+        Expr::AggregateFn { name, args, .. } => match find_function(name)?.result {
+            ResultType::Untyped => None,
+            ResultType::Fixed(ty) => Some(ty),
+            ResultType::FirstArg => static_type(args.first()?.as_expr().ok()?, cols, ctx),
+            ResultType::SumOfFirstArg => {
+                match static_type(args.first()?.as_expr().ok()?, cols, ctx) {
+                    Some(ColumnType::Integer) => Some(ColumnType::Integer),
+                    _ => Some(ColumnType::Number),
+                }
+            }
+        },
+        _ => None,
     }
 }
 
-fn eval_bin_op(op: &BinOp, lhs: String, rhs: String) -> Result<String, EvalError> {
+/// The type of the argument of min or max if its values are compared in that type and output as they are
+/// written, which is the case of text and dates. Numbers and values without a type are compared as numbers.
+fn extreme_type(args: &[ColSpecifier], cols: &[Expr], ctx: &QueryContext) -> Option<ColumnType> {
+    let ty = static_type(args.first()?.as_expr().ok()?, cols, ctx)?;
+    (!ty.is_numeric()).then_some(ty)
+}
+
+/// The type in which the operands of a comparison are compared, which is the type of the left operand, or of
+/// the right one if the left one has no type.
+fn comparison_type(
+    lhs_ex: &Expr,
+    rhs_ex: &Expr,
+    cols: &[Expr],
+    ctx: &QueryContext,
+) -> Option<ColumnType> {
+    static_type(lhs_ex, cols, ctx).or_else(|| static_type(rhs_ex, cols, ctx))
+}
+
+/// Evaluates an operand of arithmetic, where a value that is not a number is 0. A column is read from the
+/// numbers cached in the table rather than parsed again.
+fn eval_number(
+    expr: &Expr,
+    cols: &[Expr],
+    ctx: &QueryContext,
+    row_cursor: &[RowCursor],
+    aggregates: &AggregateResult,
+) -> Result<Number, EvalError> {
+    let num = match expr {
+        Expr::Column(_) | Expr::Bound(..) => ctx
+            .column_of(expr)?
+            .ok_or_else(|| EvalError::ColNotFound(expr.to_string()))?
+            .get_number(row_cursor, ctx.options.decimal)?,
+        _ => {
+            let val = eval_expr(expr, cols, ctx, row_cursor, aggregates)?;
+            Number::parse(&val, ctx.options.decimal)
+        }
+    };
+    Ok(num.unwrap_or(Number::Float(0.)))
+}
+
+fn eval_arith(op: &BinOp, lhs: Number, rhs: Number) -> Result<String, EvalError> {
     let res = match op {
-        BinOp::Add => (coerce_f64(&lhs) + coerce_f64(&rhs)).to_string(),
-        BinOp::Sub => (coerce_f64(&lhs) - coerce_f64(&rhs)).to_string(),
-        BinOp::Mul => (coerce_f64(&lhs) * coerce_f64(&rhs)).to_string(),
-        BinOp::Div => (coerce_f64(&lhs) / coerce_f64(&rhs)).to_string(),
+        BinOp::Add => lhs.checked_add(rhs),
+        BinOp::Sub => lhs.checked_sub(rhs),
+        BinOp::Mul => lhs.checked_mul(rhs),
+        _ => lhs.checked_div(rhs),
+    };
+    res.map(|v| v.to_string()).ok_or(EvalError::Overflow)
+}
+
+fn eval_bin_op(
+    op: &BinOp,
+    lhs: &str,
+    rhs: &str,
+    ty: Option<ColumnType>,
+    ctx: &QueryContext,
+) -> Result<String, EvalError> {
+    let num = |val: &str| Number::parse(val, ctx.options.decimal).unwrap_or(Number::Float(0.));
+    let res = match op {
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => eval_arith(op, num(lhs), num(rhs))?,
+        // Numbers of a numeric operand are compared numerically, so that 9 is less than 10
+        BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge
+            if let Some(ty) = ty.filter(|ty| ty.is_numeric())
+                && Number::parse(lhs, false).is_some()
+                && Number::parse(rhs, false).is_some() =>
+        {
+            compare_result(op, ty.compare(lhs, rhs)).to_string()
+        }
+        // Dates are compared chronologically, even in different formats or time zones
+        BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge
+            if let Some(lhs) = DateTime::parse(lhs, ctx.options.utc_offset)
+                && let Some(rhs) = DateTime::parse(rhs, ctx.options.utc_offset) =>
+        {
+            compare_result(op, lhs.secs.cmp(&rhs.secs)).to_string()
+        }
         BinOp::Eq => (lhs == rhs).to_string(),
         BinOp::Ne => (lhs != rhs).to_string(),
         BinOp::Lt => (lhs < rhs).to_string(),
         BinOp::Gt => (lhs > rhs).to_string(),
         BinOp::Le => (lhs <= rhs).to_string(),
         BinOp::Ge => (lhs >= rhs).to_string(),
-        BinOp::And => (coerce_bool(&lhs) && coerce_bool(&rhs)).to_string(),
-        BinOp::Or => (coerce_bool(&lhs) || coerce_bool(&rhs)).to_string(),
+        BinOp::And => (coerce_bool(lhs) && coerce_bool(rhs)).to_string(),
+        BinOp::Or => (coerce_bool(lhs) || coerce_bool(rhs)).to_string(),
     };
     Ok(res)
 }
 
+/// Whether the ordering of the operands satisfies the comparison operator.
+fn compare_result(op: &BinOp, ord: std::cmp::Ordering) -> bool {
+    match op {
+        BinOp::Eq => ord.is_eq(),
+        BinOp::Ne => ord.is_ne(),
+        BinOp::Lt => ord.is_lt(),
+        BinOp::Gt => ord.is_gt(),
+        BinOp::Le => ord.is_le(),
+        _ => ord.is_ge(),
+    }
+}
+
 /// Mapping from node address to the accumulator of the aggregate function.
 /// Since aggregate functions can appear multiple times in an expression, we cannot allocate a fixed size buffer for
 /// accumulating them. So we use hash maps from a unique id of the AST node to the accumulator.
 /// The unique id is the memory address, which means the AST is expected to keep an address.
 #[derive(Debug, Default)]
 pub(crate) struct AggregateResult {
+    /// Whether the expression is evaluated in an aggregate context. A missing accumulator means that no values
+    /// were aggregated in that case, rather than an aggregate function called in scalar context.
+    pub active: bool,
     pub count: HashMap<usize, usize>,
-    pub sum: HashMap<usize, f64>,
+    pub sum: HashMap<usize, Number>,
     pub avg: HashMap<usize, AggregateAvg>,
-    pub min: HashMap<usize, f64>,
-    pub max: HashMap<usize, f64>,
+    pub min: HashMap<usize, Extreme>,
+    pub max: HashMap<usize, Extreme>,
+    pub distinct: HashMap<usize, HyperLogLog>,
+    pub quantile: HashMap<usize, TDigest>,
+    /// The values seen so far by count(DISTINCT col), whose count is kept in `count`
+    pub count_distinct: HashMap<usize, HashSet<String>>,
+    /// The values concatenated so far by string_agg or group_concat
+    pub string_agg: HashMap<usize, String>,
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+/// The least or the greatest value aggregated so far by min or max.
+#[derive(Clone, Debug)]
+pub(crate) enum Extreme {
+    /// A number, output with the precision of the options
+    Number(Number),
+    /// A text or a date, output as it is written in the table
+    Value(String),
+}
+
+impl std::fmt::Display for Extreme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(val) => val.fmt(f),
+            Self::Value(val) => val.fmt(f),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub(crate) struct AggregateAvg {
-    sum: f64,
+    sum: Number,
     count: usize,
 }
 
+impl AggregateAvg {
+    fn mean(&self) -> Result<Number, EvalError> {
+        self.sum
+            .checked_div(Number::Int(self.count as i64))
+            .ok_or(EvalError::Overflow)
+    }
+}
+
 pub(crate) fn aggregate_expr(
     expr: &Expr,
     cols: &[Expr],
@@ -170,17 +630,42 @@ pub(crate) fn aggregate_expr(
     row_cursor: &[RowCursor],
     results: &mut AggregateResult,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let eval_col_spec = |name: &str, col_spec: &ColSpecifier| {
-        let ex = match col_spec {
+    // Returns None if the value is NULL and should be ignored by the aggregate function
+    let eval_col_spec = |name: &'static str, args: &[ColSpecifier]| {
+        let ex = match args.first().ok_or(EvalError::InsufficientArg(name))? {
             ColSpecifier::Expr(ex) => ex,
-            ColSpecifier::Wildcard => {
+            ColSpecifier::Wildcard(_) => {
                 return Err(EvalError::DisallowedWildcard(name.to_string()));
             }
         };
-        eval_expr(ex, cols, ctx, row_cursor, results).and_then(|val| {
-            val.parse::<f64>()
-                .map_err(|_| EvalError::Coerce("String".to_string(), "f64".to_string()))
-        })
+        let null = || match ctx.options.aggregate_nulls {
+            AggregateNulls::Standard => None,
+            AggregateNulls::Spreadsheet => Number::parse("0", ctx.options.decimal),
+        };
+        let coerce = || EvalError::Coerce("String".to_string(), "f64".to_string());
+        // A column is read from the numbers cached in the table rather than parsed again
+        if let Some(col) = ctx.column_of(ex)? {
+            return match col.get(row_cursor) {
+                Ok(val) if val.is_empty() => Ok(null()),
+                Ok(_) => col
+                    .get_number(row_cursor, ctx.options.decimal)?
+                    .map(Some)
+                    .ok_or_else(coerce),
+                Err(EvalError::CursorNone(_)) => Ok(null()),
+                Err(e) => Err(e),
+            };
+        }
+        let val = match eval_expr(ex, cols, ctx, row_cursor, results) {
+            Ok(val) => val,
+            Err(EvalError::CursorNone(_)) => "".to_string(),
+            Err(e) => return Err(e),
+        };
+        if val.is_empty() {
+            return Ok(null());
+        }
+        Number::parse(&val, ctx.options.decimal)
+            .map(Some)
+            .ok_or_else(coerce)
     };
 
     match expr {
@@ -191,88 +676,532 @@ pub(crate) fn aggregate_expr(
                         .ok_or_else(|| EvalError::ColNotFound(format!("{i}")))?,
                 )
                 .ok_or_else(|| EvalError::ColNotFound(format!("{i}")))?;
-            if expr as *const _ == col as *const _ {
+            if std::ptr::eq(expr, col) {
                 return Err("Recurse".into());
             }
             aggregate_expr(col, cols, ctx, row_cursor, results)
         }
-        Expr::Binary { op, lhs, rhs } => {
-            let lhs = aggregate_expr(lhs, cols, ctx, row_cursor, results)?;
-            let rhs = aggregate_expr(rhs, cols, ctx, row_cursor, results)?;
-            Ok(eval_bin_op(op, lhs, rhs)?)
+        Expr::Binary {
+            op,
+            lhs: lhs_ex,
+            rhs: rhs_ex,
+        } => {
+            let lhs = aggregate_expr(lhs_ex, cols, ctx, row_cursor, results)?;
+            let rhs = aggregate_expr(rhs_ex, cols, ctx, row_cursor, results)?;
+            let ty = comparison_type(lhs_ex, rhs_ex, cols, ctx);
+            let (lhs, rhs) = collate_operands(op, lhs_ex, rhs_ex, lhs.into(), rhs.into());
+            Ok(eval_bin_op(op, &lhs, &rhs, ty, ctx)?)
         }
         Expr::Unary { op, operand } => {
             let val = aggregate_expr(operand, cols, ctx, row_cursor, results)?;
-            let res = match op {
-                UniOp::Not => !coerce_bool(&val),
-            };
-            Ok((if res { "1" } else { "0" }).to_string())
+            Ok(eval_uni_op(op, val, ctx)?)
         }
-        Expr::AggregateFn { name, args } => match name.to_ascii_lowercase().as_str() {
-            "length" | "upper" | "lower" => {
-                return Ok(eval_expr(expr, cols, ctx, row_cursor, results)?);
+        Expr::AggregateFn {
+            name,
+            args,
+            distinct,
+        } => match name.to_ascii_lowercase().as_str() {
+            fn_name
+                if find_function(fn_name).is_some_and(|func| func.kind == FunctionKind::Scalar) =>
+            {
+                Ok(eval_expr(expr, cols, ctx, row_cursor, results)?)
+            }
+            _ if *distinct && !name.eq_ignore_ascii_case("count") => {
+                Err(EvalError::DisallowedDistinct(name.clone()).into())
+            }
+            "count" if *distinct => {
+                let arg = args
+                    .first()
+                    .ok_or(EvalError::InsufficientArg("count"))?
+                    .as_expr()?;
+                let val = match eval_expr(arg, cols, ctx, row_cursor, results) {
+                    Ok(val) => val,
+                    Err(EvalError::CursorNone(_)) => "".to_string(),
+                    Err(e) => return Err(e.into()),
+                };
+                let key = expr as *const _ as usize;
+                let seen = results.count_distinct.entry(key).or_default();
+                // NULLs are not counted even with --spreadsheet-nulls, since they are not distinct values
+                if !val.is_empty() {
+                    seen.insert(val);
+                }
+                let count = seen.len();
+                results.count.insert(key, count);
+                Ok(count.to_string())
             }
             "count" => {
-                let entry = results.count.entry(expr as *const _ as usize);
-                let count = entry.or_default();
-                *count += 1;
-                return Ok(count.to_string());
+                let is_null = match args.first() {
+                    Some(ColSpecifier::Expr(ex)) => {
+                        match eval_expr(ex, cols, ctx, row_cursor, results) {
+                            Ok(val) => val.is_empty(),
+                            Err(EvalError::CursorNone(_)) => true,
+                            Err(e) => return Err(e.into()),
+                        }
+                    }
+                    _ => false,
+                };
+                let count = results.count.entry(expr as *const _ as usize).or_default();
+                if !is_null || ctx.options.aggregate_nulls == AggregateNulls::Spreadsheet {
+                    *count += 1;
+                }
+                Ok(count.to_string())
             }
             "sum" => {
-                let val = eval_col_spec("sum", &args[0])?;
-                let entry = results.sum.entry(expr as *const _ as usize);
-                let values = entry.or_default();
-                *values += val;
-                return Ok(values.to_string());
+                let Some(val) = eval_col_spec("sum", args)? else {
+                    return Ok(eval_expr(expr, cols, ctx, row_cursor, results)?);
+                };
+                let key = expr as *const _ as usize;
+                let sum = match results.sum.get(&key) {
+                    Some(sum) => sum.checked_add(val).ok_or(EvalError::Overflow)?,
+                    None => val,
+                };
+                results.sum.insert(key, sum);
+                Ok(sum.to_string())
             }
             "avg" => {
-                let val = eval_col_spec("avg", &args[0])?;
-                let entry = results.avg.entry(expr as *const _ as usize);
-                let values = entry.or_default();
-                values.count += 1;
-                values.sum += val;
-                return Ok((values.sum / values.count as f64).to_string());
-            }
-            "min" => {
-                let val = eval_col_spec("min", &args[0])?;
-                let entry = results.min.entry(expr as *const _ as usize);
-                let values = entry.or_default();
-                *values = values.min(val);
-                return Ok(values.to_string());
-            }
-            "max" => {
-                let val = eval_col_spec("max", &args[0])?;
-                let entry = results.max.entry(expr as *const _ as usize);
-                let values = entry.or_default();
-                *values = values.max(val);
-                return Ok(values.to_string());
-            }
-            _ => return Err(format!("Unknown function {name}").into()),
+                let Some(val) = eval_col_spec("avg", args)? else {
+                    return Ok(eval_expr(expr, cols, ctx, row_cursor, results)?);
+                };
+                let key = expr as *const _ as usize;
+                let values = match results.avg.get(&key) {
+                    Some(v) => AggregateAvg {
+                        sum: v.sum.checked_add(val).ok_or(EvalError::Overflow)?,
+                        count: v.count + 1,
+                    },
+                    None => AggregateAvg { sum: val, count: 1 },
+                };
+                results.avg.insert(key, values);
+                Ok(values.mean()?.to_string())
+            }
+            fn_name @ ("min" | "max") => {
+                let (name, wanted) = if fn_name == "min" {
+                    ("min", std::cmp::Ordering::Less)
+                } else {
+                    ("max", std::cmp::Ordering::Greater)
+                };
+                let ty = extreme_type(args, cols, ctx);
+                let val = if ty.is_some() {
+                    let arg = args.first().ok_or(EvalError::InsufficientArg(name))?;
+                    let val = match eval_expr(arg.as_expr()?, cols, ctx, row_cursor, results) {
+                        Ok(val) => val,
+                        Err(EvalError::CursorNone(_)) => String::new(),
+                        Err(e) => return Err(e.into()),
+                    };
+                    // NULLs are ignored even with --spreadsheet-nulls, since they are not 0 in this type
+                    if val.is_empty() {
+                        return Ok(eval_expr(expr, cols, ctx, row_cursor, results)?);
+                    }
+                    Extreme::Value(val)
+                } else {
+                    let Some(val) = eval_col_spec(name, args)? else {
+                        return Ok(eval_expr(expr, cols, ctx, row_cursor, results)?);
+                    };
+                    Extreme::Number(val)
+                };
+                let extremes = if fn_name == "min" {
+                    &mut results.min
+                } else {
+                    &mut results.max
+                };
+                let key = expr as *const _ as usize;
+                let ord = extremes.get(&key).map(|extreme| match (&val, extreme) {
+                    (Extreme::Number(val), Extreme::Number(v)) => {
+                        val.to_f64().partial_cmp(&v.to_f64())
+                    }
+                    (Extreme::Value(val), Extreme::Value(v)) => ty.map(|ty| ty.compare(val, v)),
+                    _ => None,
+                });
+                if ord.is_none_or(|ord| ord == Some(wanted)) {
+                    extremes.insert(key, val);
+                }
+                Ok(extremes[&key].to_string())
+            }
+            "approx_count_distinct" => {
+                let arg = args
+                    .first()
+                    .ok_or(EvalError::InsufficientArg("approx_count_distinct"))?
+                    .as_expr()?;
+                let val = match eval_expr(arg, cols, ctx, row_cursor, results) {
+                    Ok(val) => val,
+                    Err(EvalError::CursorNone(_)) => "".to_string(),
+                    Err(e) => return Err(e.into()),
+                };
+                let hll = results
+                    .distinct
+                    .entry(expr as *const _ as usize)
+                    .or_default();
+                // NULLs are not counted, like count(DISTINCT col)
+                if !val.is_empty() {
+                    hll.add(&val);
+                }
+                // The running value is not used for the output, and estimating on every row is costly.
+                Ok(String::new())
+            }
+            "approx_quantile" => {
+                // Validate the quantile before any values are aggregated
+                quantile_arg(args, cols, ctx, row_cursor, results)?;
+                if let Some(val) = eval_col_spec("approx_quantile", args)? {
+                    let digest = results
+                        .quantile
+                        .entry(expr as *const _ as usize)
+                        .or_default();
+                    digest.add(val.to_f64());
+                }
+                Ok(String::new())
+            }
+            "string_agg" | "group_concat" => {
+                let fn_name = if name.eq_ignore_ascii_case("string_agg") {
+                    "string_agg"
+                } else {
+                    "group_concat"
+                };
+                let arg = args
+                    .first()
+                    .ok_or(EvalError::InsufficientArg(fn_name))?
+                    .as_expr()?;
+                let val = match eval_expr(arg, cols, ctx, row_cursor, results) {
+                    Ok(val) => val,
+                    Err(EvalError::CursorNone(_)) => "".to_string(),
+                    Err(e) => return Err(e.into()),
+                };
+                // The separator is required by string_agg like PostgreSQL, and defaults to ',' in group_concat
+                // like MySQL and SQLite
+                let separator = match args.get(1) {
+                    Some(sep) => eval_expr(sep.as_expr()?, cols, ctx, row_cursor, results)?,
+                    None if fn_name == "group_concat" => ",".to_string(),
+                    None => return Err(EvalError::InsufficientArg(fn_name).into()),
+                };
+                // NULLs are skipped
+                if !val.is_empty() {
+                    let key = expr as *const _ as usize;
+                    if let Some(acc) = results.string_agg.get_mut(&key) {
+                        acc.push_str(&separator);
+                        acc.push_str(&val);
+                    } else {
+                        results.string_agg.insert(key, val);
+                    }
+                }
+                // The running value is not used for the output, so avoid copying the whole string on every row.
+                Ok(String::new())
+            }
+            _ => Err(format!("Unknown function {name}").into()),
         },
-        _ => {
-            return Ok(eval_expr(expr, cols, ctx, row_cursor, results)?);
-        }
+        _ => Ok(eval_expr(expr, cols, ctx, row_cursor, results)?),
     }
 }
 
-pub(crate) fn find_aggregate_fn(expr: &Expr, ctx: &QueryContext) -> Option<usize> {
+pub(crate) fn find_aggregate_fn(expr: &Expr) -> Option<usize> {
     match expr {
-        Expr::AggregateFn { name, .. } => match name.to_ascii_lowercase().as_str() {
-            "count" | "sum" | "avg" | "max" | "min" => return Some(expr as *const _ as usize),
-            _ => None,
-        },
-        Expr::Binary { lhs, rhs, .. } => {
-            find_aggregate_fn(lhs, ctx).or_else(|| find_aggregate_fn(rhs, ctx))
+        Expr::AggregateFn { name, .. } if is_aggregate(name) => Some(expr as *const _ as usize),
+        Expr::Binary { lhs, rhs, .. } => find_aggregate_fn(lhs).or_else(|| find_aggregate_fn(rhs)),
+        Expr::Unary { operand, .. } => find_aggregate_fn(operand),
+        Expr::Like { lhs, pattern, .. } => {
+            find_aggregate_fn(lhs).or_else(|| find_aggregate_fn(pattern))
         }
-        Expr::Unary { operand, .. } => find_aggregate_fn(operand, ctx),
         _ => None,
     }
 }
 
+/// An element of a LIKE pattern after the escapes are resolved
+#[derive(Clone, Copy, PartialEq)]
+enum LikeToken {
+    Literal(char),
+    AnyChar,
+    AnySequence,
+}
+
+/// Matches the characters to a LIKE pattern, in which `%` matches any sequence of characters and `_` any single
+/// character. The escape character makes the next one match literally.
+///
+/// The match runs with two cursors, remembering the last `%` and the position in the string it was tried at.
+/// On a mismatch the `%` takes one more character and the match resumes after it, which is O(n·m) at worst
+/// without recursion.
+fn like_match(s: &[char], pattern: &[char], escape: Option<char>) -> Result<bool, EvalError> {
+    let mut tokens = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.iter();
+    while let Some(&c) = chars.next() {
+        tokens.push(match c {
+            _ if Some(c) == escape => match chars.next() {
+                Some(&literal) => LikeToken::Literal(literal),
+                None => return Err(EvalError::InvalidPattern(pattern.iter().collect())),
+            },
+            '%' => LikeToken::AnySequence,
+            '_' => LikeToken::AnyChar,
+            _ => LikeToken::Literal(c),
+        });
+    }
+
+    let (mut si, mut pi) = (0, 0);
+    let mut backtrack = None;
+    while si < s.len() {
+        match tokens.get(pi) {
+            Some(LikeToken::AnySequence) => {
+                pi += 1;
+                backtrack = Some((pi, si));
+            }
+            Some(LikeToken::AnyChar) => {
+                si += 1;
+                pi += 1;
+            }
+            Some(LikeToken::Literal(c)) if *c == s[si] => {
+                si += 1;
+                pi += 1;
+            }
+            _ => {
+                let Some((star_pi, star_si)) = backtrack else {
+                    return Ok(false);
+                };
+                pi = star_pi;
+                si = star_si + 1;
+                backtrack = Some((star_pi, si));
+            }
+        }
+    }
+    Ok(tokens[pi..]
+        .iter()
+        .all(|token| *token == LikeToken::AnySequence))
+}
+
+/// Evaluates a string function on the values of its arguments. Positions are counted in characters from 1.
+fn eval_string_fn(name: &str, args: &[String]) -> Result<String, EvalError> {
+    let (func, min_args): (&'static str, usize) = match name {
+        "substr" => ("substr", 2),
+        "trim" => ("trim", 1),
+        "ltrim" => ("ltrim", 1),
+        "rtrim" => ("rtrim", 1),
+        "replace" => ("replace", 3),
+        "instr" => ("instr", 2),
+        "lpad" => ("lpad", 2),
+        "rpad" => ("rpad", 2),
+        _ => return Err(EvalError::AggregateCall(name.to_string())),
+    };
+    if args.len() < min_args {
+        return Err(EvalError::InsufficientArg(func));
+    }
+    let int_arg = |i: usize| -> Result<i64, EvalError> {
+        args[i]
+            .parse()
+            .map_err(|_| EvalError::Coerce(format!("'{}'", args[i]), "integer".to_string()))
+    };
+    let s = &args[0];
+    // The characters to trim, or whitespace if not given
+    let trimmed = |c: char| args.get(1).map_or(c.is_whitespace(), |set| set.contains(c));
+
+    Ok(match func {
+        // Like the standard SQL, the substring is the intersection of the range [start, start + len)
+        // with the string, so that a start before the first character shortens the result
+        "substr" => {
+            let start = int_arg(1)?;
+            let end = match args.get(2) {
+                Some(_) => start.saturating_add(int_arg(2)?.max(0)),
+                None => i64::MAX,
+            };
+            let (skip, end) = (start.max(1) - 1, end.max(1) - 1);
+            s.chars()
+                .skip(skip as usize)
+                .take(end.saturating_sub(skip) as usize)
+                .collect()
+        }
+        "trim" => s.trim_matches(trimmed).to_string(),
+        "ltrim" => s.trim_start_matches(trimmed).to_string(),
+        "rtrim" => s.trim_end_matches(trimmed).to_string(),
+        // Replacing an empty string would insert `to` between every character
+        "replace" if args[1].is_empty() => s.clone(),
+        "replace" => s.replace(&args[1], &args[2]),
+        "instr" => s
+            .find(&args[1])
+            .map_or(0, |pos| s[..pos].chars().count() + 1)
+            .to_string(),
+        // A string longer than the length is truncated, and an empty fill does not pad
+        _ => {
+            let len = int_arg(1)?.max(0) as usize;
+            let fill = args.get(2).map_or(" ", |fill| fill.as_str());
+            let chars = s.chars().count();
+            if len <= chars || fill.is_empty() {
+                return Ok(s.chars().take(len).collect());
+            }
+            let pad: String = fill.chars().cycle().take(len - chars).collect();
+            if func == "lpad" {
+                pad + s
+            } else {
+                s.clone() + &pad
+            }
+        }
+    })
+}
+
+/// Evaluates a numeric function on the values of its arguments, which are parsed like the operands of arithmetic.
+/// A NULL argument or a zero divisor of mod yields NULL.
+fn eval_numeric_fn(name: &str, args: &[String], decimal: bool) -> Result<String, EvalError> {
+    let (func, min_args): (&'static str, usize) = match name {
+        "round" => ("round", 1),
+        "abs" => ("abs", 1),
+        "ceil" => ("ceil", 1),
+        "floor" => ("floor", 1),
+        "mod" => ("mod", 2),
+        _ => return Err(EvalError::AggregateCall(name.to_string())),
+    };
+    if args.len() < min_args {
+        return Err(EvalError::InsufficientArg(func));
+    }
+    if args.iter().any(|arg| arg.is_empty()) {
+        return Ok("".to_string());
+    }
+    let num = |i: usize| {
+        Number::parse(&args[i], decimal)
+            .ok_or_else(|| EvalError::Coerce(format!("'{}'", args[i]), "number".to_string()))
+    };
+    let res = match func {
+        "round" => {
+            let places = match args.get(1) {
+                Some(places) => places
+                    .parse()
+                    .map_err(|_| EvalError::Coerce(format!("'{places}'"), "integer".to_string()))?,
+                None => 0,
+            };
+            num(0)?.round(places)
+        }
+        "abs" => num(0)?.checked_abs().ok_or(EvalError::Overflow)?,
+        "ceil" => num(0)?.ceil(),
+        "floor" => num(0)?.floor(),
+        _ => {
+            let divisor = num(1)?;
+            if divisor.to_f64() == 0. {
+                return Ok("".to_string());
+            }
+            num(0)?.checked_rem(divisor).ok_or(EvalError::Overflow)?
+        }
+    };
+    Ok(res.to_string())
+}
+
+/// Evaluates a date function on the values of its arguments. A NULL argument yields NULL.
+fn eval_date_fn(name: &str, args: &[String], options: &ExecOptions) -> Result<String, EvalError> {
+    let (func, min_args): (&'static str, usize) = match name {
+        "now" => return Ok(DateTime::now(options)?.to_string()),
+        "date_part" => ("date_part", 2),
+        "date_add" => ("date_add", 3),
+        "date_trunc" => ("date_trunc", 2),
+        _ => return Err(EvalError::AggregateCall(name.to_string())),
+    };
+    if args.len() < min_args {
+        return Err(EvalError::InsufficientArg(func));
+    }
+    if args.iter().any(|arg| arg.is_empty()) {
+        return Ok("".to_string());
+    }
+    let date = |i: usize| {
+        DateTime::parse(&args[i], options.utc_offset)
+            .ok_or_else(|| EvalError::Coerce(format!("'{}'", args[i]), "date".to_string()))
+    };
+    Ok(match func {
+        "date_part" => date(1)?.part(&args[0])?.to_string(),
+        "date_add" => {
+            let n = args[1]
+                .parse()
+                .map_err(|_| EvalError::Coerce(format!("'{}'", args[1]), "integer".to_string()))?;
+            date(0)?.add(n, &args[2])?.to_string()
+        }
+        _ => date(1)?.trunc(&args[0])?.to_string(),
+    })
+}
+
 pub(crate) fn coerce_bool(val: &str) -> bool {
     val == "1" || val.eq_ignore_ascii_case("true")
 }
 
-pub(crate) fn coerce_f64(val: &str) -> f64 {
-    val.parse().unwrap_or(0.)
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_like_match() {
+        let like = |s: &str, pattern: &str, escape| {
+            let s: Vec<_> = s.chars().collect();
+            let pattern: Vec<_> = pattern.chars().collect();
+            like_match(&s, &pattern, escape)
+        };
+        assert!(like("Isaac Asimov", "Is%", None).unwrap());
+        assert!(like("Isaac Asimov", "%A_imov", None).unwrap());
+        assert!(!like("Isaac Asimov", "is%", None).unwrap());
+        assert!(like("", "%", None).unwrap());
+        assert!(!like("", "_", None).unwrap());
+        assert!(like("50%", "%\\%", Some('\\')).unwrap());
+        assert!(!like("500", "%\\%", Some('\\')).unwrap());
+        assert!(like("snake_case", "snake!_%", Some('!')).unwrap());
+        assert!(!like("snakeXcase", "snake!_%", Some('!')).unwrap());
+        assert!(like("a!b", "a!!b", Some('!')).unwrap());
+        assert!(like("abc", "abc!", Some('!')).is_err());
+        assert!(like("abcabd", "%ab_", None).unwrap());
+        assert!(!like("abcabc", "%ab_d%", None).unwrap());
+        // Would take exponential time with backtracking over each `%`
+        let s = "a".repeat(200);
+        assert!(!like(&s, &format!("{}b", "%a".repeat(30)), None).unwrap());
+        assert!(like(&s, &"%a".repeat(30), None).unwrap());
+    }
+
+    #[test]
+    fn test_numeric_fn() {
+        let call = |name: &str, args: &[&str], decimal| {
+            let args: Vec<_> = args.iter().map(|s| s.to_string()).collect();
+            eval_numeric_fn(name, &args, decimal).map_err(|e| e.to_string())
+        };
+        assert_eq!(call("round", &["1.62", "1"], false).unwrap(), "1.6");
+        assert_eq!(call("round", &["2.5"], false).unwrap(), "3");
+        assert_eq!(call("round", &["-2.5"], false).unwrap(), "-3");
+        assert_eq!(call("round", &["1234", "-2"], false).unwrap(), "1200");
+        assert_eq!(call("round", &["1.005", "2"], true).unwrap(), "1.01");
+        assert_eq!(call("round", &["7", "2"], false).unwrap(), "7");
+        assert_eq!(call("abs", &["-3"], false).unwrap(), "3");
+        assert_eq!(call("abs", &["-1.50"], true).unwrap(), "1.50");
+        assert_eq!(
+            call("abs", &[&i64::MIN.to_string()], false),
+            Err("Integer overflow".to_string())
+        );
+        assert_eq!(call("ceil", &["1.2"], false).unwrap(), "2");
+        assert_eq!(call("floor", &["-1.2"], false).unwrap(), "-2");
+        assert_eq!(call("mod", &["7", "3"], false).unwrap(), "1");
+        assert_eq!(call("mod", &["-7", "3"], false).unwrap(), "-1");
+        assert_eq!(call("mod", &["5.5", "2"], true).unwrap(), "1.5");
+        assert_eq!(call("mod", &["7", "0"], false).unwrap(), "");
+        assert_eq!(call("round", &[""], false).unwrap(), "");
+        assert_eq!(
+            call("round", &["abc"], false),
+            Err("Coercion from 'abc' to number".to_string())
+        );
+        assert!(call("mod", &["7"], false).is_err());
+    }
+
+    #[test]
+    fn test_string_fn() {
+        let call = |name: &str, args: &[&str]| {
+            let args: Vec<_> = args.iter().map(|s| s.to_string()).collect();
+            eval_string_fn(name, &args).map_err(|e| e.to_string())
+        };
+        assert_eq!(call("substr", &["hello", "2", "3"]).unwrap(), "ell");
+        assert_eq!(call("substr", &["hello", "2"]).unwrap(), "ello");
+        assert_eq!(call("substr", &["hello", "0", "2"]).unwrap(), "h");
+        assert_eq!(call("substr", &["hello", "-5", "3"]).unwrap(), "");
+        assert_eq!(call("substr", &["hello", "4", "10"]).unwrap(), "lo");
+        assert_eq!(call("substr", &["hello", "10"]).unwrap(), "");
+        assert_eq!(call("substr", &["hello", "2", "-1"]).unwrap(), "");
+        assert_eq!(call("substr", &["héllo", "2", "1"]).unwrap(), "é");
+        assert_eq!(
+            call("substr", &["hello", "x"]),
+            Err("Coercion from 'x' to integer".to_string())
+        );
+        assert!(call("substr", &["hello"]).is_err());
+
+        assert_eq!(call("trim", &["  a b  "]).unwrap(), "a b");
+        assert_eq!(call("ltrim", &["  a  "]).unwrap(), "a  ");
+        assert_eq!(call("rtrim", &["xxaxx", "x"]).unwrap(), "xxa");
+        assert_eq!(call("replace", &["a-b-c", "-", "+"]).unwrap(), "a+b+c");
+        assert_eq!(call("replace", &["abc", "", "+"]).unwrap(), "abc");
+        assert_eq!(call("instr", &["héllo", "l"]).unwrap(), "3");
+        assert_eq!(call("instr", &["hello", "z"]).unwrap(), "0");
+        assert_eq!(call("lpad", &["7", "3", "0"]).unwrap(), "007");
+        assert_eq!(call("rpad", &["ab", "5", "xy"]).unwrap(), "abxyx");
+        assert_eq!(call("lpad", &["hello", "2"]).unwrap(), "he");
+        assert_eq!(call("rpad", &["ab", "4", ""]).unwrap(), "ab");
+    }
 }
@@ -0,0 +1,402 @@
+//! Query plan description for `EXPLAIN`.
+//! The executor does not have a separate planner, so the plan tree is derived directly from the
+//! shape of the [`SelectStmt`], mirroring the order in which `exec_select` processes the clauses.
+
+use std::io::Write;
+
+use crate::{
+    db::Database,
+    eval::find_aggregate_fn,
+    join::JoinMethod,
+    options::ExecOptions,
+    select::{ColSpecifier, Expr, GroupBy, JoinKind, SelectStmt, TableSpecifier, write_json_str},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplainStmt {
+    pub format: ExplainFormat,
+    pub stmt: SelectStmt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainFormat {
+    /// Indented tree, one node per line
+    Text,
+    /// Flowchart definition for [mermaid](https://mermaid.js.org/)
+    Mermaid,
+    /// Graphviz dot language
+    Dot,
+    /// Nested objects with the `label` and the `children` of each node, for a UI to render the tree
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PlanNode {
+    pub label: String,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    fn leaf(label: String) -> Self {
+        Self {
+            label,
+            children: vec![],
+        }
+    }
+
+    fn wrap(label: String, child: PlanNode) -> Self {
+        Self {
+            label,
+            children: vec![child],
+        }
+    }
+}
+
+fn scan_label(table: &TableSpecifier) -> String {
+    format!("Scan {table}")
+}
+
+fn cols_label(cols: &[ColSpecifier]) -> String {
+    cols.iter()
+        .map(|col| col.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Build the plan tree of a select statement, from the root (the last step) to the leaves (table scans).
+/// The options decide the method of a join as in the execution, e.g. resolving the key columns ignoring case.
+pub(crate) fn plan_select(db: &Database, sql: &SelectStmt, options: &ExecOptions) -> PlanNode {
+    let mut node = PlanNode::leaf(scan_label(&sql.table));
+
+    for (i, join) in sql.join.iter().enumerate() {
+        let kind = match join.kind {
+            JoinKind::Inner => "Inner",
+            JoinKind::Left => "Left",
+            JoinKind::Right => "Right",
+            JoinKind::Full => "Full",
+        };
+        node = PlanNode {
+            label: format!(
+                "{} {kind} Join ON {:#}",
+                JoinMethod::for_join(db, sql, i, options).name(),
+                join.condition
+            ),
+            children: vec![node, PlanNode::leaf(scan_label(&join.table))],
+        };
+    }
+
+    if let Some(ref cond) = sql.condition {
+        node = PlanNode::wrap(format!("Filter {cond}"), node);
+    }
+
+    let mut windows = vec![];
+    let mut find_window = |ex: &Expr| {
+        // The same window function may be both in the columns and QUALIFY
+        if matches!(ex, Expr::Window { .. }) && !windows.contains(&ex.to_string()) {
+            windows.push(ex.to_string());
+        }
+    };
+    for col in &sql.cols {
+        col.visit(&mut find_window);
+    }
+    if let Some(ref qualify) = sql.qualify {
+        qualify.visit(&mut find_window);
+    }
+    if !windows.is_empty() {
+        node = PlanNode::wrap(format!("Window {}", windows.join(", ")), node);
+    }
+
+    if let Some(ref qualify) = sql.qualify {
+        node = PlanNode::wrap(format!("Qualify {qualify}"), node);
+    }
+
+    let aggregate = sql
+        .cols
+        .iter()
+        .any(|col| matches!(col, ColSpecifier::Expr(ex) if find_aggregate_fn(ex).is_some()));
+    let label = match sql.group_by {
+        Some(GroupBy::All) => format!("Aggregate {} GROUP BY ALL", cols_label(&sql.cols)),
+        Some(GroupBy::Exprs(ref exprs)) => format!(
+            "Aggregate {} GROUP BY {}",
+            cols_label(&sql.cols),
+            exprs
+                .iter()
+                .map(|ex| ex.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        None if aggregate => format!("Aggregate {}", cols_label(&sql.cols)),
+        None => format!("Project {}", cols_label(&sql.cols)),
+    };
+    node = PlanNode::wrap(label, node);
+
+    if !sql.ordering.is_empty() {
+        let keys = sql
+            .ordering
+            .iter()
+            .map(|order_by| order_by.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        node = PlanNode::wrap(format!("Sort {keys}"), node);
+    }
+
+    if sql.distinct {
+        node = PlanNode::wrap("Distinct".to_string(), node);
+    }
+
+    match (sql.limit, sql.offset) {
+        (Some(limit), Some(offset)) => {
+            node = PlanNode::wrap(format!("Limit {limit} OFFSET {offset}"), node)
+        }
+        (Some(limit), None) => node = PlanNode::wrap(format!("Limit {limit}"), node),
+        (None, Some(offset)) => node = PlanNode::wrap(format!("Offset {offset}"), node),
+        (None, None) => {}
+    }
+
+    node
+}
+
+pub fn format_explain(
+    out: &mut impl Write,
+    db: &Database,
+    sql: &SelectStmt,
+    options: &ExecOptions,
+    format: ExplainFormat,
+) -> std::io::Result<()> {
+    let plan = plan_select(db, sql, options);
+    match format {
+        ExplainFormat::Text => write_text(out, &plan, 0),
+        ExplainFormat::Mermaid => {
+            writeln!(out, "graph TD")?;
+            write_graph(out, &plan, format, &mut 0)?;
+            Ok(())
+        }
+        ExplainFormat::Dot => {
+            writeln!(out, "digraph plan {{")?;
+            write_graph(out, &plan, format, &mut 0)?;
+            writeln!(out, "}}")
+        }
+        ExplainFormat::Json => {
+            write_json(out, &plan)?;
+            writeln!(out)
+        }
+    }
+}
+
+/// Write the node as a JSON object like `{"label":"Scan t","children":[]}` in a single line.
+fn write_json(out: &mut impl Write, node: &PlanNode) -> std::io::Result<()> {
+    write!(out, "{{\"label\":")?;
+    write_json_str(out, &node.label)?;
+    write!(out, ",\"children\":[")?;
+    for (i, child) in node.children.iter().enumerate() {
+        if i != 0 {
+            write!(out, ",")?;
+        }
+        write_json(out, child)?;
+    }
+    write!(out, "]}}")
+}
+
+fn write_text(out: &mut impl Write, node: &PlanNode, depth: usize) -> std::io::Result<()> {
+    if depth == 0 {
+        writeln!(out, "{}", node.label)?;
+    } else {
+        writeln!(
+            out,
+            "{:indent$}-> {}",
+            "",
+            node.label,
+            indent = (depth - 1) * 4
+        )?;
+    }
+    for child in &node.children {
+        write_text(out, child, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// Write nodes and edges of a graph format in depth-first order, numbering the nodes sequentially.
+/// Returns the id of the given node.
+fn write_graph(
+    out: &mut impl Write,
+    node: &PlanNode,
+    format: ExplainFormat,
+    next_id: &mut usize,
+) -> std::io::Result<usize> {
+    let id = *next_id;
+    *next_id += 1;
+    match format {
+        ExplainFormat::Mermaid => {
+            writeln!(out, "    n{id}[\"{}\"]", node.label.replace('"', "#quot;"))?
+        }
+        _ => writeln!(
+            out,
+            "    n{id} [label=\"{}\"];",
+            node.label.replace('"', "\\\"")
+        )?,
+    }
+    for child in &node.children {
+        let child_id = write_graph(out, child, format, next_id)?;
+        match format {
+            ExplainFormat::Mermaid => writeln!(out, "    n{id} --> n{child_id}")?,
+            _ => writeln!(out, "    n{id} -> n{child_id};")?,
+        }
+    }
+    Ok(id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Statement, parser::statement};
+
+    fn explain(sql: &str) -> String {
+        let Statement::Explain(stmt) = statement(sql).unwrap().1 else {
+            panic!("Not an EXPLAIN statement");
+        };
+        let mut buf = vec![];
+        format_explain(
+            &mut buf,
+            &Database::new(),
+            &stmt.stmt,
+            &ExecOptions::DEFAULT,
+            stmt.format,
+        )
+        .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_explain_text() {
+        assert_eq!(
+            explain(
+                "EXPLAIN SELECT name FROM authors INNER JOIN books ON author_id = author LIMIT 2"
+            ),
+            r#"Limit 2
+-> Project name
+    -> Nested Loop Inner Join ON (author_id = author)
+        -> Scan authors
+        -> Scan books
+"#
+        );
+    }
+
+    #[test]
+    fn test_explain_merge_join() {
+        let db = crate::test_util::db_from_csv(&[("a", "id\n1\n2\n"), ("b", "a_id\n1\n1\n")]);
+        let Statement::Explain(stmt) =
+            statement("EXPLAIN SELECT * FROM a INNER JOIN b ON id = a_id")
+                .unwrap()
+                .1
+        else {
+            panic!("Not an EXPLAIN statement");
+        };
+        assert_eq!(
+            plan_select(&db, &stmt.stmt, &ExecOptions::DEFAULT).children[0].label,
+            "Merge Inner Join ON (id = a_id)"
+        );
+
+        // The key columns are resolved with the options of the execution
+        let Statement::Explain(stmt) =
+            statement("EXPLAIN SELECT * FROM a INNER JOIN b ON ID = A_ID")
+                .unwrap()
+                .1
+        else {
+            panic!("Not an EXPLAIN statement");
+        };
+        let label = |options: &ExecOptions| {
+            plan_select(&db, &stmt.stmt, options).children[0]
+                .label
+                .clone()
+        };
+        assert_eq!(
+            label(&ExecOptions::DEFAULT),
+            "Merge Inner Join ON (ID = A_ID)"
+        );
+        assert_eq!(
+            label(&ExecOptions::DEFAULT.with_case_insensitive(false)),
+            "Nested Loop Inner Join ON (ID = A_ID)"
+        );
+    }
+
+    #[test]
+    fn test_explain_joins() {
+        let db = crate::test_util::db_from_csv(&[
+            ("a", "id\n1\n2\n"),
+            ("b", "a_id\n1\n1\n"),
+            ("c", "a_id\n2\n1\n"),
+        ]);
+        let plan = |sql| {
+            let Statement::Select(stmt) = statement(sql).unwrap().1 else {
+                panic!("Not a SELECT statement");
+            };
+            let mut buf = vec![];
+            format_explain(
+                &mut buf,
+                &db,
+                &stmt,
+                &ExecOptions::DEFAULT,
+                ExplainFormat::Text,
+            )
+            .unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+        assert_eq!(
+            plan("SELECT * FROM a INNER JOIN b ON a.id = b.a_id"),
+            r#"Project *
+-> Merge Inner Join ON (a.id = b.a_id)
+    -> Scan a
+    -> Scan b
+"#
+        );
+        // Only the join of a statement with a single JOIN has the keys
+        assert_eq!(
+            plan("SELECT * FROM a INNER JOIN b ON a.id = b.a_id INNER JOIN c ON c.a_id = a.id"),
+            r#"Project *
+-> Nested Loop Inner Join ON (c.a_id = a.id)
+    -> Nested Loop Inner Join ON (a.id = b.a_id)
+        -> Scan a
+        -> Scan b
+    -> Scan c
+"#
+        );
+    }
+
+    #[test]
+    fn test_explain_mermaid() {
+        assert_eq!(
+            explain("EXPLAIN (FORMAT MERMAID) SELECT count(*) FROM t WHERE name = 'a'"),
+            r#"graph TD
+    n0["Aggregate count(*)"]
+    n1["Filter (name = 'a')"]
+    n2["Scan t"]
+    n1 --> n2
+    n0 --> n1
+"#
+        );
+    }
+
+    #[test]
+    fn test_explain_dot() {
+        assert_eq!(
+            explain("EXPLAIN (FORMAT DOT) SELECT * FROM t AS u ORDER BY id DESC"),
+            r#"digraph plan {
+    n0 [label="Sort id DESC"];
+    n1 [label="Project *"];
+    n2 [label="Scan t AS u"];
+    n1 -> n2;
+    n0 -> n1;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_explain_json() {
+        assert_eq!(
+            explain(r#"EXPLAIN (FORMAT JSON) SELECT * FROM t WHERE name = 'say "hi"' LIMIT 1"#),
+            r#"{"label":"Limit 1","children":[{"label":"Project *","children":[{"label":"Filter (name = 'say \"hi\"')","children":[{"label":"Scan t","children":[]}]}]}]}
+"#
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! The registry of the functions that can be called in queries, with their arities and descriptions, listed by
+//! `SHOW FUNCTIONS` so that the users can find what is available without leaving the REPL or the playground.
+
+use std::error::Error;
+
+use crate::{select::QueryOutput, table::ColumnType};
+
+use FunctionKind::{Aggregate, Scalar, Window};
+use ResultType::{FirstArg, Fixed, SumOfFirstArg};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionKind {
+    /// Evaluated on each row
+    Scalar,
+    /// Evaluated on the rows of a group, or of a window with `OVER`
+    Aggregate,
+    /// Evaluated on the rows of a window with `OVER`
+    Window,
+}
+
+impl std::fmt::Display for FunctionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scalar => write!(f, "scalar"),
+            Self::Aggregate => write!(f, "aggregate"),
+            Self::Window => write!(f, "window"),
+        }
+    }
+}
+
+/// The type of the values that a function returns, known before evaluating it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ResultType {
+    /// Values of any type, like those of a string literal
+    Untyped,
+    Fixed(ColumnType),
+    /// The type of the first argument, like the smallest of the values
+    FirstArg,
+    /// An integer for an integer argument and a number otherwise, like the sum of the values
+    SumOfFirstArg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionInfo {
+    /// The name in lowercase, though it is called in any case
+    pub name: &'static str,
+    pub kind: FunctionKind,
+    pub min_args: usize,
+    pub max_args: usize,
+    /// What the function returns, in a line
+    pub description: &'static str,
+    pub(crate) result: ResultType,
+}
+
+impl FunctionInfo {
+    /// The number of the arguments like `1` or `2-3`
+    pub fn arity(&self) -> String {
+        if self.min_args == self.max_args {
+            self.min_args.to_string()
+        } else {
+            format!("{}-{}", self.min_args, self.max_args)
+        }
+    }
+
+    /// The function returning the values of the type, rather than untyped ones
+    const fn with_result(self, result: ResultType) -> Self {
+        Self { result, ..self }
+    }
+}
+
+const fn function(
+    name: &'static str,
+    kind: FunctionKind,
+    min_args: usize,
+    max_args: usize,
+    description: &'static str,
+) -> FunctionInfo {
+    FunctionInfo {
+        name,
+        kind,
+        min_args,
+        max_args,
+        description,
+        result: ResultType::Untyped,
+    }
+}
+
+/// All the functions, by kind and then by category
+pub const FUNCTIONS: &[FunctionInfo] = &[
+    function("length", Scalar, 1, 1, "Number of characters of the string")
+        .with_result(Fixed(ColumnType::Integer)),
+    function("upper", Scalar, 1, 1, "The string in uppercase"),
+    function("lower", Scalar, 1, 1, "The string in lowercase"),
+    function(
+        "substr",
+        Scalar,
+        2,
+        3,
+        "Substring from the position counted from 1, optionally of the length",
+    ),
+    function(
+        "trim",
+        Scalar,
+        1,
+        2,
+        "The string without the leading and trailing whitespace, or the given characters",
+    ),
+    function(
+        "ltrim",
+        Scalar,
+        1,
+        2,
+        "The string without the leading whitespace, or the given characters",
+    ),
+    function(
+        "rtrim",
+        Scalar,
+        1,
+        2,
+        "The string without the trailing whitespace, or the given characters",
+    ),
+    function(
+        "replace",
+        Scalar,
+        3,
+        3,
+        "The string with every occurrence of the second replaced by the third",
+    ),
+    function(
+        "instr",
+        Scalar,
+        2,
+        2,
+        "Position of the first occurrence of the second string from 1, or 0",
+    )
+    .with_result(Fixed(ColumnType::Integer)),
+    function(
+        "lpad",
+        Scalar,
+        2,
+        3,
+        "The string padded on the left to the length with spaces or the given fill",
+    ),
+    function(
+        "rpad",
+        Scalar,
+        2,
+        3,
+        "The string padded on the right to the length with spaces or the given fill",
+    ),
+    function(
+        "round",
+        Scalar,
+        1,
+        2,
+        "The number rounded to the integer, or to the given decimal places",
+    )
+    .with_result(Fixed(ColumnType::Number)),
+    function("abs", Scalar, 1, 1, "Absolute value of the number")
+        .with_result(Fixed(ColumnType::Number)),
+    function(
+        "ceil",
+        Scalar,
+        1,
+        1,
+        "Smallest integer not less than the number",
+    )
+    .with_result(Fixed(ColumnType::Number)),
+    function(
+        "floor",
+        Scalar,
+        1,
+        1,
+        "Largest integer not greater than the number",
+    )
+    .with_result(Fixed(ColumnType::Number)),
+    function(
+        "mod",
+        Scalar,
+        2,
+        2,
+        "Remainder of the division, or NULL for 0",
+    )
+    .with_result(Fixed(ColumnType::Number)),
+    function("now", Scalar, 0, 0, "Current timestamp"),
+    function("current_date", Scalar, 0, 0, "Current date"),
+    function("current_time", Scalar, 0, 0, "Current time of the day"),
+    function(
+        "date_part",
+        Scalar,
+        2,
+        2,
+        "The field like 'year' or 'hour' of the date",
+    ),
+    function(
+        "date_add",
+        Scalar,
+        3,
+        3,
+        "The date plus the number of the units like 'day'",
+    ),
+    function(
+        "date_trunc",
+        Scalar,
+        2,
+        2,
+        "The date truncated to the unit like 'month'",
+    ),
+    function(
+        "env",
+        Scalar,
+        1,
+        1,
+        "Value of the environment variable, or NULL if undefined",
+    ),
+    function(
+        "count",
+        Aggregate,
+        1,
+        1,
+        "Number of the rows, or of the non-NULL values",
+    )
+    .with_result(Fixed(ColumnType::Integer)),
+    function("sum", Aggregate, 1, 1, "Sum of the values").with_result(SumOfFirstArg),
+    function("avg", Aggregate, 1, 1, "Mean of the values").with_result(Fixed(ColumnType::Number)),
+    function("min", Aggregate, 1, 1, "Smallest value").with_result(FirstArg),
+    function("max", Aggregate, 1, 1, "Largest value").with_result(FirstArg),
+    function(
+        "approx_count_distinct",
+        Aggregate,
+        1,
+        1,
+        "Estimated number of the distinct values, by HyperLogLog",
+    )
+    .with_result(Fixed(ColumnType::Integer)),
+    function(
+        "approx_quantile",
+        Aggregate,
+        2,
+        2,
+        "Estimated quantile like 0.5 of the values, by t-digest",
+    )
+    .with_result(Fixed(ColumnType::Number)),
+    function(
+        "string_agg",
+        Aggregate,
+        2,
+        2,
+        "The values concatenated with the separator",
+    ),
+    function(
+        "group_concat",
+        Aggregate,
+        1,
+        2,
+        "The values concatenated with the separator, ',' by default",
+    ),
+    function(
+        "row_number",
+        Window,
+        0,
+        0,
+        "Position of the row in the partition from 1",
+    ),
+    function(
+        "rank",
+        Window,
+        0,
+        0,
+        "Rank of the row in the partition with gaps after ties",
+    ),
+    function(
+        "dense_rank",
+        Window,
+        0,
+        0,
+        "Rank of the row in the partition without gaps after ties",
+    ),
+];
+
+/// The function of the name in any case
+pub(crate) fn find_function(name: &str) -> Option<&'static FunctionInfo> {
+    FUNCTIONS
+        .iter()
+        .find(|func| func.name.eq_ignore_ascii_case(name))
+}
+
+pub(crate) fn is_aggregate(name: &str) -> bool {
+    find_function(name).is_some_and(|func| func.kind == Aggregate)
+}
+
+/// Outputs a row for each function with its name, kind, number of arguments and description.
+pub fn exec_show_functions(out: &mut impl QueryOutput) -> Result<(), Box<dyn Error>> {
+    out.output(&["function", "kind", "args", "description"].map(String::from))?;
+    for func in FUNCTIONS {
+        out.output(&[
+            func.name.to_string(),
+            func.kind.to_string(),
+            func.arity(),
+            func.description.to_string(),
+        ])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        Session,
+        test_util::{db_from_tables, table_from_rows},
+    };
+
+    /// The registry agrees with the evaluator on the names and the required arguments of the functions
+    #[test]
+    fn test_registry() {
+        let db = db_from_tables([table_from_rows("t", &["x"], &[&["1"]])]);
+        let session = Session::new(&db);
+        let call = |func: &FunctionInfo, args: usize| {
+            let args = vec!["x"; args].join(", ");
+            match func.kind {
+                Window => format!("SELECT {}({args}) OVER () FROM t", func.name),
+                _ => format!("SELECT {}({args}) FROM t", func.name),
+            }
+        };
+        for func in FUNCTIONS {
+            let sql = call(func, func.max_args);
+            if let Err(e) = session.execute(&sql) {
+                assert!(!e.to_string().contains("scalar context"), "{sql}: {e}");
+            }
+            if func.min_args > 0 && func.kind != Aggregate {
+                let sql = call(func, func.min_args - 1);
+                let err = session.execute(&sql).unwrap_err().to_string();
+                assert!(err.contains("requires an argument"), "{sql}: {err}");
+            }
+        }
+        assert!(is_aggregate("COUNT"));
+        assert!(!is_aggregate("upper"));
+        assert_eq!(find_function("Date_Add").unwrap().arity(), "3");
+        assert_eq!(find_function("substr").unwrap().arity(), "2-3");
+    }
+}
@@ -0,0 +1,461 @@
+//! Aggregation of the rows into groups by the GROUP BY keys, within the memory budget of [`ExecOptions`].
+//! The groups are kept in a hash table until their estimated size reaches the budget. After that, the rows
+//! of the groups not in the table are spilled to temporary files partitioned by the hash of the key, and each
+//! partition is aggregated in turn after the groups in memory are output, spilling again if it is still too
+//! large. Without the `fs` feature, e.g. in wasm, exceeding the budget is an error instead.
+//!
+//! [`ExecOptions`]: crate::ExecOptions
+
+use std::{collections::HashMap, error::Error, mem::size_of};
+
+use crate::{
+    eval::{AggregateResult, EvalError, aggregate_expr, eval_expr},
+    select::{Expr, QueryContext, QueryOutput, RowCursor, scan_rows},
+    trace::TraceOutcome,
+};
+
+/// The estimated size of the state of an aggregate function in a group, like a count or a sum in a hash map
+const AGGREGATE_SIZE: usize = 64;
+
+/// Aggregates the rows into groups by the keys, or all rows into a single group without them, and outputs a row
+/// for each group.
+pub(crate) fn exec_grouped(
+    out: &mut impl QueryOutput,
+    ctx: &QueryContext,
+    cols: &[Expr],
+    group_exprs: Option<&[Expr]>,
+) -> Result<(), Box<dyn Error>> {
+    let mut emitter = Emitter {
+        out,
+        offset: ctx.sql.offset.unwrap_or(0),
+        limit: ctx.sql.limit.unwrap_or(usize::MAX),
+        count: 0,
+    };
+    let mut groups = Groups::new(ctx, cols, group_exprs, 0);
+    if group_exprs.is_none() {
+        // Without GROUP BY, all rows belong to the group with the empty key, which yields a row even if
+        // no rows are aggregated.
+        groups.insert(vec![], &vec![RowCursor::new(); ctx.tables.len()]);
+    }
+    scan_rows(ctx, cols, |row_cursor| {
+        ctx.record(row_cursor, TraceOutcome::Aggregated);
+        groups.add(row_cursor)?;
+        Ok(true)
+    })?;
+    groups.finish(&mut emitter)
+}
+
+/// Applies OFFSET and LIMIT to the rows of the groups output in turn.
+struct Emitter<'a, O> {
+    out: &'a mut O,
+    offset: usize,
+    limit: usize,
+    count: usize,
+}
+
+impl<O: QueryOutput> Emitter<'_, O> {
+    fn done(&self) -> bool {
+        self.offset.saturating_add(self.limit) <= self.count
+    }
+
+    /// Outputs the row made by `values` unless it is skipped by OFFSET or LIMIT.
+    fn output(
+        &mut self,
+        values: impl FnOnce() -> Result<Vec<String>, EvalError>,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.offset <= self.count && !self.done() {
+            self.out.output(&values()?)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// The groups aggregated in memory, and the partitions of the rows spilled for the rest.
+struct Groups<'a, 'b> {
+    ctx: &'b QueryContext<'a>,
+    cols: &'b [Expr],
+    group_exprs: Option<&'b [Expr]>,
+    index: HashMap<Vec<String>, usize>,
+    /// The aggregates and the first row of each group, which the columns not aggregated are evaluated on
+    results: Vec<(AggregateResult, Vec<RowCursor>)>,
+    /// The estimated size of the groups in memory in bytes
+    size: usize,
+    /// How many times the rows have been spilled, which varies the hash partitioning them
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    depth: u64,
+    #[cfg(feature = "fs")]
+    spill: Option<spill::Partitions>,
+}
+
+impl<'a, 'b> Groups<'a, 'b> {
+    fn new(
+        ctx: &'b QueryContext<'a>,
+        cols: &'b [Expr],
+        group_exprs: Option<&'b [Expr]>,
+        depth: u64,
+    ) -> Self {
+        Self {
+            ctx,
+            cols,
+            group_exprs,
+            index: HashMap::new(),
+            results: vec![],
+            size: 0,
+            depth,
+            #[cfg(feature = "fs")]
+            spill: None,
+        }
+    }
+
+    fn insert(&mut self, key: Vec<String>, row_cursor: &[RowCursor]) -> usize {
+        self.size += group_size(&key, self.cols.len(), row_cursor.len());
+        self.results.push((
+            AggregateResult {
+                active: true,
+                ..AggregateResult::default()
+            },
+            row_cursor.to_vec(),
+        ));
+        self.index.insert(key, self.results.len() - 1);
+        self.results.len() - 1
+    }
+
+    /// Aggregates the row into its group, or spills it if the group is not in memory and does not fit.
+    fn add(&mut self, row_cursor: &[RowCursor]) -> Result<(), Box<dyn Error>> {
+        let (ctx, cols) = (self.ctx, self.cols);
+        let key = self
+            .group_exprs
+            .unwrap_or(&[])
+            .iter()
+            .map(
+                |ex| match eval_expr(ex, cols, ctx, row_cursor, &AggregateResult::default()) {
+                    Ok(res) => Ok(ex.collation_key(res)),
+                    Err(EvalError::CursorNone(_)) => Ok("".to_string()),
+                    Err(e) => Err(e),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        let group_idx = match self.index.get(&key) {
+            Some(idx) => *idx,
+            None => {
+                let budget = ctx.options.memory_budget.unwrap_or(usize::MAX);
+                let size = group_size(&key, cols.len(), row_cursor.len());
+                // At least one group is kept, so that every pass over the spilled rows makes progress
+                if !self.results.is_empty() && budget < self.size.saturating_add(size) {
+                    return self.spill(&key, row_cursor);
+                }
+                self.insert(key, row_cursor)
+            }
+        };
+        let results = &mut self.results[group_idx].0;
+        // A column index refers to another column, which is aggregated by itself
+        for col in cols.iter().filter(|col| !matches!(col, Expr::ColIdx(_))) {
+            aggregate_expr(col, cols, ctx, row_cursor, results)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "fs")]
+    fn spill(&mut self, key: &[String], row_cursor: &[RowCursor]) -> Result<(), Box<dyn Error>> {
+        let partitions = match self.spill {
+            Some(ref mut partitions) => partitions,
+            None => self.spill.insert(spill::Partitions::new()?),
+        };
+        partitions.write(self.depth, key, row_cursor)?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "fs"))]
+    fn spill(&mut self, _key: &[String], _row_cursor: &[RowCursor]) -> Result<(), Box<dyn Error>> {
+        Err(format!(
+            "GROUP BY has more groups than fit in the memory budget of {} bytes",
+            self.ctx.options.memory_budget.unwrap_or(usize::MAX)
+        )
+        .into())
+    }
+
+    /// Outputs the groups in memory, then aggregates and outputs the spilled rows.
+    fn finish(self, emitter: &mut Emitter<impl QueryOutput>) -> Result<(), Box<dyn Error>> {
+        let (ctx, cols) = (self.ctx, self.cols);
+        for (results, group_cursor) in &self.results {
+            if emitter.done() {
+                return Ok(());
+            }
+            emitter.output(|| {
+                cols.iter()
+                    .map(|ex| match eval_expr(ex, cols, ctx, group_cursor, results) {
+                        Ok(res) => Ok(res),
+                        Err(EvalError::CursorNone(_)) => Ok("".to_string()),
+                        Err(e) => Err(e),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })?;
+        }
+        #[cfg(feature = "fs")]
+        if let Some(partitions) = self.spill {
+            // The groups of different partitions are disjoint, so each one is aggregated on its own
+            for rows in partitions.into_readers()? {
+                if emitter.done() {
+                    break;
+                }
+                let mut groups = Groups::new(ctx, cols, self.group_exprs, self.depth + 1);
+                for row_cursor in rows {
+                    groups.add(&row_cursor?)?;
+                }
+                groups.finish(emitter)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The estimated size of a group in memory: the key, the aggregates and the first row, in the hash table.
+/// The values collected by `count(DISTINCT col)` and `string_agg` are not counted.
+fn group_size(key: &[String], num_cols: usize, num_tables: usize) -> usize {
+    size_of::<(Vec<String>, usize)>()
+        + key
+            .iter()
+            .map(|s| size_of::<String>() + s.len())
+            .sum::<usize>()
+        + size_of::<(AggregateResult, Vec<RowCursor>)>()
+        + num_tables * size_of::<RowCursor>()
+        + num_cols * AGGREGATE_SIZE
+}
+
+#[cfg(feature = "fs")]
+mod spill {
+    use std::{
+        fs::File,
+        hash::{DefaultHasher, Hash, Hasher},
+        io::{BufReader, BufWriter, Read, Seek, Write},
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::select::RowCursor;
+
+    /// The number of files the spilled rows are partitioned into
+    const PARTITIONS: usize = 16;
+
+    /// A temporary file removed when dropped.
+    struct SpillFile {
+        path: PathBuf,
+        file: BufWriter<File>,
+    }
+
+    impl SpillFile {
+        fn new() -> std::io::Result<Self> {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "bogosql-spill-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            let file = File::options()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            Ok(Self {
+                path,
+                file: BufWriter::new(file),
+            })
+        }
+    }
+
+    impl Drop for SpillFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    /// The rows spilled into files by the hash of the group key, as the row index in each table plus 1,
+    /// or 0 for the missing side of an outer join.
+    pub(super) struct Partitions {
+        files: Vec<SpillFile>,
+        num_tables: usize,
+    }
+
+    impl Partitions {
+        pub fn new() -> std::io::Result<Self> {
+            Ok(Self {
+                files: (0..PARTITIONS)
+                    .map(|_| SpillFile::new())
+                    .collect::<Result<_, _>>()?,
+                num_tables: 0,
+            })
+        }
+
+        pub fn write(
+            &mut self,
+            depth: u64,
+            key: &[String],
+            row_cursor: &[RowCursor],
+        ) -> std::io::Result<()> {
+            let mut hasher = DefaultHasher::new();
+            depth.hash(&mut hasher);
+            key.hash(&mut hasher);
+            let file = &mut self.files[hasher.finish() as usize % PARTITIONS].file;
+            for cursor in row_cursor {
+                let row = cursor.row.map_or(0, |row| row as u64 + 1);
+                file.write_all(&row.to_le_bytes())?;
+            }
+            self.num_tables = row_cursor.len();
+            Ok(())
+        }
+
+        /// The rows in each partition, read back in the order they were written.
+        pub fn into_readers(self) -> std::io::Result<Vec<PartitionRows>> {
+            let num_tables = self.num_tables;
+            self.files
+                .into_iter()
+                .map(|mut spill| {
+                    spill.file.flush()?;
+                    spill.file.get_mut().rewind()?;
+                    Ok(PartitionRows {
+                        reader: BufReader::new(spill.file.get_ref().try_clone()?),
+                        num_tables,
+                        _spill: spill,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    /// Iterator over the rows spilled into a partition.
+    pub(super) struct PartitionRows {
+        reader: BufReader<File>,
+        num_tables: usize,
+        /// Kept until the rows are read, so that the file is removed afterwards
+        _spill: SpillFile,
+    }
+
+    impl Iterator for PartitionRows {
+        type Item = std::io::Result<Vec<RowCursor>>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let mut buf = [0u8; 8];
+            let mut row_cursor = Vec::with_capacity(self.num_tables);
+            for _ in 0..self.num_tables {
+                match self.reader.read_exact(&mut buf) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return None,
+                    Err(e) => return Some(Err(e)),
+                }
+                let row = u64::from_le_bytes(buf);
+                row_cursor.push(RowCursor {
+                    row: row.checked_sub(1).map(|row| row as usize),
+                    shown: false,
+                });
+            }
+            Some(Ok(row_cursor))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        BufferOutput, Database, ExecOptions, Statement, exec_select_with,
+        parser::parse_statement,
+        test_util::{db_from_tables, table_from_rows},
+    };
+
+    fn run(db: &Database, sql: &str, options: &ExecOptions) -> Result<Vec<Vec<String>>, String> {
+        let Ok(Statement::Select(stmt)) = parse_statement(sql) else {
+            panic!("Not a SELECT statement: {sql}");
+        };
+        let mut out = BufferOutput::default();
+        exec_select_with(&mut out, db, &stmt, options, None).map_err(|e| e.to_string())?;
+        Ok(out.0)
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_spill_groups() {
+        let rows = (0..1000)
+            .map(|i| format!("{i},{}\n", i % 300))
+            .collect::<String>();
+        let users = (0..300)
+            .filter(|i| i % 7 != 0)
+            .map(|i| format!("{i},u{i}\n"))
+            .collect::<String>();
+        let db = crate::test_util::db_from_csv(&[
+            ("t", &format!("id,user_id\n{rows}")),
+            ("u", &format!("uid,name\n{users}")),
+        ]);
+        let unlimited = ExecOptions::DEFAULT;
+        let bounded = ExecOptions::DEFAULT.with_memory_budget(Some(4096));
+        for sql in [
+            "SELECT user_id, count(*), sum(id), max(id) FROM t GROUP BY user_id ORDER BY user_id",
+            "SELECT name, count(*) FROM t INNER JOIN u ON user_id = uid GROUP BY name ORDER BY name",
+            "SELECT MOD(user_id, 50), count(DISTINCT user_id) FROM t GROUP BY ALL ORDER BY ALL",
+            "SELECT count(*) FROM t",
+        ] {
+            let expected = run(&db, sql, &unlimited).unwrap();
+            assert_eq!(run(&db, sql, &bounded).unwrap(), expected, "{sql}");
+        }
+
+        // The groups spilled are output after the ones in memory, but OFFSET and LIMIT count all of them
+        let sql = "SELECT user_id, count(*) FROM t GROUP BY user_id LIMIT 100 OFFSET 150";
+        let rows = run(&db, sql, &bounded).unwrap();
+        assert_eq!(rows.len(), 101);
+        assert!(rows[1..].iter().all(|row| row[1] == "3" || row[1] == "4"));
+
+        let prefix = format!("bogosql-spill-{}-", std::process::id());
+        let leftover = std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(&prefix)
+            })
+            .count();
+        assert_eq!(leftover, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_budget_below_one_group() {
+        let db = db_from_tables([table_from_rows("t", &["k"], &[&["a"], &["b"], &["a"]])]);
+        let options = ExecOptions::DEFAULT.with_memory_budget(Some(0));
+        assert_eq!(
+            run(&db, "SELECT k, count(*) FROM t GROUP BY k", &options).unwrap(),
+            [["k", "count(*)"], ["a", "2"], ["b", "1"]]
+        );
+    }
+
+    /// An error in a group fails the query, whether the group is kept in memory or spilled
+    #[test]
+    fn test_aggregate_error() {
+        let db = db_from_tables([table_from_rows(
+            "t",
+            &["k", "v"],
+            &[&["a", "1"], &["b", "2"], &["b", "x"]],
+        )]);
+        let budgets: &[_] = if cfg!(feature = "fs") {
+            &[None, Some(0)]
+        } else {
+            &[None]
+        };
+        for budget in budgets {
+            let options = ExecOptions::DEFAULT.with_memory_budget(*budget);
+            assert_eq!(
+                run(&db, "SELECT k, sum(v) FROM t GROUP BY k", &options),
+                Err("Coercion from String to f64".to_string()),
+                "{budget:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "fs"))]
+    fn test_budget_exceeded() {
+        let db = db_from_tables([table_from_rows("t", &["k"], &[&["a"], &["b"]])]);
+        let options = ExecOptions::DEFAULT.with_memory_budget(Some(0));
+        assert_eq!(
+            run(&db, "SELECT k, count(*) FROM t GROUP BY k", &options),
+            Err("GROUP BY has more groups than fit in the memory budget of 0 bytes".to_string())
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Hash join for an equi-join on tables that are not sorted on the key columns.
+//! A hash index is built on the key column of the smaller table, so that the join takes time linear in the sizes
+//! of the tables and the result, instead of their product in the nested loop.
+//! The pairs are yielded in the same order as the nested loop would, i.e. by the left row and then the right row.
+
+use std::collections::HashMap;
+
+use crate::{
+    join::{JoinKey, JoinKeys},
+    merge_join::row_count,
+    table::Table,
+};
+
+/// Iterator over the row indices of the left and right tables for each pair with equal keys.
+pub(crate) struct HashJoin<'a> {
+    left: &'a Table,
+    keys: JoinKeys,
+    matches: Matches<'a>,
+    left_row: usize,
+    /// Position in the matching right rows of the current left row
+    pos: usize,
+}
+
+enum Matches<'a> {
+    /// Index on the right table from the key to the rows, probed by each left row
+    Index(HashMap<JoinKey<'a>, Vec<usize>>),
+    /// The matching right rows of each left row, found by probing an index on the left table with the right rows
+    PerLeftRow(Vec<Vec<usize>>),
+}
+
+fn build_index<'a>(
+    table: &'a Table,
+    col: usize,
+    keys: &JoinKeys,
+) -> HashMap<JoinKey<'a>, Vec<usize>> {
+    let mut index: HashMap<JoinKey, Vec<usize>> = HashMap::new();
+    for row in 0..row_count(table) {
+        if let Some(cell) = table.get(row, col) {
+            index.entry(keys.key(cell)).or_default().push(row);
+        }
+    }
+    index
+}
+
+impl<'a> HashJoin<'a> {
+    pub fn new(left: &'a Table, right: &'a Table, keys: JoinKeys) -> Self {
+        let matches = if row_count(left) < row_count(right) {
+            let index = build_index(left, keys.left_col, &keys);
+            let mut per_left_row = vec![vec![]; row_count(left)];
+            for row in 0..row_count(right) {
+                let Some(left_rows) = right
+                    .get(row, keys.right_col)
+                    .and_then(|cell| index.get(&keys.key(cell)))
+                else {
+                    continue;
+                };
+                for &left_row in left_rows {
+                    per_left_row[left_row].push(row);
+                }
+            }
+            Matches::PerLeftRow(per_left_row)
+        } else {
+            Matches::Index(build_index(right, keys.right_col, &keys))
+        };
+        Self {
+            left,
+            keys,
+            matches,
+            left_row: 0,
+            pos: 0,
+        }
+    }
+
+    fn right_rows(&self, left_row: usize) -> &[usize] {
+        match self.matches {
+            Matches::Index(ref index) => self
+                .left
+                .get(left_row, self.keys.left_col)
+                .and_then(|cell| index.get(&self.keys.key(cell)))
+                .map_or(&[], |rows| rows.as_slice()),
+            Matches::PerLeftRow(ref rows) => &rows[left_row],
+        }
+    }
+}
+
+impl Iterator for HashJoin<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.left_row < row_count(self.left) {
+            if let Some(&right_row) = self.right_rows(self.left_row).get(self.pos) {
+                self.pos += 1;
+                return Some((self.left_row, right_row));
+            }
+            self.left_row += 1;
+            self.pos = 0;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{make_table, table::ColumnType};
+
+    #[test]
+    fn test_hash_join() {
+        let big = make_table("a", "id\n2\n1\n3\n2\n").unwrap();
+        let small = make_table("b", "a_id\n2\n3.0\n2\n").unwrap();
+        let keys = JoinKeys {
+            left_col: 0,
+            right_col: 0,
+            ty: ColumnType::Integer,
+            utc_offset: 0,
+        };
+        assert_eq!(
+            HashJoin::new(&big, &small, keys).collect::<Vec<_>>(),
+            vec![(0, 0), (0, 2), (2, 1), (3, 0), (3, 2)]
+        );
+        // The index is built on the left side, but the order is the same
+        assert_eq!(
+            HashJoin::new(&small, &big, keys).collect::<Vec<_>>(),
+            vec![(0, 0), (0, 3), (1, 2), (2, 0), (2, 3)]
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! Choice of the method to join tables.
+//! A single INNER JOIN on the equality of a column of each table, like `FROM a INNER JOIN b ON a.id = b.a_id`,
+//! visits only the matching pairs of rows with a merge join if both tables are sorted on the key columns,
+//! or a hash join otherwise. The equality can be ANDed with other conditions like `a.x = b.y AND a.z > b.w`,
+//! which filter the matching pairs. Other joins are executed in a nested loop over all the combinations of
+//! rows. The keys are matched as the equality compares them, so that `1` in a numeric column matches `1.0`, and
+//! `2024-01-01` matches `2024-01-01T00:00:00`.
+
+use crate::{
+    datetime::DateTime,
+    db::Database,
+    merge_join::is_sorted,
+    number::Number,
+    options::ExecOptions,
+    select::{BinOp, Column, Expr, JoinKind, SelectStmt},
+    table::ColumnType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum JoinMethod {
+    NestedLoop,
+    /// Merge join on the key columns in the left and right tables
+    Merge(JoinKeys),
+    /// Hash join on the key columns in the left and right tables
+    Hash(JoinKeys),
+}
+
+/// The key columns of an equi-join and how their cells are compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct JoinKeys {
+    pub left_col: usize,
+    pub right_col: usize,
+    /// The type of the left operand of the equality, in which the nested loop compares the keys
+    pub ty: ColumnType,
+    /// The time zone of the timestamps without an offset, from the options
+    pub utc_offset: i32,
+}
+
+impl JoinKeys {
+    /// The key of a cell, which is equal to the key of another cell if the equality of the cells is true
+    pub fn key<'a>(&self, cell: &'a str) -> JoinKey<'a> {
+        if self.ty.is_numeric() {
+            match Number::parse(cell, false) {
+                Some(Number::Int(val)) => return JoinKey::Int(val),
+                Some(num) => {
+                    // An integer and a float are compared as floats, where -0.0 is less than 0
+                    let val = num.to_f64();
+                    if val.fract() == 0.0
+                        && val as i64 as f64 == val
+                        && !(val == 0.0 && val.is_sign_negative())
+                    {
+                        return JoinKey::Int(val as i64);
+                    }
+                    return JoinKey::Float(val.to_bits());
+                }
+                None => {}
+            }
+        }
+        // Dates are compared chronologically in any column, like in the nested loop
+        if let Some(time) = DateTime::parse(cell, self.utc_offset) {
+            return JoinKey::Time(time.secs);
+        }
+        JoinKey::Text(cell)
+    }
+}
+
+/// A cell of a key column as the equality of the join condition compares it. The keys are sorted in the order
+/// that a merge join walks the tables in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum JoinKey<'a> {
+    /// A number in a numeric column, including a float without a fraction like `1.0`
+    Int(i64),
+    /// The bits of a float with a fraction in a numeric column
+    Float(u64),
+    /// A date or a timestamp as the seconds since the Unix epoch
+    Time(i64),
+    Text(&'a str),
+}
+
+impl JoinMethod {
+    pub fn choose(db: &Database, sql: &SelectStmt, options: &ExecOptions) -> Self {
+        let Some(keys) = equi_join_keys(db, sql, options) else {
+            return Self::NestedLoop;
+        };
+        let (Some(left), Some(right)) = (db.get(&sql.table.name), db.get(&sql.join[0].table.name))
+        else {
+            return Self::NestedLoop;
+        };
+        if is_sorted(left, keys.left_col, &keys) && is_sorted(right, keys.right_col, &keys) {
+            Self::Merge(keys)
+        } else {
+            Self::Hash(keys)
+        }
+    }
+
+    /// The method of the join at the index in the JOIN clauses of the statement. Only the join of a statement
+    /// with a single JOIN is a merge join or a hash join, so the others are nested loops.
+    pub fn for_join(db: &Database, sql: &SelectStmt, index: usize, options: &ExecOptions) -> Self {
+        if index == 0 {
+            Self::choose(db, sql, options)
+        } else {
+            Self::NestedLoop
+        }
+    }
+
+    /// Name shown in `EXPLAIN`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NestedLoop => "Nested Loop",
+            Self::Merge(..) => "Merge",
+            Self::Hash(..) => "Hash",
+        }
+    }
+}
+
+/// Whether the join condition has more than the equality of the key columns, which must be checked on the
+/// pairs of rows that a merge join or a hash join yields.
+pub(crate) fn has_residual_condition(condition: &Expr) -> bool {
+    !matches!(condition, Expr::Binary { op: BinOp::Eq, .. })
+}
+
+/// The conditions ANDed in the expression
+fn conjuncts(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::Binary {
+            op: BinOp::And,
+            lhs,
+            rhs,
+        } => {
+            let mut res = conjuncts(lhs);
+            res.extend(conjuncts(rhs));
+            res
+        }
+        _ => vec![expr],
+    }
+}
+
+/// Returns the key columns in the left and right tables if the statement has a single INNER JOIN on the equality
+/// of a column of each table, possibly ANDed with other conditions.
+fn equi_join_keys(db: &Database, sql: &SelectStmt, options: &ExecOptions) -> Option<JoinKeys> {
+    let [join] = sql.join.as_slice() else {
+        return None;
+    };
+    if join.kind != JoinKind::Inner {
+        return None;
+    }
+    let left = db.get(&sql.table.name)?;
+    let right = db.get(&join.table.name)?;
+
+    // Returns the side (0 for left and 1 for right) and the index of the column
+    let resolve = |col: &Column| -> Option<(usize, usize)> {
+        let tables = [(&sql.table, left), (&join.table, right)];
+        let mut found = tables
+            .iter()
+            .enumerate()
+            .filter_map(|(side, (spec, table))| {
+                // An alias hides the name of the table, as in the execution
+                let qualifier = spec.alias.as_ref().unwrap_or(&spec.name);
+                if let Some(ref name) = col.table
+                    && !name.eq_ignore_ascii_case(qualifier)
+                {
+                    return None;
+                }
+                Some((
+                    side,
+                    table.find_col(&col.column, options.case_insensitive && !col.quoted)?,
+                ))
+            });
+        let res = found.next()?;
+        // An ambiguous column is left to the nested loop to report
+        found.next().is_none().then_some(res)
+    };
+    // The first equality of a column of each table is the key
+    conjuncts(&join.condition).into_iter().find_map(|cond| {
+        let Expr::Binary {
+            op: BinOp::Eq,
+            lhs,
+            rhs,
+        } = cond
+        else {
+            return None;
+        };
+        // A column bound before execution is already resolved to the side and the column
+        let side = |ex: &Expr| match ex {
+            Expr::Bound(_, id) => Some((id.joindex, id.col)),
+            Expr::Column(col) => resolve(col),
+            _ => None,
+        };
+        let (lhs, rhs) = (side(lhs)?, side(rhs)?);
+        let (left_col, right_col) = match (lhs, rhs) {
+            ((0, l), (1, r)) | ((1, r), (0, l)) => (l, r),
+            _ => return None,
+        };
+        let ty = [left, right][lhs.0].column_type(lhs.1);
+        Some(JoinKeys {
+            left_col,
+            right_col,
+            ty,
+            utc_offset: options.utc_offset,
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Session, Statement, StatementOutput, parser::statement, test_util::db_from_csv};
+
+    #[test]
+    fn test_join_method() {
+        let db = db_from_csv(&[
+            ("a", "id,name\n1, x\n2, y\n2, z\n4, w\n"),
+            ("b", "a_id,val\n2, p\n2, q\n3, r\n4, s\n"),
+            ("c", "a_id\n4\n2\n"),
+        ]);
+        let method = |sql| {
+            let Statement::Select(stmt) = statement(sql).unwrap().1 else {
+                panic!("Not a SELECT statement");
+            };
+            JoinMethod::choose(
+                &db,
+                &stmt,
+                &ExecOptions::DEFAULT.with_case_insensitive(false),
+            )
+        };
+        let keys = JoinKeys {
+            left_col: 0,
+            right_col: 0,
+            ty: ColumnType::Integer,
+            utc_offset: 0,
+        };
+        assert_eq!(
+            method("SELECT * FROM a INNER JOIN b ON b.a_id = id"),
+            JoinMethod::Merge(keys)
+        );
+        assert_eq!(
+            method("SELECT * FROM a AS t INNER JOIN b ON t.id = a_id"),
+            JoinMethod::Merge(keys)
+        );
+        assert_eq!(
+            method("SELECT * FROM a INNER JOIN c ON id = a_id"),
+            JoinMethod::Hash(keys)
+        );
+        assert_eq!(
+            method("SELECT * FROM c AS l INNER JOIN c AS r ON l.a_id = r.a_id"),
+            JoinMethod::Hash(keys)
+        );
+        // The name of a table with an alias does not qualify it
+        assert_eq!(
+            method("SELECT * FROM a AS t INNER JOIN b ON a.id = a_id"),
+            JoinMethod::NestedLoop
+        );
+        assert_eq!(
+            method("SELECT * FROM a LEFT JOIN b ON id = a_id"),
+            JoinMethod::NestedLoop
+        );
+        assert_eq!(
+            method("SELECT * FROM a INNER JOIN b ON id < a_id"),
+            JoinMethod::NestedLoop
+        );
+        assert_eq!(
+            method("SELECT * FROM a INNER JOIN c ON name <> 'w' AND id = c.a_id"),
+            JoinMethod::Hash(keys)
+        );
+        assert_eq!(
+            method("SELECT * FROM a INNER JOIN b ON id = id AND id = a_id AND val > name"),
+            JoinMethod::Merge(keys)
+        );
+        assert_eq!(
+            method("SELECT * FROM a INNER JOIN b ON id = a_id OR val > name"),
+            JoinMethod::NestedLoop
+        );
+    }
+
+    /// The hash join, the merge join and the nested loop match the same rows
+    #[test]
+    fn test_join_methods_agree() {
+        let db = db_from_csv(&[
+            ("a", "id\n1\n2\n3\n"),
+            ("unsorted", "id\n3\n1\n2\n"),
+            ("b", "a_id,val\n1.0,p\n2,q\n2.5,r\n"),
+            ("days", "day\n2024-01-01\n2024-01-02\n2024-01-03\n"),
+            ("unsorted_days", "day\n2024-01-03\n2024-01-01\n2024-01-02\n"),
+            (
+                "events",
+                "at,val\n2024-01-01T00:00:00,p\n2024-01-02 00:00,q\n2024-01-02T12:00:00,r\n",
+            ),
+        ]);
+        let session = Session::new(&db);
+        let rows = |sql: &str| match session.execute(sql) {
+            Ok(StatementOutput::Rows(rows)) => rows,
+            res => panic!("{sql}: {res:?}"),
+        };
+        for (from, on, method) in [
+            ("a INNER JOIN b", "id = a_id", "Merge"),
+            ("unsorted INNER JOIN b", "id = a_id", "Hash"),
+            ("days INNER JOIN events", "day = at", "Merge"),
+            ("unsorted_days INNER JOIN events", "day = at", "Hash"),
+        ] {
+            let inner = format!("SELECT * FROM {from} ON {on}");
+            let Statement::Select(stmt) = statement(&inner).unwrap().1 else {
+                panic!("Not a SELECT statement");
+            };
+            assert_eq!(
+                JoinMethod::choose(&db, &stmt, &ExecOptions::DEFAULT).name(),
+                method,
+                "{inner}"
+            );
+            let matched = rows(&inner);
+            // The header and 2 rows
+            assert_eq!(matched.len(), 3, "{inner}");
+            assert_eq!(rows(&format!("{inner} OR 1 = 0")), matched, "{inner}");
+            let mut left = rows(&inner.replace("INNER", "LEFT"));
+            left.retain(|row| !row[1].is_empty());
+            assert_eq!(left, matched, "{inner}");
+        }
+    }
+}
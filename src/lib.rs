@@ -1,14 +1,91 @@
+//! A toy SQL engine running queries on CSV files loaded as tables.
+//!
+//! The common types are in [`prelude`]: load tables with [`make_table`] into a [`Database`], and run statements
+//! with a [`Session`] or a [`PreparedStatement`]. The free functions like [`exec_select_with`] run a parsed
+//! statement with an output of your choice. Cells are strings, where NULL is the empty string.
+
+mod catalog;
+mod compound;
 mod csv;
+mod cte;
+mod datetime;
 mod db;
+mod decimal;
+mod describe;
+mod error_json;
 mod eval;
+mod explain;
+mod functions;
+mod group;
+mod hash_join;
+mod join;
+mod limits;
+mod lint;
+mod load;
+mod merge_join;
+mod number;
+mod observer;
+mod options;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod params;
+mod parse_error;
 mod parser;
+mod partition;
+mod schema_diff;
+mod search;
 mod select;
+mod select_iter;
+mod semi_join;
+mod session;
+mod sketch;
 mod table;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod trace;
+mod value;
+mod window;
 
+#[cfg(feature = "fs")]
+pub use crate::load::{load_csv_columns, load_csv_filtered};
 pub use crate::{
-    csv::parse_csv,
+    catalog::Catalog,
+    compound::{CompoundStmt, SetOp, exec_compound, format_compound},
+    csv::{parse_csv, write_csv_row},
+    cte::Cte,
     db::{Database, Statement},
-    parser::statement,
-    select::{CsvOutput, SelectStmt, exec_select, format_select},
-    table::{Table, make_table},
+    describe::{exec_describe, exec_show_tables},
+    error_json::format_error_json,
+    explain::{ExplainFormat, ExplainStmt, format_explain},
+    functions::{FUNCTIONS, FunctionInfo, FunctionKind, exec_show_functions},
+    lint::{LintWarning, lint},
+    load::{make_table_columns, make_table_filtered},
+    observer::{ExecObserver, Observer},
+    options::{
+        AggregateNulls, Clock, DETERMINISTIC_NOW, ExecOptions, parse_byte_size, parse_utc_offset,
+    },
+    params::{Params, PreparedStatement},
+    parse_error::{ParseError, format_error_text},
+    parser::parse_statement,
+    partition::{PartitionSummary, make_table_partitioned},
+    schema_diff::exec_compare_schema,
+    search::exec_search,
+    select::{
+        BufferOutput, Collation, Column, CsvOutput, JsonOutput, QueryOutput, RowNumbers,
+        SelectStmt, exec_select, exec_select_traced, exec_select_with, format_select,
+        format_select_traced, format_select_with,
+    },
+    select_iter::{SelectIter, exec_select_iter, exec_select_iter_with},
+    session::{SavedQueries, Session, StatementOutput, StatementResult, split_statements},
+    table::{ColumnType, RowSchema, Table, make_table, make_table_with},
+    trace::{ExecStats, ExecTrace, TraceEvent, TraceOutcome},
 };
+
+/// The types needed to load tables and run statements, for `use bogosql::prelude::*`.
+pub mod prelude {
+    pub use crate::{
+        AggregateNulls, BufferOutput, CsvOutput, Database, ExecOptions, JsonOutput, ParseError,
+        PreparedStatement, QueryOutput, Session, Statement, StatementOutput, StatementResult,
+        Table, make_table, parse_statement,
+    };
+}
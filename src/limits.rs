@@ -0,0 +1,192 @@
+//! Limits on the work of a query, so that a mistake like a cross join of large tables fails early instead of
+//! freezing the REPL or the browser tab of the playground. The rows output, the combinations of rows evaluated
+//! and the time are limited by [`ExecOptions::max_rows`], [`ExecOptions::max_combinations`] and
+//! [`ExecOptions::time_budget`].
+
+use std::{
+    error::Error,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    observer::{Observer, PROGRESS_INTERVAL},
+    options::{Clock, ExecOptions},
+};
+
+/// The number of the combinations evaluated between reading the clock, which is slow in a browser
+const CLOCK_INTERVAL: usize = 1024;
+
+/// The counts of the work of a query so far, shared by the copies of its context and the threads scanning the rows
+pub(crate) struct Limits {
+    max_rows: usize,
+    max_combinations: usize,
+    /// The time since the start and the budget, if the time is limited and there is a clock to measure it
+    deadline: Option<(Stopwatch, Duration)>,
+    rows: AtomicUsize,
+    combinations: AtomicUsize,
+    /// Set when the query fails while the threads of a parallel scan are still evaluating the combinations
+    stopped: AtomicBool,
+    /// Told the number of the combinations every [`PROGRESS_INTERVAL`] of them
+    observer: Option<Observer>,
+}
+
+enum Stopwatch {
+    Instant(Instant),
+    /// A clock given in the options, and its time at the start
+    Clock(Clock, Duration),
+}
+
+impl Stopwatch {
+    fn elapsed(&self) -> Duration {
+        match self {
+            Self::Instant(start) => start.elapsed(),
+            Self::Clock(clock, start) => (clock.0)().saturating_sub(*start),
+        }
+    }
+}
+
+impl Limits {
+    pub fn new(options: &ExecOptions) -> Self {
+        let stopwatch = || match options.clock {
+            Some(clock) => Some(Stopwatch::Clock(clock, (clock.0)())),
+            // Instant::now() panics in wasm32-unknown-unknown
+            None if cfg!(target_arch = "wasm32") => None,
+            None => Some(Stopwatch::Instant(Instant::now())),
+        };
+        Self {
+            max_rows: options.max_rows.unwrap_or(usize::MAX),
+            max_combinations: options.max_combinations.unwrap_or(usize::MAX),
+            deadline: options
+                .time_budget
+                .and_then(|budget| Some((stopwatch()?, budget))),
+            rows: AtomicUsize::new(0),
+            combinations: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+            observer: options.observer.clone(),
+        }
+    }
+
+    /// Counts a row to output, failing if it is beyond the limit.
+    pub fn output_row(&self) -> Result<(), Box<dyn Error>> {
+        if self.max_rows < self.rows.fetch_add(1, Ordering::Relaxed) + 1 {
+            return Err(format!(
+                "Query exceeded the limit of {} rows to output",
+                self.max_rows
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Fails the evaluation of the combinations from now on, so that the threads of a parallel scan stop when
+    /// the query has failed.
+    #[cfg(feature = "parallel")]
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Counts a combination of rows to evaluate, failing if it is beyond the limit, the time is up or the query
+    /// is stopped, and tells the observer the progress.
+    pub fn evaluate_combination(&self) -> Result<(), Box<dyn Error>> {
+        if self.stopped.load(Ordering::Relaxed) {
+            return Err("Query stopped".into());
+        }
+        let combinations = self.combinations.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.max_combinations < combinations {
+            return Err(format!(
+                "Query exceeded the limit of {} combinations of rows to evaluate",
+                self.max_combinations
+            )
+            .into());
+        }
+        if combinations.is_multiple_of(PROGRESS_INTERVAL)
+            && let Some(ref observer) = self.observer
+        {
+            observer.on_rows_scanned(combinations);
+        }
+        if combinations.is_multiple_of(CLOCK_INTERVAL)
+            && let Some((ref stopwatch, budget)) = self.deadline
+            && budget < stopwatch.elapsed()
+        {
+            return Err(format!(
+                "Query exceeded the time budget of {} ms",
+                budget.as_millis()
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicU64;
+
+    use super::*;
+    use crate::{
+        Session,
+        test_util::{db_from_tables, table_from_rows},
+    };
+
+    /// A clock advancing a millisecond each time it is read
+    fn ticking_clock() -> Duration {
+        static MILLIS: AtomicU64 = AtomicU64::new(0);
+        Duration::from_millis(MILLIS.fetch_add(1, Ordering::Relaxed))
+    }
+
+    #[test]
+    fn test_limits() {
+        let rows: Vec<_> = (0..100).map(|i| i.to_string()).collect();
+        let rows: Vec<_> = rows.iter().map(|row| [row.as_str()]).collect();
+        let rows: Vec<_> = rows.iter().map(|row| &row[..]).collect();
+        let db = db_from_tables([
+            table_from_rows("a", &["x"], &rows),
+            table_from_rows("b", &["y"], &rows),
+        ]);
+        let run = |options: ExecOptions, sql: &str| {
+            let mut session = Session::new(&db);
+            session.options = options;
+            session.execute(sql).map_err(|e| e.to_string())
+        };
+
+        let options = ExecOptions::DEFAULT.with_max_rows(Some(10));
+        assert!(run(options.clone(), "SELECT x FROM a WHERE x < 10").is_ok());
+        for sql in [
+            "SELECT x FROM a",
+            "SELECT x FROM a ORDER BY x DESC",
+            "SELECT x FROM a GROUP BY x",
+            "SELECT x FROM a UNION SELECT y FROM b",
+        ] {
+            assert_eq!(
+                run(options.clone(), sql).unwrap_err(),
+                "Query exceeded the limit of 10 rows to output",
+                "{sql}"
+            );
+        }
+        // The rows of a subquery are not output
+        assert!(
+            run(
+                options,
+                "SELECT x FROM a WHERE x IN (SELECT y FROM b) LIMIT 10"
+            )
+            .is_ok()
+        );
+
+        let options = ExecOptions::DEFAULT.with_max_combinations(Some(1000));
+        assert!(run(options.clone(), "SELECT count(*) FROM a").is_ok());
+        assert_eq!(
+            run(options, "SELECT count(*) FROM a INNER JOIN b ON x < y").unwrap_err(),
+            "Query exceeded the limit of 1000 combinations of rows to evaluate"
+        );
+
+        let options = ExecOptions::DEFAULT
+            .with_time_budget(Some(Duration::from_millis(3)))
+            .with_clock(Some(Clock(ticking_clock)));
+        assert!(run(options.clone(), "SELECT count(*) FROM a").is_ok());
+        assert_eq!(
+            run(options, "SELECT count(*) FROM a INNER JOIN b ON x < y").unwrap_err(),
+            "Query exceeded the time budget of 3 ms"
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! Static checks for likely mistakes in a statement, without executing it.
+//! The warnings are about queries that run, but probably do not yield what was meant, like a comparison of
+//! numbers to a string, which is always false, or a join that multiplies the rows.
+
+use std::collections::HashSet;
+
+use crate::{
+    db::{Database, Statement},
+    number::Number,
+    options::ExecOptions,
+    select::{BinOp, ColSpecifier, Column, Expr, SelectStmt},
+    table::Table,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintWarning {
+    /// A table in FROM or JOIN that is not in the database
+    UnknownTable { table: String },
+    /// A column that is not in any of the tables of the statement
+    UnknownColumn { column: String },
+    /// A comparison of a column with only numbers to a string literal that is not a number
+    NumericComparison { column: String, literal: String },
+    /// A column name that `SELECT *` yields more than once from the joined tables
+    DuplicateColumns { column: String },
+    /// A joined table whose condition does not relate it to the preceding tables, so that every row is joined
+    /// to every row
+    CartesianProduct { table: String },
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownTable { table } => write!(f, "Table '{table}' is not loaded"),
+            Self::UnknownColumn { column } => {
+                write!(f, "Column '{column}' is not in any table of the query")
+            }
+            Self::NumericComparison { column, literal } => write!(
+                f,
+                "Column '{column}' has only numbers, but is compared to '{literal}', which is not a number"
+            ),
+            Self::DuplicateColumns { column } => {
+                write!(f, "SELECT * yields more than one column '{column}'")
+            }
+            Self::CartesianProduct { table } => write!(
+                f,
+                "Join condition of '{table}' does not relate it to the preceding tables, which makes a cartesian product"
+            ),
+        }
+    }
+}
+
+/// Checks the SELECT statements in the statement and their subqueries for likely mistakes.
+/// Search, schema comparison, describe and listing statements have nothing to check.
+pub fn lint(db: &Database, stmt: &Statement, options: &ExecOptions) -> Vec<LintWarning> {
+    let selects = match stmt {
+        Statement::Select(select) => vec![select],
+        Statement::Explain(explain) => vec![&explain.stmt],
+        Statement::Compound(compound) => std::iter::once(&compound.first)
+            .chain(compound.rest.iter().map(|(_, select)| select))
+            .collect(),
+        Statement::Search(_)
+        | Statement::CompareSchema(..)
+        | Statement::Describe(_)
+        | Statement::ShowTables
+        | Statement::ShowQueries
+        | Statement::ShowFunctions => vec![],
+    };
+    let mut warnings = vec![];
+    for select in selects {
+        // A CTE is known to the following ones and the statement, though its columns are not
+        let mut ctes = vec![];
+        for cte in &select.with {
+            lint_select(db, &cte.stmt, &ctes, options, &mut warnings);
+            ctes.push(cte.name.as_str());
+        }
+        lint_select(db, select, &ctes, options, &mut warnings);
+    }
+    warnings
+}
+
+fn lint_select(
+    db: &Database,
+    select: &SelectStmt,
+    ctes: &[&str],
+    options: &ExecOptions,
+    warnings: &mut Vec<LintWarning>,
+) {
+    let (_, subqueries) = select.own_exprs();
+    for select in std::iter::once(select).chain(subqueries) {
+        Scope::new(db, select, ctes, options).lint(warnings);
+    }
+}
+
+/// The tables of a SELECT statement, by which the columns in it are resolved.
+struct Scope<'a> {
+    stmt: &'a SelectStmt,
+    /// Names or aliases of the tables in FROM and JOIN clauses, and the tables if they are loaded
+    tables: Vec<(&'a str, &'a str, Option<&'a Table>)>,
+    /// The names in `tables` that refer to CTEs, whose columns are unknown until they run
+    ctes: Vec<&'a str>,
+    case_insensitive: bool,
+}
+
+impl<'a> Scope<'a> {
+    fn new(db: &'a Database, stmt: &'a SelectStmt, ctes: &[&str], options: &ExecOptions) -> Self {
+        let is_cte = |name: &str| ctes.iter().any(|cte| cte.eq_ignore_ascii_case(name));
+        let tables = std::iter::once(&stmt.table)
+            .chain(stmt.join.iter().map(|join| &join.table))
+            .map(|spec| {
+                let alias = spec.alias.as_deref().unwrap_or(&spec.name);
+                // A CTE hides the table of the same name
+                let table = if is_cte(&spec.name) {
+                    None
+                } else {
+                    db.get(&spec.name)
+                };
+                (spec.name.as_str(), alias, table)
+            })
+            .collect::<Vec<_>>();
+        let ctes = tables
+            .iter()
+            .map(|(name, ..)| *name)
+            .filter(|name| is_cte(name))
+            .collect();
+        Self {
+            stmt,
+            tables,
+            ctes,
+            case_insensitive: options.case_insensitive,
+        }
+    }
+
+    fn lint(&self, warnings: &mut Vec<LintWarning>) {
+        for (name, _, table) in &self.tables {
+            if table.is_none() && !self.ctes.contains(name) {
+                warnings.push(LintWarning::UnknownTable {
+                    table: name.to_string(),
+                });
+            }
+        }
+
+        let (exprs, _) = self.stmt.own_exprs();
+        for ex in &exprs {
+            match ex {
+                Expr::Column(col) if self.resolve(col).is_empty() => {
+                    warnings.push(LintWarning::UnknownColumn {
+                        column: col.to_string(),
+                    });
+                }
+                Expr::Binary {
+                    op: BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge,
+                    lhs,
+                    rhs,
+                } => {
+                    let (col, literal) = match (&**lhs, &**rhs) {
+                        (Expr::Column(col), Expr::StrLiteral(literal))
+                        | (Expr::StrLiteral(literal), Expr::Column(col)) => (col, literal),
+                        _ => continue,
+                    };
+                    if !literal.is_empty()
+                        && Number::parse(literal, false).is_none()
+                        && self.is_numeric(col)
+                    {
+                        warnings.push(LintWarning::NumericComparison {
+                            column: col.to_string(),
+                            literal: literal.clone(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.lint_wildcard(warnings);
+
+        for (i, join) in self.stmt.join.iter().enumerate() {
+            let joindex = i + 1;
+            let mut referenced = HashSet::new();
+            join.condition.visit(&mut |ex| {
+                if let Expr::Column(col) = ex {
+                    referenced.extend(self.resolve(col).into_iter().map(|(j, _)| j));
+                }
+            });
+            if !referenced.contains(&joindex) || !referenced.iter().any(|j| *j < joindex) {
+                warnings.push(LintWarning::CartesianProduct {
+                    table: self.tables[joindex].1.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Warns about the column names that appear more than once in the expansion of `SELECT *` with joins.
+    fn lint_wildcard(&self, warnings: &mut Vec<LintWarning>) {
+        if self.stmt.join.is_empty() {
+            return;
+        }
+        let key = |name: &str| {
+            if self.case_insensitive {
+                name.to_ascii_lowercase()
+            } else {
+                name.to_string()
+            }
+        };
+        for col in &self.stmt.cols {
+            let ColSpecifier::Wildcard(modifiers) = col else {
+                continue;
+            };
+            let excluded: HashSet<_> = modifiers.exclude.iter().map(|name| key(name)).collect();
+            let mut seen = HashSet::new();
+            let mut reported = HashSet::new();
+            for (_, _, table) in &self.tables {
+                for schema in table.iter().flat_map(|table| &table.schema) {
+                    let name = key(&schema.name);
+                    if !excluded.contains(&name)
+                        && !seen.insert(name.clone())
+                        && reported.insert(name)
+                    {
+                        warnings.push(LintWarning::DuplicateColumns {
+                            column: schema.name.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// The indices of the tables that may have the column, and the index of the column in each of them, which
+    /// is `None` if the table is not loaded, since it is unknown whether it has the column.
+    fn resolve(&self, col: &Column) -> Vec<(usize, Option<usize>)> {
+        let qualified: Vec<_> = match col.table {
+            // An alias hides the name of the table, as in the execution
+            Some(ref qualifier) => (0..self.tables.len())
+                .filter(|i| self.tables[*i].1.eq_ignore_ascii_case(qualifier))
+                .collect(),
+            None => (0..self.tables.len()).collect(),
+        };
+        qualified
+            .into_iter()
+            .filter_map(|i| match self.tables[i].2 {
+                Some(table) => table
+                    .find_col(&col.column, self.case_insensitive && !col.quoted)
+                    .map(|c| (i, Some(c))),
+                None => Some((i, None)),
+            })
+            .collect()
+    }
+
+    /// Whether the column resolves to a single column whose values are all numbers, ignoring NULLs.
+    fn is_numeric(&self, col: &Column) -> bool {
+        let [(i, Some(c))] = self.resolve(col)[..] else {
+            return false;
+        };
+        self.tables[i]
+            .2
+            .is_some_and(|table| table.column_type(c).is_numeric())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::parse_statement, test_util::db_from_csv};
+
+    fn lint_sql(sql: &str) -> Vec<String> {
+        let db = db_from_csv(&[
+            ("people", "id,name,age\n1,Alice,30\n2,Bob,25\n"),
+            ("orders", "id,person_id,item\n1,1,apple\n2,2,banana\n"),
+        ]);
+        let stmt = parse_statement(sql).unwrap();
+        lint(&db, &stmt, &ExecOptions::DEFAULT)
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        assert!(
+            lint_sql(
+                "SELECT name, item FROM people INNER JOIN orders AS o ON people.id = o.person_id WHERE age > 20"
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            lint_sql("SELECT name FROM people WHERE age > 'abc' AND name = 'Bob' AND age <> ''"),
+            ["Column 'age' has only numbers, but is compared to 'abc', which is not a number"]
+        );
+        assert_eq!(
+            lint_sql("SELECT naem FROM people WHERE id IN (SELECT person FROM orders)"),
+            [
+                "Column 'naem' is not in any table of the query",
+                "Column 'person' is not in any table of the query",
+            ]
+        );
+        assert_eq!(
+            lint_sql("SELECT * FROM people INNER JOIN orders ON people.id = orders.person_id"),
+            ["SELECT * yields more than one column 'id'"]
+        );
+        assert!(
+            lint_sql(
+                "SELECT * EXCLUDE (id) FROM people INNER JOIN orders ON people.id = person_id"
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            lint_sql("SELECT name FROM people INNER JOIN orders AS o ON o.id = o.person_id"),
+            [
+                "Join condition of 'o' does not relate it to the preceding tables, which makes a cartesian product"
+            ]
+        );
+        assert_eq!(
+            lint_sql("SELECT x FROM missing"),
+            ["Table 'missing' is not loaded"]
+        );
+        // The columns of a CTE are unknown, even if it hides a loaded table
+        assert_eq!(
+            lint_sql(
+                "WITH adults AS (SELECT naem FROM people WHERE age >= 18), people AS (SELECT * FROM adults) \
+                    SELECT anything FROM people WHERE id IN (SELECT id FROM adults)"
+            ),
+            ["Column 'naem' is not in any table of the query"]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! Loading a CSV file filtered by a condition, keeping only the matching rows in memory, e.g. to analyze a
+//! subset of a file much larger than the subset.
+
+use std::{error::Error, io::BufRead};
+#[cfg(feature = "fs")]
+use std::{fs::File, io::BufReader, path::Path};
+
+use crate::{
+    csv::parse_csv,
+    db::Database,
+    parser::parse_expression,
+    select::{BufferOutput, ColSpecifier, SelectStmt, TableSpecifier, exec_select},
+    table::{RowSchema, Table},
+};
+
+/// The number of rows read before filtering them, which bounds the memory for the rows that do not match
+const CHUNK_ROWS: usize = 1024;
+
+/// Loads the CSV file as a table named after the file without the extension, like the files in `data`, with
+/// only the rows for which the condition like `author = 2 AND title LIKE 'The%'` holds.
+/// The condition can refer only to the columns of the file.
+#[cfg(feature = "fs")]
+pub fn load_csv_filtered(
+    db: &mut Database,
+    path: impl AsRef<Path>,
+    predicate_sql: &str,
+) -> Result<(), Box<dyn Error>> {
+    load_csv(db, path.as_ref(), None, Some(predicate_sql))
+}
+
+/// Loads the CSV file as a table named after the file without the extension, with only the given columns,
+/// so that the columns never queried in a wide file do not take memory. The table still records all the
+/// columns of the file in [`Table::source_header`], which `DESCRIBE` lists.
+#[cfg(feature = "fs")]
+pub fn load_csv_columns(
+    db: &mut Database,
+    path: impl AsRef<Path>,
+    columns: &[&str],
+) -> Result<(), Box<dyn Error>> {
+    load_csv(db, path.as_ref(), Some(columns), None)
+}
+
+#[cfg(feature = "fs")]
+fn load_csv(
+    db: &mut Database,
+    path: &Path,
+    columns: Option<&[&str]>,
+    predicate_sql: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let name = path
+        .file_stem()
+        .ok_or_else(|| format!("No file name in {path:?}"))?
+        .to_string_lossy()
+        .to_string();
+    let file = File::open(path).map_err(|e| format!("error reading file {path:?}: {e}"))?;
+    let table = read_table(&name, BufReader::new(file), columns, predicate_sql)?;
+    db.insert(name, table);
+    Ok(())
+}
+
+/// Same as [`make_table`](crate::make_table), but reads the CSV line by line and keeps only the rows for
+/// which the condition holds.
+pub fn make_table_filtered(
+    name: &str,
+    csv: impl BufRead,
+    predicate_sql: &str,
+) -> Result<Table, Box<dyn Error>> {
+    read_table(name, csv, None, Some(predicate_sql))
+}
+
+/// Same as [`make_table`](crate::make_table), but reads the CSV line by line and keeps only the given
+/// columns, in the order in the file.
+pub fn make_table_columns(
+    name: &str,
+    csv: impl BufRead,
+    columns: &[&str],
+) -> Result<Table, Box<dyn Error>> {
+    read_table(name, csv, Some(columns), None)
+}
+
+/// Reads the CSV line by line, keeping the given columns, or all of them if `None`, of the rows for which the
+/// condition holds. The condition can refer to the columns not kept.
+fn read_table(
+    name: &str,
+    csv: impl BufRead,
+    columns: Option<&[&str]>,
+    predicate_sql: Option<&str>,
+) -> Result<Table, Box<dyn Error>> {
+    let condition = predicate_sql.map(parse_expression).transpose()?;
+    let mut lines = records(csv);
+    let header = lines
+        .next()
+        .ok_or_else(|| "CSV needs at least 1 line for the header".to_string())??;
+    let header: Vec<_> = parse_csv(&header)?
+        .into_iter()
+        .next()
+        .unwrap_or_default()
+        .iter()
+        .map(|name| name.trim().to_string())
+        .collect();
+    let kept: Vec<usize> = match columns {
+        Some(columns) => {
+            if let Some(missing) = columns.iter().find(|col| !header.iter().any(|h| h == *col)) {
+                return Err(format!("Column {missing} is not in the header of the CSV").into());
+            }
+            (0..header.len())
+                .filter(|i| columns.contains(&header[*i].as_str()))
+                .collect()
+        }
+        None => (0..header.len()).collect(),
+    };
+    let schema: Vec<_> = header
+        .iter()
+        .map(|name| RowSchema::new(name.clone()))
+        .collect();
+    let filter = condition.map(|condition| SelectStmt {
+        with: vec![],
+        distinct: false,
+        cols: vec![ColSpecifier::Wildcard(Default::default())],
+        table: TableSpecifier {
+            name: name.to_string(),
+            alias: None,
+        },
+        join: vec![],
+        condition: Some(condition),
+        group_by: None,
+        qualify: None,
+        ordering: vec![],
+        limit: None,
+        offset: None,
+    });
+    let project = |row: &[String], data: &mut Vec<String>| {
+        data.extend(kept.iter().map(|i| row[*i].clone()));
+    };
+
+    let mut data = vec![];
+    let mut chunk = vec![];
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = parse_csv(&line)?.into_iter().next().unwrap_or_default();
+        if record.len() != header.len() {
+            return Err("CSV needs the same number of columns as the header".into());
+        }
+        let Some(ref filter) = filter else {
+            project(&record, &mut data);
+            continue;
+        };
+        chunk.extend(record);
+        if CHUNK_ROWS * header.len() <= chunk.len() {
+            for row in filter_chunk(filter, &schema, std::mem::take(&mut chunk))? {
+                project(&row, &mut data);
+            }
+        }
+    }
+    // Run it even on an empty chunk, so that an unknown column is an error even if the file has no rows
+    if let Some(ref filter) = filter {
+        for row in filter_chunk(filter, &schema, chunk)? {
+            project(&row, &mut data);
+        }
+    }
+
+    let schema = kept.iter().map(|i| schema[*i].clone()).collect();
+    let mut table = Table::new(name.to_string(), schema, data);
+    if kept.len() < header.len() {
+        table.source_header = Some(header);
+    }
+    Ok(table)
+}
+
+/// The lines of the CSV joined into records, since a quoted cell can have line breaks. A line with an odd
+/// number of quotes starts or ends such a cell, given that a quote in a quoted cell is written as `""`.
+fn records(csv: impl BufRead) -> impl Iterator<Item = std::io::Result<String>> {
+    let mut lines = csv.lines();
+    std::iter::from_fn(move || {
+        let mut record = match lines.next()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        while record.matches('"').count() % 2 == 1 {
+            match lines.next() {
+                Some(Ok(line)) => {
+                    record.push('\n');
+                    record.push_str(&line);
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                // The parser reports the unterminated quote
+                None => break,
+            }
+        }
+        Some(Ok(record))
+    })
+}
+
+/// The rows in the chunk that the statement yields.
+fn filter_chunk(
+    stmt: &SelectStmt,
+    schema: &[RowSchema],
+    chunk: Vec<String>,
+) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let name = stmt.table.name.clone();
+    let table = Table::new(name.clone(), schema.to_vec(), chunk);
+    let db = Database::from([(name, table)]);
+    let mut out = BufferOutput::default();
+    exec_select(&mut out, &db, stmt)?;
+    // The first row is the header
+    Ok(out.0.into_iter().skip(1).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_make_table_filtered() {
+        let csv = "id,name\n1, a\n2, \"b\n\"\"B\"\"\"\r\n\n3, c\n";
+        let table = make_table_filtered("t", csv.as_bytes(), "id >= 2 AND t.name <> 'c'").unwrap();
+        assert_eq!(table.data, ["2", "b\n\"B\""]);
+
+        let rows = (0..3000).map(|i| format!("{i}\n")).collect::<String>();
+        let table = make_table_filtered("t", format!("n\n{rows}").as_bytes(), "MOD(n, 1000) = 999");
+        assert_eq!(table.unwrap().data, ["999", "1999", "2999"]);
+
+        let err = make_table_filtered("t", "id\n".as_bytes(), "naem = 1").unwrap_err();
+        assert!(
+            err.to_string().starts_with("Unknown column 'naem'"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_make_table_columns() {
+        let csv = "id,name,phone\n1, a, 123\n2, b, 456\n";
+        let table = make_table_columns("t", csv.as_bytes(), &["phone", "id"]).unwrap();
+        let names: Vec<_> = table.schema.iter().map(|col| col.name.as_str()).collect();
+        assert_eq!(names, ["id", "phone"]);
+        assert_eq!(table.data, ["1", "123", "2", "456"]);
+        assert_eq!(
+            table.source_header,
+            Some(vec![
+                "id".to_string(),
+                "name".to_string(),
+                "phone".to_string()
+            ])
+        );
+
+        let mut out = BufferOutput::default();
+        let db = Database::from([("t".to_string(), table)]);
+        crate::exec_describe(&mut out, &db, "t").unwrap();
+        assert_eq!(
+            out.0[1..],
+            [
+                ["1", "id", "integer"],
+                ["2", "name", "not loaded"],
+                ["3", "phone", "integer"]
+            ]
+        );
+
+        // The condition can refer to the columns not kept
+        let table = read_table("t", csv.as_bytes(), Some(&["id"]), Some("name = 'b'")).unwrap();
+        assert_eq!(table.data, ["2"]);
+
+        let err = make_table_columns("t", csv.as_bytes(), &["email"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Column email is not in the header of the CSV"
+        );
+    }
+}
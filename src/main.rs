@@ -1,78 +1,688 @@
-mod csv;
-mod db;
-mod eval;
-mod parser;
-mod select;
-mod table;
-
-use std::{collections::HashMap, error::Error, fs::read_dir};
-
-use nom::Finish;
+use std::{
+    error::Error,
+    fs::read_dir,
+    io::{BufRead, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
-use crate::{
-    db::Statement,
-    parser::statement,
-    select::{CsvOutput, SelectStmt, exec_select, format_select},
-    table::{Table, make_table},
+use bogosql::{
+    AggregateNulls, BufferOutput, Catalog, CsvOutput, Database, ExecObserver, ExecOptions,
+    ExecTrace, JsonOutput, Observer, Params, QueryOutput, RowNumbers, Session, StatementOutput,
+    format_error_json, format_error_text, make_table_partitioned, make_table_with, parse_byte_size,
+    parse_utc_offset, split_statements,
 };
 
 #[derive(Parser)]
 struct Args {
-    #[clap(default_value = "SELECT * FROM phonebook", help = "SQL string")]
+    #[clap(
+        default_value = "SELECT * FROM phonebook",
+        help = "SQL string, or - to read it from stdin"
+    )]
     query: String,
-    #[clap(short, long, default_value = "false", help = "Format output in CSV")]
+    #[clap(
+        short,
+        long,
+        default_value = "false",
+        conflicts_with = "output",
+        help = "Format output in CSV, same as --output csv"
+    )]
     output_csv: bool,
+    #[clap(long, value_enum, help = "Output format [default: table]")]
+    output: Option<OutputFormat>,
+    #[clap(
+        long,
+        default_value = "30",
+        help = "Switch the table output to vertical for results with more columns than this"
+    )]
+    max_columns: usize,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "text",
+        conflicts_with = "file",
+        help = "Format of errors, JSON for tools to locate them in the query"
+    )]
+    error_format: ErrorFormat,
+    #[clap(
+        short,
+        long,
+        help = "Trace the execution of SELECT, recording up to the given number of events"
+    )]
+    trace: Option<usize>,
+    #[clap(
+        long,
+        help = "Treat NULLs as 0 in aggregate functions and yield 0 over no values, like a spreadsheet"
+    )]
+    spreadsheet_nulls: bool,
+    #[clap(
+        long,
+        help = "Print the rows scanned per table, the combinations evaluated, the rows emitted and the time of SELECT"
+    )]
+    stats: bool,
+    #[clap(
+        long,
+        help = "Show the number of the combinations of rows scanned on stderr while a long SELECT runs, if stderr is a terminal"
+    )]
+    progress: bool,
+    #[clap(
+        long,
+        help = "Maximum number of decimal places in the results of aggregate functions [default: 6]"
+    )]
+    precision: Option<usize>,
+    #[clap(
+        long,
+        help = "Compute arithmetic and aggregates on plain decimal values like prices exactly, instead of in floating point"
+    )]
+    decimal: bool,
+    #[clap(
+        long,
+        help = "Resolve column names only if they match exactly, rather than ignoring case as by default"
+    )]
+    case_sensitive: bool,
+    #[clap(
+        long,
+        value_parser = parse_utc_offset,
+        allow_hyphen_values = true,
+        help = "Time zone of current_date and current_time, as UTC or an offset like +09:00 [default: UTC]"
+    )]
+    timezone: Option<i32>,
+    #[clap(
+        long,
+        help = "Make the results reproducible: sort the rows of SELECT without ORDER BY, and fix now() at 2000-01-01 00:00:00 UTC"
+    )]
+    deterministic: bool,
+    #[clap(
+        long,
+        value_parser = parse_byte_size,
+        help = "Estimated memory for the groups of GROUP BY, like 64M, beyond which rows are spilled to temporary files [default: unlimited]"
+    )]
+    memory_budget: Option<usize>,
+    #[clap(
+        long,
+        help = "Add columns _row_<table> with the index of the row of each joined table that an output row is made of"
+    )]
+    provenance: bool,
+    #[clap(
+        long,
+        value_parser = parse_byte_size,
+        help = "Truncate the output cells larger than the size, like 4K, marking the number of the bytes cut [default: unlimited]"
+    )]
+    max_cell_size: Option<usize>,
+    #[clap(
+        long,
+        help = "Fail a SELECT that outputs more rows than this [default: unlimited]"
+    )]
+    max_rows: Option<usize>,
+    #[clap(
+        long,
+        help = "Fail a SELECT that evaluates more combinations of the rows of the joined tables than this, like a cross join of large tables [default: unlimited]"
+    )]
+    max_combinations: Option<usize>,
+    #[clap(
+        long,
+        help = "Fail a SELECT that runs longer than the milliseconds [default: unlimited]"
+    )]
+    time_budget: Option<u64>,
+    #[clap(
+        long,
+        help = "Scan the rows of the first table of a nested loop join on all the CPUs, keeping the order of the output rows"
+    )]
+    parallel: bool,
+    #[clap(
+        long = "data-dir",
+        help = "Load the CSV files in the directory as tables named after the files, with the column types and the saved queries in its catalog file .bogosql_catalog.json if any, can be repeated [default: data]"
+    )]
+    data_dirs: Vec<PathBuf>,
+    #[clap(
+        long = "table",
+        value_parser = parse_table,
+        help = "Load a CSV file as a table, e.g. books=path/to/books.csv, or named after the file without name=, can be repeated. The files of a directory, or the files given with the same name, are the partitions of one table"
+    )]
+    tables: Vec<(String, PathBuf)>,
+    #[clap(
+        long,
+        help = "Reject the partitions of a table with other columns than the first one, instead of filling the missing columns with NULL"
+    )]
+    strict_schema: bool,
+    #[clap(
+        long,
+        value_parser = parse_delimiter,
+        help = "Delimiter of the cells in the CSV files, e.g. ';' or tab [default: guessed from the header]"
+    )]
+    delimiter: Option<char>,
+    #[clap(
+        short,
+        long = "param",
+        value_parser = parse_param,
+        help = "Bind a value to a placeholder, e.g. name=value for :name or 1=value for ?1"
+    )]
+    params: Vec<(String, String)>,
+    #[clap(
+        long,
+        value_delimiter = ',',
+        help = "Output only the given columns, replacing the select list, e.g. --columns name,phone"
+    )]
+    columns: Vec<String>,
+    #[clap(
+        long,
+        help = "Prepend a column '#' with the row numbers to the results"
+    )]
+    row_numbers: bool,
+    #[clap(
+        long,
+        conflicts_with = "file",
+        help = "Print warnings about likely mistakes in the query instead of running it"
+    )]
+    lint: bool,
+    #[clap(
+        long,
+        help = "Read statements terminated by ';' from stdin until EOF, instead of running QUERY"
+    )]
+    repl: bool,
+    #[clap(
+        short,
+        long,
+        conflicts_with = "repl",
+        help = "Run the statements separated by ';' in the file, or stdin if -, instead of running QUERY"
+    )]
+    file: Option<String>,
+    #[clap(
+        long,
+        requires = "file",
+        help = "Skip the rest of the file after the first statement that fails"
+    )]
+    stop_on_error: bool,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// ASCII table
+    Table,
+    /// A record of `column | value` lines for each row
+    Vertical,
+    Csv,
+    /// Array of objects keyed by column names, e.g. for piping into jq
+    Json,
+}
 
-    let mut db = HashMap::new();
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    /// `Error: ` followed by the message, and the query with a caret at where a parse error is
+    Text,
+    /// An object with the kind, the message, the span in the query and the suggestions
+    Json,
+}
 
-    for entry in read_dir("data")? {
-        if let Ok(f) = entry
-            && let Ok(t) = f.file_type()
-            && t.is_file()
-        {
-            let str = std::fs::read_to_string(f.path())?;
-            let path = f.path();
-            let Some(name) = path.file_stem() else {
-                continue;
-            };
-            let table_name = name.to_string_lossy().to_string();
-            let table = make_table(&table_name, &str).map_err(|e| {
-                format!(
-                    "error processing file {file:?}: {e}",
-                    file = f.path().to_str()
-                )
-            })?;
-            db.insert(table_name, table);
-        }
-    }
-
-    let (rest, stmt) = statement(&args.query).finish().unwrap();
-
-    if rest != "" {
-        return Err(format!("SQL has not finished: extra string: \"{rest}\"").into());
-    }
-
-    match stmt {
-        Statement::Select(ref rows) => {
-            if args.output_csv {
-                let mut buf = CsvOutput(vec![]);
-                let _ = exec_select(&mut buf, &db, rows)?;
-                let out = String::from_utf8(buf.0)?;
-                println!("Result: \n{out}");
+impl Args {
+    fn output_format(&self) -> OutputFormat {
+        if self.output_csv {
+            OutputFormat::Csv
+        } else {
+            self.output.unwrap_or(OutputFormat::Table)
+        }
+    }
+
+    /// Whether to show the progress, which would clutter the output redirected to a file
+    fn show_progress(&self) -> bool {
+        self.progress && std::io::stderr().is_terminal()
+    }
+}
+
+/// Shows the number of the combinations scanned so far on a line of stderr, which is cleared before the results
+struct ProgressIndicator;
+
+impl ExecObserver for ProgressIndicator {
+    fn on_rows_scanned(&mut self, rows: usize) {
+        eprint!("\rScanned {rows} combinations of rows...");
+    }
+}
+
+fn parse_param(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Parameter must be in the form of name=value: \"{s}\""))?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+fn main() {
+    let mut args = Args::parse();
+    // Read before running, so that the caret of a parse error points into the query read
+    if args.query == "-" && !args.repl && args.file.is_none() {
+        match read_source("-") {
+            Ok(query) => {
+                // A query in a file tends to end with a semicolon
+                let query = query.trim();
+                args.query = query.strip_suffix(';').unwrap_or(query).to_string();
+            }
+            Err(e) => {
+                eprintln!("Error: error reading stdin: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    // Print the error by Display rather than Debug, which returning it from main would do
+    if let Err(e) = run_main(&args) {
+        eprintln!("{}", format_error(&args, &*e, &args.query));
+        std::process::exit(1);
+    }
+}
+
+/// Formats an error of running `sql` as text or JSON, as given by `--error-format`.
+fn format_error(args: &Args, e: &(dyn Error + 'static), sql: &str) -> String {
+    match args.error_format {
+        ErrorFormat::Text => format_error_text(e, sql),
+        ErrorFormat::Json => format_error_json(e, sql),
+    }
+}
+
+fn parse_table(s: &str) -> Result<(String, PathBuf), String> {
+    let (name, path) = match s.split_once('=') {
+        Some((name, path)) => (name.to_string(), PathBuf::from(path)),
+        None => {
+            let path = PathBuf::from(s);
+            let name = table_name(&path).ok_or_else(|| format!("No file name in \"{s}\""))?;
+            (name, path)
+        }
+    };
+    if name.is_empty() {
+        return Err(format!("Table name must not be empty: \"{s}\""));
+    }
+    Ok((name, path))
+}
+
+/// The table name of a CSV file, which is the file name without the extension
+fn table_name(path: &Path) -> Option<String> {
+    Some(path.file_stem()?.to_string_lossy().to_string())
+}
+
+fn parse_delimiter(s: &str) -> Result<char, String> {
+    let delimiter = match s {
+        "tab" | "\\t" => '\t',
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(format!("Delimiter must be a character or tab: \"{s}\"")),
+            }
+        }
+    };
+    if delimiter == '"' || delimiter == '\n' {
+        return Err(format!("Delimiter cannot be {delimiter:?}"));
+    }
+    Ok(delimiter)
+}
+
+fn load_table(
+    db: &mut Database,
+    name: String,
+    path: &Path,
+    delimiter: Option<char>,
+) -> Result<(), Box<dyn Error>> {
+    let str =
+        std::fs::read_to_string(path).map_err(|e| format!("error reading file {path:?}: {e}"))?;
+    let table = make_table_with(&name, &str, delimiter)
+        .map_err(|e| format!("error processing file {path:?}: {e}"))?;
+    db.insert(name, table);
+    Ok(())
+}
+
+/// Loads the files as the partitions of a table, printing how the partitions with other columns than the
+/// first one were fitted
+fn load_partitioned(
+    db: &mut Database,
+    name: &str,
+    paths: &[PathBuf],
+    args: &Args,
+) -> Result<(), Box<dyn Error>> {
+    let csvs = paths
+        .iter()
+        .map(|path| {
+            std::fs::read_to_string(path).map_err(|e| format!("error reading file {path:?}: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let sources: Vec<_> = paths.iter().map(|path| path.to_string_lossy()).collect();
+    let partitions: Vec<_> = sources
+        .iter()
+        .zip(&csvs)
+        .map(|(source, csv)| (source.as_ref(), csv.as_str()))
+        .collect();
+    let (table, summaries) =
+        make_table_partitioned(name, &partitions, args.delimiter, args.strict_schema)
+            .map_err(|e| format!("error processing table {name}: {e}"))?;
+    if summaries.iter().any(|summary| !summary.is_unchanged()) {
+        eprintln!("Table {name} has partitions with different columns:");
+        for summary in summaries {
+            eprintln!("  {summary}");
+        }
+    }
+    db.insert(name.to_string(), table);
+    Ok(())
+}
+
+fn run_main(args: &Args) -> Result<(), Box<dyn Error>> {
+    let mut db = Database::new();
+
+    let mut catalogs = vec![];
+    for dir in &data_dirs(args) {
+        let entries = read_dir(dir).map_err(|e| format!("error reading directory {dir:?}: {e}"))?;
+        for entry in entries {
+            if let Ok(f) = entry
+                && let Ok(t) = f.file_type()
+                && t.is_file()
+            {
+                let path = f.path();
+                // Hidden files like the catalog are not tables
+                let Some(name) = table_name(&path).filter(|name| !name.starts_with('.')) else {
+                    continue;
+                };
+                load_table(&mut db, name, &path, args.delimiter)?;
+            }
+        }
+        catalogs.extend(Catalog::load(dir)?);
+    }
+    // Given after the directories, so that they replace the tables of the same names in them
+    let mut partitioned: Vec<(&String, Vec<PathBuf>)> = vec![];
+    for (name, path) in &args.tables {
+        let paths = if path.is_dir() {
+            let mut paths = read_dir(path)
+                .map_err(|e| format!("error reading directory {path:?}: {e}"))?
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.is_file())
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        } else {
+            vec![path.clone()]
+        };
+        match partitioned.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, partitions)) => partitions.extend(paths),
+            None => partitioned.push((name, paths)),
+        }
+    }
+    for (name, paths) in partitioned {
+        match &paths[..] {
+            [path] => load_table(&mut db, name.clone(), path, args.delimiter)?,
+            _ => load_partitioned(&mut db, name, &paths, args)?,
+        }
+    }
+    for catalog in &catalogs {
+        catalog.declare_types(&mut db)?;
+    }
+
+    let options = ExecOptions::DEFAULT
+        .with_aggregate_nulls(if args.spreadsheet_nulls {
+            AggregateNulls::Spreadsheet
+        } else {
+            AggregateNulls::Standard
+        })
+        .with_float_precision(Some(args.precision.unwrap_or(6)))
+        .with_decimal(args.decimal)
+        .with_case_insensitive(!args.case_sensitive)
+        .with_utc_offset(args.timezone.unwrap_or(0))
+        .with_deterministic(args.deterministic)
+        .with_memory_budget(args.memory_budget)
+        .with_provenance(args.provenance)
+        .with_max_cell_size(args.max_cell_size)
+        .with_max_rows(args.max_rows)
+        .with_max_combinations(args.max_combinations)
+        .with_time_budget(args.time_budget.map(Duration::from_millis))
+        .with_parallel(args.parallel)
+        .with_observer(
+            args.show_progress()
+                .then(|| Observer::new(ProgressIndicator)),
+        );
+
+    let mut session = Session::new(&db);
+    session.options = options;
+    session.params = args.params.iter().cloned().collect();
+    session.columns = args.columns.clone();
+    session.stop_on_error = args.stop_on_error;
+    for catalog in &catalogs {
+        catalog.save_queries(&mut session.saved)?;
+    }
+
+    if args.repl {
+        return repl(&mut session, args);
+    }
+
+    if let Some(ref file) = args.file {
+        return run_file(&session, file, args);
+    }
+
+    run(&session, &args.query, args)
+}
+
+/// The directories to load the tables from. The tables in the repository are the default, as long as no
+/// tables are given.
+fn data_dirs(args: &Args) -> Vec<PathBuf> {
+    if args.data_dirs.is_empty() && args.tables.is_empty() {
+        vec![PathBuf::from("data")]
+    } else {
+        args.data_dirs.clone()
+    }
+}
+
+/// Reads the whole file, or stdin if the path is `-`.
+fn read_source(path: &str) -> std::io::Result<String> {
+    if path == "-" {
+        let mut buf = String::new();
+        std::io::stdin().read_to_string(&mut buf)?;
+        return Ok(buf);
+    }
+    std::fs::read_to_string(path)
+}
+
+/// Reads statements from stdin and runs them one by one. Errors are printed and do not end the session.
+/// A line starting with `\` between statements is a command of the REPL, as in [`run_command`].
+fn repl(session: &mut Session, args: &Args) -> Result<(), Box<dyn Error>> {
+    let stdin = std::io::stdin();
+    let mut buf = String::new();
+    loop {
+        print!(
+            "{}",
+            if buf.trim().is_empty() {
+                "bogosql> "
             } else {
-                let mut buf: Vec<u8> = vec![];
-                format_select(&mut buf, &db, rows)?;
-                let out = String::from_utf8(buf)?;
-                println!("Result: \n{out}");
+                "      -> "
+            }
+        );
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        if buf.trim().is_empty()
+            && let Some(command) = line.trim().strip_prefix('\\')
+        {
+            if let Err(e) = run_command(session, command, args) {
+                println!("Error: {e}");
+            }
+            continue;
+        }
+        buf.push_str(&line);
+        let (stmts, rest) = split_statements(&buf);
+        for sql in stmts {
+            if let Err(e) = run(session, &sql, args) {
+                println!("{}", format_error(args, &*e, &sql));
             }
         }
+        buf = rest;
     }
+    if !buf.trim().is_empty() {
+        println!("Ignoring an unterminated statement: {}", buf.trim());
+    }
+    Ok(())
+}
 
+/// Runs a command of the REPL, which is the line after `\`: `save <name> <query>` saves the query in the
+/// session, and `run <name> [param=value ...]` runs the query saved under the name with the values bound to its
+/// placeholders. `SHOW QUERIES` lists the saved queries.
+fn run_command(session: &mut Session, command: &str, args: &Args) -> Result<(), Box<dyn Error>> {
+    let (command, rest) = command
+        .split_once(char::is_whitespace)
+        .unwrap_or((command, ""));
+    match command {
+        "save" => {
+            let (name, sql) = rest
+                .trim()
+                .split_once(char::is_whitespace)
+                .ok_or("Usage: \\save <name> <query>")?;
+            let sql = sql.trim().trim_end_matches(';');
+            if let Err(e) = session.save_query(name, sql) {
+                println!("{}", format_error(args, &e, sql));
+                return Ok(());
+            }
+            // Kept in the catalog of the first data directory, so that the query is saved in the next sessions
+            if let Some(dir) = data_dirs(args).first() {
+                let mut catalog = Catalog::load(dir)?.unwrap_or_default();
+                catalog.queries.insert(name.to_string(), sql.to_string());
+                catalog.store(dir)?;
+            }
+        }
+        "run" => {
+            let mut words = rest.split_whitespace();
+            let name = words
+                .next()
+                .ok_or("Usage: \\run <name> [param=value ...]")?;
+            let params = words.map(parse_param).collect::<Result<Params, _>>()?;
+            match session.run_saved(name, &params) {
+                Ok(output) => print_output(args, output)?,
+                Err(e) => {
+                    let sql = session.saved.get(name).unwrap_or_default();
+                    println!("{}", format_error(args, &*e, sql));
+                }
+            }
+        }
+        _ => return Err(format!("Unknown command \\{command}, expected \\save or \\run").into()),
+    }
+    Ok(())
+}
+
+/// Runs the statements in the file and prints the result or the error of each one.
+fn run_file(session: &Session, file: &str, args: &Args) -> Result<(), Box<dyn Error>> {
+    let script = read_source(file)?;
+    let mut failed = false;
+    for res in session.execute_script(&script) {
+        println!("{}", res.sql);
+        match res.result {
+            Ok(output) => print_output(args, output)?,
+            Err(e) => {
+                println!("Error: {e}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err("Some statements failed".into());
+    }
+    Ok(())
+}
+
+/// Prints the rows or the plan of the result of a statement run in the session.
+fn print_output(args: &Args, output: StatementOutput) -> Result<(), Box<dyn Error>> {
+    match output {
+        StatementOutput::Rows(rows) => print_rows(args, &mut |out| {
+            for row in &rows {
+                out.output(row)?;
+            }
+            Ok(())
+        }),
+        plan => {
+            let mut buf: Vec<u8> = vec![];
+            plan.format(&mut buf)?;
+            println!("{}", String::from_utf8(buf)?);
+            Ok(())
+        }
+    }
+}
+
+/// Runs the statement in the session and prints its result, or only its warnings with `--lint`.
+fn run(session: &Session, sql: &str, args: &Args) -> Result<(), Box<dyn Error>> {
+    if args.lint {
+        let warnings = session.lint(sql)?;
+        if warnings.is_empty() {
+            println!("No warnings");
+        }
+        for warning in warnings {
+            println!("Warning: {warning}");
+        }
+        return Ok(());
+    }
+
+    let mut trace = ExecTrace::new(args.trace.unwrap_or(0));
+    let output = session
+        .execute_traced(sql, &mut trace)
+        .inspect_err(|_| clear_progress(args))?;
+    print_output(args, output)?;
+    // Only a SELECT is traced, which times itself
+    if trace.stats.elapsed.is_none() {
+        return Ok(());
+    }
+    if args.trace.is_some() {
+        println!("Trace:");
+        for event in &trace.events {
+            println!("{event}");
+        }
+        if trace.truncated {
+            println!("(trace truncated)");
+        }
+    }
+    if args.stats {
+        println!("Stats:\n{}", trace.stats);
+    }
+    Ok(())
+}
+
+/// Clears the line of the progress indicator, if shown, before printing the result or the error.
+fn clear_progress(args: &Args) {
+    if args.show_progress() {
+        eprint!("\r\x1b[K");
+    }
+}
+
+/// Writes the rows of a result to the output
+type WriteRows<'a> = dyn FnMut(&mut dyn QueryOutput) -> Result<(), Box<dyn Error>> + 'a;
+
+/// Runs `exec` with an output in the format given by the arguments, and prints the rows written to it.
+fn print_rows(args: &Args, exec: &mut WriteRows) -> Result<(), Box<dyn Error>> {
+    let mut exec = |out: &mut dyn QueryOutput| {
+        let res = if args.row_numbers {
+            exec(&mut RowNumbers::new(out))
+        } else {
+            exec(out)
+        };
+        clear_progress(args);
+        res
+    };
+    match args.output_format() {
+        OutputFormat::Csv => {
+            let mut buf = CsvOutput(vec![]);
+            exec(&mut buf)?;
+            println!("Result: \n{}", String::from_utf8(buf.0)?);
+        }
+        OutputFormat::Json => {
+            let mut buf = JsonOutput::default();
+            exec(&mut buf)?;
+            // No heading, so that the output can be parsed as it is
+            println!("{}", String::from_utf8(buf.finish())?);
+        }
+        OutputFormat::Table => {
+            let mut buf = BufferOutput::default();
+            exec(&mut buf)?;
+            let mut out: Vec<u8> = vec![];
+            buf.format_auto(&mut out, args.max_columns)?;
+            println!("Result: \n{}", String::from_utf8(out)?);
+        }
+        OutputFormat::Vertical => {
+            let mut buf = BufferOutput::default();
+            exec(&mut buf)?;
+            let mut out: Vec<u8> = vec![];
+            buf.format_vertical(&mut out)?;
+            println!("Result: \n{}", String::from_utf8(out)?);
+        }
+    }
     Ok(())
 }
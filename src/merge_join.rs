@@ -0,0 +1,125 @@
+//! Merge join for an equi-join when both tables are already sorted on the key columns,
+//! which is typical for key-sorted export files.
+//! It yields the matching pairs in the same order as the nested loop would, since the left table is scanned
+//! in its order.
+
+use crate::{
+    join::{JoinKey, JoinKeys},
+    table::Table,
+};
+
+pub(crate) fn row_count(table: &Table) -> usize {
+    table.data.len() / table.schema.len()
+}
+
+/// Whether the column is sorted by the keys of its cells
+pub(crate) fn is_sorted(table: &Table, col: usize, keys: &JoinKeys) -> bool {
+    (0..row_count(table))
+        .map(|row| table.get(row, col).map(|cell| keys.key(cell)))
+        .is_sorted()
+}
+
+/// Iterator over the row indices of the left and right tables for each pair with equal keys.
+pub(crate) struct MergeJoin<'a> {
+    left: &'a Table,
+    right: &'a Table,
+    keys: JoinKeys,
+    /// The current left row
+    left_row: usize,
+    /// The range of the right rows with the same key as the current left row, and the next one to yield in it
+    right_start: usize,
+    right_end: usize,
+    right_row: usize,
+}
+
+impl<'a> MergeJoin<'a> {
+    pub fn new(left: &'a Table, right: &'a Table, keys: JoinKeys) -> Self {
+        Self {
+            left,
+            right,
+            keys,
+            left_row: 0,
+            right_start: 0,
+            right_end: 0,
+            right_row: 0,
+        }
+    }
+
+    fn left_key(&self, row: usize) -> Option<JoinKey<'a>> {
+        let cell = self.left.get(row, self.keys.left_col)?;
+        Some(self.keys.key(cell))
+    }
+
+    fn right_key(&self, row: usize) -> Option<JoinKey<'a>> {
+        let cell = self.right.get(row, self.keys.right_col)?;
+        Some(self.keys.key(cell))
+    }
+}
+
+impl Iterator for MergeJoin<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (left_rows, right_rows) = (row_count(self.left), row_count(self.right));
+        if self.right_row < self.right_end {
+            self.right_row += 1;
+            return Some((self.left_row, self.right_row - 1));
+        }
+        if self.right_start < self.right_end {
+            // The next left row may have the same key and match the same range
+            self.left_row += 1;
+            let key = self.right_key(self.right_start);
+            if self.left_row < left_rows && self.left_key(self.left_row) == key {
+                self.right_row = self.right_start + 1;
+                return Some((self.left_row, self.right_start));
+            }
+            self.right_start = self.right_end;
+        }
+
+        let (mut i, mut j) = (self.left_row, self.right_start);
+        while i < left_rows && j < right_rows {
+            let key = self.left_key(i);
+            match key.cmp(&self.right_key(j)) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    self.left_row = i;
+                    self.right_start = j;
+                    self.right_end = (j..right_rows)
+                        .find(|&row| self.right_key(row) != key)
+                        .unwrap_or(right_rows);
+                    self.right_row = j + 1;
+                    return Some((i, j));
+                }
+            }
+        }
+        self.left_row = left_rows;
+        self.right_start = right_rows;
+        self.right_end = right_rows;
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{make_table, table::ColumnType};
+
+    #[test]
+    fn test_merge_join() {
+        let left = make_table("a", "id,name\n1, x\n2, y\n2, z\n4, w\n10, v\n").unwrap();
+        let right = make_table("b", "a_id,val\n2, p\n2.0, q\n3, r\n4, s\n10, t\n").unwrap();
+        let keys = JoinKeys {
+            left_col: 0,
+            right_col: 0,
+            ty: ColumnType::Integer,
+            utc_offset: 0,
+        };
+        // Sorted by the numbers, not by the text
+        assert!(is_sorted(&left, 0, &keys));
+        assert!(is_sorted(&right, 0, &keys));
+        assert!(!is_sorted(&left, 1, &keys));
+        let pairs = MergeJoin::new(&left, &right, keys).collect::<Vec<_>>();
+        assert_eq!(pairs, vec![(1, 0), (1, 1), (2, 0), (2, 1), (3, 3), (4, 4)]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Numeric values in arithmetic.
+//! Cells are strings, so the operands are parsed on every operation into the most exact representation:
+//! integers, decimals (if enabled) or f64.
+
+use crate::decimal::Decimal;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Number {
+    Int(i64),
+    Decimal(Decimal),
+    Float(f64),
+}
+
+impl Number {
+    /// Parses a value as an integer, a decimal if `decimal` is set and the value is a plain decimal literal,
+    /// or f64.
+    pub fn parse(s: &str, decimal: bool) -> Option<Self> {
+        if let Ok(val) = s.parse() {
+            return Some(Self::Int(val));
+        }
+        if decimal && let Some(val) = Decimal::parse(s) {
+            return Some(Self::Decimal(val));
+        }
+        s.parse().ok().map(Self::Float)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Self::Int(val) => val as f64,
+            Self::Decimal(val) => val.to_f64(),
+            Self::Float(val) => val,
+        }
+    }
+
+    fn to_decimal(self) -> Option<Decimal> {
+        match self {
+            Self::Int(val) => Some(Decimal::from_int(val as i128)),
+            Self::Decimal(val) => Some(val),
+            Self::Float(_) => None,
+        }
+    }
+
+    /// Applies an operation in the most exact representation of the operands.
+    /// Integer overflow yields None, while an operation that does not fit in a decimal falls back to f64.
+    fn binary(
+        self,
+        rhs: Self,
+        int: impl Fn(i64, i64) -> Option<Number>,
+        dec: impl Fn(Decimal, Decimal) -> Option<Decimal>,
+        float: impl Fn(f64, f64) -> f64,
+    ) -> Option<Self> {
+        if let (Self::Int(lhs), Self::Int(rhs)) = (self, rhs) {
+            return int(lhs, rhs);
+        }
+        if let (Some(lhs), Some(rhs)) = (self.to_decimal(), rhs.to_decimal())
+            && let Some(res) = dec(lhs, rhs)
+        {
+            return Some(Self::Decimal(res));
+        }
+        Some(Self::Float(float(self.to_f64(), rhs.to_f64())))
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.binary(
+            rhs,
+            |l, r| l.checked_add(r).map(Self::Int),
+            Decimal::checked_add,
+            |l, r| l + r,
+        )
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.binary(
+            rhs,
+            |l, r| l.checked_sub(r).map(Self::Int),
+            Decimal::checked_sub,
+            |l, r| l - r,
+        )
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        self.binary(
+            rhs,
+            |l, r| l.checked_mul(r).map(Self::Int),
+            Decimal::checked_mul,
+            |l, r| l * r,
+        )
+    }
+
+    /// Integer division yields an integer only if it is exact, e.g. `5 / 2` is `2.5`.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        self.binary(
+            rhs,
+            |l, r| {
+                if l.checked_rem(r) == Some(0) {
+                    l.checked_div(r).map(Self::Int)
+                } else if r == -1 {
+                    // i64::MIN / -1
+                    None
+                } else {
+                    Some(Self::Float(l as f64 / r as f64))
+                }
+            },
+            Decimal::checked_div,
+            |l, r| l / r,
+        )
+    }
+
+    /// The remainder with the sign of the dividend. A zero divisor yields None for integers and decimals.
+    pub fn checked_rem(self, rhs: Self) -> Option<Self> {
+        self.binary(
+            rhs,
+            |l, r| l.checked_rem(r).map(Self::Int),
+            Decimal::checked_rem,
+            |l, r| l % r,
+        )
+    }
+
+    pub fn checked_abs(self) -> Option<Self> {
+        if self.to_f64() < 0. {
+            Self::Int(0).checked_sub(self)
+        } else {
+            Some(self)
+        }
+    }
+
+    /// Rounds half away from zero to the number of decimal places, which can be negative to round to tens,
+    /// hundreds and so on.
+    pub fn round(self, places: i32) -> Self {
+        match self {
+            Self::Int(_) if 0 <= places => self,
+            Self::Decimal(val) if 0 <= places => Self::Decimal(val.round_to(places as u32)),
+            _ => {
+                let pow = 10f64.powi(places);
+                Self::from_integral((self.to_f64() * pow).round() / pow)
+            }
+        }
+    }
+
+    pub fn ceil(self) -> Self {
+        match self {
+            Self::Int(_) => self,
+            _ => Self::from_integral(self.to_f64().ceil()),
+        }
+    }
+
+    pub fn floor(self) -> Self {
+        match self {
+            Self::Int(_) => self,
+            _ => Self::from_integral(self.to_f64().floor()),
+        }
+    }
+
+    /// An integer if the value is a whole number that fits in i64, or f64 otherwise.
+    fn from_integral(val: f64) -> Self {
+        if val.fract() == 0. && val.abs() < i64::MAX as f64 {
+            Self::Int(val as i64)
+        } else {
+            Self::Float(val)
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(val) => write!(f, "{val}"),
+            Self::Decimal(val) => write!(f, "{val}"),
+            Self::Float(val) => write!(f, "{val}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn num(s: &str) -> Number {
+        Number::parse(s, true).unwrap()
+    }
+
+    #[test]
+    fn test_number() {
+        let big = "9007199254740993";
+        assert_eq!(
+            num(big).checked_add(num("0")).unwrap().to_string(),
+            big,
+            "beyond the precision of f64"
+        );
+        assert_eq!(num(&i64::MAX.to_string()).checked_add(num("1")), None);
+        assert_eq!(num(&i64::MIN.to_string()).checked_div(num("-1")), None);
+        assert_eq!(num("5").checked_div(num("2")), Some(Number::Float(2.5)));
+        assert_eq!(
+            num("1.50").checked_mul(num("2")).unwrap().to_string(),
+            "3.00"
+        );
+        assert_eq!(num("1e2").checked_sub(num("1")), Some(Number::Float(99.)));
+    }
+}
@@ -0,0 +1,85 @@
+//! Hooks called while a query runs, like showing the progress of a long scan in the CLI or posting it from the
+//! Web Worker running the wasm module to the page.
+
+use std::sync::{Arc, Mutex};
+
+/// The number of the combinations evaluated between the calls to the observer
+pub(crate) const PROGRESS_INTERVAL: usize = 1024;
+
+/// Receives the progress of the execution of a SELECT.
+pub trait ExecObserver: Send {
+    /// Called every 1024 combinations of the rows of the joined tables with the number of them evaluated so far
+    /// against the join conditions and WHERE. The count starts from 0 again for each CTE and each SELECT of a
+    /// UNION. The calls from the threads of a parallel scan can come slightly out of order.
+    fn on_rows_scanned(&mut self, rows: usize);
+}
+
+/// An [`ExecObserver`] shared by the copies of the options and the threads scanning the rows, which are compared
+/// by the identity of the observer
+#[derive(Clone)]
+pub struct Observer(pub Arc<Mutex<dyn ExecObserver>>);
+
+impl Observer {
+    pub fn new(observer: impl ExecObserver + 'static) -> Self {
+        Self(Arc::new(Mutex::new(observer)))
+    }
+
+    pub(crate) fn on_rows_scanned(&self, rows: usize) {
+        self.0.lock().unwrap().on_rows_scanned(rows);
+    }
+}
+
+impl PartialEq for Observer {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Observer {}
+
+impl std::fmt::Debug for Observer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Observer")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ExecOptions, Session,
+        test_util::{db_from_tables, table_from_rows},
+    };
+
+    #[derive(Default)]
+    struct Progress(Vec<usize>);
+
+    impl ExecObserver for Progress {
+        fn on_rows_scanned(&mut self, rows: usize) {
+            self.0.push(rows);
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let rows: Vec<_> = (0..50).map(|i| i.to_string()).collect();
+        let rows: Vec<_> = rows.iter().map(|row| [row.as_str()]).collect();
+        let rows: Vec<_> = rows.iter().map(|row| &row[..]).collect();
+        let db = db_from_tables([
+            table_from_rows("a", &["x"], &rows),
+            table_from_rows("b", &["y"], &rows),
+        ]);
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let mut session = Session::new(&db);
+        session.options =
+            ExecOptions::DEFAULT.with_observer(Some(Observer(Arc::clone(&progress) as _)));
+
+        session.execute("SELECT count(*) FROM a").unwrap();
+        assert!(progress.lock().unwrap().0.is_empty());
+        // A subquery in WHERE is not scanned for each row, so only the combinations of the join are counted
+        session
+            .execute("SELECT count(*) FROM a INNER JOIN b ON x < y WHERE x IN (SELECT y FROM b)")
+            .unwrap();
+        assert_eq!(progress.lock().unwrap().0, [1024, 2048]);
+    }
+}
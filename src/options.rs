@@ -0,0 +1,300 @@
+//! Options that change the behavior of query execution, not its result set shape.
+
+use std::time::Duration;
+
+use crate::observer::Observer;
+
+/// Options of executing statements, taken by the `*_with` variants of the entry points like
+/// [`exec_select_with`](crate::exec_select_with), [`Session`](crate::Session) and
+/// [`PreparedStatement::execute_with`](crate::PreparedStatement::execute_with).
+/// Build one from the defaults with the `with_*` methods, e.g.
+/// `ExecOptions::DEFAULT.with_decimal(true).with_float_precision(Some(2))`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecOptions {
+    pub aggregate_nulls: AggregateNulls,
+    /// The maximum number of decimal places in the results of sum, avg, min and max.
+    /// Trailing zeros are trimmed, so whole numbers are printed without a decimal point.
+    /// None prints the shortest representation that round-trips, e.g. `3.3333333333333335`.
+    pub float_precision: Option<usize>,
+    /// Compute arithmetic and aggregates exactly in fixed-point decimals on values written as plain decimals,
+    /// such as prices, instead of f64. A value that is not a plain decimal makes the result fall back to f64.
+    pub decimal: bool,
+    /// Resolve column names ignoring ASCII case if no column has exactly the name, e.g. `name` matches a column
+    /// `Name`, as in most SQL engines. A quoted name like `"Name"` always matches exactly. On by default.
+    pub case_insensitive: bool,
+    /// The time zone of `current_date` and `current_time`, as the offset from UTC in minutes.
+    pub utc_offset: i32,
+    /// Make the results reproducible, e.g. for tutorials and golden files: the rows of a SELECT without
+    /// ORDER BY are sorted as with `ORDER BY ALL`, and `now()`, `current_date` and `current_time` are at
+    /// [`DETERMINISTIC_NOW`] instead of the clock.
+    pub deterministic: bool,
+    /// The estimated memory in bytes for the groups of GROUP BY, or None for no limit. The rows of the groups
+    /// beyond it are spilled to temporary files with the `fs` feature, or make the query fail without it.
+    pub memory_budget: Option<usize>,
+    /// Add a column for each table in the FROM and JOIN clauses of a SELECT after the output columns, named
+    /// `_row_` and the alias or the name of the table, with the 0-based index of the row of the table that the
+    /// output row is made of, or NULL for the missing side of a left join, to trace an unexpected row of a join
+    /// back to the source rows. Rows of DISTINCT, GROUP BY or aggregates have none, since they are made of many.
+    pub provenance: bool,
+    /// The size in bytes beyond which a cell of the output is cut and marked like `abc...[1234 more bytes]`, or
+    /// None to output the cells whole. Large cells like embedded JSON or base64 blobs are then read from the
+    /// table without being copied whole for each row. The values compared, sorted and grouped are not truncated.
+    pub max_cell_size: Option<usize>,
+    /// The number of the rows a SELECT can output, beyond which it fails, or None for no limit
+    pub max_rows: Option<usize>,
+    /// The number of the combinations of the rows of the joined tables a SELECT can evaluate against the join
+    /// conditions and WHERE, beyond which it fails, or None for no limit. It stops a cross join of large tables
+    /// before it runs for hours.
+    pub max_combinations: Option<usize>,
+    /// The time a SELECT can run, beyond which it fails, or None for no limit. It is measured by `clock`, or by
+    /// [`std::time::Instant`] without it, which is not available in wasm32-unknown-unknown.
+    pub time_budget: Option<Duration>,
+    /// The clock measuring `time_budget` instead of [`std::time::Instant`], like `Date.now()` in a browser
+    pub clock: Option<Clock>,
+    /// Called with the progress of the execution, like the number of the rows scanned, to show it while a long
+    /// query runs
+    pub observer: Option<Observer>,
+    /// Whether to scan the combinations of the rows of the joined tables on the threads of rayon, each taking a
+    /// range of the rows of the first table, if built with the feature `parallel`. The rows are output in the same
+    /// order as a scan on a thread. Only the rows of a nested loop join without RIGHT or FULL JOIN, LIMIT or
+    /// OFFSET, grouping or window functions are scanned in parallel, and the others on a thread as before.
+    pub parallel: bool,
+}
+
+/// A monotonic clock, as the time since an arbitrary point
+#[derive(Clone, Copy)]
+pub struct Clock(pub fn() -> Duration);
+
+impl PartialEq for Clock {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::fn_addr_eq(self.0, other.0)
+    }
+}
+
+impl Eq for Clock {}
+
+impl std::fmt::Debug for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Clock")
+    }
+}
+
+/// The time of the clock in the deterministic mode, 2000-01-01 00:00:00 UTC as the seconds since the Unix epoch
+pub const DETERMINISTIC_NOW: i64 = 946_684_800;
+
+impl ExecOptions {
+    /// The default options as a constant, which can be borrowed for `'static`.
+    pub const DEFAULT: Self = Self {
+        aggregate_nulls: AggregateNulls::Standard,
+        float_precision: Some(6),
+        decimal: false,
+        case_insensitive: true,
+        utc_offset: 0,
+        deterministic: false,
+        memory_budget: None,
+        provenance: false,
+        max_cell_size: None,
+        max_rows: None,
+        max_combinations: None,
+        time_budget: None,
+        clock: None,
+        observer: None,
+        parallel: false,
+    };
+
+    pub const fn with_aggregate_nulls(mut self, aggregate_nulls: AggregateNulls) -> Self {
+        self.aggregate_nulls = aggregate_nulls;
+        self
+    }
+
+    pub const fn with_float_precision(mut self, float_precision: Option<usize>) -> Self {
+        self.float_precision = float_precision;
+        self
+    }
+
+    pub const fn with_decimal(mut self, decimal: bool) -> Self {
+        self.decimal = decimal;
+        self
+    }
+
+    pub const fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Sets the time zone as the offset from UTC in minutes, as parsed by [`parse_utc_offset`].
+    pub const fn with_utc_offset(mut self, utc_offset: i32) -> Self {
+        self.utc_offset = utc_offset;
+        self
+    }
+
+    pub const fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    pub const fn with_memory_budget(mut self, memory_budget: Option<usize>) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+
+    pub const fn with_provenance(mut self, provenance: bool) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    pub const fn with_max_cell_size(mut self, max_cell_size: Option<usize>) -> Self {
+        self.max_cell_size = max_cell_size;
+        self
+    }
+
+    pub const fn with_max_rows(mut self, max_rows: Option<usize>) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    pub const fn with_max_combinations(mut self, max_combinations: Option<usize>) -> Self {
+        self.max_combinations = max_combinations;
+        self
+    }
+
+    pub const fn with_time_budget(mut self, time_budget: Option<Duration>) -> Self {
+        self.time_budget = time_budget;
+        self
+    }
+
+    pub const fn with_clock(mut self, clock: Option<Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_observer(mut self, observer: Option<Observer>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    pub const fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+}
+
+impl Default for ExecOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Parses a time zone given as a fixed offset from UTC, like `+09:00`, `-0530`, `+9` or `UTC`, into minutes.
+/// Named zones like `Asia/Tokyo` are not supported, since they need a database of the daylight saving rules.
+pub fn parse_utc_offset(s: &str) -> Result<i32, String> {
+    let err = || format!("Time zone must be UTC or an offset like +09:00: \"{s}\"");
+    if s.eq_ignore_ascii_case("utc") || s.eq_ignore_ascii_case("z") {
+        return Ok(0);
+    }
+    let (sign, rest) = match s.split_at_checked(1) {
+        Some(("+", rest)) => (1, rest),
+        Some(("-", rest)) => (-1, rest),
+        _ => return Err(err()),
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some(hm) => hm,
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let hours: i32 = hours.parse().map_err(|_| err())?;
+    let minutes: i32 = minutes.parse().map_err(|_| err())?;
+    if hours > 14 || minutes >= 60 {
+        return Err(err());
+    }
+    Ok(sign * (hours * 60 + minutes))
+}
+
+/// Parses a size in bytes, optionally with a binary unit like `512K`, `64M` or `1G`.
+pub fn parse_byte_size(s: &str) -> Result<usize, String> {
+    let err = || format!("Size must be a number of bytes, optionally with K, M or G: \"{s}\"");
+    let (num, unit) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c.to_ascii_uppercase()),
+        _ => (s, 'B'),
+    };
+    let shift = match unit {
+        'B' => 0,
+        'K' => 10,
+        'M' => 20,
+        'G' => 30,
+        _ => return Err(err()),
+    };
+    let num: usize = num.trim().parse().map_err(|_| err())?;
+    num.checked_mul(1 << shift).ok_or_else(err)
+}
+
+/// How aggregate functions treat NULLs (empty cells and the missing side of a left join)
+/// and sets of rows without any values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AggregateNulls {
+    /// The SQL standard behavior. NULLs are ignored by all aggregate functions,
+    /// `count(*)` and `count(col)` over no values yield 0, and other aggregates over no values yield NULL.
+    #[default]
+    Standard,
+    /// Spreadsheet-like behavior. NULLs are treated as 0 and counted by `count(col)`,
+    /// and every aggregate over no values yields 0.
+    Spreadsheet,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_utc_offset() {
+        assert_eq!(parse_utc_offset("UTC"), Ok(0));
+        assert_eq!(parse_utc_offset("+09:00"), Ok(540));
+        assert_eq!(parse_utc_offset("-0530"), Ok(-330));
+        assert_eq!(parse_utc_offset("+9"), Ok(540));
+        assert!(parse_utc_offset("09:00").is_err());
+        assert!(parse_utc_offset("+25:00").is_err());
+        assert!(parse_utc_offset("Asia/Tokyo").is_err());
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("1000"), Ok(1000));
+        assert_eq!(parse_byte_size("512k"), Ok(512 * 1024));
+        assert_eq!(parse_byte_size("64M"), Ok(64 << 20));
+        assert_eq!(parse_byte_size("1G"), Ok(1 << 30));
+        assert!(parse_byte_size("1T").is_err());
+        assert!(parse_byte_size("M").is_err());
+        assert!(parse_byte_size("-1").is_err());
+    }
+
+    #[test]
+    fn test_builder() {
+        const OPTIONS: ExecOptions = ExecOptions::DEFAULT
+            .with_aggregate_nulls(AggregateNulls::Spreadsheet)
+            .with_decimal(true)
+            .with_utc_offset(540)
+            .with_deterministic(true)
+            .with_memory_budget(Some(1 << 20));
+        assert_eq!(
+            OPTIONS,
+            ExecOptions {
+                aggregate_nulls: AggregateNulls::Spreadsheet,
+                decimal: true,
+                utc_offset: 540,
+                deterministic: true,
+                memory_budget: Some(1 << 20),
+                ..ExecOptions::DEFAULT
+            }
+        );
+        assert_eq!(
+            ExecOptions::default()
+                .with_float_precision(None)
+                .with_case_insensitive(false),
+            ExecOptions {
+                float_precision: None,
+                case_insensitive: false,
+                ..ExecOptions::DEFAULT
+            }
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Scanning a nested loop join on the threads of rayon for [`ExecOptions::parallel`]. The rows of the first table
+//! are partitioned into ranges, each scanned by a thread with cursors of its own, sharing the bound statement, the
+//! results of the subqueries and the limits. The rows found by the threads are sent back in batches and output in
+//! the order of the ranges, which is the order of a scan on a thread, while the later ranges are still scanned.
+//!
+//! [`ExecOptions::parallel`]: crate::ExecOptions::parallel
+
+use std::{
+    error::Error,
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{Receiver, channel},
+    },
+};
+
+use crate::{
+    eval::EvalError,
+    join::JoinMethod,
+    select::{Expr, JoinKind, QueryContext, QueryOutput, RowScanner, SharedContext, eval_row},
+};
+
+/// The number of the combinations of rows below which a scan is not worth starting the threads
+const MIN_COMBINATIONS: usize = 4096;
+
+/// The number of the ranges per thread, so that a thread done with the ranges of fewer rows takes over the rest
+const RANGES_PER_THREAD: usize = 4;
+
+/// The number of the rows that a thread sends back at once
+const BATCH_ROWS: usize = 256;
+
+/// An error of a thread, which is sent back to the thread running the query
+type ThreadError = Box<dyn Error + Send + Sync>;
+
+/// The rows found in a range by its index, and whether they are the last of the range or the error that ended it
+struct Batch {
+    range: usize,
+    rows: Vec<Vec<String>>,
+    end: Option<Result<(), ThreadError>>,
+}
+
+/// A scan of the ranges of the rows of the first table on the threads
+pub(crate) struct ParallelScan<'a> {
+    shared: SharedContext<'a>,
+    ranges: Vec<Range<usize>>,
+}
+
+/// The parallel scan of the context, or None if the scan is not parallel: the option is off, the scan is traced,
+/// it stops at LIMIT, or the join is not a nested loop or is small. The unmatched rows of RIGHT and FULL JOIN
+/// depend on the matches in all the ranges, so they are not scanned in parallel either. Neither is a scan on a
+/// thread of rayon, like that of a subquery run by a thread of another scan, which would wait for the threads
+/// busy with that scan.
+pub(crate) fn plan_parallel_scan<'a>(ctx: &'a QueryContext) -> Option<ParallelScan<'a>> {
+    if !ctx.options.parallel || rayon::current_thread_index().is_some() {
+        return None;
+    }
+    let shared = ctx.shared()?;
+    let sql = shared.sql;
+    if sql.limit.is_some()
+        || sql.offset.is_some()
+        || sql
+            .join
+            .iter()
+            .any(|join| matches!(join.kind, JoinKind::Right | JoinKind::Full))
+        || JoinMethod::choose(shared.db, sql, shared.options) != JoinMethod::NestedLoop
+    {
+        return None;
+    }
+    let row_counts: Vec<_> = shared
+        .tables
+        .iter()
+        .map(|table| table.data.len() / table.schema.len())
+        .collect();
+    let combinations = row_counts
+        .iter()
+        .fold(1, |acc: usize, rows| acc.saturating_mul(*rows));
+    if combinations < MIN_COMBINATIONS {
+        return None;
+    }
+    let rows = row_counts[0];
+    let ranges = (rayon::current_num_threads() * RANGES_PER_THREAD).min(rows);
+    let range_size = rows.div_ceil(ranges);
+    let ranges = (0..rows)
+        .step_by(range_size)
+        .map(|start| start..(start + range_size).min(rows))
+        .collect();
+    Some(ParallelScan { shared, ranges })
+}
+
+impl ParallelScan<'_> {
+    /// Outputs the output columns of the combinations of rows that pass the join conditions and WHERE, in the
+    /// order of a scan on a thread. The error is the first in that order. The ranges after a range that failed
+    /// stop at their next row, and all of them stop at the next combination if the output fails.
+    pub fn run(self, cols: &[Expr], out: &mut impl QueryOutput) -> Result<(), Box<dyn Error>> {
+        let shared = &self.shared;
+        // The ranges from this index on stop at the next row
+        let stop = &AtomicUsize::new(usize::MAX);
+        let (tx, rx) = channel();
+        rayon::in_place_scope(|scope| {
+            for (range, rows) in self.ranges.iter().cloned().enumerate() {
+                let tx = tx.clone();
+                scope.spawn(move |_| {
+                    let mut batch = vec![];
+                    let res = scan_range(shared, cols, rows, |row| {
+                        batch.push(row);
+                        if BATCH_ROWS <= batch.len() {
+                            let rows = std::mem::take(&mut batch);
+                            let _ = tx.send(Batch {
+                                range,
+                                rows,
+                                end: None,
+                            });
+                        }
+                        range < stop.load(Ordering::Relaxed)
+                    });
+                    if res.is_err() {
+                        stop.fetch_min(range + 1, Ordering::Relaxed);
+                    }
+                    // The receiver is gone if the output failed
+                    let _ = tx.send(Batch {
+                        range,
+                        rows: batch,
+                        end: Some(res),
+                    });
+                });
+            }
+            drop(tx);
+            let res = output_in_order(rx, self.ranges.len(), out);
+            if res.is_err() {
+                shared.limits.stop();
+            }
+            res
+        })
+    }
+}
+
+/// Scans the rows of the first table in the range, passing the output columns of each combination that passes
+/// to the callback, which returns false to stop.
+fn scan_range(
+    shared: &SharedContext,
+    cols: &[Expr],
+    rows: Range<usize>,
+    mut f: impl FnMut(Vec<String>) -> bool,
+) -> Result<(), ThreadError> {
+    let ctx = shared.context();
+    let mut scanner = RowScanner::new(&ctx).with_outer_rows(rows);
+    while let Some(row_cursor) = scanner.next(&ctx, cols).map_err(thread_error)? {
+        if !f(eval_row(&ctx, cols, &row_cursor)?) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Outputs the batches of the ranges in the order of the ranges, holding those of a range until the ranges before
+/// it end. Returns the first error in that order.
+fn output_in_order(
+    rx: Receiver<Batch>,
+    ranges: usize,
+    out: &mut impl QueryOutput,
+) -> Result<(), Box<dyn Error>> {
+    let mut pending: Vec<Vec<Batch>> = (0..ranges).map(|_| vec![]).collect();
+    let mut current = 0;
+    for batch in rx {
+        let range = batch.range;
+        pending[range].push(batch);
+        while current < ranges && !pending[current].is_empty() {
+            for batch in std::mem::take(&mut pending[current]) {
+                for row in &batch.rows {
+                    out.output(row)?;
+                }
+                if let Some(end) = batch.end {
+                    end.map_err(|e| e as Box<dyn Error>)?;
+                    current += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The error as one that can be sent between the threads, keeping an [`EvalError`] for the details of the error
+/// and the others as messages.
+fn thread_error(err: Box<dyn Error>) -> ThreadError {
+    match err.downcast::<EvalError>() {
+        Ok(err) => err,
+        Err(err) => err.to_string().into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ExecOptions, Session, Statement, StatementOutput, parse_statement,
+        test_util::{db_from_tables, table_from_rows},
+    };
+
+    #[test]
+    fn test_parallel_scan() {
+        let numbers: Vec<_> = (0..100)
+            .map(|i| [i.to_string(), (i % 7).to_string()])
+            .collect();
+        let numbers: Vec<_> = numbers
+            .iter()
+            .map(|[x, m]| [x.as_str(), m.as_str()])
+            .collect();
+        let rows: Vec<_> = numbers.iter().map(|row| &row[..]).collect();
+        let db = db_from_tables([
+            table_from_rows("a", &["x", "m"], &rows),
+            table_from_rows("b", &["y", "n"], &rows),
+            table_from_rows("c", &["z"], &[&["1"], &["2"], &["3"]]),
+        ]);
+        let parallel = ExecOptions::DEFAULT.with_parallel(true);
+        let run = |options: &ExecOptions, sql: &str| {
+            let mut session = Session::new(&db);
+            session.options = options.clone();
+            match session.execute(sql) {
+                Ok(StatementOutput::Rows(rows)) => Ok(rows),
+                Ok(res) => panic!("{sql}: {res:?}"),
+                Err(e) => Err(e.to_string()),
+            }
+        };
+        let is_parallel = |sql: &str| {
+            let Statement::Select(stmt) = parse_statement(sql).unwrap() else {
+                panic!("{sql}");
+            };
+            let ctx = QueryContext::new(&db, &stmt, &parallel, None).unwrap();
+            plan_parallel_scan(&ctx).is_some()
+        };
+
+        for sql in [
+            "SELECT x, y FROM a INNER JOIN b ON x < y WHERE m = n",
+            "SELECT x, y FROM a LEFT JOIN b ON x * 3 < y AND m = n",
+            "SELECT x, y, z FROM a INNER JOIN b ON x < y INNER JOIN c ON m + z = n",
+            "SELECT x, (SELECT max(z) FROM c) FROM a INNER JOIN b ON x <= y WHERE n IN (SELECT z FROM c)",
+        ] {
+            assert!(is_parallel(sql), "{sql}");
+            let rows = run(&parallel, sql).unwrap();
+            assert_eq!(rows, run(&ExecOptions::DEFAULT, sql).unwrap(), "{sql}");
+            assert!(100 < rows.len(), "{sql}");
+        }
+        // Sorted after the scan
+        for sql in [
+            "SELECT y, x FROM a INNER JOIN b ON x < y ORDER BY y DESC",
+            "SELECT DISTINCT n, m FROM a INNER JOIN b ON x < y",
+        ] {
+            assert_eq!(
+                run(&parallel, sql),
+                run(&ExecOptions::DEFAULT, sql),
+                "{sql}"
+            );
+        }
+
+        for sql in [
+            "SELECT x, y FROM a INNER JOIN b ON x < y LIMIT 10",
+            "SELECT x, y FROM a RIGHT JOIN b ON x * 3 < y",
+            "SELECT x, y FROM a INNER JOIN b ON x = y",
+            "SELECT x, z FROM a INNER JOIN c ON x < z",
+        ] {
+            assert!(!is_parallel(sql), "{sql}");
+        }
+
+        // The threads share the limits
+        assert_eq!(
+            run(
+                &parallel.clone().with_max_combinations(Some(1000)),
+                "SELECT x, y FROM a INNER JOIN b ON x < y"
+            )
+            .unwrap_err(),
+            "Query exceeded the limit of 1000 combinations of rows to evaluate"
+        );
+    }
+
+    #[test]
+    fn test_parallel_scan_stops() {
+        let numbers: Vec<_> = (0..1000).map(|i| i.to_string()).collect();
+        let rows: Vec<_> = numbers.iter().map(|x| [x.as_str()]).collect();
+        let rows: Vec<_> = rows.iter().map(|row| &row[..]).collect();
+        let db = db_from_tables([
+            table_from_rows("a", &["x"], &rows),
+            table_from_rows("b", &["y"], &rows),
+        ]);
+        let mut session = Session::new(&db);
+        // The rows are output while the scan runs, so the scan stops long before all the combinations
+        session.options = ExecOptions::DEFAULT
+            .with_parallel(true)
+            .with_max_rows(Some(10))
+            .with_max_combinations(Some(1000 * 1000 - 1));
+        assert_eq!(
+            session
+                .execute("SELECT x, y FROM a INNER JOIN b ON x < y")
+                .unwrap_err()
+                .to_string(),
+            "Query exceeded the limit of 10 rows to output"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Binding values to parameter placeholders.
+//! Values are substituted as string literals in the AST, not in the SQL text, so they never change
+//! the structure of the query no matter what characters they contain.
+
+use std::{collections::HashMap, error::Error};
+
+use crate::{
+    db::{Database, Statement},
+    options::ExecOptions,
+    parse_error::ParseError,
+    parser::parse_statement,
+    select::Expr,
+    session::{StatementOutput, exec_statement},
+};
+
+/// Values of parameters keyed by their names. Positional parameters like `?1` have numeric names like `1`.
+pub type Params = HashMap<String, String>;
+
+pub(crate) fn bind_params(stmt: &mut Statement, params: &Params) -> Result<(), String> {
+    let mut unbound = None;
+    for select in stmt.selects_mut() {
+        select.visit_exprs_mut(&mut |ex| {
+            if let Expr::Param(name) = ex {
+                if let Some(value) = params.get(name) {
+                    *ex = Expr::StrLiteral(value.clone());
+                } else if unbound.is_none() {
+                    unbound = Some(ex.to_string());
+                }
+            }
+        });
+    }
+    if let Some(name) = unbound {
+        return Err(format!("Parameter {name} is not bound"));
+    }
+    Ok(())
+}
+
+/// A statement parsed once, to be executed repeatedly with different values bound to its placeholders.
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    stmt: Statement,
+    params: Params,
+}
+
+impl PreparedStatement {
+    /// Parses a statement with placeholders, `?`, `?1`, `$1` or `:name`. Bare `?`s are numbered from 1
+    /// in the order of appearance.
+    pub fn new(sql: &str) -> Result<Self, ParseError> {
+        Ok(Self {
+            stmt: parse_statement(sql)?,
+            params: Params::new(),
+        })
+    }
+
+    /// Binds the values to the positional placeholders in order, i.e. the first one to `?1`.
+    pub fn bind(&mut self, values: &[impl ToString]) -> &mut Self {
+        for (i, value) in values.iter().enumerate() {
+            self.params.insert((i + 1).to_string(), value.to_string());
+        }
+        self
+    }
+
+    /// Binds the value to the named placeholder `:name`.
+    pub fn bind_named(&mut self, name: &str, value: impl ToString) -> &mut Self {
+        self.params.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Clears the values bound so far.
+    pub fn clear_bindings(&mut self) -> &mut Self {
+        self.params.clear();
+        self
+    }
+
+    pub fn execute(&self, db: &Database) -> Result<StatementOutput, Box<dyn Error>> {
+        self.execute_with(db, &ExecOptions::DEFAULT)
+    }
+
+    /// Executes the statement with the bound values. Every placeholder must have a value.
+    pub fn execute_with(
+        &self,
+        db: &Database,
+        options: &ExecOptions,
+    ) -> Result<StatementOutput, Box<dyn Error>> {
+        let mut stmt = self.stmt.clone();
+        bind_params(&mut stmt, &self.params)?;
+        exec_statement(db, &stmt, options, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::statement, select::BinOp};
+
+    #[test]
+    fn test_bind_params() {
+        let (_, mut stmt) = statement("SELECT * FROM t WHERE name = :name AND id > ?1").unwrap();
+        let params = Params::from([
+            ("name".to_string(), "O'Brien".to_string()),
+            ("1".to_string(), "10".to_string()),
+        ]);
+        bind_params(&mut stmt, &params).unwrap();
+        let Statement::Select(select) = stmt else {
+            panic!("Not a SELECT statement");
+        };
+        let Some(Expr::Binary {
+            op: BinOp::And,
+            lhs,
+            rhs,
+        }) = select.condition
+        else {
+            panic!("Unexpected condition");
+        };
+        assert_eq!(lhs.to_string(), "(name = 'O''Brien')");
+        assert_eq!(rhs.to_string(), "(id > '10')");
+    }
+
+    #[test]
+    fn test_anonymous_params() {
+        let (_, mut stmt) =
+            statement("SELECT * FROM t WHERE name = ? AND id > $2 AND id < ?").unwrap();
+        let params = Params::from([
+            ("1".to_string(), "a".to_string()),
+            ("2".to_string(), "b".to_string()),
+        ]);
+        bind_params(&mut stmt, &params).unwrap();
+        let Statement::Select(select) = stmt else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(
+            select.condition.unwrap().to_string(),
+            "(((name = 'a') AND (id > 'b')) AND (id < 'b'))"
+        );
+    }
+
+    #[test]
+    fn test_prepared_statement() {
+        let mut db = Database::new();
+        db.insert(
+            "t".to_string(),
+            crate::make_table("t", "id,name\n1,x\n2,y\n").unwrap(),
+        );
+        let mut stmt = PreparedStatement::new("SELECT name FROM t WHERE id = ?").unwrap();
+        for (id, name) in [(1, "x"), (2, "y")] {
+            assert_eq!(
+                stmt.bind(&[id]).execute(&db).unwrap(),
+                StatementOutput::Rows(vec![vec!["name".to_string()], vec![name.to_string()]])
+            );
+        }
+        assert!(stmt.clear_bindings().execute(&db).is_err());
+    }
+
+    #[test]
+    fn test_unbound_param() {
+        let (_, mut stmt) = statement("SELECT * FROM t WHERE name = :name").unwrap();
+        assert_eq!(
+            bind_params(&mut stmt, &Params::new()),
+            Err("Parameter :name is not bound".to_string())
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Errors of parsing statements, reporting where the parser failed and what it expected there,
+//! like `expected FROM, found 'FORM'`.
+
+use nom::error::ErrorKind;
+
+/// Something the parser expected at a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Expected {
+    /// A keyword like `FROM`, matched ignoring case
+    Keyword(&'static str),
+    /// A punctuation like `)`
+    Symbol(&'static str),
+    /// A syntactic element like an expression
+    Other(&'static str),
+}
+
+impl std::fmt::Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Keyword(kw) => write!(f, "{kw}"),
+            Self::Symbol(sym) => write!(f, "'{sym}'"),
+            Self::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The error type of the parser functions. Of the alternatives that failed, it keeps the one that got
+/// the furthest in the input, and all the expectations at that position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SyntaxError<I> {
+    pub input: I,
+    pub expected: Vec<Expected>,
+}
+
+impl<I> SyntaxError<I> {
+    pub fn new(input: I, _kind: ErrorKind) -> Self {
+        Self {
+            input,
+            expected: vec![],
+        }
+    }
+
+    pub fn expected(input: I, expected: Expected) -> Self {
+        Self {
+            input,
+            expected: vec![expected],
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<&'a str> for SyntaxError<&'a str> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        Self::new(input, kind)
+    }
+
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(mut self, other: Self) -> Self {
+        // Shorter rest of the input means further in the input
+        match self.input.len().cmp(&other.input.len()) {
+            std::cmp::Ordering::Less => self,
+            std::cmp::Ordering::Greater => other,
+            std::cmp::Ordering::Equal => {
+                for expected in other.expected {
+                    if !self.expected.contains(&expected) {
+                        self.expected.push(expected);
+                    }
+                }
+                self
+            }
+        }
+    }
+}
+
+/// Error of parsing a statement, located in the source text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset in the source text
+    pub offset: usize,
+    /// Line number starting from 1
+    pub line: usize,
+    /// Column number in characters starting from 1
+    pub column: usize,
+    /// What went wrong, e.g. `expected FROM, found 'FORM'`
+    pub message: String,
+}
+
+impl ParseError {
+    /// Locates the error in `src`, given the rest of the input where the parser failed.
+    pub(crate) fn new(src: &str, rest: &str, expected: &[Expected]) -> Self {
+        // Point at the token after the whitespace
+        let rest = rest.trim_start();
+        let offset = src.len() - rest.len();
+        let (line, column) = line_column(src, offset);
+
+        let word_len = token_len(rest);
+        let found = match rest.chars().next() {
+            Some(_) if word_len != 0 => format!("'{}'", &rest[..word_len]),
+            Some(c) => format!("'{c}'"),
+            None => "end of input".to_string(),
+        };
+        let message = match expected {
+            [] => format!("unexpected {found}"),
+            [expected] => format!("expected {expected}, found {found}"),
+            [init @ .., last] => {
+                let init: Vec<_> = init.iter().map(|ex| ex.to_string()).collect();
+                format!("expected {} or {last}, found {found}", init.join(", "))
+            }
+        };
+
+        Self {
+            offset,
+            line,
+            column,
+            message,
+        }
+    }
+
+    /// The line of `src` where the parser stopped, and a caret under the position below it, like
+    ///
+    /// ```text
+    /// SELECT * FROM phonebook LIMT 1
+    ///                         ^
+    /// ```
+    pub fn caret(&self, src: &str) -> String {
+        let line = src.lines().nth(self.line - 1).unwrap_or("");
+        // Keep the tabs, so that the caret is aligned however wide they are shown
+        let indent: String = line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!("{line}\n{indent}^")
+    }
+}
+
+/// Formats an error of running `sql` as a line of text. A parse error is followed by the line of the query
+/// with a caret at where the parser stopped, so that it is obvious which part of the query was not understood.
+pub fn format_error_text(err: &(dyn std::error::Error + 'static), sql: &str) -> String {
+    match err.downcast_ref::<ParseError>() {
+        Some(err) => format!("Error: {err}\n{}", err.caret(sql)),
+        None => format!("Error: {err}"),
+    }
+}
+
+/// The line and the column in characters, both starting from 1, of the byte offset in the source text.
+pub(crate) fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit_once('\n')
+        .map_or(before, |(_, line)| line)
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// The length in bytes of the word at the start of the text, or 0 if it starts with a punctuation or is empty.
+pub(crate) fn token_len(s: &str) -> usize {
+    s.find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(s.len())
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Parse error at line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -1,21 +1,105 @@
 use nom::{
-    IResult, Parser,
+    Finish, Parser,
     branch::alt,
-    bytes::complete::{tag, tag_no_case},
-    character::complete::{alpha1, alphanumeric1, digit1, multispace0, multispace1, none_of},
-    combinator::{opt, recognize},
-    multi::{fold_many0, many0, separated_list0},
-    sequence::{delimited, pair, terminated},
+    bytes::complete::{is_not, tag, tag_no_case},
+    character::complete::{alpha1, alphanumeric1, digit1, multispace0, none_of},
+    combinator::{cut, opt, recognize, value, verify},
+    multi::{fold_many0, many0, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, separated_pair, terminated},
 };
 
 use crate::{
+    compound::{CompoundStmt, SetOp},
+    cte::Cte,
+    datetime::DateTime,
     db::Statement,
+    explain::{ExplainFormat, ExplainStmt},
+    parse_error::{Expected, ParseError, SyntaxError},
     select::{
-        BinOp, ColSpecifier, Column, Expr, JoinClause, JoinKind, OrderBy, Ordering, TableSpecifier,
-        UniOp,
+        BinOp, ColSpecifier, Collation, Column, Expr, GroupBy, JoinClause, JoinKind, Nulls,
+        OrderBy, OrderKey, Ordering, SelectStmt, TableSpecifier, UniOp, WildcardModifiers,
     },
 };
 
+type IResult<I, O> = nom::IResult<I, O, SyntaxError<I>>;
+
+/// Parses a whole statement, failing if anything but whitespace follows it.
+pub fn parse_statement(sql: &str) -> Result<Statement, ParseError> {
+    let (rest, stmt) = statement(sql)
+        .finish()
+        .map_err(|e| ParseError::new(sql, e.input, &e.expected))?;
+    if !rest.trim().is_empty() {
+        return Err(ParseError::new(
+            sql,
+            rest,
+            &[Expected::Other("end of statement")],
+        ));
+    }
+    Ok(stmt)
+}
+
+/// Parses a whole expression like the condition of WHERE, failing if anything but whitespace follows it.
+pub(crate) fn parse_expression(src: &str) -> Result<Expr, ParseError> {
+    let (rest, expr) = expression(src.trim_start())
+        .finish()
+        .map_err(|e| ParseError::new(src, e.input, &e.expected))?;
+    if !rest.trim().is_empty() {
+        return Err(ParseError::new(
+            src,
+            rest,
+            &[Expected::Other("end of expression")],
+        ));
+    }
+    Ok(expr)
+}
+
+/// A keyword, which is reported as expected if it is missing. It must not be followed by a character of
+/// an identifier, so that `IN` does not match the beginning of `INNER`.
+fn keyword<'a>(
+    kw: &'static str,
+) -> impl Parser<&'a str, Output = &'a str, Error = SyntaxError<&'a str>> {
+    move |i: &'a str| {
+        let err = || nom::Err::Error(SyntaxError::expected(i, Expected::Keyword(kw)));
+        let (r, res) = tag_no_case(kw)
+            .parse(i)
+            .map_err(|_: nom::Err<SyntaxError<&str>>| err())?;
+        if r.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+            return Err(err());
+        }
+        Ok((r, res))
+    }
+}
+
+/// A punctuation, which is reported as expected if it is missing
+fn symbol<'a>(
+    sym: &'static str,
+) -> impl Parser<&'a str, Output = &'a str, Error = SyntaxError<&'a str>> {
+    move |i: &'a str| {
+        tag(sym).parse(i).map_err(|e| {
+            e.map(|_: SyntaxError<&str>| SyntaxError::expected(i, Expected::Symbol(sym)))
+        })
+    }
+}
+
+/// Reports `name` as expected if the parser fails at the start of the input, instead of the details like
+/// the keywords and symbols that the alternatives in it expected.
+fn expected<'a, O>(
+    name: &'static str,
+    mut parser: impl Parser<&'a str, Output = O, Error = SyntaxError<&'a str>>,
+) -> impl Parser<&'a str, Output = O, Error = SyntaxError<&'a str>> {
+    move |i: &'a str| {
+        parser.parse(i).map_err(|e| {
+            e.map(|e| {
+                if e.input.trim_start().len() == i.trim_start().len() {
+                    SyntaxError::expected(e.input, Expected::Other(name))
+                } else {
+                    e
+                }
+            })
+        })
+    }
+}
+
 pub(crate) fn token(i: &str) -> IResult<&str, &str> {
     delimited(
         multispace0,
@@ -28,124 +112,355 @@ pub(crate) fn token(i: &str) -> IResult<&str, &str> {
     .parse(i)
 }
 
-pub fn statement(i: &str) -> IResult<&str, Statement> {
+pub(crate) fn statement(i: &str) -> IResult<&str, Statement> {
     let (r, directive) = token(i)?;
-    let (r, stmt) = match directive.to_lowercase().as_str() {
+    let (r, mut stmt) = match directive.to_lowercase().as_str() {
         "select" => {
-            let (r, cols) = separated_list0(tag(","), col_spec).parse(r)?;
-
-            let (r, table) = from_table(r)?;
-
-            let (r, join) = many0(join).parse(r)?;
-
-            let (r, condition) = opt(where_clause).parse(r)?;
-
-            let (r, ordering) = opt(order_by).parse(r)?;
-
-            let (r, limit) = opt(limit).parse(r)?;
-
-            let (r, offset) = opt(offset).parse(r)?;
-
+            let (r, first) = select_stmt(r)?;
+            let (r, rest) =
+                many0(pair(set_op, cut(preceded(select_keyword, select_stmt)))).parse(r)?;
+            if rest.is_empty() {
+                (r, Statement::Select(first))
+            } else {
+                (r, Statement::Compound(CompoundStmt { first, rest }))
+            }
+        }
+        "with" => {
+            let (r, ctes) = cut(separated_list1(symbol(","), cte)).parse(r)?;
+            let (r, _) = cut(select_keyword).parse(r)?;
+            let (r, mut stmt) = cut(select_stmt).parse(r)?;
+            stmt.with = ctes;
+            (r, Statement::Select(stmt))
+        }
+        "explain" => {
+            let (r, format) = opt(explain_format).parse(r)?;
+            let (r, _) = select_keyword(r)?;
+            let (r, stmt) = select_stmt(r)?;
             (
                 r,
-                Statement::Select(crate::SelectStmt {
-                    cols,
-                    table,
-                    join,
-                    condition,
-                    ordering,
-                    limit,
-                    offset,
+                Statement::Explain(ExplainStmt {
+                    format: format.unwrap_or(ExplainFormat::Text),
+                    stmt,
                 }),
             )
         }
-        _ => {
-            return Err(nom::Err::Error(nom::error::Error::new(
+        "search" => {
+            let (r, value) = cut(expected("string literal", str_literal)).parse(r)?;
+            (r, Statement::Search(value))
+        }
+        "compare" => {
+            let (r, _) = cut(delimited(multispace0, keyword("SCHEMA"), multispace0)).parse(r)?;
+            let (r, (old, new)) = cut(separated_pair(
+                expected("table name", token),
+                symbol(","),
+                expected("table name", token),
+            ))
+            .parse(r)?;
+            (
                 r,
-                nom::error::ErrorKind::Verify,
-            )));
+                Statement::CompareSchema(old.to_string(), new.to_string()),
+            )
+        }
+        "describe" => {
+            let (r, table) = cut(expected("table name", token)).parse(r)?;
+            (r, Statement::Describe(table.to_string()))
+        }
+        "show" => {
+            let (r, what) = cut(delimited(
+                multispace0,
+                alt((
+                    keyword("TABLES"),
+                    keyword("COLUMNS"),
+                    keyword("QUERIES"),
+                    keyword("FUNCTIONS"),
+                )),
+                multispace0,
+            ))
+            .parse(r)?;
+            if what.eq_ignore_ascii_case("TABLES") {
+                (r, Statement::ShowTables)
+            } else if what.eq_ignore_ascii_case("QUERIES") {
+                (r, Statement::ShowQueries)
+            } else if what.eq_ignore_ascii_case("FUNCTIONS") {
+                (r, Statement::ShowFunctions)
+            } else {
+                let (r, _) = cut(delimited(multispace0, keyword("FROM"), multispace0)).parse(r)?;
+                let (r, table) = cut(expected("table name", token)).parse(r)?;
+                (r, Statement::Describe(table.to_string()))
+            }
+        }
+        _ => {
+            return Err(nom::Err::Error(SyntaxError {
+                input: i,
+                expected: vec![
+                    Expected::Keyword("SELECT"),
+                    Expected::Keyword("WITH"),
+                    Expected::Keyword("EXPLAIN"),
+                    Expected::Keyword("SEARCH"),
+                    Expected::Keyword("COMPARE"),
+                    Expected::Keyword("DESCRIBE"),
+                    Expected::Keyword("SHOW"),
+                ],
+            }));
         }
     };
+    number_anonymous_params(&mut stmt);
 
     Ok((r, stmt))
 }
 
-fn from_table(i: &str) -> IResult<&str, TableSpecifier> {
-    let (r, _) = delimited(multispace0, tag_no_case("FROM"), multispace0).parse(i)?;
-    table_specifier(r)
+/// Numbers the bare `?` placeholders from 1 in the order of appearance, like `?1`, `?2` and so on.
+fn number_anonymous_params(stmt: &mut Statement) {
+    let mut positions = vec![];
+    for select in stmt.selects_mut() {
+        select.visit_exprs_mut(&mut |ex| {
+            if let Expr::Param(name) = ex
+                && let Some(pos) = name.strip_prefix('?')
+                && let Ok(pos) = pos.parse::<usize>()
+            {
+                positions.push(pos);
+            }
+        });
+    }
+    // The positions count from the end of the input, so the first placeholder has the largest one
+    positions.sort_unstable_by(|lhs, rhs| rhs.cmp(lhs));
+    positions.dedup();
+    for select in stmt.selects_mut() {
+        select.visit_exprs_mut(&mut |ex| {
+            if let Expr::Param(name) = ex
+                && let Some(pos) = name.strip_prefix('?')
+                && let Ok(pos) = pos.parse::<usize>()
+                && let Some(idx) = positions.iter().position(|p| *p == pos)
+            {
+                *name = (idx + 1).to_string();
+            }
+        });
+    }
 }
 
-fn table_specifier(r: &str) -> IResult<&str, TableSpecifier> {
-    let (r, table) = token(r)?;
+/// Parses the rest of a SELECT statement after the `SELECT` keyword.
+fn select_stmt(r: &str) -> IResult<&str, SelectStmt> {
+    let (r, distinct) = opt(distinct_keyword).parse(r)?;
+
+    let (r, cols) = separated_list0(tag(","), col_spec).parse(r)?;
+
+    let (r, table) = from_table(r)?;
+
+    let (r, join) = many0(join).parse(r)?;
+
+    let (r, condition) = opt(where_clause).parse(r)?;
+
+    let (r, group_by) = opt(group_by).parse(r)?;
 
-    let (r, alias) = opt(pair(
-        delimited(multispace0, tag_no_case("AS"), multispace1),
-        token,
+    let (r, qualify) = opt(qualify_clause).parse(r)?;
+
+    let (r, ordering) = opt(order_by).parse(r)?;
+    let ordering = ordering.unwrap_or_default();
+
+    let (r, limit) = opt(limit).parse(r)?;
+
+    let (r, offset) = opt(offset).parse(r)?;
+
+    Ok((
+        r,
+        SelectStmt {
+            with: vec![],
+            distinct: distinct.is_some(),
+            cols,
+            table,
+            join,
+            condition,
+            group_by,
+            qualify,
+            ordering,
+            limit,
+            offset,
+        },
     ))
-    .parse(r)?;
+}
 
+fn select_keyword(i: &str) -> IResult<&str, ()> {
+    let (r, _) = delimited(multispace0, keyword("SELECT"), multispace0).parse(i)?;
+    Ok((r, ()))
+}
+
+/// `UNION` or `UNION ALL` between SELECT statements
+fn set_op(i: &str) -> IResult<&str, SetOp> {
+    let (r, _) = delimited(multispace0, keyword("UNION"), multispace0).parse(i)?;
+    let (r, all) = opt(all_keyword).parse(r)?;
     Ok((
         r,
-        TableSpecifier {
-            name: table.to_string(),
-            alias: alias.map(|(_, name)| name.to_string()),
+        if all.is_some() {
+            SetOp::UnionAll
+        } else {
+            SetOp::Union
         },
     ))
 }
 
+fn distinct_keyword(i: &str) -> IResult<&str, ()> {
+    let (r, _) = delimited(multispace0, keyword("DISTINCT"), multispace0).parse(i)?;
+    Ok((r, ()))
+}
+
+fn explain_format(i: &str) -> IResult<&str, ExplainFormat> {
+    let (r, _) = (
+        multispace0,
+        symbol("("),
+        multispace0,
+        keyword("FORMAT"),
+        multispace0,
+    )
+        .parse(i)?;
+    let (r, format) = cut(alt((
+        keyword("TEXT"),
+        keyword("MERMAID"),
+        keyword("DOT"),
+        keyword("JSON"),
+    )))
+    .parse(r)?;
+    let (r, _) = delimited(multispace0, symbol(")"), multispace0).parse(r)?;
+    let format = match format.to_ascii_uppercase().as_str() {
+        "TEXT" => ExplainFormat::Text,
+        "MERMAID" => ExplainFormat::Mermaid,
+        "DOT" => ExplainFormat::Dot,
+        "JSON" => ExplainFormat::Json,
+        _ => unreachable!(),
+    };
+    Ok((r, format))
+}
+
+fn from_table(i: &str) -> IResult<&str, TableSpecifier> {
+    let (r, _) = delimited(multispace0, keyword("FROM"), multispace0).parse(i)?;
+    cut(table_specifier).parse(r)
+}
+
+fn table_specifier(r: &str) -> IResult<&str, TableSpecifier> {
+    let (r, name) = expected("table name", ident).parse(r)?;
+
+    let (r, alias) = opt(preceded(
+        delimited(multispace0, keyword("AS"), multispace0),
+        ident,
+    ))
+    .parse(r)?;
+
+    Ok((r, TableSpecifier { name, alias }))
+}
+
 fn join(i: &str) -> IResult<&str, JoinClause> {
     let (r, kind) = delimited(
         multispace0,
-        alt((tag_no_case("INNER"), tag_no_case("LEFT"))),
-        multispace1,
+        alt((
+            keyword("INNER"),
+            keyword("LEFT"),
+            keyword("RIGHT"),
+            keyword("FULL"),
+        )),
+        multispace0,
     )
     .parse(i)?;
 
-    let (r, _) = delimited(multispace0, tag_no_case("JOIN"), multispace0).parse(r)?;
+    let kind = match kind.to_ascii_uppercase().as_str() {
+        "INNER" => JoinKind::Inner,
+        "LEFT" => JoinKind::Left,
+        "RIGHT" => JoinKind::Right,
+        "FULL" => JoinKind::Full,
+        _ => unreachable!(),
+    };
+
+    // OUTER is optional noise for outer joins
+    let (r, _) = if kind == JoinKind::Inner {
+        (r, None)
+    } else {
+        opt(delimited(multispace0, keyword("OUTER"), multispace0)).parse(r)?
+    };
 
-    let (r, table) = table_specifier(r)?;
+    // Nothing else starts with the join kind, so fail without backtracking from here
+    let (r, _) = cut(delimited(multispace0, keyword("JOIN"), multispace0)).parse(r)?;
 
-    let (r, _) = delimited(multispace0, tag_no_case("ON"), multispace0).parse(r)?;
+    let (r, table) = cut(table_specifier).parse(r)?;
 
-    let (r, condition) = expression(r)?;
+    let (r, _) = cut(delimited(multispace0, keyword("ON"), multispace0)).parse(r)?;
+
+    let (r, condition) = cut(expression).parse(r)?;
 
     Ok((
         r,
         JoinClause {
-            kind: if kind.eq_ignore_ascii_case("INNER") {
-                JoinKind::Inner
-            } else {
-                JoinKind::Left
-            },
+            kind,
             table,
             condition,
         },
     ))
 }
 
-fn order_by(i: &str) -> IResult<&str, OrderBy> {
+/// The `ALL` keyword in `GROUP BY ALL` or `ORDER BY ALL`
+fn all_keyword(i: &str) -> IResult<&str, ()> {
+    let (r, _) = delimited(multispace0, keyword("ALL"), multispace0).parse(i)?;
+    Ok((r, ()))
+}
+
+fn group_by(i: &str) -> IResult<&str, GroupBy> {
     let (r, _) = (
         multispace0,
-        tag_no_case("ORDER"),
-        multispace1,
-        tag_no_case("BY"),
-        multispace1,
+        keyword("GROUP"),
+        multispace0,
+        keyword("BY"),
+        multispace0,
     )
         .parse(i)?;
 
-    let (r, expr) = column_expr(r)?;
+    cut(alt((
+        all_keyword.map(|_| GroupBy::All),
+        separated_list1(tag(","), column_expr).map(GroupBy::Exprs),
+    )))
+    .parse(r)
+}
+
+fn order_by(i: &str) -> IResult<&str, Vec<OrderBy>> {
+    let (r, _) = (
+        multispace0,
+        keyword("ORDER"),
+        multispace0,
+        keyword("BY"),
+        multispace0,
+    )
+        .parse(i)?;
+
+    cut(separated_list1(tag(","), order_key)).parse(r)
+}
+
+fn order_key(i: &str) -> IResult<&str, OrderBy> {
+    let (r, key) = alt((
+        all_keyword.map(|_| OrderKey::All),
+        column_expr.map(OrderKey::Expr),
+    ))
+    .parse(i)?;
 
     let (r, ordering) = opt(delimited(
         multispace0,
-        alt((tag_no_case("ASC"), tag_no_case("DESC"))),
+        alt((keyword("ASC"), keyword("DESC"))),
         multispace0,
     ))
     .parse(r)?;
 
+    let (r, nulls) = opt(preceded(
+        delimited(multispace0, keyword("NULLS"), multispace0),
+        cut(delimited(
+            multispace0,
+            alt((
+                keyword("FIRST").map(|_| Nulls::First),
+                keyword("LAST").map(|_| Nulls::Last),
+            )),
+            multispace0,
+        )),
+    ))
+    .parse(r)?;
+
     Ok((
         r,
         OrderBy {
-            expr,
+            key,
             ordering: ordering.map_or(Ordering::Asc, |o| {
                 if o.eq_ignore_ascii_case("ASC") {
                     Ordering::Asc
@@ -153,32 +468,40 @@ fn order_by(i: &str) -> IResult<&str, OrderBy> {
                     Ordering::Desc
                 }
             }),
+            nulls,
         },
     ))
 }
 
 fn limit(i: &str) -> IResult<&str, usize> {
-    let (r, _) = delimited(multispace0, tag_no_case("LIMIT"), multispace1).parse(i)?;
-    let (r, limit) = digit1(r)?;
-    let limit = limit
-        .parse()
-        .map_err(|_| nom::Err::Failure(nom::error::Error::new(r, nom::error::ErrorKind::Verify)))?;
-    Ok((r, limit))
+    let (r, _) = delimited(multispace0, keyword("LIMIT"), multispace0).parse(i)?;
+    cut(row_count).parse(r)
 }
 
 fn offset(i: &str) -> IResult<&str, usize> {
-    let (r, _) = delimited(multispace0, tag_no_case("OFFSET"), multispace1).parse(i)?;
-    let (r, offset) = digit1(r)?;
-    let offset = offset
-        .parse()
-        .map_err(|_| nom::Err::Failure(nom::error::Error::new(r, nom::error::ErrorKind::Verify)))?;
-    Ok((r, offset))
+    let (r, _) = delimited(multispace0, keyword("OFFSET"), multispace0).parse(i)?;
+    cut(row_count).parse(r)
+}
+
+/// A number of rows for LIMIT or OFFSET, which is an error rather than wrapping around if it is too large
+fn row_count(i: &str) -> IResult<&str, usize> {
+    let (r, digits) = expected("number", digit1).parse(i)?;
+    let count = digits.parse().map_err(|_| {
+        nom::Err::Failure(SyntaxError::expected(i, Expected::Other("smaller number")))
+    })?;
+    Ok((r, count))
 }
 
 fn where_clause(i: &str) -> IResult<&str, Expr> {
-    let (r, _) = delimited(multispace0, tag_no_case("WHERE"), multispace0).parse(i)?;
+    let (r, _) = delimited(multispace0, keyword("WHERE"), multispace0).parse(i)?;
+
+    cut(expression).parse(r)
+}
+
+fn qualify_clause(i: &str) -> IResult<&str, Expr> {
+    let (r, _) = delimited(multispace0, keyword("QUALIFY"), multispace0).parse(i)?;
 
-    expression(r)
+    cut(expression).parse(r)
 }
 
 fn expression(i: &str) -> IResult<&str, Expr> {
@@ -192,8 +515,8 @@ fn logical_ex(i: &str) -> IResult<&str, Expr> {
         pair(
             delimited(
                 multispace0,
-                alt((tag_no_case("AND"), tag_no_case("OR"))),
-                multispace1,
+                alt((keyword("AND"), keyword("OR"))),
+                multispace0,
             ),
             comparison_ex,
         ),
@@ -243,9 +566,102 @@ fn comparison_op(i: &str) -> IResult<&str, BinOp> {
     ))
 }
 
+/// Parses `[NOT] BETWEEN low AND high`, which is a syntax sugar for `lhs >= low AND lhs <= high`.
+fn between(lhs: Expr) -> impl Fn(&str) -> IResult<&str, Expr> {
+    move |i| {
+        let (r, not) = opt(delimited(multispace0, keyword("NOT"), multispace0)).parse(i)?;
+        let (r, _) = delimited(multispace0, keyword("BETWEEN"), multispace0).parse(r)?;
+        let (r, low) = cut(additive_ex).parse(r)?;
+        let (r, _) = cut(delimited(multispace0, keyword("AND"), multispace0)).parse(r)?;
+        let (r, high) = cut(additive_ex).parse(r)?;
+
+        let range = Expr::Binary {
+            op: BinOp::And,
+            lhs: Box::new(Expr::Binary {
+                op: BinOp::Ge,
+                lhs: Box::new(lhs.clone()),
+                rhs: Box::new(low),
+            }),
+            rhs: Box::new(Expr::Binary {
+                op: BinOp::Le,
+                lhs: Box::new(lhs.clone()),
+                rhs: Box::new(high),
+            }),
+        };
+
+        if not.is_some() {
+            Ok((
+                r,
+                Expr::Unary {
+                    op: UniOp::Not,
+                    operand: Box::new(range),
+                },
+            ))
+        } else {
+            Ok((r, range))
+        }
+    }
+}
+
+/// Parses `[NOT] IN (SELECT ...)`
+fn in_subquery(lhs: Expr) -> impl Fn(&str) -> IResult<&str, Expr> {
+    move |i| {
+        let (r, not) = opt(delimited(multispace0, keyword("NOT"), multispace0)).parse(i)?;
+        let (r, _) = delimited(multispace0, keyword("IN"), multispace0).parse(r)?;
+        let (r, subquery) = cut(parenthesized_select).parse(r)?;
+        Ok((
+            r,
+            Expr::InSubquery {
+                lhs: Box::new(lhs.clone()),
+                subquery: Box::new(subquery),
+                negated: not.is_some(),
+            },
+        ))
+    }
+}
+
+/// Parses `[NOT] LIKE pattern [ESCAPE 'c']`. The escape character must be a single character.
+fn like(lhs: Expr) -> impl Fn(&str) -> IResult<&str, Expr> {
+    move |i| {
+        let (r, not) = opt(delimited(multispace0, keyword("NOT"), multispace0)).parse(i)?;
+        let (r, _) = delimited(multispace0, keyword("LIKE"), multispace0).parse(r)?;
+        let (r, pattern) = cut(additive_ex).parse(r)?;
+        let (r, escape) = opt(preceded(
+            delimited(multispace0, keyword("ESCAPE"), multispace0),
+            cut(expected(
+                "escape character",
+                verify(str_literal, |s: &String| s.chars().count() == 1),
+            )),
+        ))
+        .parse(r)?;
+
+        Ok((
+            r,
+            Expr::Like {
+                lhs: Box::new(lhs.clone()),
+                pattern: Box::new(pattern),
+                escape: escape.and_then(|s| s.chars().next()),
+                negated: not.is_some(),
+            },
+        ))
+    }
+}
+
 fn comparison_ex(i: &str) -> IResult<&str, Expr> {
     let (r, lhs) = additive_ex(i)?;
 
+    for res in [
+        between(lhs.clone())(r),
+        like(lhs.clone())(r),
+        in_subquery(lhs.clone())(r),
+    ] {
+        match res {
+            Ok(res) => return Ok(res),
+            Err(nom::Err::Error(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
     let Ok((r, op)) = comparison_op(r) else {
         return Ok((r, lhs));
     };
@@ -305,11 +721,35 @@ fn multiplicative_op(i: &str) -> IResult<&str, BinOp> {
     ))
 }
 
+/// A term optionally followed by `COLLATE NOCASE` or `COLLATE BINARY`, which binds tighter than operators
+fn collated_term(i: &str) -> IResult<&str, Expr> {
+    let (r, expr) = term(i)?;
+    let (r, collation) = opt(preceded(
+        delimited(multispace0, keyword("COLLATE"), multispace0),
+        cut(alt((
+            keyword("NOCASE").map(|_| Collation::NoCase),
+            keyword("BINARY").map(|_| Collation::Binary),
+        ))),
+    ))
+    .parse(r)?;
+    let Some(collation) = collation else {
+        return Ok((r, expr));
+    };
+    let (r, _) = multispace0(r)?;
+    Ok((
+        r,
+        Expr::Collate {
+            expr: Box::new(expr),
+            collation,
+        },
+    ))
+}
+
 fn multiplicative_ex(i: &str) -> IResult<&str, Expr> {
-    let (r, lhs) = term(i)?;
+    let (r, lhs) = collated_term(i)?;
 
     let (r, res) = fold_many0(
-        pair(multiplicative_op, term),
+        pair(multiplicative_op, collated_term),
         move || lhs.clone(),
         |acc, (op, sub_ex)| Expr::Binary {
             op,
@@ -322,8 +762,33 @@ fn multiplicative_ex(i: &str) -> IResult<&str, Expr> {
     Ok((r, res))
 }
 
+/// Unary minus, which binds tighter than binary operators, e.g. `-a * b` is `(-a) * b`
+fn neg(i: &str) -> IResult<&str, Expr> {
+    let (r, _) = delimited(multispace0, tag("-"), multispace0).parse(i)?;
+
+    let (r, res) = term(r)?;
+
+    Ok((
+        r,
+        Expr::Unary {
+            op: UniOp::Neg,
+            operand: Box::new(res),
+        },
+    ))
+}
+
+fn num_literal(i: &str) -> IResult<&str, Expr> {
+    let (r, lit) = delimited(
+        multispace0,
+        recognize(pair(digit1, opt(pair(tag("."), digit1)))),
+        multispace0,
+    )
+    .parse(i)?;
+    Ok((r, Expr::NumLiteral(lit.to_string())))
+}
+
 fn not(i: &str) -> IResult<&str, Expr> {
-    let (r, _) = delimited(multispace0, tag_no_case("NOT"), multispace0).parse(i)?;
+    let (r, _) = delimited(multispace0, keyword("NOT"), multispace0).parse(i)?;
 
     let (r, res) = comparison_ex(r)?;
 
@@ -337,50 +802,225 @@ fn not(i: &str) -> IResult<&str, Expr> {
 }
 
 fn term(i: &str) -> IResult<&str, Expr> {
-    let (r, res) = alt((
-        not,
-        parentheses,
-        fn_invoke,
-        str_literal.map(Expr::StrLiteral),
-        column_name.map(Expr::Column),
-    ))
+    let (r, res) = expected(
+        "expression",
+        alt((
+            not,
+            exists,
+            subquery,
+            parentheses,
+            fn_invoke,
+            str_literal.map(Expr::StrLiteral),
+            date_literal,
+            num_literal,
+            neg,
+            param,
+            niladic_fn,
+            column_name.map(Expr::Column),
+        )),
+    )
     .parse(i)?;
     Ok((r, res))
 }
 
+/// A placeholder, `:name`, `?1` or `$1`. A bare `?` is named by its position from the end of the input,
+/// which [`number_anonymous_params`] replaces with the number in the order of appearance.
+fn param(i: &str) -> IResult<&str, Expr> {
+    if let Ok((r, _)) = preceded(multispace0, tag::<_, _, SyntaxError<_>>("?")).parse(i)
+        && !r.starts_with(|c: char| c.is_ascii_digit())
+    {
+        let (r, _) = multispace0(r)?;
+        return Ok((r, Expr::Param(format!("?{}", r.len()))));
+    }
+    let (r, name) = preceded(
+        multispace0,
+        alt((
+            preceded(
+                tag(":"),
+                recognize(pair(
+                    alt((alpha1, tag("_"))),
+                    many0(alt((alphanumeric1, tag("_")))),
+                )),
+            ),
+            preceded(alt((tag("?"), tag("$"))), digit1),
+        )),
+    )
+    .parse(i)?;
+    let (r, _) = multispace0(r)?;
+    Ok((r, Expr::Param(name.to_string())))
+}
+
+/// A parenthesized SELECT statement in expression position, e.g. `(SELECT max(id) FROM books)`
+fn subquery(i: &str) -> IResult<&str, Expr> {
+    let (r, stmt) = parenthesized_select(i)?;
+    Ok((r, Expr::Subquery(Box::new(stmt))))
+}
+
+/// `name [(columns)] AS (SELECT ...)` in a `WITH` clause
+fn cte(i: &str) -> IResult<&str, Cte> {
+    let (r, name) = expected("table name", ident).parse(i)?;
+    let (r, columns) = opt(delimited(
+        symbol("("),
+        cut(separated_list1(symbol(","), ident)),
+        cut(symbol(")")),
+    ))
+    .parse(r)?;
+    let (r, _) = cut(delimited(multispace0, keyword("AS"), multispace0)).parse(r)?;
+    let (r, stmt) = cut(parenthesized_select).parse(r)?;
+    Ok((
+        r,
+        Cte {
+            name,
+            columns: columns.unwrap_or_default(),
+            stmt,
+        },
+    ))
+}
+
+fn parenthesized_select(i: &str) -> IResult<&str, SelectStmt> {
+    let (r, _) = delimited(multispace0, symbol("("), multispace0).parse(i)?;
+    let (r, _) = select_keyword(r)?;
+    let (r, stmt) = cut(select_stmt).parse(r)?;
+    let (r, _) = cut(delimited(multispace0, symbol(")"), multispace0)).parse(r)?;
+    Ok((r, stmt))
+}
+
+fn exists(i: &str) -> IResult<&str, Expr> {
+    let (r, _) = delimited(multispace0, keyword("EXISTS"), multispace0).parse(i)?;
+    let (r, stmt) = parenthesized_select(r)?;
+    Ok((r, Expr::Exists(Box::new(stmt))))
+}
+
 fn parentheses(i: &str) -> IResult<&str, Expr> {
-    let (r, _) = delimited(multispace0, tag("("), multispace0).parse(i)?;
-    let (r, res) = expression(r)?;
-    let (r, _) = delimited(multispace0, tag(")"), multispace0).parse(r)?;
+    let (r, _) = delimited(multispace0, symbol("("), multispace0).parse(i)?;
+    let (r, res) = cut(expression).parse(r)?;
+    let (r, _) = cut(delimited(multispace0, symbol(")"), multispace0)).parse(r)?;
     Ok((r, res))
 }
 
+/// A string literal in single quotes, where a quote is written as `''`
 fn str_literal(i: &str) -> IResult<&str, String> {
     let (r, _) = pair(multispace0, tag("'")).parse(i)?;
-    let (r, s) = recognize(many0(none_of("'"))).parse(r)?;
+    let (r, s) = fold_many0(
+        alt((value("'", tag("''")), is_not("'"))),
+        String::new,
+        |mut acc, s| {
+            acc.push_str(s);
+            acc
+        },
+    )
+    .parse(r)?;
     let (r, _) = tag("'").parse(r)?;
-    Ok((r, s.to_string()))
+    Ok((r, s))
+}
+
+/// `DATE '2024-01-31'` or `TIMESTAMP '2024-01-31 09:00:00'`, which is validated and normalized to a string
+/// literal. A date keyword not followed by a string literal is a column name.
+fn date_literal(i: &str) -> IResult<&str, Expr> {
+    let (lit_start, kw) = delimited(
+        multispace0,
+        alt((keyword("DATE"), keyword("TIMESTAMP"))),
+        multispace0,
+    )
+    .parse(i)?;
+    let (r, lit) = str_literal(lit_start)?;
+    let Some(date) = DateTime::parse(&lit, 0) else {
+        return Err(nom::Err::Failure(SyntaxError::expected(
+            lit_start,
+            Expected::Other("ISO-8601 date"),
+        )));
+    };
+    let lit = if kw.eq_ignore_ascii_case("DATE") {
+        date.date()
+    } else if lit.trim().len() > 19 {
+        // Keep the offset of the time zone, which depends on the session time zone
+        lit
+    } else {
+        date.to_string()
+    };
+    Ok((r, Expr::StrLiteral(lit)))
 }
 
 fn fn_invoke(i: &str) -> IResult<&str, Expr> {
     let (r, name) = delimited(multispace0, ident, multispace0).parse(i)?;
-    let (r, _) = delimited(multispace0, tag("("), multispace0).parse(r)?;
-    let (r, cs) = col_spec(r)?;
-    let (r, _) = delimited(multispace0, tag(")"), multispace0).parse(r)?;
+    let (r, _) = delimited(multispace0, symbol("("), multispace0).parse(r)?;
+    // An identifier followed by a parenthesis can only be a function call
+    let (r, distinct) = opt(distinct_keyword).parse(r)?;
+    let (r, args) = cut(separated_list0(tag(","), fn_arg)).parse(r)?;
+    let (r, _) = cut(delimited(multispace0, symbol(")"), multispace0)).parse(r)?;
+    let func = Expr::AggregateFn {
+        name,
+        args,
+        distinct: distinct.is_some(),
+    };
+    let (r, window) = opt(over_clause).parse(r)?;
+    let Some((partition_by, order_by)) = window else {
+        return Ok((r, func));
+    };
+    Ok((
+        r,
+        Expr::Window {
+            func: Box::new(func),
+            partition_by,
+            order_by,
+        },
+    ))
+}
+
+/// `OVER ([PARTITION BY exprs] [ORDER BY keys])` after a window function call
+fn over_clause(i: &str) -> IResult<&str, (Vec<Expr>, Vec<OrderBy>)> {
+    let (r, _) = (multispace0, keyword("OVER"), multispace0, symbol("(")).parse(i)?;
+    let (r, partition_by) = opt(preceded(
+        (
+            multispace0,
+            keyword("PARTITION"),
+            multispace0,
+            keyword("BY"),
+            multispace0,
+        ),
+        separated_list1(tag(","), expression),
+    ))
+    .parse(r)?;
+    let (r, order_by) = opt(order_by).parse(r)?;
+    let (r, _) = delimited(multispace0, symbol(")"), multispace0).parse(r)?;
+    Ok((
+        r,
+        (
+            partition_by.unwrap_or_default(),
+            order_by.unwrap_or_default(),
+        ),
+    ))
+}
+
+/// Niladic functions that are called without parentheses in SQL, like `current_date`
+fn niladic_fn(i: &str) -> IResult<&str, Expr> {
+    let (r, name) = token(i)?;
+    let name = name.to_ascii_lowercase();
+    if name != "current_date" && name != "current_time" {
+        return Err(nom::Err::Error(SyntaxError::new(
+            i,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
     Ok((
         r,
         Expr::AggregateFn {
             name,
-            args: vec![cs],
+            args: vec![],
+            distinct: false,
         },
     ))
 }
 
+/// A name of a table or a column, which is quoted if it has other characters than letters, digits and `_`.
 fn ident(i: &str) -> IResult<&str, String> {
+    if let Ok(res) = quoted_ident(i) {
+        return Ok(res);
+    }
     let (r, id) = token(i)?;
 
     if id == "FROM" {
-        return Err(nom::Err::Error(nom::error::Error::new(
+        return Err(nom::Err::Error(SyntaxError::new(
             i,
             nom::error::ErrorKind::Verify,
         )));
@@ -391,7 +1031,43 @@ fn ident(i: &str) -> IResult<&str, String> {
 
 fn col_wildcard(i: &str) -> IResult<&str, ColSpecifier> {
     let (r, _) = delimited(multispace0, tag("*"), multispace0).parse(i)?;
-    Ok((r, ColSpecifier::Wildcard))
+
+    let (r, exclude) = opt(wildcard_modifier("EXCLUDE", ident)).parse(r)?;
+
+    let (r, replace) = opt(wildcard_modifier("REPLACE", replace_item)).parse(r)?;
+
+    Ok((
+        r,
+        ColSpecifier::Wildcard(WildcardModifiers {
+            exclude: exclude.unwrap_or_default(),
+            replace: replace.unwrap_or_default(),
+        }),
+    ))
+}
+
+/// Parses a modifier like `EXCLUDE (a, b)` with a comma-separated list of items in parentheses.
+fn wildcard_modifier<'a, O>(
+    keyword: &'static str,
+    item: impl Parser<&'a str, Output = O, Error = SyntaxError<&'a str>>,
+) -> impl Parser<&'a str, Output = Vec<O>, Error = SyntaxError<&'a str>> {
+    delimited(
+        (
+            multispace0,
+            tag_no_case(keyword),
+            multispace0,
+            symbol("("),
+            multispace0,
+        ),
+        separated_list1(tag(","), item),
+        (multispace0, symbol(")"), multispace0),
+    )
+}
+
+fn replace_item(i: &str) -> IResult<&str, (Expr, String)> {
+    let (r, expr) = expression(i)?;
+    let (r, _) = delimited(multispace0, keyword("AS"), multispace0).parse(r)?;
+    let (r, name) = ident(r)?;
+    Ok((r, (expr, name)))
 }
 
 fn col_spec(i: &str) -> IResult<&str, ColSpecifier> {
@@ -399,26 +1075,73 @@ fn col_spec(i: &str) -> IResult<&str, ColSpecifier> {
     Ok((r, res))
 }
 
+/// An argument of a function call, in which a bare integer like `substr(name, 1, 3)` is a number rather
+/// than a column index.
+fn fn_arg(i: &str) -> IResult<&str, ColSpecifier> {
+    alt((col_wildcard, expression.map(ColSpecifier::Expr))).parse(i)
+}
+
 fn column_expr(i: &str) -> IResult<&str, Expr> {
     alt((column_digit, expression)).parse(i)
 }
 
+/// A column index like `ORDER BY 2`, which is not a part of a larger expression like `2 * a`
 fn column_digit(i: &str) -> IResult<&str, Expr> {
-    let (r, s) = digit1(i)?;
+    let (r, s) = delimited(
+        multispace0,
+        terminated(digit1, not_followed_by_operator),
+        multispace0,
+    )
+    .parse(i)?;
     let col_idx = s
         .parse()
-        .map_err(|_| nom::Err::Failure(nom::error::Error::new(r, nom::error::ErrorKind::Verify)))?;
+        .map_err(|_| nom::Err::Failure(SyntaxError::new(r, nom::error::ErrorKind::Verify)))?;
     Ok((r, Expr::ColIdx(col_idx)))
 }
 
+fn not_followed_by_operator(i: &str) -> IResult<&str, ()> {
+    let (r, _) = multispace0(i)?;
+    if r.starts_with(['+', '-', '*', '/', '<', '>', '=', '.']) {
+        return Err(nom::Err::Error(SyntaxError::new(
+            i,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+    Ok((i, ()))
+}
+
 fn column_name(i: &str) -> IResult<&str, Column> {
     let (r, table) = opt(terminated(
         ident,
         delimited(multispace0, tag("."), multispace0),
     ))
     .parse(i)?;
-    let (r, column) = ident(r)?;
-    Ok((r, Column { table, column }))
+    let (r, (column, quoted)) = alt((
+        quoted_ident.map(|column| (column, true)),
+        ident.map(|column| (column, false)),
+    ))
+    .parse(r)?;
+    Ok((
+        r,
+        Column {
+            table,
+            column,
+            quoted,
+        },
+    ))
+}
+
+/// An identifier in double quotes or backticks like `"First Name"`, which can have any characters other than
+/// the quote.
+fn quoted_ident(i: &str) -> IResult<&str, String> {
+    let (r, _) = multispace0(i)?;
+    let (r, s) = alt((
+        delimited(tag("\""), recognize(many0(none_of("\""))), tag("\"")),
+        delimited(tag("`"), recognize(many0(none_of("`"))), tag("`")),
+    ))
+    .parse(r)?;
+    let (r, _) = multispace0(r)?;
+    Ok((r, s.to_string()))
 }
 
 #[cfg(test)]
@@ -449,6 +1172,8 @@ mod test {
         assert_eq!(
             statement(src).unwrap().1,
             Statement::Select(SelectStmt {
+                with: vec![],
+                distinct: false,
                 cols: vec![
                     ColSpecifier::Expr(Expr::Column(Column::new("id"))),
                     ColSpecifier::Expr(Expr::Column(Column::new("data")))
@@ -456,7 +1181,9 @@ mod test {
                 table: TableSpecifier::new("table"),
                 join: vec![],
                 condition: None,
-                ordering: None,
+                group_by: None,
+                qualify: None,
+                ordering: vec![],
                 limit: None,
                 offset: None,
             })
@@ -469,11 +1196,15 @@ mod test {
         assert_eq!(
             statement(src).unwrap().1,
             Statement::Select(SelectStmt {
-                cols: vec![ColSpecifier::Wildcard],
+                with: vec![],
+                distinct: false,
+                cols: vec![ColSpecifier::Wildcard(WildcardModifiers::default())],
                 table: TableSpecifier::new_with_alias("table", "t"),
                 join: vec![],
                 condition: None,
-                ordering: None,
+                group_by: None,
+                qualify: None,
+                ordering: vec![],
                 limit: None,
                 offset: None,
             })
@@ -487,6 +1218,8 @@ mod test {
         assert_eq!(
             statement(src).unwrap().1,
             Statement::Select(SelectStmt {
+                with: vec![],
+                distinct: false,
                 cols: vec![
                     ColSpecifier::Expr(Expr::Column(Column::new("id"))),
                     ColSpecifier::Expr(Expr::Column(Column::new("data")))
@@ -502,7 +1235,9 @@ mod test {
                     },
                 }],
                 condition: None,
-                ordering: None,
+                group_by: None,
+                qualify: None,
+                ordering: vec![],
                 limit: None,
                 offset: None,
             })
@@ -511,22 +1246,104 @@ mod test {
 
     #[test]
     fn test_err() {
-        let src = "SELOCT id, data FROM table";
+        let err = |src| {
+            let err = parse_statement(src).unwrap_err();
+            (err.line, err.column, err.message)
+        };
         assert_eq!(
-            statement(src),
-            Err(nom::Err::Error(nom::error::Error {
-                input: "id, data FROM table",
-                code: nom::error::ErrorKind::Verify
-            }))
+            err("SELOCT id, data FROM table"),
+            (
+                1,
+                1,
+                "expected SELECT, WITH, EXPLAIN, SEARCH, COMPARE, DESCRIBE or SHOW, found 'SELOCT'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            err("SELECT id, data\n  FORM table"),
+            (2, 3, "expected FROM, found 'FORM'".to_string())
+        );
+        assert_eq!(
+            err("SELECT count(* FROM table"),
+            (1, 16, "expected ')', found 'FROM'".to_string())
+        );
+        assert_eq!(
+            err("SELECT * FROM table WHERE id ="),
+            (1, 31, "expected expression, found end of input".to_string())
+        );
+        assert_eq!(
+            err("SELECT * FROM table LIMIT x"),
+            (1, 27, "expected number, found 'x'".to_string())
+        );
+        assert_eq!(
+            err("SELECT * FROM table WHRE id = 1"),
+            (1, 21, "expected end of statement, found 'WHRE'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_limit_offset() {
+        let stmt = parse_statement("SELECT * FROM t ORDER BY a LIMIT 10 OFFSET 20").unwrap();
+        let Statement::Select(select) = stmt else {
+            panic!("not a SELECT: {stmt:?}");
+        };
+        assert_eq!(select.ordering.len(), 1);
+        assert_eq!((select.limit, select.offset), (Some(10), Some(20)));
+
+        let stmt = parse_statement("select * from t offset 5").unwrap();
+        let Statement::Select(select) = stmt else {
+            panic!("not a SELECT: {stmt:?}");
+        };
+        assert_eq!((select.limit, select.offset), (None, Some(5)));
+
+        assert_eq!(
+            parse_statement("SELECT * FROM t LIMIT 99999999999999999999")
+                .unwrap_err()
+                .message,
+            "expected smaller number, found '99999999999999999999'"
         );
     }
 
+    #[test]
+    fn test_show() {
+        assert_eq!(parse_statement("show tables"), Ok(Statement::ShowTables));
+        assert_eq!(
+            parse_statement("SHOW COLUMNS FROM phonebook"),
+            Ok(Statement::Describe("phonebook".to_string()))
+        );
+        assert_eq!(
+            parse_statement("SHOW phonebook").unwrap_err().message,
+            "expected TABLES, COLUMNS, QUERIES or FUNCTIONS, found 'phonebook'"
+        );
+        assert_eq!(parse_statement("SHOW QUERIES"), Ok(Statement::ShowQueries));
+        assert_eq!(
+            parse_statement("show functions"),
+            Ok(Statement::ShowFunctions)
+        );
+    }
+
+    #[test]
+    fn test_err_caret() {
+        let caret = |src| parse_statement(src).unwrap_err().caret(src);
+        assert_eq!(
+            caret("SELECT * FROM table WHRE id = 1"),
+            "SELECT * FROM table WHRE id = 1\n                    ^"
+        );
+        assert_eq!(
+            caret("SELECT id,\n\tdata\n\tFORM table"),
+            "\tFORM table\n\t^"
+        );
+        assert_eq!(caret("SELECT * FROM"), "SELECT * FROM\n             ^");
+    }
+
     #[test]
     fn test_order_by() {
         let src = "SELECT id, data FROM table ORDER BY id";
         assert_eq!(
             statement(src).unwrap().1,
             Statement::Select(SelectStmt {
+                with: vec![],
+                distinct: false,
                 cols: vec![
                     ColSpecifier::Expr(Expr::Column(Column::new("id"))),
                     ColSpecifier::Expr(Expr::Column(Column::new("data")))
@@ -534,13 +1351,13 @@ mod test {
                 table: TableSpecifier::new("table"),
                 join: vec![],
                 condition: None,
-                ordering: Some(OrderBy {
-                    expr: Expr::Column(Column {
-                        table: None,
-                        column: "id".to_string(),
-                    }),
+                group_by: None,
+                qualify: None,
+                ordering: vec![OrderBy {
+                    key: OrderKey::Expr(Expr::Column(Column::new("id"))),
                     ordering: Ordering::Asc,
-                }),
+                    nulls: None,
+                }],
                 limit: None,
                 offset: None,
             })
@@ -553,6 +1370,8 @@ mod test {
         assert_eq!(
             statement(src).unwrap().1,
             Statement::Select(SelectStmt {
+                with: vec![],
+                distinct: false,
                 cols: vec![
                     ColSpecifier::Expr(Expr::Column(Column::new("id"))),
                     ColSpecifier::Expr(Expr::Column(Column::new("data")))
@@ -560,13 +1379,13 @@ mod test {
                 table: TableSpecifier::new("table"),
                 join: vec![],
                 condition: Some(Expr::StrLiteral("1".to_string())),
-                ordering: Some(OrderBy {
-                    expr: Expr::Column(Column {
-                        table: None,
-                        column: "id".to_string(),
-                    }),
+                group_by: None,
+                qualify: None,
+                ordering: vec![OrderBy {
+                    key: OrderKey::Expr(Expr::Column(Column::new("id"))),
                     ordering: Ordering::Asc,
-                }),
+                    nulls: None,
+                }],
                 limit: None,
                 offset: None,
             })
@@ -735,4 +1554,391 @@ mod test {
             ))
         );
     }
+
+    #[test]
+    fn test_date_literal() {
+        let lit = |s: &str| Ok(("", Expr::StrLiteral(s.to_string())));
+        assert_eq!(expression("DATE '2024-01-31'"), lit("2024-01-31"));
+        assert_eq!(expression("date '2024-01-31 10:00'"), lit("2024-01-31"));
+        assert_eq!(
+            expression("TIMESTAMP '2024-01-31T10:00'"),
+            lit("2024-01-31 10:00:00")
+        );
+        assert_eq!(
+            expression("TIMESTAMP '2024-01-31T10:00:00+09:00'"),
+            lit("2024-01-31T10:00:00+09:00")
+        );
+        assert_eq!(
+            expression("date"),
+            Ok(("", Expr::Column(Column::new("date"))))
+        );
+        assert!(matches!(
+            expression("DATE '2024-02-30'"),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_like() {
+        let like = |escape, negated| Expr::Like {
+            lhs: Box::new(Expr::Column(Column::new("a"))),
+            pattern: Box::new(Expr::StrLiteral("%!_%".to_string())),
+            escape,
+            negated,
+        };
+        assert_eq!(expression("a LIKE '%!_%'"), Ok(("", like(None, false))));
+        assert_eq!(
+            expression("a NOT LIKE '%!_%' ESCAPE '!'"),
+            Ok(("", like(Some('!'), true)))
+        );
+        assert_eq!(
+            like(Some('!'), true).to_string(),
+            "(a NOT LIKE '%!_%' ESCAPE '!')"
+        );
+        assert!(matches!(
+            expression("a LIKE '%' ESCAPE 'ab'"),
+            Err(nom::Err::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_between() {
+        let range = Expr::Binary {
+            op: BinOp::And,
+            lhs: Box::new(Expr::Binary {
+                op: BinOp::Ge,
+                lhs: Box::new(Expr::Column(Column::new("a"))),
+                rhs: Box::new(Expr::StrLiteral("1".to_string())),
+            }),
+            rhs: Box::new(Expr::Binary {
+                op: BinOp::Le,
+                lhs: Box::new(Expr::Column(Column::new("a"))),
+                rhs: Box::new(Expr::StrLiteral("2".to_string())),
+            }),
+        };
+
+        assert_eq!(expression("a BETWEEN '1' AND '2'"), Ok(("", range.clone())));
+
+        assert_eq!(
+            expression("a NOT BETWEEN '1' AND '2' OR b"),
+            Ok((
+                "",
+                Expr::Binary {
+                    op: BinOp::Or,
+                    lhs: Box::new(Expr::Unary {
+                        op: UniOp::Not,
+                        operand: Box::new(range),
+                    }),
+                    rhs: Box::new(Expr::Column(Column::new("b"))),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_group_by_all() {
+        let src = "SELECT name, count(*) FROM t GROUP BY ALL ORDER BY ALL DESC";
+        let Statement::Select(stmt) = statement(src).unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(stmt.group_by, Some(GroupBy::All));
+        assert_eq!(
+            stmt.ordering,
+            vec![OrderBy {
+                key: OrderKey::All,
+                ordering: Ordering::Desc,
+                nulls: None,
+            }]
+        );
+
+        let src = "SELECT allowance FROM t GROUP BY allowance, 1";
+        let Statement::Select(stmt) = statement(src).unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(
+            stmt.group_by,
+            Some(GroupBy::Exprs(vec![
+                Expr::Column(Column::new("allowance")),
+                Expr::ColIdx(1)
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_distinct() {
+        let src = "SELECT DISTINCT name FROM t";
+        let Statement::Select(stmt) = statement(src).unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        assert!(stmt.distinct);
+        assert_eq!(
+            stmt.cols,
+            vec![ColSpecifier::Expr(Expr::Column(Column::new("name")))]
+        );
+
+        let src = "SELECT distinction FROM t";
+        let Statement::Select(stmt) = statement(src).unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        assert!(!stmt.distinct);
+    }
+
+    #[test]
+    fn test_wildcard_modifiers() {
+        let src = "SELECT * EXCLUDE (id, secret) REPLACE (upper(name) AS name) FROM t";
+        let Statement::Select(stmt) = statement(src).unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(
+            stmt.cols,
+            vec![ColSpecifier::Wildcard(WildcardModifiers {
+                exclude: vec!["id".to_string(), "secret".to_string()],
+                replace: vec![(
+                    Expr::AggregateFn {
+                        name: "upper".to_string(),
+                        args: vec![ColSpecifier::Expr(Expr::Column(Column::new("name")))],
+                        distinct: false,
+                    },
+                    "name".to_string()
+                )],
+            })]
+        );
+        assert_eq!(
+            stmt.cols[0].to_string(),
+            "* EXCLUDE (id, secret) REPLACE (upper(name) AS name)"
+        );
+    }
+
+    #[test]
+    fn test_multi_order_by() {
+        let src = "SELECT * FROM t ORDER BY a DESC NULLS LAST, b ASC, 3 nulls first LIMIT 1";
+        let Statement::Select(stmt) = statement(src).unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(
+            stmt.ordering,
+            vec![
+                OrderBy {
+                    key: OrderKey::Expr(Expr::Column(Column::new("a"))),
+                    ordering: Ordering::Desc,
+                    nulls: Some(Nulls::Last),
+                },
+                OrderBy {
+                    key: OrderKey::Expr(Expr::Column(Column::new("b"))),
+                    ordering: Ordering::Asc,
+                    nulls: None,
+                },
+                OrderBy {
+                    key: OrderKey::Expr(Expr::ColIdx(3)),
+                    ordering: Ordering::Asc,
+                    nulls: Some(Nulls::First),
+                },
+            ]
+        );
+        assert_eq!(stmt.limit, Some(1));
+        assert_eq!(
+            stmt.to_string(),
+            "SELECT * FROM t ORDER BY a DESC NULLS LAST, b ASC, 3 ASC NULLS FIRST LIMIT 1"
+        );
+    }
+
+    #[test]
+    fn test_quoted_ident() {
+        let src = "SELECT p.\"First Name\", `Last Name` FROM \"my people\" AS p \
+            WHERE \"p\".id = 1 ORDER BY `Last Name`";
+        let Statement::Select(stmt) = statement(src).unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(
+            stmt.cols[0],
+            ColSpecifier::Expr(Expr::Column(Column {
+                table: Some("p".to_string()),
+                column: "First Name".to_string(),
+                quoted: true,
+            }))
+        );
+        assert_eq!(stmt.table, TableSpecifier::new_with_alias("my people", "p"));
+        assert_eq!(stmt.table.to_string(), "\"my people\" AS p");
+        let ColSpecifier::Expr(Expr::Column(ref col)) = stmt.cols[1] else {
+            panic!("Not a column");
+        };
+        assert_eq!(col.to_string(), "\"Last Name\"");
+        assert!(statement("SELECT \"a FROM t").is_err());
+    }
+
+    #[test]
+    fn test_fn_args() {
+        assert_eq!(
+            expression("env('HOME')"),
+            Ok((
+                "",
+                Expr::AggregateFn {
+                    name: "env".to_string(),
+                    args: vec![ColSpecifier::Expr(Expr::StrLiteral("HOME".to_string()))],
+                    distinct: false,
+                }
+            ))
+        );
+        let current_date = Expr::AggregateFn {
+            name: "current_date".to_string(),
+            args: vec![],
+            distinct: false,
+        };
+        assert_eq!(expression("current_date()"), Ok(("", current_date.clone())));
+        assert_eq!(expression("CURRENT_DATE"), Ok(("", current_date)));
+    }
+
+    #[test]
+    fn test_outer_joins() {
+        let kinds = |src| {
+            let Statement::Select(stmt) = statement(src).unwrap().1 else {
+                panic!("Not a SELECT statement");
+            };
+            stmt.join.into_iter().map(|j| j.kind).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds("SELECT * FROM a RIGHT JOIN b ON x = y FULL OUTER JOIN c ON y = z"),
+            vec![JoinKind::Right, JoinKind::Full]
+        );
+        assert_eq!(
+            kinds("SELECT * FROM a LEFT OUTER JOIN b ON x = y"),
+            vec![JoinKind::Left]
+        );
+    }
+
+    #[test]
+    fn test_subquery() {
+        let src = "SELECT name FROM authors WHERE author_id = (SELECT max(author) FROM books)";
+        let Statement::Select(stmt) = statement(src).unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(
+            stmt.condition,
+            Some(Expr::Binary {
+                op: BinOp::Eq,
+                lhs: Box::new(Expr::Column(Column::new("author_id"))),
+                rhs: Box::new(Expr::Subquery(Box::new(SelectStmt {
+                    with: vec![],
+                    distinct: false,
+                    cols: vec![ColSpecifier::Expr(Expr::AggregateFn {
+                        name: "max".to_string(),
+                        args: vec![ColSpecifier::Expr(Expr::Column(Column::new("author")))],
+                        distinct: false,
+                    })],
+                    table: TableSpecifier::new("books"),
+                    join: vec![],
+                    condition: None,
+                    group_by: None,
+                    qualify: None,
+                    ordering: vec![],
+                    limit: None,
+                    offset: None,
+                }))),
+            })
+        );
+        assert_eq!(
+            stmt.condition.unwrap().to_string(),
+            "(author_id = (SELECT max(author) FROM books))"
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let Statement::Select(stmt) = statement("SELECT 2 * -price + 1.5 FROM t ORDER BY 1")
+            .unwrap()
+            .1
+        else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(
+            stmt.cols,
+            vec![ColSpecifier::Expr(Expr::Binary {
+                op: BinOp::Add,
+                lhs: Box::new(Expr::Binary {
+                    op: BinOp::Mul,
+                    lhs: Box::new(Expr::NumLiteral("2".to_string())),
+                    rhs: Box::new(Expr::Unary {
+                        op: UniOp::Neg,
+                        operand: Box::new(Expr::Column(Column::new("price"))),
+                    }),
+                }),
+                rhs: Box::new(Expr::NumLiteral("1.5".to_string())),
+            })]
+        );
+        assert_eq!(stmt.cols[0].to_string(), "((2 * -price) + 1.5)");
+        assert_eq!(stmt.ordering[0].key, OrderKey::Expr(Expr::ColIdx(1)));
+    }
+
+    #[test]
+    fn test_window() {
+        let src = "SELECT * FROM t QUALIFY row_number() OVER (PARTITION BY email ORDER BY updated DESC) = 1";
+        let Statement::Select(stmt) = statement(src).unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(
+            stmt.qualify,
+            Some(Expr::Binary {
+                op: BinOp::Eq,
+                lhs: Box::new(Expr::Window {
+                    func: Box::new(Expr::AggregateFn {
+                        name: "row_number".to_string(),
+                        args: vec![],
+                        distinct: false,
+                    }),
+                    partition_by: vec![Expr::Column(Column::new("email"))],
+                    order_by: vec![OrderBy {
+                        key: OrderKey::Expr(Expr::Column(Column::new("updated"))),
+                        ordering: Ordering::Desc,
+                        nulls: None,
+                    }],
+                }),
+                rhs: Box::new(Expr::NumLiteral("1".to_string())),
+            })
+        );
+        assert_eq!(
+            stmt.to_string(),
+            "SELECT * FROM t QUALIFY (row_number() OVER (PARTITION BY email ORDER BY updated DESC) = 1)"
+        );
+    }
+
+    #[test]
+    fn test_in_subquery() {
+        let Statement::Select(stmt) = statement(
+            "SELECT * FROM a WHERE id NOT IN (SELECT a_id FROM b) AND NOT EXISTS (SELECT * FROM c)",
+        )
+        .unwrap()
+        .1
+        else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(
+            stmt.to_string(),
+            "SELECT * FROM a WHERE ((id NOT IN (SELECT a_id FROM b)) AND NOT EXISTS (SELECT * FROM c))"
+        );
+        assert_eq!(stmt.referenced_tables(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_with() {
+        let Statement::Select(stmt) = statement(
+            "WITH a AS (SELECT id FROM t), b (x, y) AS (SELECT id, count(*) FROM a GROUP BY id) \
+                SELECT * FROM b INNER JOIN u ON b.x = u.id",
+        )
+        .unwrap()
+        .1
+        else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(stmt.with.len(), 2);
+        assert_eq!(stmt.with[1].columns, ["x", "y"]);
+        assert_eq!(
+            stmt.to_string(),
+            "WITH a AS (SELECT id FROM t), b(x, y) AS (SELECT id, count(*) FROM a GROUP BY id) \
+                SELECT * FROM b INNER JOIN u ON (x = id)"
+        );
+        assert_eq!(stmt.referenced_tables(), vec!["t", "u"]);
+
+        assert!(statement("WITH a AS SELECT id FROM t SELECT * FROM a").is_err());
+        assert!(statement("WITH a AS (SELECT id FROM t)").is_err());
+    }
 }
@@ -0,0 +1,191 @@
+//! Loading several CSVs, like the files of logs split by day, as the partitions of one table. The columns of
+//! the files can change over time, so the table has all the columns of all the files, in the order they first
+//! appear, and the cells of the columns that a file lacks are NULL.
+
+use std::error::Error;
+
+use crate::table::{RowSchema, Table, make_table_with};
+
+/// How a partition was fitted into the table made of all the partitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionSummary {
+    /// The name of the partition given to [`make_table_partitioned`], like the path of the file
+    pub source: String,
+    pub rows: usize,
+    /// The columns of the table that the partition does not have, whose cells are filled with NULL
+    pub missing: Vec<String>,
+    /// The columns of the partition that the first partition does not have
+    pub added: Vec<String>,
+}
+
+impl PartitionSummary {
+    /// Whether the partition has all the columns of the table and nothing was filled, maybe in another order
+    pub fn is_unchanged(&self) -> bool {
+        self.missing.is_empty() && self.added.is_empty()
+    }
+}
+
+impl std::fmt::Display for PartitionSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = if self.rows == 1 { "" } else { "s" };
+        write!(f, "{}: {} row{plural}", self.source, self.rows)?;
+        if !self.added.is_empty() {
+            write!(f, ", added columns {}", self.added.join(", "))?;
+        }
+        if !self.missing.is_empty() {
+            write!(
+                f,
+                ", missing columns {} filled with NULL",
+                self.missing.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Makes a table of the rows of all the partitions, given as the pairs of their names and CSVs with the
+/// header in the first line, parsed as by [`make_table_with`]. The columns are matched by name, and the table
+/// has the columns of all the partitions. If `strict`, a partition with other columns than the first one is an
+/// error instead. Returns the table and the summary of each partition.
+pub fn make_table_partitioned(
+    name: &str,
+    partitions: &[(&str, &str)],
+    delimiter: Option<char>,
+    strict: bool,
+) -> Result<(Table, Vec<PartitionSummary>), Box<dyn Error>> {
+    let parts = partitions
+        .iter()
+        .map(|(source, csv)| {
+            make_table_with(name, csv, delimiter).map_err(|e| format!("Partition {source}: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(first) = parts.first() else {
+        return Err(format!("Table {name} needs at least 1 partition").into());
+    };
+
+    let has_col = |part: &Table, col: &String| part.schema.iter().any(|c| c.name == *col);
+    let cols_not_in = |part: &Table, other: &Table| {
+        part.schema
+            .iter()
+            .map(|col| &col.name)
+            .filter(|col| !has_col(other, col))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    if strict {
+        for ((source, _), part) in partitions.iter().zip(&parts) {
+            let (added, missing) = (cols_not_in(part, first), cols_not_in(first, part));
+            if !added.is_empty() || !missing.is_empty() {
+                return Err(format!(
+                    "Partition {source} does not have the columns of partition {}: added [{}], missing [{}]",
+                    partitions[0].0,
+                    added.join(", "),
+                    missing.join(", ")
+                )
+                .into());
+            }
+        }
+    }
+
+    let mut columns: Vec<String> = vec![];
+    for part in &parts {
+        for col in &part.schema {
+            if !columns.contains(&col.name) {
+                columns.push(col.name.clone());
+            }
+        }
+    }
+
+    let mut summaries = vec![];
+    let mut data = vec![];
+    for ((source, _), part) in partitions.iter().zip(&parts) {
+        let summary = PartitionSummary {
+            source: source.to_string(),
+            rows: part.data.len() / part.schema.len().max(1),
+            missing: columns
+                .iter()
+                .filter(|col| !has_col(part, col))
+                .cloned()
+                .collect(),
+            added: cols_not_in(part, first),
+        };
+        // The first column of the name wins, as in resolving a column of a table
+        let indices: Vec<_> = columns
+            .iter()
+            .map(|col| part.schema.iter().position(|c| c.name == *col))
+            .collect();
+        for row in part.data.chunks(part.schema.len().max(1)) {
+            data.extend(
+                indices
+                    .iter()
+                    .map(|i| i.map_or_else(String::new, |i| row[i].clone())),
+            );
+        }
+        summaries.push(summary);
+    }
+
+    let schema = columns.into_iter().map(RowSchema::new).collect();
+    Ok((Table::new(name.to_string(), schema, data), summaries))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assert_query_results, test_util::db_from_tables};
+
+    #[test]
+    fn test_partitioned() {
+        let partitions = [
+            ("day1.csv", "id,name\n1,a\n2,b\n"),
+            ("day2.csv", "name,id,score\nc,3,10\n"),
+            ("day3.csv", "id\n4\n"),
+        ];
+        let (table, summaries) = make_table_partitioned("t", &partitions, None, false).unwrap();
+        assert_eq!(
+            summaries
+                .iter()
+                .map(|summary| summary.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "day1.csv: 2 rows, missing columns score filled with NULL",
+                "day2.csv: 1 row, added columns score",
+                "day3.csv: 1 row, missing columns name, score filled with NULL",
+            ]
+        );
+        let db = db_from_tables([table]);
+        assert_query_results!(
+            db,
+            "SELECT * FROM t ORDER BY id",
+            [
+                ["id", "name", "score"],
+                ["1", "a", ""],
+                ["2", "b", ""],
+                ["3", "c", "10"],
+                ["4", "", ""],
+            ]
+        );
+        // The types are inferred from all the partitions
+        assert_query_results!(
+            db,
+            "SELECT sum(score), count(name) FROM t",
+            [["sum(score)", "count(name)"], ["10", "3"]]
+        );
+
+        let err = make_table_partitioned("t", &partitions, None, true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Partition day2.csv does not have the columns of partition day1.csv: \
+                added [score], missing []"
+        );
+        let reordered = [partitions[0], ("day4.csv", "name,id\nd,5\n")];
+        let (table, _) = make_table_partitioned("t", &reordered, None, true).unwrap();
+        assert_eq!(table.data, ["1", "a", "2", "b", "5", "d"]);
+
+        let err =
+            make_table_partitioned("t", &[("bad.csv", "id,name\n1\n")], None, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Partition bad.csv: CSV needs the same number of columns as the header"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! `COMPARE SCHEMA a, b`, which reports the columns added, removed or renamed from table `a` to table `b`,
+//! e.g. to check that a new CSV export still has the layout the queries expect before running them.
+
+use std::error::Error;
+
+use crate::{db::Database, select::QueryOutput, table::Table};
+
+fn find_table<'a>(db: &'a Database, name: &str) -> Result<&'a Table, String> {
+    db.get(name)
+        .ok_or_else(|| format!("Table {name} not found"))
+}
+
+/// Outputs a row for each difference of the columns of `old` to `new`, in the order of the positions.
+/// A column missing in the other table at the same position as a column only in the other table is reported
+/// as renamed rather than removed and added. Positions are numbered from 1. No rows means the same columns.
+pub fn exec_compare_schema(
+    out: &mut impl QueryOutput,
+    db: &Database,
+    old: &str,
+    new: &str,
+) -> Result<(), Box<dyn Error>> {
+    let (old_table, new_table) = (find_table(db, old)?, find_table(db, new)?);
+    let old_cols: Vec<_> = old_table.schema.iter().map(|col| &col.name).collect();
+    let new_cols: Vec<_> = new_table.schema.iter().map(|col| &col.name).collect();
+    out.output(&["change", "position", old, new].map(String::from))?;
+
+    for pos in 0..old_cols.len().max(new_cols.len()) {
+        let removed = old_cols.get(pos).filter(|col| !new_cols.contains(col));
+        let added = new_cols.get(pos).filter(|col| !old_cols.contains(col));
+        let (change, old_name, new_name) = match (removed, added) {
+            (Some(old_name), Some(new_name)) => ("renamed", old_name.as_str(), new_name.as_str()),
+            (Some(old_name), None) => ("removed", old_name.as_str(), ""),
+            (None, Some(new_name)) => ("added", "", new_name.as_str()),
+            (None, None) => continue,
+        };
+        out.output(&[
+            change.to_string(),
+            (pos + 1).to_string(),
+            old_name.to_string(),
+            new_name.to_string(),
+        ])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{select::BufferOutput, test_util::db_from_csv};
+
+    #[test]
+    fn test_compare_schema() {
+        let db = db_from_csv(&[
+            ("old", "id,name,phone,email\n"),
+            ("new", "id,full_name,email,phone,zip\n"),
+        ]);
+        let mut out = BufferOutput::default();
+        exec_compare_schema(&mut out, &db, "old", "new").unwrap();
+        assert_eq!(
+            out.0,
+            vec![
+                vec!["change", "position", "old", "new"],
+                vec!["renamed", "2", "name", "full_name"],
+                vec!["added", "5", "", "zip"],
+            ]
+        );
+
+        let mut out = BufferOutput::default();
+        exec_compare_schema(&mut out, &db, "old", "old").unwrap();
+        assert_eq!(out.0.len(), 1);
+        assert!(exec_compare_schema(&mut out, &db, "old", "missing").is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! `SEARCH 'value'`, which finds the cells equal to or containing the value in all the tables,
+//! e.g. to find out where a mysterious id lives across a pile of CSV exports.
+
+use std::error::Error;
+
+use crate::{db::Database, merge_join::row_count, select::QueryOutput};
+
+/// Outputs the table, column and row of each cell that is equal to or contains `value`, ordered by them.
+/// Rows are numbered from 1 without the header, in the same way as the row numbers in the results.
+pub fn exec_search(
+    out: &mut impl QueryOutput,
+    db: &Database,
+    value: &str,
+) -> Result<(), Box<dyn Error>> {
+    if value.is_empty() {
+        return Err("SEARCH needs a non-empty value".into());
+    }
+    out.output(&["table", "column", "row", "match", "value"].map(String::from))?;
+
+    let mut tables: Vec<_> = db.tables().collect();
+    tables.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+    for table in tables {
+        for (col, schema) in table.schema.iter().enumerate() {
+            for row in 0..row_count(table) {
+                let Some(cell) = table.get(row, col) else {
+                    continue;
+                };
+                let kind = if cell == value {
+                    "exact"
+                } else if cell.contains(value) {
+                    "partial"
+                } else {
+                    continue;
+                };
+                out.output(&[
+                    table.name.clone(),
+                    schema.name.clone(),
+                    (row + 1).to_string(),
+                    kind.to_string(),
+                    cell.clone(),
+                ])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{select::BufferOutput, test_util::db_from_csv};
+
+    #[test]
+    fn test_search() {
+        let db = db_from_csv(&[("b", "id,ref\n10,x\n20,10\n"), ("a", "code\n110\n")]);
+        let mut out = BufferOutput::default();
+        exec_search(&mut out, &db, "10").unwrap();
+        assert_eq!(
+            out.0,
+            vec![
+                vec!["table", "column", "row", "match", "value"],
+                vec!["a", "code", "1", "partial", "110"],
+                vec!["b", "id", "1", "exact", "10"],
+                vec!["b", "ref", "2", "exact", "10"],
+            ]
+        );
+        assert!(exec_search(&mut out, &db, "").is_err());
+    }
+}
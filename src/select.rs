@@ -1,38 +1,369 @@
-use std::{collections::HashMap, error::Error, io::Write};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    error::Error,
+    io::Write,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use unicode_width::UnicodeWidthStr;
 
+#[cfg(feature = "parallel")]
+use crate::parallel;
 use crate::{
     Table,
+    csv::write_csv_row,
+    cte::{Cte, materialize_ctes},
     db::Database,
-    eval::{AggregateResult, EvalError, aggregate_expr, coerce_bool, eval_expr, find_aggregate_fn},
+    eval::{
+        AggregateResult, EvalError, coerce_bool, eval_cell, eval_expr, find_aggregate_fn,
+        static_type,
+    },
+    group::exec_grouped,
+    hash_join::HashJoin,
+    join::{JoinMethod, has_residual_condition},
+    limits::Limits,
+    merge_join::MergeJoin,
+    number::Number,
+    options::ExecOptions,
+    semi_join::SubqueryValues,
+    table::ColumnType,
+    trace::{ExecTrace, TraceOutcome},
+    value,
+    window::{WindowState, exec_windowed, has_window_fn},
 };
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SelectStmt {
+    /// The named subqueries of `WITH`, materialized as tables before the statement runs
+    pub with: Vec<Cte>,
+    pub distinct: bool,
     pub cols: Vec<ColSpecifier>,
     pub table: TableSpecifier,
     pub join: Vec<JoinClause>,
     pub condition: Option<Expr>,
-    pub ordering: Option<OrderBy>,
+    pub group_by: Option<GroupBy>,
+    /// Filter on the results of window functions
+    pub qualify: Option<Expr>,
+    /// Sort keys in the order of precedence. Empty if the statement has no ORDER BY.
+    pub ordering: Vec<OrderBy>,
     pub limit: Option<usize>,
     pub offset: Option<usize>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl SelectStmt {
+    /// Names of the tables in FROM and JOIN clauses, including those in subqueries and CTEs, without
+    /// duplicates. The names of the CTEs themselves are not included.
+    pub fn referenced_tables(&self) -> Vec<&str> {
+        let mut tables: Vec<&str> = self
+            .with
+            .iter()
+            .flat_map(|cte| cte.stmt.referenced_tables())
+            .collect();
+        tables.push(self.table.name.as_str());
+        tables.extend(self.join.iter().map(|join| join.table.name.as_str()));
+        // Nested subqueries are visited as well
+        self.visit_exprs(&mut |ex| {
+            if let Expr::Subquery(stmt)
+            | Expr::Exists(stmt)
+            | Expr::InSubquery { subquery: stmt, .. } = ex
+            {
+                tables.push(&stmt.table.name);
+                tables.extend(stmt.join.iter().map(|join| join.table.name.as_str()));
+            }
+        });
+        let mut seen = HashSet::new();
+        tables.retain(|name| !self.with.iter().any(|cte| cte.name == *name) && seen.insert(*name));
+        tables
+    }
+
+    /// Replaces the select list with the given columns, e.g. for `--columns a,b` on a saved query.
+    /// A name that is the same as the header of an expression in the select list, like `count(*)`, keeps that
+    /// expression. Other names refer to the columns of the tables, optionally qualified like `books.title`.
+    pub fn project_columns(&mut self, names: &[impl AsRef<str>]) {
+        self.cols = names
+            .iter()
+            .map(|name| {
+                let name = name.as_ref().trim();
+                self.cols
+                    .iter()
+                    .find(|col| matches!(col, ColSpecifier::Expr(ex) if ex.to_string() == name))
+                    .cloned()
+                    .unwrap_or_else(|| {
+                        let column = match name.split_once('.') {
+                            Some((table, column)) => Column {
+                                table: Some(table.to_string()),
+                                column: column.to_string(),
+                                quoted: false,
+                            },
+                            None => Column::new(name),
+                        };
+                        ColSpecifier::Expr(Expr::Column(column))
+                    })
+            })
+            .collect();
+    }
+
+    /// Columns referenced by name anywhere in the statement, including subqueries, without duplicates.
+    /// Columns covered by a wildcard are not included, since they depend on the schema.
+    pub fn referenced_columns(&self) -> Vec<&Column> {
+        let mut columns: Vec<&Column> = vec![];
+        self.visit_exprs(&mut |ex| {
+            if let Expr::Column(col) = ex
+                && !columns.contains(&col)
+            {
+                columns.push(col);
+            }
+        });
+        columns
+    }
+
+    /// The expressions in the statement and their sub-expressions excluding those in subqueries and `WITH`, which
+    /// are in the scope of their own tables, and the subqueries at any depth outside `WITH`.
+    pub(crate) fn own_exprs(&self) -> (Vec<&Expr>, Vec<&SelectStmt>) {
+        let mut exprs = vec![];
+        let mut subqueries = vec![];
+        let mut in_with = HashSet::new();
+        for cte in &self.with {
+            cte.stmt.visit_exprs(&mut |ex| {
+                in_with.insert(ex as *const Expr);
+            });
+        }
+        self.visit_exprs(&mut |ex| {
+            if in_with.contains(&(ex as *const Expr)) {
+                return;
+            }
+            if let Expr::Subquery(stmt)
+            | Expr::Exists(stmt)
+            | Expr::InSubquery { subquery: stmt, .. } = ex
+            {
+                subqueries.push(&**stmt);
+            }
+            exprs.push(ex);
+        });
+        let mut nested = HashSet::new();
+        for stmt in &subqueries {
+            stmt.visit_exprs(&mut |ex| {
+                nested.insert(ex as *const Expr);
+            });
+        }
+        exprs.retain(|ex| !nested.contains(&(*ex as *const Expr)));
+        (exprs, subqueries)
+    }
+
+    /// Visit all the expressions in the statement and their sub-expressions, including those in `WITH`.
+    pub(crate) fn visit_exprs<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        for cte in &self.with {
+            cte.stmt.visit_exprs(f);
+        }
+        for col in &self.cols {
+            col.visit(f);
+        }
+        for join in &self.join {
+            join.condition.visit(f);
+        }
+        if let Some(ref cond) = self.condition {
+            cond.visit(f);
+        }
+        if let Some(GroupBy::Exprs(ref exprs)) = self.group_by {
+            for ex in exprs {
+                ex.visit(f);
+            }
+        }
+        if let Some(ref qualify) = self.qualify {
+            qualify.visit(f);
+        }
+        for order_by in &self.ordering {
+            if let OrderKey::Expr(ref ex) = order_by.key {
+                ex.visit(f);
+            }
+        }
+    }
+
+    /// Mutable version of [`Self::visit_exprs`].
+    pub(crate) fn visit_exprs_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        for cte in &mut self.with {
+            cte.stmt.visit_exprs_mut(f);
+        }
+        for col in &mut self.cols {
+            col.visit_mut(f);
+        }
+        for join in &mut self.join {
+            join.condition.visit_mut(f);
+        }
+        if let Some(ref mut cond) = self.condition {
+            cond.visit_mut(f);
+        }
+        if let Some(GroupBy::Exprs(ref mut exprs)) = self.group_by {
+            for ex in exprs {
+                ex.visit_mut(f);
+            }
+        }
+        if let Some(ref mut qualify) = self.qualify {
+            qualify.visit_mut(f);
+        }
+        for order_by in &mut self.ordering {
+            if let OrderKey::Expr(ref mut ex) = order_by.key {
+                ex.visit_mut(f);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SelectStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, cte) in self.with.iter().enumerate() {
+            write!(f, "{}{cte}", if i == 0 { "WITH " } else { ", " })?;
+        }
+        if !self.with.is_empty() {
+            write!(f, " ")?;
+        }
+        write!(f, "SELECT ")?;
+        if self.distinct {
+            write!(f, "DISTINCT ")?;
+        }
+        for (i, col) in self.cols.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{col}")?;
+        }
+        write!(f, " FROM {}", self.table)?;
+        for join in &self.join {
+            let kind = match join.kind {
+                JoinKind::Inner => "INNER",
+                JoinKind::Left => "LEFT",
+                JoinKind::Right => "RIGHT",
+                JoinKind::Full => "FULL OUTER",
+            };
+            write!(f, " {kind} JOIN {} ON {}", join.table, join.condition)?;
+        }
+        if let Some(ref cond) = self.condition {
+            write!(f, " WHERE {cond}")?;
+        }
+        match self.group_by {
+            Some(GroupBy::All) => write!(f, " GROUP BY ALL")?,
+            Some(GroupBy::Exprs(ref exprs)) => {
+                write!(f, " GROUP BY ")?;
+                for (i, ex) in exprs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{ex}")?;
+                }
+            }
+            None => {}
+        }
+        if let Some(ref qualify) = self.qualify {
+            write!(f, " QUALIFY {qualify}")?;
+        }
+        if !self.ordering.is_empty() {
+            write!(f, " ORDER BY ")?;
+            fmt_order_by(f, &self.ordering)?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {limit}")?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " OFFSET {offset}")?;
+        }
+        Ok(())
+    }
+}
+
+fn fmt_order_by(f: &mut std::fmt::Formatter<'_>, ordering: &[OrderBy]) -> std::fmt::Result {
+    for (i, order_by) in ordering.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{order_by}")?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TableSpecifier {
     pub name: String,
     pub alias: Option<String>,
 }
 
+impl std::fmt::Display for TableSpecifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ref alias) = self.alias {
+            write!(f, "{} AS {}", Ident(&self.name), Ident(alias))
+        } else {
+            write!(f, "{}", Ident(&self.name))
+        }
+    }
+}
+
+/// A name formatted as an identifier, in double quotes unless it consists of letters, digits and `_`
+/// starting with a letter or `_`, e.g. `"First Name"`, so that the statement can be parsed back.
+struct Ident<'a>(&'a str);
+
+impl Ident<'_> {
+    fn is_bare(&self) -> bool {
+        self.0
+            .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && self
+                .0
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+}
+
+impl std::fmt::Display for Ident<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_bare() {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "\"{}\"", self.0)
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ColSpecifier {
-    Wildcard,
+    Wildcard(WildcardModifiers),
     Expr(Expr),
 }
 
+/// `EXCLUDE` and `REPLACE` modifiers of a wildcard, e.g. `* EXCLUDE (id) REPLACE (upper(name) AS name)`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WildcardModifiers {
+    /// Names of the columns to leave out of the expansion
+    pub exclude: Vec<String>,
+    /// Expressions to put in place of the columns with the given names
+    pub replace: Vec<(Expr, String)>,
+}
+
 impl ColSpecifier {
+    pub(crate) fn visit<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        match self {
+            Self::Wildcard(modifiers) => {
+                for (ex, _) in &modifiers.replace {
+                    ex.visit(f);
+                }
+            }
+            Self::Expr(ex) => ex.visit(f),
+        }
+    }
+
+    pub(crate) fn visit_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        match self {
+            Self::Wildcard(modifiers) => {
+                for (ex, _) in &mut modifiers.replace {
+                    ex.visit_mut(f);
+                }
+            }
+            Self::Expr(ex) => ex.visit_mut(f),
+        }
+    }
+
     pub(crate) fn as_expr(&self) -> Result<&Expr, EvalError> {
         match self {
-            Self::Wildcard => Err(EvalError::DisallowedWildcard(self.to_string())),
+            Self::Wildcard(_) => Err(EvalError::DisallowedWildcard(self.to_string())),
             Self::Expr(ex) => Ok(ex),
         }
     }
@@ -42,12 +373,35 @@ impl std::fmt::Display for ColSpecifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Expr(ex) => ex.fmt(f),
-            Self::Wildcard => write!(f, "*"),
+            Self::Wildcard(modifiers) => {
+                write!(f, "*")?;
+                if !modifiers.exclude.is_empty() {
+                    write!(f, " EXCLUDE (")?;
+                    for (i, name) in modifiers.exclude.iter().enumerate() {
+                        if i != 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", Ident(name))?;
+                    }
+                    write!(f, ")")?;
+                }
+                if !modifiers.replace.is_empty() {
+                    write!(f, " REPLACE (")?;
+                    for (i, (ex, name)) in modifiers.replace.iter().enumerate() {
+                        if i != 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{ex} AS {}", Ident(name))?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct JoinClause {
     pub kind: JoinKind,
     pub table: TableSpecifier,
@@ -58,12 +412,96 @@ pub struct JoinClause {
 pub enum JoinKind {
     Inner,
     Left,
+    Right,
+    Full,
+}
+
+/// How strings are compared in grouping, deduplication, sorting and comparisons
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collation {
+    /// Byte-wise comparison, which is the default
+    Binary,
+    /// Comparison ignoring case, so that "Alice" and "alice" are in the same group
+    NoCase,
+}
+
+impl std::fmt::Display for Collation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Binary => f.write_str("BINARY"),
+            Self::NoCase => f.write_str("NOCASE"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupBy {
+    Exprs(Vec<Expr>),
+    /// `GROUP BY ALL`, grouping by all the output columns without aggregate functions
+    All,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OrderBy {
-    pub expr: Expr,
+    pub key: OrderKey,
     pub ordering: Ordering,
+    /// `NULLS FIRST` or `NULLS LAST`. NULLs (empty cells) sort as the smallest values if not given.
+    pub nulls: Option<Nulls>,
+}
+
+impl OrderBy {
+    /// Compares the values of the key in the order of this clause, as the values of the type of the key.
+    pub(crate) fn compare(&self, ty: ColumnType, lhs: &str, rhs: &str) -> std::cmp::Ordering {
+        use std::cmp::Ordering::{Greater, Less};
+        match (self.nulls, lhs.is_empty(), rhs.is_empty()) {
+            (Some(Nulls::First), true, false) | (Some(Nulls::Last), false, true) => return Less,
+            (Some(Nulls::First), false, true) | (Some(Nulls::Last), true, false) => return Greater,
+            _ => {}
+        }
+        let res = ty.compare(lhs, rhs);
+        if self.ordering == Ordering::Desc {
+            res.reverse()
+        } else {
+            res
+        }
+    }
+}
+
+impl std::fmt::Display for OrderBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ordering = match self.ordering {
+            Ordering::Asc => "ASC",
+            Ordering::Desc => "DESC",
+        };
+        write!(f, "{} {ordering}", self.key)?;
+        match self.nulls {
+            Some(Nulls::First) => write!(f, " NULLS FIRST"),
+            Some(Nulls::Last) => write!(f, " NULLS LAST"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderKey {
+    Expr(Expr),
+    /// `ORDER BY ALL`, sorting by all the output columns from left to right
+    All,
+}
+
+impl std::fmt::Display for OrderKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Expr(ex) => ex.fmt(f),
+            Self::All => write!(f, "ALL"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -110,12 +548,15 @@ impl std::fmt::Display for BinOp {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UniOp {
     Not,
+    /// Unary minus
+    Neg,
 }
 
 impl std::fmt::Display for UniOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Not => f.write_str("NOT"),
+            Self::Neg => f.write_str("-"),
         }
     }
 }
@@ -125,6 +566,8 @@ pub enum Expr {
     Column(Column),
     ColIdx(usize),
     StrLiteral(String),
+    /// A numeric literal, kept as written in the source, e.g. `3` or `1.50`
+    NumLiteral(String),
     Binary {
         op: BinOp,
         lhs: Box<Expr>,
@@ -137,32 +580,246 @@ pub enum Expr {
     AggregateFn {
         name: String,
         args: Vec<ColSpecifier>,
+        /// `DISTINCT` before the arguments, like `count(DISTINCT col)`
+        distinct: bool,
+    },
+    /// A placeholder, `:name` for a named parameter or `?1` for a positional one, which has a numeric name.
+    Param(String),
+    /// A parenthesized SELECT statement that yields a single value
+    Subquery(Box<SelectStmt>),
+    /// `lhs [NOT] IN (SELECT ...)`, executed as a hash semi-join, or anti-join if negated
+    InSubquery {
+        lhs: Box<Expr>,
+        subquery: Box<SelectStmt>,
+        negated: bool,
+    },
+    /// `EXISTS (SELECT ...)`
+    Exists(Box<SelectStmt>),
+    /// `expr COLLATE NOCASE`, which has the value of `expr`, but is grouped, deduplicated, sorted and compared
+    /// in the collation
+    Collate {
+        expr: Box<Expr>,
+        collation: Collation,
+    },
+    /// `lhs [NOT] LIKE pattern [ESCAPE 'c']`, where `%` in the pattern matches any string and `_` any character,
+    /// unless preceded by the escape character
+    Like {
+        lhs: Box<Expr>,
+        pattern: Box<Expr>,
+        escape: Option<char>,
+        negated: bool,
+    },
+    /// A window function call like `row_number() OVER (PARTITION BY a ORDER BY b)`
+    Window {
+        func: Box<Expr>,
+        partition_by: Vec<Expr>,
+        order_by: Vec<OrderBy>,
     },
+    /// The 0-based index of the row of the joined table at the index in the FROM and JOIN clauses in its source
+    /// table, or NULL for the missing side of a left join. It is not written in SQL, but added as the columns
+    /// of [`ExecOptions::provenance`].
+    SourceRow(usize),
+    /// A column reference resolved to a column of the joined tables when the statement is bound to them before
+    /// execution, so that the rows are evaluated without looking up the name again. Shown as written.
+    Bound(Column, ColumnId),
+}
+
+impl Expr {
+    /// Converts a value of this expression to the key that is equal for the values equal in its collation.
+    pub(crate) fn collation_key(&self, val: String) -> String {
+        match self {
+            Self::Collate {
+                collation: Collation::NoCase,
+                ..
+            } => val.to_lowercase(),
+            _ => val,
+        }
+    }
+
+    /// Visit this expression and all of its sub-expressions in depth-first order, including those in subqueries.
+    pub(crate) fn visit<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        f(self);
+        match self {
+            Self::Binary { lhs, rhs, .. } => {
+                lhs.visit(f);
+                rhs.visit(f);
+            }
+            Self::Unary { operand, .. } | Self::Collate { expr: operand, .. } => operand.visit(f),
+            Self::AggregateFn { args, .. } => {
+                for arg in args {
+                    arg.visit(f);
+                }
+            }
+            Self::Subquery(stmt) | Self::Exists(stmt) => stmt.visit_exprs(f),
+            Self::InSubquery { lhs, subquery, .. } => {
+                lhs.visit(f);
+                subquery.visit_exprs(f);
+            }
+            Self::Like { lhs, pattern, .. } => {
+                lhs.visit(f);
+                pattern.visit(f);
+            }
+            Self::Window {
+                func,
+                partition_by,
+                order_by,
+            } => {
+                func.visit(f);
+                for ex in partition_by {
+                    ex.visit(f);
+                }
+                for order_by in order_by {
+                    if let OrderKey::Expr(ref ex) = order_by.key {
+                        ex.visit(f);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Mutable version of [`Self::visit`].
+    pub(crate) fn visit_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        f(self);
+        match self {
+            Self::Binary { lhs, rhs, .. } => {
+                lhs.visit_mut(f);
+                rhs.visit_mut(f);
+            }
+            Self::Unary { operand, .. } | Self::Collate { expr: operand, .. } => {
+                operand.visit_mut(f)
+            }
+            Self::AggregateFn { args, .. } => {
+                for arg in args {
+                    arg.visit_mut(f);
+                }
+            }
+            Self::Subquery(stmt) | Self::Exists(stmt) => stmt.visit_exprs_mut(f),
+            Self::InSubquery { lhs, subquery, .. } => {
+                lhs.visit_mut(f);
+                subquery.visit_exprs_mut(f);
+            }
+            Self::Like { lhs, pattern, .. } => {
+                lhs.visit_mut(f);
+                pattern.visit_mut(f);
+            }
+            Self::Window {
+                func,
+                partition_by,
+                order_by,
+            } => {
+                func.visit_mut(f);
+                for ex in partition_by {
+                    ex.visit_mut(f);
+                }
+                for order_by in order_by {
+                    if let OrderKey::Expr(ref mut ex) = order_by.key {
+                        ex.visit_mut(f);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
+/// Shows the expression with the names of the columns as the headers of the output. The alternate form `{:#}`
+/// shows the columns as written, with the tables like `a.name`.
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Column(col) => f.write_str(&col.column),
+            Self::Column(col) | Self::Bound(col, _) if f.alternate() => write!(f, "{col}"),
+            Self::Column(col) | Self::Bound(col, _) => f.write_str(&col.column),
             Self::ColIdx(idx) => write!(f, "{idx}"),
-            Self::StrLiteral(lit) => write!(f, "'{lit}'"),
+            Self::SourceRow(joindex) => write!(f, "source_row({joindex})"),
+            Self::StrLiteral(lit) => value::write_literal(f, lit),
+            Self::NumLiteral(lit) => write!(f, "{lit}"),
+            Self::Param(name) if name.bytes().all(|b| b.is_ascii_digit()) => write!(f, "?{name}"),
+            Self::Param(name) => write!(f, ":{name}"),
             Self::Binary { op, lhs, rhs } => {
-                write!(f, "({lhs} {op} {rhs})")
+                write!(f, "(")?;
+                lhs.fmt(f)?;
+                write!(f, " {op} ")?;
+                rhs.fmt(f)?;
+                write!(f, ")")
+            }
+            Self::Unary {
+                op: UniOp::Neg,
+                operand,
+            } => {
+                write!(f, "-")?;
+                operand.fmt(f)
             }
             Self::Unary { op, operand } => {
-                write!(f, "{op} {operand}")
+                write!(f, "{op} ")?;
+                operand.fmt(f)
             }
-            Self::AggregateFn { name, args } => {
+            Self::AggregateFn {
+                name,
+                args,
+                distinct,
+            } => {
                 write!(f, "{name}(")?;
+                if *distinct {
+                    write!(f, "DISTINCT ")?;
+                }
                 for (i, arg) in args.iter().enumerate() {
                     if i != 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "{arg}")?;
+                    arg.fmt(f)?;
                 }
                 write!(f, ")")?;
                 Ok(())
             }
+            Self::Subquery(stmt) => write!(f, "({stmt})"),
+            Self::InSubquery {
+                lhs,
+                subquery,
+                negated,
+            } => {
+                write!(f, "(")?;
+                lhs.fmt(f)?;
+                write!(f, " {}IN ({subquery}))", if *negated { "NOT " } else { "" })
+            }
+            Self::Exists(stmt) => write!(f, "EXISTS ({stmt})"),
+            Self::Collate { expr, collation } => {
+                expr.fmt(f)?;
+                write!(f, " COLLATE {collation}")
+            }
+            Self::Like {
+                lhs,
+                pattern,
+                escape,
+                negated,
+            } => {
+                write!(f, "(")?;
+                lhs.fmt(f)?;
+                write!(f, " {}LIKE ", if *negated { "NOT " } else { "" })?;
+                pattern.fmt(f)?;
+                if let Some(escape) = escape {
+                    write!(f, " ESCAPE '{escape}'")?;
+                }
+                write!(f, ")")
+            }
+            Self::Window {
+                func,
+                partition_by,
+                order_by,
+            } => {
+                write!(f, "{func} OVER (")?;
+                for (i, ex) in partition_by.iter().enumerate() {
+                    write!(f, "{}{ex}", if i == 0 { "PARTITION BY " } else { ", " })?;
+                }
+                if !order_by.is_empty() {
+                    if !partition_by.is_empty() {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "ORDER BY ")?;
+                    fmt_order_by(f, order_by)?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
@@ -171,14 +828,17 @@ impl std::fmt::Display for Expr {
 pub struct Column {
     pub table: Option<String>,
     pub column: String,
+    /// Written in double quotes or backticks like `"Name"`, which matches the column name exactly even if column
+    /// names are resolved ignoring case
+    pub quoted: bool,
 }
 
-#[cfg(test)]
 impl Column {
     pub fn new(column: impl Into<String>) -> Self {
         Self {
             table: None,
             column: column.into(),
+            quoted: false,
         }
     }
 }
@@ -186,13 +846,24 @@ impl Column {
 impl std::fmt::Display for Column {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(ref table) = self.table {
-            write!(f, "{}.{}", table, self.column)
+            write!(f, "{}.", Ident(table))?;
+        }
+        if self.quoted {
+            write!(f, "\"{}\"", self.column)
         } else {
-            write!(f, "{}", self.column)
+            write!(f, "{}", Ident(&self.column))
         }
     }
 }
 
+/// A column of the joined tables, by the index of the table in the FROM and JOIN clauses and the index of the
+/// column in that table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ColumnId {
+    pub joindex: usize,
+    pub col: usize,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct ColRef<'a> {
     table: &'a Table,
@@ -210,106 +881,563 @@ impl<'a> ColRef<'a> {
         }
     }
 
-    pub fn get(&self, row_indices: &[RowCursor]) -> Result<&String, EvalError> {
+    pub fn id(&self) -> ColumnId {
+        ColumnId {
+            joindex: self.joindex,
+            col: self.col,
+        }
+    }
+
+    pub fn get(&self, row_indices: &[RowCursor]) -> Result<&'a String, EvalError> {
         let row = row_indices
             .get(self.joindex)
             .ok_or_else(|| EvalError::ColNotFound(self.joindex.to_string()))?
             .row
-            .ok_or_else(|| EvalError::CursorNone(self.joindex))?;
+            .ok_or(EvalError::CursorNone(self.joindex))?;
         self.table
             .get(row, self.col)
-            .ok_or_else(|| EvalError::RowNotFound(row))
+            .ok_or(EvalError::RowNotFound(row))
+    }
+
+    /// The type of the column inferred on loading the table.
+    pub fn column_type(&self) -> ColumnType {
+        self.table.column_type(self.col)
+    }
+
+    /// The cell parsed as a number, cached in the table. `None` if it is not a number, including NULL.
+    pub fn get_number(
+        &self,
+        row_indices: &[RowCursor],
+        decimal: bool,
+    ) -> Result<Option<Number>, EvalError> {
+        let row = row_indices
+            .get(self.joindex)
+            .ok_or_else(|| EvalError::ColNotFound(self.joindex.to_string()))?
+            .row
+            .ok_or(EvalError::CursorNone(self.joindex))?;
+        if self.table.get(row, self.col).is_none() {
+            return Err(EvalError::RowNotFound(row));
+        }
+        Ok(self.table.get_number(row, self.col, decimal))
     }
 }
 
 #[derive(Clone)]
 pub(crate) struct QueryContext<'a> {
-    sql: &'a SelectStmt,
-    tables: Vec<&'a Table>,
-    aliases: HashMap<&'a String, usize>,
+    db: &'a Database,
+    /// The statement with its column references bound to the tables, shared by the copies of the context
+    pub sql: Arc<SelectStmt>,
+    pub tables: Vec<&'a Table>,
+    /// The name qualifying the columns of each joined table: its alias, or the name written in the statement.
+    /// A table with an alias is not qualified by its name, so that the sides of a self-join are told apart.
+    qualifiers: Vec<&'a str>,
+    pub options: &'a ExecOptions,
+    trace: Option<&'a RefCell<ExecTrace>>,
+    /// Results of scalar subqueries keyed by the address of the AST node, since they do not depend on the row.
+    /// Shared by the copies of the context, including those of the threads of a parallel scan.
+    subqueries: Arc<Mutex<HashMap<usize, String>>>,
+    /// Values of the subqueries in IN, keyed by the address of the AST node like `subqueries`
+    subquery_values: Arc<Mutex<HashMap<usize, SubqueryValues>>>,
+    /// Values of window functions, available while evaluating the output columns and QUALIFY
+    pub window: Option<&'a WindowState>,
+    /// The size beyond which the output cells are truncated, from the options. None while buffering the rows to
+    /// sort, whose cells are truncated after sorting.
+    pub max_cell_size: Option<usize>,
+    /// The rows output and the combinations evaluated so far, within the limits in the options
+    pub limits: Arc<Limits>,
 }
 
-impl<'a> QueryContext<'a> {
-    pub fn find_col(&self, column: &Column) -> Option<ColRef<'a>> {
-        if let Some(ref table_name) = column.table {
-            let (joindex, table) = self
-                .aliases
-                .get(table_name)
-                .and_then(|i| Some((*i, *self.tables.get(*i)?)))
-                .or_else(|| {
-                    self.tables
-                        .iter()
-                        .enumerate()
-                        .find(|(_, t)| t.name == *table_name)
-                        .map(|(i, t)| (i, *t))
-                })?;
-            return table
-                .schema
-                .iter()
-                .enumerate()
-                .find(|(_, s)| s.name == column.column)
-                .map(|(i, _)| ColRef::new(table, joindex, i));
-        }
-        self.tables
-            .iter()
-            .enumerate()
-            .fold(None, |mut acc, (joindex, table)| {
-                let candidate = table
-                    .schema
-                    .iter()
-                    .enumerate()
-                    .find(|(_, s)| s.name == column.column)
-                    .map(|(i, _)| ColRef::new(table, joindex, i));
-                if candidate.is_some() {
-                    if acc.is_some() {
-                        panic!("Column name {}", column.column);
-                    } else {
-                        acc = candidate;
-                    }
-                }
-                acc
-            })
-    }
+/// The parts of a [`QueryContext`] that the threads of a parallel scan share, which are `Sync` unlike the trace
+/// and the values of the windows in the context
+#[cfg(feature = "parallel")]
+pub(crate) struct SharedContext<'a> {
+    pub db: &'a Database,
+    pub sql: &'a Arc<SelectStmt>,
+    pub tables: &'a [&'a Table],
+    qualifiers: &'a [&'a str],
+    pub options: &'a ExecOptions,
+    subqueries: &'a Arc<Mutex<HashMap<usize, String>>>,
+    subquery_values: &'a Arc<Mutex<HashMap<usize, SubqueryValues>>>,
+    max_cell_size: Option<usize>,
+    pub limits: &'a Arc<Limits>,
 }
 
-fn extend_colspecs<'a>(
-    ctx: &'a QueryContext<'a>,
-    colspecs: &'a [ColSpecifier],
-) -> Result<(Vec<Expr>, Vec<String>), Box<dyn Error>> {
-    let mut exprs = vec![];
-    let mut header = vec![];
-    for col_spec in colspecs {
-        match col_spec {
-            ColSpecifier::Wildcard => {
-                for (_i, table) in ctx.tables.iter().enumerate() {
-                    for (_j, col) in table.schema.iter().enumerate() {
-                        exprs.push(Expr::Column(Column {
-                            table: Some(table.name.clone()),
-                            column: col.name.to_string(),
-                        }));
-                        header.push(col.name.clone());
-                    }
-                }
-            }
-            ColSpecifier::Expr(expr) => {
-                exprs.push(expr.clone());
-                header.push(expr.to_string());
-            }
+#[cfg(feature = "parallel")]
+impl<'a> SharedContext<'a> {
+    /// A context for a thread, which shares the bound statement, the results of the subqueries and the limits
+    /// with the other threads.
+    pub fn context(&self) -> QueryContext<'a> {
+        QueryContext {
+            db: self.db,
+            sql: Arc::clone(self.sql),
+            tables: self.tables.to_vec(),
+            qualifiers: self.qualifiers.to_vec(),
+            options: self.options,
+            trace: None,
+            subqueries: Arc::clone(self.subqueries),
+            subquery_values: Arc::clone(self.subquery_values),
+            window: None,
+            max_cell_size: self.max_cell_size,
+            limits: Arc::clone(self.limits),
         }
     }
-    Ok((exprs, header))
 }
 
-#[derive(Clone, Copy, Debug)]
-pub(crate) struct RowCursor {
-    pub row: Option<usize>,
-    pub shown: bool,
+/// Locks a cache of the subqueries. A panic while running a subquery leaves nothing half-inserted, so a poisoned
+/// lock is still usable.
+fn lock<T>(cache: &Mutex<T>) -> MutexGuard<'_, T> {
+    cache.lock().unwrap_or_else(|e| e.into_inner())
 }
 
-impl RowCursor {
-    fn new() -> Self {
-        Self {
-            row: Some(0),
+impl<'a> QueryContext<'a> {
+    pub fn new(
+        db: &'a Database,
+        sql: &'a SelectStmt,
+        options: &'a ExecOptions,
+        trace: Option<&'a RefCell<ExecTrace>>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let Some(table) = db.get(&sql.table.name) else {
+            return Err(format!("Table {} not found", sql.table.name).into());
+        };
+
+        let joined_tables = std::iter::once(Ok(table))
+            .chain(sql.join.iter().map(|join| {
+                db.get(&join.table.name)
+                    .ok_or_else(|| format!("Table {} not found", join.table.name))
+            }))
+            .collect::<Result<Vec<_>, String>>()?;
+        // The table found ignoring case is qualified by the name written in the statement
+        let qualifiers = std::iter::once(&sql.table)
+            .chain(sql.join.iter().map(|join| &join.table))
+            .map(|spec| spec.alias.as_deref().unwrap_or(&spec.name))
+            .collect();
+
+        // The CTEs are already materialized as tables
+        let mut ctx = Self {
+            db,
+            sql: Arc::new(SelectStmt {
+                with: vec![],
+                ..sql.clone()
+            }),
+            tables: joined_tables,
+            qualifiers,
+            options,
+            trace,
+            subqueries: Arc::default(),
+            subquery_values: Arc::default(),
+            window: None,
+            max_cell_size: options.max_cell_size,
+            limits: Arc::new(Limits::new(options)),
+        };
+        ctx.sql = Arc::new(ctx.bind_columns()?);
+        Ok(ctx)
+    }
+
+    /// Resolves all the columns referenced in the statement before processing any rows, so that a typo is
+    /// reported up front rather than in the middle of the output, and returns the statement with the references
+    /// replaced by [`Expr::Bound`]. Subqueries are validated against their own tables, and bound when they run.
+    fn bind_columns(&self) -> Result<SelectStmt, Box<dyn Error>> {
+        let mut sql = (*self.sql).clone();
+        let (exprs, subqueries) = sql.own_exprs();
+        let mut bindings = HashMap::new();
+        for ex in exprs {
+            let Expr::Column(col) = ex else {
+                continue;
+            };
+            let Some(found) = self.find_col(col)? else {
+                let (available, more) = self.available_columns(col);
+                return Err(EvalError::UnknownColumn {
+                    column: col.to_string(),
+                    suggestion: self.similar_column(col),
+                    available,
+                    more,
+                }
+                .into());
+            };
+            bindings.insert(ex as *const Expr, found.id());
+        }
+        for stmt in subqueries {
+            QueryContext::new(self.db, stmt, self.options, None)?;
+        }
+        sql.visit_exprs_mut(&mut |ex| {
+            if let Some(id) = bindings.get(&(ex as *const Expr))
+                && let Expr::Column(col) = ex
+            {
+                *ex = Expr::Bound(col.clone(), *id);
+            }
+        });
+        Ok(sql)
+    }
+
+    /// The column of a bound column reference.
+    pub fn col_ref(&self, id: ColumnId) -> ColRef<'a> {
+        ColRef::new(self.tables[id.joindex], id.joindex, id.col)
+    }
+
+    /// The column that the expression reads if it is a column reference, bound or not.
+    pub fn column_of(&self, expr: &Expr) -> Result<Option<ColRef<'a>>, EvalError> {
+        match expr {
+            Expr::Bound(_, id) => Ok(Some(self.col_ref(*id))),
+            Expr::Column(col) => self.find_col(col),
+            _ => Ok(None),
+        }
+    }
+
+    /// The tables in scope of a column reference with their indices, which are all the tables unless it is
+    /// qualified by one of them. A qualifier of no table, like the name of a table hidden by its alias, has all
+    /// the tables in scope to show what can be referenced instead.
+    fn tables_in_scope(&self, col: &Column) -> impl Iterator<Item = (usize, &'a Table)> {
+        let qualifier = col.table.as_ref().filter(|qualifier| {
+            self.qualifiers
+                .iter()
+                .any(|q| q.eq_ignore_ascii_case(qualifier))
+        });
+        self.tables
+            .iter()
+            .enumerate()
+            .filter(move |(joindex, _)| {
+                qualifier.is_none_or(|qualifier| {
+                    self.qualifiers[*joindex].eq_ignore_ascii_case(qualifier)
+                })
+            })
+            .map(|(joindex, table)| (joindex, *table))
+    }
+
+    /// The column in scope with the most similar name to a column that is not found, if any is close enough to
+    /// be a typo. The same name is not suggested, since the qualifier is wrong then.
+    fn similar_column(&self, col: &Column) -> Option<String> {
+        let name = col.column.to_ascii_lowercase();
+        let max_distance = (name.len() / 3).max(1);
+        self.tables_in_scope(col)
+            .flat_map(|(_, table)| table.schema.iter().map(|schema| &schema.name))
+            .filter(|candidate| **candidate != col.column)
+            .map(|candidate| {
+                let distance = edit_distance(&name, &candidate.to_ascii_lowercase());
+                (distance, candidate)
+            })
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate.clone())
+    }
+
+    /// The names of the columns in scope of a column that is not found, up to [`MAX_AVAILABLE_COLUMNS`], and
+    /// the number of the rest. The names are qualified by the tables, or their aliases, if there are joins.
+    fn available_columns(&self, col: &Column) -> (Vec<String>, usize) {
+        let qualify = 1 < self.tables.len();
+        let mut names = self.tables_in_scope(col).flat_map(|(joindex, table)| {
+            let qualifier = self.qualifiers[joindex];
+            table.schema.iter().map(move |schema| {
+                if qualify {
+                    format!("{qualifier}.{}", schema.name)
+                } else {
+                    schema.name.clone()
+                }
+            })
+        });
+        let available = names.by_ref().take(MAX_AVAILABLE_COLUMNS).collect();
+        (available, names.count())
+    }
+
+    pub fn record(&self, row_cursor: &[RowCursor], outcome: TraceOutcome) {
+        if let Some(trace) = self.trace {
+            trace.borrow_mut().record(row_cursor, outcome);
+        }
+    }
+
+    /// The parts of the context to make the contexts of the threads of a parallel scan from, or None if the scan
+    /// is traced, which records the combinations in the order of a scan on a thread.
+    #[cfg(feature = "parallel")]
+    pub fn shared(&self) -> Option<SharedContext<'_>> {
+        if self.trace.is_some() {
+            return None;
+        }
+        Some(SharedContext {
+            db: self.db,
+            sql: &self.sql,
+            tables: &self.tables,
+            qualifiers: &self.qualifiers,
+            options: self.options,
+            subqueries: &self.subqueries,
+            subquery_values: &self.subquery_values,
+            max_cell_size: self.max_cell_size,
+            limits: &self.limits,
+        })
+    }
+
+    /// The same context with the values of window functions available.
+    pub fn with_window(&self, window: &'a WindowState) -> Self {
+        Self {
+            window: Some(window),
+            ..self.clone()
+        }
+    }
+
+    /// Run a scalar subquery once and cache the result. No rows yield NULL.
+    pub fn eval_subquery(&self, expr: &Expr, stmt: &SelectStmt) -> Result<String, EvalError> {
+        let key = expr as *const _ as usize;
+        // The cache is locked while the subquery runs, so that the threads of a parallel scan run it once
+        let mut subqueries = lock(&self.subqueries);
+        if let Some(val) = subqueries.get(&key) {
+            return Ok(val.clone());
+        }
+        let mut buf = BufferOutput::default();
+        exec_select_impl(&mut buf, self.db, stmt, &self.subquery_options(), None)
+            .map_err(|e| EvalError::Subquery(e.to_string()))?;
+        let val = match buf.0.as_slice() {
+            [header, ..] if header.len() != 1 => {
+                return Err(EvalError::Subquery(format!(
+                    "subquery returned {} columns",
+                    header.len()
+                )));
+            }
+            [_] => "".to_string(),
+            [_, row] => row[0].clone(),
+            _ => {
+                return Err(EvalError::Subquery(
+                    "subquery returned more than one row".to_string(),
+                ));
+            }
+        };
+        subqueries.insert(key, val.clone());
+        Ok(val)
+    }
+
+    /// Tests `val [NOT] IN (subquery)`, running the subquery on the first call.
+    pub fn eval_in_subquery(
+        &self,
+        expr: &Expr,
+        stmt: &SelectStmt,
+        val: &str,
+        negated: bool,
+    ) -> Result<String, EvalError> {
+        let key = expr as *const _ as usize;
+        let mut subquery_values = lock(&self.subquery_values);
+        if let Some(values) = subquery_values.get(&key) {
+            return Ok(values.test(val, negated).to_string());
+        }
+        let mut buf = BufferOutput::default();
+        exec_select_impl(&mut buf, self.db, stmt, &self.subquery_options(), None)
+            .map_err(|e| EvalError::Subquery(e.to_string()))?;
+        let values = SubqueryValues::new(buf.0)?;
+        let res = values.test(val, negated);
+        subquery_values.insert(key, values);
+        Ok(res.to_string())
+    }
+
+    /// Tests `EXISTS (subquery)`, which does not depend on the row either.
+    pub fn eval_exists(&self, expr: &Expr, stmt: &SelectStmt) -> Result<String, EvalError> {
+        let key = expr as *const _ as usize;
+        let mut subqueries = lock(&self.subqueries);
+        if let Some(val) = subqueries.get(&key) {
+            return Ok(val.clone());
+        }
+        // One row is enough to tell
+        let stmt = SelectStmt {
+            limit: Some(1),
+            ..stmt.clone()
+        };
+        let mut buf = BufferOutput::default();
+        exec_select_impl(&mut buf, self.db, &stmt, &self.subquery_options(), None)
+            .map_err(|e| EvalError::Subquery(e.to_string()))?;
+        let val = (1 < buf.0.len()).to_string();
+        subqueries.insert(key, val.clone());
+        Ok(val)
+    }
+
+    /// The options of running the subqueries, whose rows are values rather than output rows to trace back,
+    /// truncate or count, and whose scans start the count of the progress from 0 again
+    fn subquery_options(&self) -> ExecOptions {
+        self.options
+            .clone()
+            .with_provenance(false)
+            .with_max_cell_size(None)
+            .with_max_rows(None)
+            .with_observer(None)
+    }
+
+    /// The index of the table qualifying a column by its alias or name, which are matched ignoring case like the
+    /// names of the tables in the database, if none matches exactly. A qualifier of more than one table, like
+    /// the name of a table joined to itself without aliases, is an error.
+    fn qualifier_index(&self, qualifier: &str) -> Result<Option<usize>, EvalError> {
+        let by_name = |eq: &dyn Fn(&str, &str) -> bool| {
+            (0..self.qualifiers.len())
+                .filter(|i| eq(self.qualifiers[*i], qualifier))
+                .collect::<Vec<_>>()
+        };
+        let mut found = by_name(&|lhs, rhs| lhs == rhs);
+        if found.is_empty() {
+            found = by_name(&|lhs, rhs| lhs.eq_ignore_ascii_case(rhs));
+        }
+        match found[..] {
+            [] => Ok(None),
+            [joindex] => Ok(Some(joindex)),
+            _ => Err(EvalError::AmbiguousTable(qualifier.to_string())),
+        }
+    }
+
+    /// Resolves a column reference to a column of a table in scope, or `None` if no table has it.
+    /// An unqualified name in more than one table is an error, since either could be meant.
+    pub fn find_col(&self, column: &Column) -> Result<Option<ColRef<'a>>, EvalError> {
+        let case_insensitive = self.options.case_insensitive && !column.quoted;
+        if let Some(ref table_name) = column.table {
+            let Some(joindex) = self.qualifier_index(table_name)? else {
+                return Ok(None);
+            };
+            let table = self.tables[joindex];
+            return Ok(table
+                .find_col(&column.column, case_insensitive)
+                .map(|i| ColRef::new(table, joindex, i)));
+        }
+        let mut candidates = self
+            .tables
+            .iter()
+            .enumerate()
+            .filter_map(|(joindex, table)| {
+                table
+                    .find_col(&column.column, case_insensitive)
+                    .map(|i| ColRef::new(table, joindex, i))
+            })
+            .peekable();
+        let Some(found) = candidates.next() else {
+            return Ok(None);
+        };
+        if candidates.peek().is_some() {
+            return Err(EvalError::AmbiguousColumn {
+                column: column.column.clone(),
+                tables: std::iter::once(found)
+                    .chain(candidates)
+                    .map(|col| self.qualifiers[col.joindex].to_string())
+                    .collect(),
+            });
+        }
+        Ok(Some(found))
+    }
+}
+
+/// The maximum number of the available columns listed in an error for an unknown column, which would be
+/// unreadable with all the columns of a wide table.
+const MAX_AVAILABLE_COLUMNS: usize = 10;
+
+/// Edit distance between two strings in characters, counting a transposition of adjacent characters as one edit
+/// as well as an insertion, a deletion or a substitution, since it is a typical typo like `naem`.
+fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let (lhs, rhs): (Vec<_>, Vec<_>) = (lhs.chars().collect(), rhs.chars().collect());
+    // dist[i][j] is the distance between the first i characters of lhs and the first j characters of rhs
+    let mut dist = vec![vec![0; rhs.len() + 1]; lhs.len() + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=lhs.len() {
+        for j in 1..=rhs.len() {
+            let substitution = dist[i - 1][j - 1] + usize::from(lhs[i - 1] != rhs[j - 1]);
+            let mut best = substitution.min(dist[i - 1][j] + 1).min(dist[i][j - 1] + 1);
+            if 1 < i && 1 < j && lhs[i - 1] == rhs[j - 2] && lhs[i - 2] == rhs[j - 1] {
+                best = best.min(dist[i - 2][j - 2] + 1);
+            }
+            dist[i][j] = best;
+        }
+    }
+    dist[lhs.len()][rhs.len()]
+}
+
+pub(crate) fn extend_colspecs<'a>(
+    ctx: &'a QueryContext<'a>,
+    colspecs: &'a [ColSpecifier],
+) -> Result<(Vec<Expr>, Vec<String>), Box<dyn Error>> {
+    let mut exprs = vec![];
+    let mut header = vec![];
+    for col_spec in colspecs {
+        match col_spec {
+            ColSpecifier::Wildcard(modifiers) => {
+                let case_insensitive = ctx.options.case_insensitive;
+                let has_col = |name: &String| {
+                    ctx.tables
+                        .iter()
+                        .any(|table| table.find_col(name, case_insensitive).is_some())
+                };
+                // Looked up by name for each column, so that a wide table with long modifiers does not take
+                // quadratic time
+                let key = |name: &str| {
+                    if case_insensitive {
+                        name.to_ascii_lowercase()
+                    } else {
+                        name.to_string()
+                    }
+                };
+                if let Some(name) = modifiers.exclude.iter().find(|name| !has_col(name)) {
+                    return Err(format!("Column {name} in EXCLUDE not found").into());
+                }
+                if let Some((_, name)) = modifiers.replace.iter().find(|(_, name)| !has_col(name)) {
+                    return Err(format!("Column {name} in REPLACE not found").into());
+                }
+                let exclude: HashSet<_> = modifiers.exclude.iter().map(|name| key(name)).collect();
+                let mut replace = HashMap::new();
+                for (ex, name) in &modifiers.replace {
+                    // The first one wins, as it did in a linear search
+                    replace.entry(key(name)).or_insert(ex);
+                }
+                exprs.reserve(ctx.tables.iter().map(|table| table.schema.len()).sum());
+                let specs = std::iter::once(&ctx.sql.table)
+                    .chain(ctx.sql.join.iter().map(|join| &join.table));
+                for (joindex, (table, spec)) in ctx.tables.iter().zip(specs).enumerate() {
+                    for (i, col) in table.schema.iter().enumerate() {
+                        let col_key = key(&col.name);
+                        if exclude.contains(&col_key) {
+                            continue;
+                        }
+                        if let Some(ex) = replace.get(&col_key) {
+                            exprs.push((*ex).clone());
+                        } else {
+                            // Bound to the table at the index, which a self-join cannot tell by the name
+                            let column = Column {
+                                table: Some(spec.alias.as_ref().unwrap_or(&spec.name).clone()),
+                                column: col.name.to_string(),
+                                quoted: false,
+                            };
+                            exprs.push(Expr::Bound(column, ColumnId { joindex, col: i }));
+                        }
+                        header.push(col.name.clone());
+                    }
+                }
+            }
+            ColSpecifier::Expr(expr) => {
+                exprs.push(expr.clone());
+                header.push(expr.to_string());
+            }
+        }
+    }
+    // A row of DISTINCT or aggregates can come from many source rows, so it has no provenance
+    if ctx.options.provenance
+        && !ctx.sql.distinct
+        && ctx.sql.group_by.is_none()
+        && !exprs.iter().any(|ex| find_aggregate_fn(ex).is_some())
+    {
+        let specs =
+            std::iter::once(&ctx.sql.table).chain(ctx.sql.join.iter().map(|join| &join.table));
+        for (joindex, spec) in specs.enumerate() {
+            exprs.push(Expr::SourceRow(joindex));
+            header.push(format!(
+                "_row_{}",
+                spec.alias.as_ref().unwrap_or(&spec.name)
+            ));
+        }
+    }
+    Ok((exprs, header))
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RowCursor {
+    pub row: Option<usize>,
+    pub shown: bool,
+}
+
+impl RowCursor {
+    pub fn new() -> Self {
+        Self {
+            row: Some(0),
             shown: false,
         }
     }
@@ -350,36 +1478,128 @@ pub trait QueryOutput {
     fn output(&mut self, row: &[String]) -> Result<(), Box<dyn Error>>;
 }
 
+impl<O: QueryOutput + ?Sized> QueryOutput for &mut O {
+    fn output(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        (**self).output(row)
+    }
+}
+
+/// Prepends a column `#` with the row numbers starting from 1 to the rows passed to another output,
+/// for referring to rows like "row 37" when reading the results.
+#[derive(Default, Debug)]
+pub struct RowNumbers<O> {
+    pub inner: O,
+    /// The number of rows so far, including the header
+    rows: usize,
+}
+
+impl<O> RowNumbers<O> {
+    pub fn new(inner: O) -> Self {
+        Self { inner, rows: 0 }
+    }
+}
+
+impl<O: QueryOutput> QueryOutput for RowNumbers<O> {
+    fn output(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        let num = if self.rows == 0 {
+            "#".to_string()
+        } else {
+            self.rows.to_string()
+        };
+        self.rows += 1;
+        let numbered: Vec<String> = std::iter::once(num).chain(row.iter().cloned()).collect();
+        self.inner.output(&numbered)
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct CsvOutput(pub Vec<u8>);
 
 impl QueryOutput for CsvOutput {
     fn output(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
-        for cell in row {
-            write!(&mut self.0, "{},", cell)?;
+        write_csv_row(&mut self.0, row)?;
+        Ok(())
+    }
+}
+
+/// Writes the rows as a JSON array of objects keyed by the column names in the first (header) row.
+/// Empty cells are written as `null`. Call [`JsonOutput::finish`] to close the array.
+#[derive(Default, Debug)]
+pub struct JsonOutput {
+    buf: Vec<u8>,
+    header: Option<Vec<String>>,
+    rows: usize,
+}
+
+impl JsonOutput {
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.header.is_none() {
+            return b"[]".to_vec();
+        }
+        self.buf.extend_from_slice(b"\n]");
+        self.buf
+    }
+}
+
+impl QueryOutput for JsonOutput {
+    fn output(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        let Some(ref header) = self.header else {
+            self.header = Some(row.to_vec());
+            write!(&mut self.buf, "[")?;
+            return Ok(());
+        };
+        if self.rows != 0 {
+            write!(&mut self.buf, ",")?;
+        }
+        self.rows += 1;
+        write!(&mut self.buf, "\n  {{")?;
+        for (i, (name, cell)) in header.iter().zip(row).enumerate() {
+            if i != 0 {
+                write!(&mut self.buf, ", ")?;
+            }
+            write_json_str(&mut self.buf, name)?;
+            write!(&mut self.buf, ": ")?;
+            value::write_json(&mut self.buf, cell)?;
         }
-        writeln!(&mut self.0, "")?;
+        write!(&mut self.buf, "}}")?;
         Ok(())
     }
 }
 
+pub(crate) fn write_json_str(f: &mut impl Write, s: &str) -> std::io::Result<()> {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Buffers the rows, including the header row, to format them as an ASCII table with [`BufferOutput::format`].
 #[derive(Default, Debug)]
-struct BufferOutput(Vec<Vec<String>>);
+pub struct BufferOutput(pub Vec<Vec<String>>);
 
 impl BufferOutput {
-    fn format(&self, f: &mut impl Write) -> std::io::Result<()> {
+    pub fn format(&self, f: &mut impl Write) -> std::io::Result<()> {
         let num_cols = self.0.first().map_or(0, |header| header.len());
         let col_widths: Vec<_> = (0..num_cols)
-            .map(|col| self.0.iter().map(|row| row[col].len()).max().unwrap_or(0))
+            .map(|col| self.0.iter().map(|row| row[col].width()).max().unwrap_or(0))
             .collect();
         if let Some(header) = self.0.first() {
             for (i, cell) in header.iter().enumerate() {
-                write!(f, "{:width$}", cell, width = col_widths[i])?;
+                write_padded(f, cell, col_widths[i])?;
                 if i != header.len() - 1 {
                     write!(f, " | ")?;
                 }
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
             for (i, col_width) in col_widths.iter().enumerate() {
                 for _ in 0..col_width + 1 {
                     write!(f, "-")?;
@@ -388,19 +1608,68 @@ impl BufferOutput {
                     write!(f, "+-")?;
                 }
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
         for row in &self.0[1..] {
             for (i, cell) in row.iter().enumerate() {
-                write!(f, "{:width$}", cell, width = col_widths[i])?;
+                write_padded(f, cell, col_widths[i])?;
                 if i != row.len() - 1 {
                     write!(f, " | ")?;
                 }
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    /// Formats each row as a record of lines with the column name and the value, which is readable for tables
+    /// too wide for the terminal.
+    pub fn format_vertical(&self, f: &mut impl Write) -> std::io::Result<()> {
+        let Some((header, rows)) = self.0.split_first() else {
+            return Ok(());
+        };
+        let name_width = header.iter().map(|name| name.width()).max().unwrap_or(0);
+        let value_width = rows
+            .iter()
+            .flatten()
+            .map(|val| val.width())
+            .max()
+            .unwrap_or(0);
+        for (i, row) in rows.iter().enumerate() {
+            let title = format!("-[ RECORD {} ]", i + 1);
+            let line_width = (name_width + 3 + value_width).max(title.len());
+            writeln!(f, "{title:-<line_width$}")?;
+            for (name, val) in header.iter().zip(row) {
+                write_padded(f, name, name_width)?;
+                writeln!(f, " | {val}")?;
+            }
         }
         Ok(())
     }
+
+    /// Formats the rows as a table, or vertically if they have more than `max_columns` columns.
+    pub fn format_auto(&self, f: &mut impl Write, max_columns: usize) -> std::io::Result<()> {
+        if self
+            .0
+            .first()
+            .is_some_and(|header| max_columns < header.len())
+        {
+            self.format_vertical(f)
+        } else {
+            self.format(f)
+        }
+    }
+}
+
+/// Writes the cell padded with spaces to the width in the columns of a terminal, where a wide character like
+/// `表` takes 2 columns and a combining character none, unlike the padding of `{:width$}` in characters.
+fn write_padded(f: &mut impl Write, cell: &str, width: usize) -> std::io::Result<()> {
+    write!(
+        f,
+        "{cell}{:pad$}",
+        "",
+        pad = width.saturating_sub(cell.width())
+    )
 }
 
 impl QueryOutput for BufferOutput {
@@ -421,235 +1690,618 @@ pub fn format_select(
     Ok(())
 }
 
+pub fn format_select_traced(
+    out: &mut impl Write,
+    db: &Database,
+    sql: &SelectStmt,
+    trace: &mut ExecTrace,
+) -> Result<(), Box<dyn Error>> {
+    format_select_with(out, db, sql, &ExecOptions::default(), Some(trace))
+}
+
+pub fn format_select_with(
+    out: &mut impl Write,
+    db: &Database,
+    sql: &SelectStmt,
+    options: &ExecOptions,
+    trace: Option<&mut ExecTrace>,
+) -> Result<(), Box<dyn Error>> {
+    let mut buf = BufferOutput::default();
+    exec_select_with(&mut buf, db, sql, options, trace)?;
+    buf.format(out)?;
+    Ok(())
+}
+
 pub fn exec_select(
     out: &mut impl QueryOutput,
     db: &Database,
     sql: &SelectStmt,
 ) -> Result<(), Box<dyn Error>> {
-    let Some(table) = db.get(&sql.table.name) else {
-        return Err(format!("Table {} not found", sql.table.name).into());
+    exec_select_impl(out, db, sql, &ExecOptions::default(), None)
+}
+
+/// Same as [`exec_select`], but records what happened to each combination of rows in `trace`.
+pub fn exec_select_traced(
+    out: &mut impl QueryOutput,
+    db: &Database,
+    sql: &SelectStmt,
+    trace: &mut ExecTrace,
+) -> Result<(), Box<dyn Error>> {
+    exec_select_with(out, db, sql, &ExecOptions::default(), Some(trace))
+}
+
+/// Execute a select statement with the given options, optionally recording the trace and the statistics.
+pub fn exec_select_with(
+    out: &mut impl QueryOutput,
+    db: &Database,
+    sql: &SelectStmt,
+    options: &ExecOptions,
+    trace: Option<&mut ExecTrace>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(trace) = trace else {
+        return exec_select_impl(out, db, sql, options, None);
     };
+    // Instant::now() panics in wasm32-unknown-unknown
+    let start = (!cfg!(target_arch = "wasm32")).then(std::time::Instant::now);
+    let cell = RefCell::new(std::mem::take(trace));
+    let mut counter = CountRows { out, rows: 0 };
+    let res = exec_select_impl(&mut counter, db, sql, options, Some(&cell));
+    *trace = cell.into_inner();
+    let stats = &mut trace.stats;
+    stats.tables = std::iter::once(&sql.table)
+        .chain(sql.join.iter().map(|join| &join.table))
+        .map(|table| table.name.clone())
+        .collect();
+    // The header is not a result row
+    stats.rows_emitted += counter.rows.saturating_sub(1);
+    stats.elapsed = start.map(|start| start.elapsed() + stats.elapsed.unwrap_or_default());
+    res
+}
+
+/// Counts the rows passed to another output
+struct CountRows<O> {
+    out: O,
+    rows: usize,
+}
 
-    let mut aliases = HashMap::new();
-    if let Some(ref alias) = sql.table.alias {
-        aliases.insert(alias, 0);
+impl<O: QueryOutput> QueryOutput for CountRows<O> {
+    fn output(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.rows += 1;
+        self.out.output(row)
     }
+}
 
-    let joined_tables = std::iter::once(Ok((table, &sql.table.alias)))
-        .chain(sql.join.iter().map(|join| {
-            Ok((
-                db.get(&join.table.name)
-                    .ok_or_else(|| format!("Table {} not found", join.table.name))?,
-                &join.table.alias,
-            ))
-        }))
-        .enumerate()
-        .map(|(i, join)| match join {
-            Ok((table, alias)) => {
-                if let Some(alias) = alias {
-                    aliases.insert(alias, i);
-                }
-                Ok(table)
-            }
-            Err(e) => Err(e),
-        })
-        .collect::<Result<Vec<_>, String>>()?;
+/// Counts the rows passed to another output after the header against [`ExecOptions::max_rows`]
+pub(crate) struct LimitRows<'l, O> {
+    pub out: O,
+    pub limits: &'l Limits,
+    pub header: bool,
+}
+
+impl<O: QueryOutput> QueryOutput for LimitRows<'_, O> {
+    fn output(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        if !std::mem::take(&mut self.header) {
+            self.limits.output_row()?;
+        }
+        self.out.output(row)
+    }
+}
+
+/// Truncates the cells larger than the size passed to another output, as [`truncate_cell`].
+pub(crate) struct TruncateCells<O> {
+    pub out: O,
+    pub max_cell_size: Option<usize>,
+}
+
+impl<O: QueryOutput> QueryOutput for TruncateCells<O> {
+    fn output(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        let Some(max) = self
+            .max_cell_size
+            .filter(|max| row.iter().any(|cell| *max < cell.len()))
+        else {
+            return self.out.output(row);
+        };
+        let row: Vec<_> = row
+            .iter()
+            .map(|cell| truncate_cell(Cow::Borrowed(cell), Some(max)))
+            .collect();
+        self.out.output(&row)
+    }
+}
 
-    let ctx = QueryContext {
-        sql,
-        tables: joined_tables,
-        aliases,
+/// The cell cut to at most `max_cell_size` bytes at a character boundary and followed by the number of the bytes
+/// cut, like `abc...[1234 more bytes]`, or the whole cell if it fits. Only the bytes kept are copied from a
+/// borrowed cell.
+pub(crate) fn truncate_cell(cell: Cow<str>, max_cell_size: Option<usize>) -> String {
+    match max_cell_size {
+        Some(max) if max < cell.len() => {
+            let end = cell.floor_char_boundary(max);
+            format!("{}...[{} more bytes]", &cell[..end], cell.len() - end)
+        }
+        _ => cell.into_owned(),
+    }
+}
+
+pub(crate) fn exec_select_impl(
+    out: &mut impl QueryOutput,
+    db: &Database,
+    sql: &SelectStmt,
+    options: &ExecOptions,
+    trace: Option<&RefCell<ExecTrace>>,
+) -> Result<(), Box<dyn Error>> {
+    let scoped;
+    let db = if sql.with.is_empty() {
+        db
+    } else {
+        scoped = materialize_ctes(db, &sql.with, options)?;
+        &scoped
+    };
+    let sorted;
+    let sql = if options.deterministic && sql.ordering.is_empty() {
+        sorted = SelectStmt {
+            ordering: vec![OrderBy {
+                key: OrderKey::All,
+                ordering: Ordering::Asc,
+                nulls: None,
+            }],
+            ..sql.clone()
+        };
+        &sorted
+    } else {
+        sql
+    };
+    let ctx = QueryContext::new(db, sql, options, trace)?;
+    let sql = Arc::clone(&ctx.sql);
+    let limits = Arc::clone(&ctx.limits);
+    let out = &mut LimitRows {
+        out,
+        limits: &limits,
+        header: true,
     };
 
-    if let Some(ref order_by) = sql.ordering {
+    // Sorting and deduplication need all the rows before applying LIMIT and OFFSET
+    if !sql.ordering.is_empty() || sql.distinct {
         let mut buf = BufferOutput(vec![]);
-        let mut subsql = sql.clone();
+        let mut subsql = (*sql).clone();
         let (mut cols, names) = extend_colspecs(&ctx, &sql.cols)?;
+        let group_exprs = group_exprs(&ctx, &cols);
 
         out.output(&names)?;
 
-        let col_idx = cols.len();
-        cols.push(order_by.expr.clone());
-        subsql.ordering = None;
+        // Sort key expressions are evaluated on the output rows, after grouping if any, so that an aggregate
+        // like `count(*)` is the value of each group. A key that is an output column is sorted by that
+        // column, and others are evaluated as hidden columns after the output columns.
+        let num_cols = cols.len();
+        let sort_keys = sql
+            .ordering
+            .iter()
+            .map(|order_by| match order_by.key {
+                OrderKey::Expr(ref expr) => {
+                    // A column however written, like `t.id` for `id`, is the same column
+                    let col_idx = cols[..num_cols]
+                        .iter()
+                        .position(|col| match (col, expr) {
+                            (Expr::Bound(_, lhs), Expr::Bound(_, rhs)) => lhs == rhs,
+                            _ => col == expr,
+                        })
+                        .unwrap_or_else(|| {
+                            cols.push(expr.clone());
+                            cols.len() - 1
+                        });
+                    (Some(col_idx), order_by)
+                }
+                OrderKey::All => (None, order_by),
+            })
+            .collect::<Vec<_>>();
+        subsql.ordering = vec![];
         subsql.limit = None;
         subsql.offset = None;
         let subctx = QueryContext {
-            sql: &subsql,
+            sql: Arc::new(subsql),
+            max_cell_size: None,
             ..ctx.clone()
         };
-        exec_select_sub(&mut buf, &subctx, &cols)?;
+        exec_select_sub(&mut buf, &subctx, &cols, group_exprs.as_deref())?;
 
-        buf.0.sort_by(move |lhs, rhs| {
-            let res = lhs[col_idx].cmp(&rhs[col_idx]);
-            if order_by.ordering == Ordering::Desc {
-                res.reverse()
-            } else {
-                res
+        let types: Vec<_> = cols
+            .iter()
+            .map(|col| static_type(col, &cols, &ctx).unwrap_or(ColumnType::Text))
+            .collect();
+        buf.0.sort_by(|lhs, rhs| {
+            for (col_idx, order_by) in &sort_keys {
+                let res = match col_idx {
+                    Some(col_idx) => {
+                        let key =
+                            |row: &[String]| cols[*col_idx].collation_key(row[*col_idx].clone());
+                        order_by.compare(types[*col_idx], &key(lhs), &key(rhs))
+                    }
+                    None => lhs[..num_cols]
+                        .iter()
+                        .zip(&rhs[..num_cols])
+                        .zip(&types)
+                        .map(|((lhs, rhs), ty)| order_by.compare(*ty, lhs, rhs))
+                        .find(|res| res.is_ne())
+                        .unwrap_or(std::cmp::Ordering::Equal),
+                };
+                if res.is_ne() {
+                    return res;
+                }
             }
+            std::cmp::Ordering::Equal
         });
 
-        if let Some(limit) = sql.limit {
-            let offset = sql.offset.unwrap_or(0);
-            for row in buf.0.iter().skip(offset).take(limit) {
-                out.output(&row[..row.len() - 1])?;
-            }
-        } else {
-            for row in buf.0 {
-                out.output(&row[..row.len() - 1])?;
-            }
+        if sql.distinct {
+            let mut seen = HashSet::new();
+            buf.0.retain(|row| {
+                let key: Vec<_> = cols[..num_cols]
+                    .iter()
+                    .zip(row)
+                    .map(|(col, val)| col.collation_key(val.clone()))
+                    .collect();
+                seen.insert(key)
+            });
+        }
+
+        let offset = sql.offset.unwrap_or(0);
+        let limit = sql.limit.unwrap_or(usize::MAX);
+        let mut out = TruncateCells {
+            out,
+            max_cell_size: ctx.max_cell_size,
+        };
+        for row in buf.0.iter().skip(offset).take(limit) {
+            out.output(&row[..num_cols])?;
         }
 
         return Ok(());
     }
 
     let (cols, names) = extend_colspecs(&ctx, &ctx.sql.cols)?;
+    let group_exprs = group_exprs(&ctx, &cols);
 
     out.output(&names)?;
 
-    exec_select_sub(out, &ctx, &cols)
+    exec_select_sub(out, &ctx, &cols, group_exprs.as_deref())
+}
+
+/// Resolve the expressions to group the rows by, given the output columns.
+pub(crate) fn group_exprs(ctx: &QueryContext, cols: &[Expr]) -> Option<Vec<Expr>> {
+    match ctx.sql.group_by.as_ref()? {
+        GroupBy::Exprs(exprs) => Some(exprs.clone()),
+        GroupBy::All => Some(
+            cols.iter()
+                .filter(|col| find_aggregate_fn(col).is_none())
+                .cloned()
+                .collect(),
+        ),
+    }
 }
 
 fn exec_select_sub(
     out: &mut impl QueryOutput,
     ctx: &QueryContext,
     cols: &[Expr],
+    group_exprs: Option<&[Expr]>,
 ) -> Result<(), Box<dyn Error>> {
-    let join_allow_none = std::iter::once(false)
-        .chain(
-            ctx.sql
-                .join
-                .iter()
-                .map(|join| matches!(join.kind, JoinKind::Left)),
-        )
-        .collect::<Vec<_>>();
+    // The values of the groups and the windows are computed, so they are truncated as they are output
+    let mut truncated = TruncateCells {
+        out: &mut *out,
+        max_cell_size: ctx.max_cell_size,
+    };
+    if group_exprs.is_some() || cols.iter().any(|col| find_aggregate_fn(col).is_some()) {
+        return exec_grouped(&mut truncated, ctx, cols, group_exprs);
+    }
 
-    let row_counts = ctx
-        .tables
-        .iter()
-        .map(|table| table.data.len() / table.schema.len())
-        .collect::<Vec<_>>();
-    let mut row_cursor = vec![RowCursor::new(); ctx.tables.len()];
+    if ctx.sql.qualify.is_some() || has_window_fn(cols, None) {
+        return exec_windowed(&mut truncated, ctx, cols);
+    }
 
-    let has_left_join = join_allow_none.iter().any(|a| *a);
+    #[cfg(feature = "parallel")]
+    if let Some(scan) = parallel::plan_parallel_scan(ctx) {
+        return scan.run(cols, out);
+    }
 
-    // Returns whether to print the row. Most of the combinations in a join is typically filtered out.
-    let check_print = |row_cursor: &[RowCursor]| -> Result<bool, Box<dyn Error>> {
-        let join_cond = if ctx.sql.join.is_empty() {
-            row_cursor.iter().all(|r| r.row.is_some())
+    let offset = ctx.sql.offset.unwrap_or(0);
+    let limit = ctx.sql.limit.unwrap_or(usize::MAX);
+    if limit == 0 {
+        return Ok(());
+    }
+    let mut printed_rows = 0;
+    scan_rows(ctx, cols, |row_cursor| {
+        let values = eval_row(ctx, cols, row_cursor)?;
+        if offset <= printed_rows {
+            ctx.record(row_cursor, TraceOutcome::Emitted);
+            out.output(&values)?;
         } else {
-            ctx.sql.join.iter().all(|join| {
-                let val = eval_expr(
-                    &join.condition,
-                    &cols,
-                    ctx,
-                    &row_cursor,
-                    &AggregateResult::default(),
-                );
-                match val {
-                    Ok(val) => coerce_bool(&val),
-                    Err(EvalError::CursorNone(table_idx)) => {
-                        join_allow_none[table_idx] && !row_cursor[table_idx].shown
-                    }
-                    _ => false,
-                }
-            }) || has_left_join
-                && row_cursor.iter().zip(join_allow_none.iter()).all(|(r, a)| {
-                    if *a {
-                        r.row.is_none() && !r.shown
-                    } else {
-                        r.row.is_some()
-                    }
-                })
+            ctx.record(row_cursor, TraceOutcome::Skipped);
+        }
+        printed_rows += 1;
+        Ok(printed_rows < offset.saturating_add(limit))
+    })
+}
+
+/// The output columns evaluated on a combination of rows, where a column of a table without a row in an outer join
+/// is NULL.
+pub(crate) fn eval_row(
+    ctx: &QueryContext,
+    cols: &[Expr],
+    row_cursor: &[RowCursor],
+) -> Result<Vec<String>, EvalError> {
+    let aggregates = AggregateResult::default();
+    cols.iter()
+        .map(
+            |ex| match eval_cell(ex, cols, ctx, row_cursor, &aggregates) {
+                Ok(res) => Ok(truncate_cell(res, ctx.max_cell_size)),
+                Err(EvalError::CursorNone(_)) => Ok("".to_string()),
+                Err(e) => Err(e),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()
+}
+
+/// Visit every combination of rows in the joined tables that passes the join conditions and the WHERE clause.
+/// The callback returns false to stop scanning.
+pub(crate) fn scan_rows(
+    ctx: &QueryContext,
+    cols: &[Expr],
+    mut f: impl FnMut(&[RowCursor]) -> Result<bool, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut scanner = RowScanner::new(ctx);
+    while let Some(row_cursor) = scanner.next(ctx, cols)? {
+        if !f(&row_cursor)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The state of [`scan_rows`], which yields the combinations of rows one at a time,
+/// so that the results can also be pulled lazily.
+pub(crate) struct RowScanner<'a> {
+    join_allow_none: Vec<bool>,
+    /// Tables whose rows should appear even if they match no rows on the left side
+    right_outer: Vec<bool>,
+    matched: Vec<HashSet<usize>>,
+    row_counts: Vec<usize>,
+    state: ScanState<'a>,
+}
+
+enum ScanState<'a> {
+    NestedLoop(Vec<RowCursor>),
+    /// Pairs of the left and right rows with equal keys, by a merge join or a hash join, and whether the join
+    /// condition has more than the equality to check on them
+    EquiJoin(Box<dyn Iterator<Item = (usize, usize)> + 'a>, bool),
+    /// The right side of RIGHT or FULL JOIN that did not match any row, by the table index and the next row
+    Unmatched(usize, usize),
+    Done,
+}
+
+impl<'a> RowScanner<'a> {
+    pub fn new(ctx: &QueryContext<'a>) -> Self {
+        let join_allow_none = std::iter::once(false)
+            .chain(
+                ctx.sql
+                    .join
+                    .iter()
+                    .map(|join| matches!(join.kind, JoinKind::Left | JoinKind::Full)),
+            )
+            .collect::<Vec<_>>();
+
+        let right_outer = std::iter::once(false)
+            .chain(
+                ctx.sql
+                    .join
+                    .iter()
+                    .map(|join| matches!(join.kind, JoinKind::Right | JoinKind::Full)),
+            )
+            .collect::<Vec<_>>();
+
+        let row_counts = ctx
+            .tables
+            .iter()
+            .map(|table| table.data.len() / table.schema.len())
+            .collect::<Vec<_>>();
+
+        let residual = ctx
+            .sql
+            .join
+            .first()
+            .is_some_and(|join| has_residual_condition(&join.condition));
+        let state = match JoinMethod::choose(ctx.db, &ctx.sql, ctx.options) {
+            JoinMethod::NestedLoop => {
+                ScanState::NestedLoop(vec![RowCursor::new(); ctx.tables.len()])
+            }
+            JoinMethod::Merge(keys) => ScanState::EquiJoin(
+                Box::new(MergeJoin::new(ctx.tables[0], ctx.tables[1], keys)),
+                residual,
+            ),
+            JoinMethod::Hash(keys) => ScanState::EquiJoin(
+                Box::new(HashJoin::new(ctx.tables[0], ctx.tables[1], keys)),
+                residual,
+            ),
         };
-        let res = join_cond
-            && ctx
-                .sql
-                .condition
-                .as_ref()
-                .map_or(Ok::<bool, Box<dyn Error>>(true), |cond| {
-                    Ok(coerce_bool(&eval_expr(
-                        cond,
-                        cols,
-                        ctx,
-                        row_cursor,
-                        &AggregateResult::default(),
-                    )?))
-                })?;
-        Ok(res)
-    };
 
-    if let Some(_addr) = cols.iter().find_map(|col| find_aggregate_fn(col, ctx)) {
-        let mut results = AggregateResult::default();
+        Self {
+            join_allow_none,
+            right_outer,
+            matched: vec![HashSet::new(); ctx.tables.len()],
+            row_counts,
+            state,
+        }
+    }
+
+    /// The same scanner visiting only the rows of the first table in the range, which is not empty, for a thread
+    /// of a parallel scan of a nested loop join
+    #[cfg(feature = "parallel")]
+    pub fn with_outer_rows(mut self, rows: std::ops::Range<usize>) -> Self {
+        if let ScanState::NestedLoop(ref mut row_cursor) = self.state {
+            row_cursor[0].row = Some(rows.start);
+        }
+        self.row_counts[0] = rows.end;
+        self
+    }
+
+    /// Returns the next combination of rows that passes the join conditions and WHERE, or None at the end.
+    pub fn next(
+        &mut self,
+        ctx: &QueryContext,
+        cols: &[Expr],
+    ) -> Result<Option<Vec<RowCursor>>, Box<dyn Error>> {
         loop {
-            for col in cols {
-                if check_print(&row_cursor)? {
-                    let _ = aggregate_expr(col, cols, ctx, &row_cursor, &mut results)
-                        .inspect_err(|e| println!("Error from aggregate_expr: {e}"))?;
+            match self.state {
+                // Most of the combinations in a join is typically filtered out.
+                ScanState::NestedLoop(ref mut row_cursor) => {
+                    ctx.limits.evaluate_combination()?;
+                    let mut res = None;
+                    if check_join(ctx, cols, &self.join_allow_none, row_cursor)
+                        && check_where(ctx, cols, row_cursor)?
+                    {
+                        for (i, rc) in row_cursor.iter_mut().enumerate() {
+                            rc.shown = true;
+                            if self.right_outer[i]
+                                && let Some(row) = rc.row
+                            {
+                                self.matched[i].insert(row);
+                            }
+                        }
+                        res = Some(row_cursor.clone());
+                    }
+                    if !incr_row_cursor(row_cursor, &self.row_counts) {
+                        self.state = ScanState::Unmatched(0, 0);
+                    }
+                    if res.is_some() {
+                        return Ok(res);
+                    }
                 }
-            }
-            if !incr_row_cursor(&mut row_cursor, &row_counts)
-                || row_cursor.iter().any(|c| c.row.is_none())
-            {
-                break;
+                ScanState::EquiJoin(ref mut pairs, residual) => {
+                    let Some((left, right)) = pairs.next() else {
+                        self.state = ScanState::Done;
+                        continue;
+                    };
+                    ctx.limits.evaluate_combination()?;
+                    let row_cursor = vec![
+                        RowCursor {
+                            row: Some(left),
+                            shown: true,
+                        },
+                        RowCursor {
+                            row: Some(right),
+                            shown: true,
+                        },
+                    ];
+                    if residual && !check_join(ctx, cols, &self.join_allow_none, &row_cursor) {
+                        continue;
+                    }
+                    if check_where(ctx, cols, &row_cursor)? {
+                        return Ok(Some(row_cursor));
+                    }
+                }
+                ScanState::Unmatched(i, row) => {
+                    if self.right_outer.len() <= i {
+                        self.state = ScanState::Done;
+                        continue;
+                    }
+                    if !self.right_outer[i] || self.row_counts[i] <= row {
+                        self.state = ScanState::Unmatched(i + 1, 0);
+                        continue;
+                    }
+                    self.state = ScanState::Unmatched(i, row + 1);
+                    if self.matched[i].contains(&row) {
+                        continue;
+                    }
+                    ctx.limits.evaluate_combination()?;
+                    // NULLs in other tables
+                    let row_cursor = (0..ctx.tables.len())
+                        .map(|j| RowCursor {
+                            row: (i == j).then_some(row),
+                            shown: true,
+                        })
+                        .collect::<Vec<_>>();
+                    if check_where(ctx, cols, &row_cursor)? {
+                        return Ok(Some(row_cursor));
+                    }
+                }
+                ScanState::Done => return Ok(None),
             }
         }
-        let values = cols
-            .iter()
-            .map(|ex| match eval_expr(ex, cols, ctx, &row_cursor, &results) {
-                Ok(res) => Ok(res),
-                Err(EvalError::CursorNone(_)) => Ok("".to_string()),
-                Err(e) => Err(e),
-            })
-            .collect::<Result<Vec<_>, _>>()
-            .inspect_err(|e| println!("Cell eval error: {e}"))?;
-        out.output(&values)?;
-        return Ok(());
     }
+}
 
-    let offset = ctx.sql.offset.unwrap_or(0);
-    let mut printed_rows = 0;
-    loop {
-        if let Some(limit) = ctx.sql.limit
-            && offset + limit <= printed_rows
-        {
-            break;
-        }
-        if check_print(&row_cursor)? {
-            for rc in row_cursor.iter_mut() {
-                rc.shown = true;
+fn check_join(
+    ctx: &QueryContext,
+    cols: &[Expr],
+    join_allow_none: &[bool],
+    row_cursor: &[RowCursor],
+) -> bool {
+    let (join_cond, rejection) = if ctx.sql.join.is_empty() {
+        (
+            row_cursor.iter().all(|r| r.row.is_some()),
+            TraceOutcome::NoRow,
+        )
+    } else {
+        let failed_join = ctx.sql.join.iter().position(|join| {
+            let val = eval_expr(
+                &join.condition,
+                cols,
+                ctx,
+                row_cursor,
+                &AggregateResult::default(),
+            );
+            !match val {
+                Ok(val) => coerce_bool(&val),
+                Err(EvalError::CursorNone(table_idx)) => {
+                    join_allow_none[table_idx] && !row_cursor[table_idx].shown
+                }
+                _ => false,
             }
-            let aggregates = AggregateResult::default();
-            let values = cols
-                .iter()
-                .map(
-                    |ex| match eval_expr(ex, cols, ctx, &row_cursor, &aggregates) {
-                        Ok(res) => Ok(res),
-                        Err(EvalError::CursorNone(_)) => Ok("".to_string()),
-                        Err(e) => Err(e),
-                    },
-                )
-                .collect::<Result<Vec<_>, _>>()
-                .inspect_err(|e| println!("Cell eval error: {e}"))?;
-            if offset <= printed_rows {
-                out.output(&values)?;
-            }
-            printed_rows += 1;
-        }
-
-        if !incr_row_cursor(&mut row_cursor, &row_counts) {
-            break;
-        }
+        });
+        let has_left_join = join_allow_none.iter().any(|a| *a);
+        (
+            failed_join.is_none()
+                || has_left_join
+                    && row_cursor.iter().zip(join_allow_none.iter()).all(|(r, a)| {
+                        if *a {
+                            r.row.is_none() && !r.shown
+                        } else {
+                            r.row.is_some()
+                        }
+                    }),
+            TraceOutcome::RejectedByJoin(failed_join.unwrap_or(0)),
+        )
+    };
+    if !join_cond {
+        ctx.record(row_cursor, rejection);
     }
+    join_cond
+}
 
-    Ok(())
+fn check_where(
+    ctx: &QueryContext,
+    cols: &[Expr],
+    row_cursor: &[RowCursor],
+) -> Result<bool, Box<dyn Error>> {
+    let Some(ref cond) = ctx.sql.condition else {
+        return Ok(true);
+    };
+    let res = match eval_expr(cond, cols, ctx, row_cursor, &AggregateResult::default()) {
+        Ok(val) => coerce_bool(&val),
+        // A comparison with NULL is not true
+        Err(EvalError::CursorNone(_)) => false,
+        Err(e) => return Err(e.into()),
+    };
+    if !res {
+        ctx.record(row_cursor, TraceOutcome::RejectedByWhere);
+    }
+    Ok(res)
 }
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
-
     use super::*;
-    use crate::{Statement, make_table, parser::statement};
+    use crate::{
+        AggregateNulls, Statement, assert_query_results, make_table,
+        parser::statement,
+        test_util::{db_from_csv, db_from_tables, table_from_rows},
+    };
     use nom::Finish;
 
     #[test]
@@ -659,15 +2311,16 @@ mod test {
 2, b
 3, c
 "#;
-        let mut db = HashMap::new();
+        let mut db = Database::new();
         let table_name = "t".to_string();
         let table = make_table(&table_name, csv).unwrap();
         db.insert(table_name.clone(), table);
         let sql = "SELECT * FROM t";
-        let (_, stmt) = statement(&sql).finish().unwrap();
+        let (_, stmt) = statement(sql).finish().unwrap();
         let mut buf = BufferOutput(vec![]);
         match stmt {
             Statement::Select(stmt) => exec_select(&mut buf, &db, &stmt).unwrap(),
+            _ => panic!("Not a SELECT statement"),
         }
         assert_eq!(
             buf.0,
@@ -687,15 +2340,1031 @@ mod test {
 2, b
 3, c
 "#;
-        let mut db = HashMap::new();
+        let mut db = Database::new();
         let table_name = "t".to_string();
         let table = make_table(&table_name, csv).unwrap();
         db.insert(table_name.clone(), table);
         let sql = "SELECT * FROM t WHERE id = '1'";
-        let (_, stmt) = statement(&sql).finish().unwrap();
+        let (_, stmt) = statement(sql).finish().unwrap();
+        let mut buf = BufferOutput(vec![]);
+        match stmt {
+            Statement::Select(stmt) => exec_select(&mut buf, &db, &stmt).unwrap(),
+            _ => panic!("Not a SELECT statement"),
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let csv = r#"id,name
+1, a
+2, b
+3, c
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let sql = "SELECT * FROM t WHERE id <> '2'";
+        let (_, stmt) = statement(sql).finish().unwrap();
+        let mut buf = BufferOutput(vec![]);
+        let mut trace = ExecTrace::new(3);
+        match stmt {
+            Statement::Select(stmt) => {
+                exec_select_traced(&mut buf, &db, &stmt, &mut trace).unwrap()
+            }
+            _ => panic!("Not a SELECT statement"),
+        }
+        assert_eq!(
+            trace
+                .events
+                .iter()
+                .map(|ev| (ev.cursor.clone(), ev.outcome))
+                .collect::<Vec<_>>(),
+            vec![
+                (vec![Some(0)], TraceOutcome::Emitted),
+                (vec![Some(1)], TraceOutcome::RejectedByWhere),
+                (vec![Some(2)], TraceOutcome::Emitted),
+            ]
+        );
+        assert!(trace.truncated);
+    }
+
+    #[test]
+    fn test_stats() {
+        let db = db_from_csv(&[("a", "id\n1\n2\n3\n"), ("b", "a_id\n1\n3\n")]);
+        // Not an equality, so that the nested loop evaluates all the combinations
+        let sql = "SELECT id FROM a INNER JOIN b ON id <= a_id WHERE id <> '3'";
+        let (_, Statement::Select(stmt)) = statement(sql).finish().unwrap() else {
+            panic!("Not a SELECT statement");
+        };
+        let mut buf = BufferOutput(vec![]);
+        let mut trace = ExecTrace::new(0);
+        exec_select_traced(&mut buf, &db, &stmt, &mut trace).unwrap();
+        let stats = &trace.stats;
+        assert_eq!(stats.tables, ["a", "b"]);
+        // The nested loop also visits the combinations with NULLs in place of the rows, as in outer joins
+        assert_eq!(stats.rows_scanned, [3, 8]);
+        assert_eq!(stats.combinations, 12);
+        assert_eq!(stats.rows_emitted, 3);
+        assert!(stats.elapsed.is_some());
+        assert!(trace.events.is_empty());
+    }
+
+    #[test]
+    fn test_aggregate_nulls() {
+        let csv = r#"id,price
+1, 10
+2, ""
+3, 20
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let run = |sql: &str, aggregate_nulls| {
+            let (_, stmt) = statement(sql).finish().unwrap();
+            let mut buf = BufferOutput(vec![]);
+            let options = ExecOptions {
+                aggregate_nulls,
+                ..ExecOptions::default()
+            };
+            match stmt {
+                Statement::Select(stmt) => {
+                    exec_select_with(&mut buf, &db, &stmt, &options, None).unwrap()
+                }
+                _ => panic!("Not a SELECT statement"),
+            }
+            buf.0.pop().unwrap()
+        };
+
+        let sql = "SELECT count(*), count(price), sum(price), avg(price), min(price) FROM t";
+        assert_eq!(
+            run(sql, AggregateNulls::Standard),
+            vec!["3", "2", "30", "15", "10"]
+        );
+        assert_eq!(
+            run(sql, AggregateNulls::Spreadsheet),
+            vec!["3", "3", "30", "10", "0"]
+        );
+
+        let sql = "SELECT count(*), sum(price), avg(price), max(price) FROM t WHERE id = '4'";
+        assert_eq!(run(sql, AggregateNulls::Standard), vec!["0", "", "", ""]);
+        assert_eq!(
+            run(sql, AggregateNulls::Spreadsheet),
+            vec!["0", "0", "0", "0"]
+        );
+    }
+
+    #[test]
+    fn test_group_by_all() {
+        let csv = r#"id,name,price
+1, a, 10
+2, b, 20
+3, a, 30
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let sql = "SELECT name, sum(price) FROM t GROUP BY ALL ORDER BY ALL DESC";
+        let (_, stmt) = statement(sql).finish().unwrap();
+        let mut buf = BufferOutput(vec![]);
+        match stmt {
+            Statement::Select(stmt) => exec_select(&mut buf, &db, &stmt).unwrap(),
+            _ => panic!("Not a SELECT statement"),
+        }
+        assert_eq!(
+            buf.0,
+            vec![vec!["name", "sum(price)"], vec!["b", "20"], vec!["a", "40"],]
+        )
+    }
+
+    #[test]
+    fn test_distinct() {
+        let csv = r#"id,name
+1, b
+2, a
+3, b
+4, c
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let sql = "SELECT DISTINCT name FROM t ORDER BY name LIMIT 2";
+        let (_, stmt) = statement(sql).finish().unwrap();
+        let mut buf = BufferOutput(vec![]);
+        match stmt {
+            Statement::Select(stmt) => exec_select(&mut buf, &db, &stmt).unwrap(),
+            _ => panic!("Not a SELECT statement"),
+        }
+        assert_eq!(buf.0, vec![vec!["name"], vec!["a"], vec!["b"]])
+    }
+
+    #[test]
+    fn test_unknown_column() {
+        let wide = (0..12)
+            .map(|col| format!("c{col}"))
+            .collect::<Vec<_>>()
+            .join(",")
+            + "\n";
+        let db = db_from_csv(&[
+            ("a", "id,name\n1,x\n"),
+            ("b", "id,a_id,title\n1,1,y\n"),
+            ("w", &wide),
+        ]);
+        let err = |sql: &str| {
+            let (_, stmt) = statement(sql).finish().unwrap();
+            let Statement::Select(stmt) = stmt else {
+                panic!("Not a SELECT statement");
+            };
+            let mut buf = BufferOutput(vec![]);
+            let res = exec_select(&mut buf, &db, &stmt);
+            assert!(buf.0.is_empty(), "no rows before the error");
+            res.unwrap_err().to_string()
+        };
+        assert_eq!(
+            err("SELECT naem FROM a"),
+            "Unknown column 'naem', did you mean 'name'? Available columns: id, name"
+        );
+        assert_eq!(
+            err("SELECT name FROM a WHERE id IN (SELECT titel FROM b)"),
+            "Unknown column 'titel', did you mean 'title'? Available columns: id, a_id, title"
+        );
+        assert_eq!(
+            err("SELECT t.name, t.titel FROM b AS t"),
+            "Unknown column 't.name'. Available columns: id, a_id, title"
+        );
+        assert_eq!(
+            err("SELECT xyz FROM a"),
+            "Unknown column 'xyz'. Available columns: id, name"
+        );
+        assert_eq!(
+            err("SELECT x.title FROM a AS x INNER JOIN b ON x.id = a_id"),
+            "Unknown column 'x.title'. Available columns: x.id, x.name"
+        );
+        assert_eq!(
+            err("SELECT xyz FROM w"),
+            "Unknown column 'xyz'. Available columns: c0, c1, c2, c3, c4, c5, c6, c7, c8, c9 and 2 more"
+        );
+        assert_eq!(
+            err("SELECT id FROM a INNER JOIN b ON a.id = a_id"),
+            "Column 'id' is ambiguous, qualify it with one of the tables: a, b"
+        );
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_collate_nocase() {
+        let csv = r#"id,name
+1, Alice
+2, bob
+3, alice
+4, Bob
+"#;
+        let mut db = Database::new();
+        db.insert("t".to_string(), make_table("t", csv).unwrap());
+        let run = |sql: &str| {
+            let (_, stmt) = statement(sql).finish().unwrap();
+            let Statement::Select(stmt) = stmt else {
+                panic!("Not a SELECT statement");
+            };
+            let mut buf = BufferOutput(vec![]);
+            exec_select(&mut buf, &db, &stmt).unwrap();
+            buf.0
+        };
+        assert_eq!(
+            run("SELECT name, count(*) FROM t GROUP BY name COLLATE NOCASE"),
+            vec![
+                vec!["name", "count(*)"],
+                vec!["Alice", "2"],
+                vec!["bob", "2"]
+            ]
+        );
+        assert_eq!(run("SELECT name, count(*) FROM t GROUP BY name").len(), 5);
+        assert_eq!(
+            run("SELECT DISTINCT name COLLATE NOCASE FROM t ORDER BY name COLLATE NOCASE"),
+            vec![vec!["name COLLATE NOCASE"], vec!["Alice"], vec!["bob"]]
+        );
+        assert_eq!(
+            run("SELECT id FROM t WHERE name = 'BOB' COLLATE NOCASE"),
+            vec![vec!["id"], vec!["2"], vec!["4"]]
+        );
+    }
+
+    #[test]
+    fn test_wildcard_modifiers() {
+        let csv = r#"id,name,phone
+1, a, 123
+2, b, 456
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let sql = "SELECT * EXCLUDE (id) REPLACE (upper(name) AS name) FROM t";
+        let (_, stmt) = statement(sql).finish().unwrap();
         let mut buf = BufferOutput(vec![]);
         match stmt {
             Statement::Select(stmt) => exec_select(&mut buf, &db, &stmt).unwrap(),
+            _ => panic!("Not a SELECT statement"),
         }
+        assert_eq!(
+            buf.0,
+            vec![vec!["name", "phone"], vec!["A", "123"], vec!["B", "456"]]
+        )
+    }
+
+    #[test]
+    fn test_multi_order_by() {
+        let csv = r#"id,name
+1, b
+2, a
+3, b
+4, a
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let sql = "SELECT id FROM t ORDER BY name DESC, id ASC";
+        let (_, stmt) = statement(sql).finish().unwrap();
+        let mut buf = BufferOutput(vec![]);
+        match stmt {
+            Statement::Select(stmt) => exec_select(&mut buf, &db, &stmt).unwrap(),
+            _ => panic!("Not a SELECT statement"),
+        }
+        assert_eq!(
+            buf.0,
+            vec![vec!["id"], vec!["1"], vec!["3"], vec!["2"], vec!["4"]]
+        )
+    }
+
+    #[test]
+    fn test_order_by_aggregate() {
+        let db = db_from_tables([table_from_rows(
+            "t",
+            &["k", "v"],
+            &[
+                &["a", "9"],
+                &["b", "2"],
+                &["b", "3"],
+                &["c", "10"],
+                &["c", "20"],
+                &["c", "1"],
+                &["d", "4"],
+            ],
+        )]);
+        assert_query_results!(
+            db,
+            "SELECT k, count(*) FROM t GROUP BY k ORDER BY count(*) DESC, k",
+            [
+                ["k", "count(*)"],
+                ["c", "3"],
+                ["b", "2"],
+                ["a", "1"],
+                ["d", "1"]
+            ]
+        );
+        // Sorted as numbers, where "9" would be after "31" as text
+        assert_query_results!(
+            db,
+            "SELECT k FROM t GROUP BY k ORDER BY sum(v) DESC",
+            [["k"], ["c"], ["a"], ["b"], ["d"]]
+        );
+        assert_query_results!(
+            db,
+            "SELECT k, max(v) FROM t GROUP BY k ORDER BY max(v) - min(v), k LIMIT 2",
+            [["k", "max(v)"], ["a", "9"], ["d", "4"]]
+        );
+        assert_query_results!(
+            db,
+            "SELECT DISTINCT count(*) FROM t GROUP BY k ORDER BY count(*)",
+            [["count(*)"], ["1"], ["2"], ["3"]]
+        );
+    }
+
+    #[test]
+    fn test_numeric_column_order() {
+        let csv = "n,name,at\n10, x, 2024-01-10T00:00+09:00\n9, y, 2024-01-09 20:00\n, z, \n";
+        let db = db_from_csv(&[("t", csv)]);
+        assert_query_results!(
+            db,
+            "SELECT n FROM t ORDER BY n DESC",
+            [["n"], ["10"], ["9"], [""]]
+        );
+        assert_query_results!(
+            db,
+            "SELECT n * 2, name FROM t ORDER BY ALL",
+            [["(n * 2)", "name"], ["0", "z"], ["18", "y"], ["20", "x"]]
+        );
+        assert_query_results!(
+            db,
+            "SELECT name FROM t ORDER BY at",
+            [["name"], ["z"], ["x"], ["y"]]
+        );
+        assert_query_results!(db, "SELECT name FROM t WHERE n > 9", [["name"], ["x"]]);
+        assert_query_results!(
+            db,
+            "SELECT name FROM t WHERE n >= '9'",
+            [["name"], ["x"], ["y"]]
+        );
+        assert_query_results!(
+            db,
+            "SELECT name FROM t WHERE n - 1 < 10",
+            [["name"], ["x"], ["y"], ["z"]]
+        );
+    }
+
+    #[test]
+    fn test_min_max_types() {
+        let csv = "n,name,at\n10, x, 2024-01-10T00:00+09:00\n9, y, 2024-01-09 20:00\n, z, \n";
+        let db = db_from_csv(&[("t", csv)]);
+        // Text and timestamps are compared in their types and output as they are written
+        assert_query_results!(
+            db,
+            "SELECT min(n), max(n), min(name), max(name) FROM t",
+            [
+                ["min(n)", "max(n)", "min(name)", "max(name)"],
+                ["9", "10", "x", "z"]
+            ]
+        );
+        assert_query_results!(
+            db,
+            "SELECT min(at), max(at) FROM t",
+            [
+                ["min(at)", "max(at)"],
+                ["2024-01-10T00:00+09:00", "2024-01-09 20:00"]
+            ]
+        );
+        assert_query_results!(
+            db,
+            "SELECT min(name), max(at), max(n) FROM t WHERE n > 10",
+            [["min(name)", "max(at)", "max(n)"], ["", "", ""]]
+        );
+        // The extremes keep the types of the arguments, so they are ordered in them
+        assert_query_results!(
+            db,
+            "SELECT name FROM t GROUP BY name ORDER BY max(at), count(*) DESC",
+            [["name"], ["z"], ["x"], ["y"]]
+        );
+    }
+
+    #[test]
+    fn test_outer_joins() {
+        let db = db_from_csv(&[
+            ("a", "id,name\n1, x\n2, y\n"),
+            ("b", "a_id,value\n2, p\n3, q\n"),
+        ]);
+        let run = |sql| {
+            let (_, stmt) = statement(sql).finish().unwrap();
+            let mut buf = BufferOutput(vec![]);
+            match stmt {
+                Statement::Select(stmt) => exec_select(&mut buf, &db, &stmt).unwrap(),
+                _ => panic!("Not a SELECT statement"),
+            }
+            buf.0
+        };
+        assert_eq!(
+            run("SELECT name, value FROM a RIGHT JOIN b ON id = a_id"),
+            vec![vec!["name", "value"], vec!["y", "p"], vec!["", "q"]]
+        );
+        assert_eq!(
+            run("SELECT name, value FROM a FULL OUTER JOIN b ON id = a_id"),
+            vec![
+                vec!["name", "value"],
+                vec!["x", ""],
+                vec!["y", "p"],
+                vec!["", "q"]
+            ]
+        );
+        assert_eq!(
+            run("SELECT count(*) FROM a FULL JOIN b ON id = a_id WHERE value <> 'p'"),
+            vec![vec!["count(*)"], vec!["1"]]
+        );
+    }
+
+    #[test]
+    fn test_self_join() {
+        let db = db_from_tables([table_from_rows(
+            "people",
+            &["id", "name", "manager"],
+            &[
+                &["1", "Ann", ""],
+                &["2", "Bob", "1"],
+                &["3", "Cid", "1"],
+                &["4", "Dee", "2"],
+            ],
+        )]);
+        assert_query_results!(
+            db,
+            "SELECT a.name, b.name FROM people AS a INNER JOIN people AS b ON a.manager = b.id \
+                WHERE b.name <> 'Bob'",
+            [["name", "name"], ["Bob", "Ann"], ["Cid", "Ann"]]
+        );
+        assert_query_results!(
+            db,
+            "SELECT a.name, b.name FROM people AS a LEFT JOIN people AS b ON a.manager = b.id \
+                ORDER BY b.id, a.id",
+            [
+                ["name", "name"],
+                ["Ann", ""],
+                ["Bob", "Ann"],
+                ["Cid", "Ann"],
+                ["Dee", "Bob"],
+            ]
+        );
+        // The table without an alias is the one qualified by the name
+        assert_query_results!(
+            db,
+            "SELECT people.name FROM people AS m INNER JOIN people ON people.manager = m.id \
+                WHERE m.name = 'Bob'",
+            [["name"], ["Dee"]]
+        );
+
+        let session = crate::Session::new(&db);
+        let err = |sql| session.execute(sql).unwrap_err().to_string();
+        assert_eq!(
+            err("SELECT people.name FROM people AS a INNER JOIN people AS b ON a.manager = b.id"),
+            "Unknown column 'people.name'. Available columns: a.id, a.name, a.manager, b.id, \
+                b.name, b.manager"
+        );
+        assert_eq!(
+            err("SELECT name FROM people AS a INNER JOIN people AS b ON a.manager = b.id"),
+            "Column 'name' is ambiguous, qualify it with one of the tables: a, b"
+        );
+        assert_eq!(
+            err("SELECT people.name FROM people INNER JOIN people ON people.manager = people.id"),
+            "Table 'people' is joined more than once, give each an alias with AS"
+        );
+    }
+
+    #[test]
+    fn test_float_precision() {
+        let csv = r#"id,price
+1, 0.1
+2, 0.2
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let sql = "SELECT sum(price), avg(id), max(id) FROM t";
+        let (_, stmt) = statement(sql).finish().unwrap();
+        let Statement::Select(stmt) = stmt else {
+            panic!("Not a SELECT statement");
+        };
+        let run = |options: &ExecOptions| {
+            let mut buf = BufferOutput(vec![]);
+            exec_select_with(&mut buf, &db, &stmt, options, None).unwrap();
+            buf.0.pop().unwrap()
+        };
+        assert_eq!(run(&ExecOptions::default()), vec!["0.3", "1.5", "2"]);
+        assert_eq!(
+            run(&ExecOptions {
+                float_precision: None,
+                ..ExecOptions::default()
+            }),
+            vec!["0.30000000000000004", "1.5", "2"]
+        );
+        assert_eq!(
+            run(&ExecOptions {
+                float_precision: Some(0),
+                ..ExecOptions::default()
+            }),
+            vec!["0", "2", "2"]
+        );
+    }
+
+    #[test]
+    fn test_decimal() {
+        let csv = r#"item,price
+a, 0.10
+b, 0.20
+c, 19.90
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let sql = "SELECT sum(price), avg(price), max(price), sum(price) - max(price) FROM t";
+        let (_, stmt) = statement(sql).finish().unwrap();
+        let Statement::Select(stmt) = stmt else {
+            panic!("Not a SELECT statement");
+        };
+        let options = ExecOptions {
+            float_precision: None,
+            decimal: true,
+            ..ExecOptions::default()
+        };
+        let mut buf = BufferOutput(vec![]);
+        exec_select_with(&mut buf, &db, &stmt, &options, None).unwrap();
+        assert_eq!(buf.0[1], vec!["20.20", "6.733333", "19.90", "0.30"]);
+    }
+
+    #[test]
+    fn test_subquery() {
+        let db = db_from_csv(&[("a", "id,name\n1, x\n2, y\n3, z\n"), ("b", "a_id\n1\n2\n")]);
+        let run = |sql| {
+            let (_, stmt) = statement(sql).finish().unwrap();
+            let mut buf = BufferOutput(vec![]);
+            match stmt {
+                Statement::Select(stmt) => exec_select(&mut buf, &db, &stmt).map(|_| buf.0),
+                _ => panic!("Not a SELECT statement"),
+            }
+        };
+        assert_eq!(
+            run("SELECT name FROM a WHERE id = (SELECT max(a_id) FROM b)").unwrap(),
+            vec![vec!["name"], vec!["y"]]
+        );
+        assert_eq!(
+            run("SELECT count(*) FROM a WHERE id = (SELECT a_id FROM b WHERE a_id = '3')").unwrap(),
+            vec![vec!["count(*)"], vec!["0"]]
+        );
+        assert!(run("SELECT name FROM a WHERE id = (SELECT a_id FROM b)").is_err());
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let csv = r#"Id,Name
+1, a
+2, b
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let sql = "SELECT name FROM t WHERE ID = '2'";
+        let (_, stmt) = statement(sql).finish().unwrap();
+        let Statement::Select(stmt) = stmt else {
+            panic!("Not a SELECT statement");
+        };
+        let mut buf = BufferOutput(vec![]);
+        exec_select(&mut buf, &db, &stmt).unwrap();
+        assert_eq!(buf.0, vec![vec!["name"], vec!["b"]]);
+        let options = ExecOptions {
+            case_insensitive: false,
+            ..ExecOptions::default()
+        };
+        let mut buf = BufferOutput(vec![]);
+        assert!(exec_select_with(&mut buf, &db, &stmt, &options, None).is_err());
+
+        // Table names and qualifiers are case-insensitive regardless of the option
+        let mut buf = BufferOutput(vec![]);
+        let sql = "SELECT T.Name FROM T WHERE t.Id = 1";
+        let Statement::Select(stmt) = statement(sql).finish().unwrap().1 else {
+            panic!("Not a SELECT statement");
+        };
+        exec_select_with(&mut buf, &db, &stmt, &options, None).unwrap();
+        assert_eq!(buf.0, vec![vec!["Name"], vec!["a"]]);
+
+        // A quoted name matches exactly, and an exact match takes precedence
+        assert_query_results!(
+            db,
+            r#"SELECT "Name" FROM t WHERE t."Id" = 2"#,
+            [["Name"], ["b"]]
+        );
+        let err = crate::Session::new(&db)
+            .execute(r#"SELECT "name" FROM t"#)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with(r#"Unknown column '"name"', did you mean 'Name'?"#),
+            "{err}"
+        );
+        let db = crate::test_util::db_from_csv(&[("t", "Id,id\n1,2\n")]);
+        assert_query_results!(db, "SELECT id, ID FROM t", [["id", "ID"], ["2", "1"]]);
+    }
+
+    #[test]
+    fn test_names_with_spaces() {
+        let db = crate::test_util::db_from_csv(&[(
+            "people list",
+            "First Name,Last Name\nAda,Lovelace\nAlan,Turing\n",
+        )]);
+        assert_query_results!(
+            db,
+            "SELECT `Last Name` FROM \"people list\" WHERE \"First Name\" = 'Alan'",
+            [["Last Name"], ["Turing"]]
+        );
+        let mut session = crate::Session::new(&db);
+        session.columns = vec!["Last Name".to_string()];
+        assert_eq!(
+            session.execute("SELECT * FROM `people list`").unwrap(),
+            crate::StatementOutput::Rows(vec![
+                vec!["Last Name".to_string()],
+                vec!["Lovelace".to_string()],
+                vec!["Turing".to_string()],
+            ])
+        );
+    }
+
+    #[test]
+    fn test_referenced() {
+        let sql = "SELECT title, upper(name) FROM authors INNER JOIN books ON author_id = books.author \
+            WHERE author_id = (SELECT max(author) FROM books) ORDER BY title";
+        let (_, Statement::Select(stmt)) = statement(sql).finish().unwrap() else {
+            panic!("Not a SELECT statement");
+        };
+        assert_eq!(stmt.referenced_tables(), vec!["authors", "books"]);
+        assert_eq!(
+            stmt.referenced_columns(),
+            vec![
+                &Column::new("title"),
+                &Column::new("name"),
+                &Column::new("author_id"),
+                &Column {
+                    table: Some("books".to_string()),
+                    column: "author".to_string(),
+                    quoted: false,
+                },
+                &Column::new("author"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let csv = r#"price,qty
+1.5, 2
+3, 4
+"#;
+        let mut db = Database::new();
+        let table_name = "t".to_string();
+        let table = make_table(&table_name, csv).unwrap();
+        db.insert(table_name.clone(), table);
+        let sql = "SELECT price * qty, -qty + 10 / 4, sum(price * qty) FROM t GROUP BY 1, 2";
+        let (_, stmt) = statement(sql).finish().unwrap();
+        let mut buf = BufferOutput(vec![]);
+        match stmt {
+            Statement::Select(stmt) => exec_select(&mut buf, &db, &stmt).unwrap(),
+            _ => panic!("Not a SELECT statement"),
+        }
+        assert_eq!(
+            buf.0,
+            vec![
+                vec!["(price * qty)", "(-qty + (10 / 4))", "sum((price * qty))"],
+                vec!["3", "0.5", "3"],
+                vec!["12", "-1.5", "12"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_output() {
+        let mut out = JsonOutput::default();
+        for row in [vec!["id", "name"], vec!["1", "say \"hi\"\n"], vec!["2", ""]] {
+            let row: Vec<_> = row.into_iter().map(|s| s.to_string()).collect();
+            out.output(&row).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(out.finish()).unwrap(),
+            r#"[
+  {"id": 1, "name": "say \"hi\"\n"},
+  {"id": 2, "name": null}
+]"#
+        );
+        assert_eq!(JsonOutput::default().finish(), b"[]");
+    }
+
+    #[test]
+    fn test_row_numbers() {
+        let db = Database::from([("a".to_string(), make_table("a", "id\nx\ny\n").unwrap())]);
+        let (_, Statement::Select(stmt)) = statement("SELECT * FROM a").finish().unwrap() else {
+            panic!("Not a SELECT statement");
+        };
+        let mut out = RowNumbers::new(BufferOutput::default());
+        exec_select(&mut out, &db, &stmt).unwrap();
+        assert_eq!(
+            out.inner.0,
+            vec![vec!["#", "id"], vec!["1", "x"], vec!["2", "y"]]
+        );
+    }
+
+    #[test]
+    fn test_semi_join() {
+        let db = db_from_csv(&[
+            ("a", "id,name\n1, x\n2, y\n3, z\n"),
+            ("b", "a_id\n2\n2\n4\n"),
+        ]);
+        let run = |sql| {
+            let (_, Statement::Select(stmt)) = statement(sql).finish().unwrap() else {
+                panic!("Not a SELECT statement");
+            };
+            let mut buf = BufferOutput(vec![]);
+            exec_select(&mut buf, &db, &stmt).unwrap();
+            buf.0
+        };
+        assert_eq!(
+            run("SELECT name FROM a WHERE id IN (SELECT a_id FROM b)"),
+            vec![vec!["name"], vec!["y"]]
+        );
+        assert_eq!(
+            run("SELECT name FROM a WHERE id NOT IN (SELECT a_id FROM b)"),
+            vec![vec!["name"], vec!["x"], vec!["z"]]
+        );
+        assert_eq!(
+            run("SELECT name FROM a WHERE NOT EXISTS (SELECT * FROM b WHERE a_id = '4') LIMIT 1"),
+            vec![vec!["name"]]
+        );
+        assert_eq!(
+            run("SELECT name FROM a WHERE EXISTS (SELECT * FROM b) LIMIT 1"),
+            vec![vec!["name"], vec!["x"]]
+        );
+    }
+
+    #[test]
+    fn test_project_columns() {
+        let mut db = Database::new();
+        let table = make_table("a", "id,name\n1, x\n2, y\n").unwrap();
+        db.insert("a".to_string(), table);
+        let run = |sql, names: &[&str]| {
+            let (_, Statement::Select(mut stmt)) = statement(sql).finish().unwrap() else {
+                panic!("Not a SELECT statement");
+            };
+            stmt.project_columns(names);
+            let mut buf = BufferOutput(vec![]);
+            exec_select(&mut buf, &db, &stmt).unwrap();
+            buf.0
+        };
+        assert_eq!(
+            run("SELECT * FROM a WHERE id = 2", &["name", "a.id"]),
+            vec![vec!["name", "id"], vec!["y", "2"]]
+        );
+        assert_eq!(
+            run("SELECT name, count(*) FROM a GROUP BY name", &["count(*)"]),
+            vec![vec!["count(*)"], vec!["1"], vec!["1"]]
+        );
+    }
+
+    #[test]
+    fn test_wide_table() {
+        let header: Vec<_> = (0..500).map(|col| format!("c{col}")).collect();
+        let mut csv = header.join(",") + "\n";
+        for row in 0..2 {
+            let values: Vec<_> = (0..500).map(|col| format!("v{row}_{col}")).collect();
+            csv += &(values.join(",") + "\n");
+        }
+        let mut db = Database::new();
+        db.insert("wide".to_string(), make_table("wide", &csv).unwrap());
+        let sql = "SELECT * EXCLUDE (c1, c499) REPLACE (upper(c0) AS c0) FROM wide";
+        let (_, Statement::Select(stmt)) = statement(sql).finish().unwrap() else {
+            panic!("Not a SELECT statement");
+        };
+        let mut buf = BufferOutput(vec![]);
+        exec_select(&mut buf, &db, &stmt).unwrap();
+        assert_eq!(buf.0.len(), 3);
+        assert_eq!(buf.0[0].len(), 498);
+        assert_eq!(buf.0[0][..2], ["c0", "c2"]);
+        assert_eq!(buf.0[1][..2], ["V0_0", "v0_2"]);
+        assert_eq!(buf.0[2][497], "v1_498");
+
+        let mut out = vec![];
+        buf.format_auto(&mut out, 30).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 2 * 499);
+        assert_eq!(lines[0], "-[ RECORD 1 ]");
+        assert_eq!(lines[1], "c0   | V0_0");
+        assert_eq!(lines[499], "-[ RECORD 2 ]");
+        assert_eq!(lines[997], "c498 | v1_498");
+
+        let mut out = vec![];
+        buf.format_auto(&mut out, 500).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 4);
+    }
+
+    #[test]
+    fn test_format_wide_characters() {
+        let buf = BufferOutput(
+            [["name", "city"], ["表示", "Zürich"], ["e\u{301}", "東京"]]
+                .map(|row| row.map(String::from).to_vec())
+                .to_vec(),
+        );
+        let mut out = vec![];
+        buf.format(&mut out).unwrap();
+        // The cells are padded to the same width on a terminal, where a CJK character takes 2 columns and a
+        // combining character none
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name | city  \n\
+             -----+--------\n\
+             表示 | Zürich\n\
+             e\u{301}    | 東京  \n"
+        );
+
+        let mut out = vec![];
+        buf.format_vertical(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "-[ RECORD 1 ]\nname | 表示\ncity | Zürich\n\
+             -[ RECORD 2 ]\nname | e\u{301}\ncity | 東京\n"
+        );
+    }
+
+    #[test]
+    fn test_provenance() {
+        let db = db_from_tables([
+            table_from_rows(
+                "a",
+                &["id", "name"],
+                &[&["1", "x"], &["2", "y"], &["3", "z"]],
+            ),
+            table_from_rows(
+                "b",
+                &["a_id", "tag"],
+                &[&["2", "p"], &["1", "q"], &["2", "r"]],
+            ),
+        ]);
+        let mut session = crate::Session::new(&db);
+        session.options = ExecOptions::DEFAULT.with_provenance(true);
+        let rows = |sql| match session.execute(sql) {
+            Ok(crate::StatementOutput::Rows(rows)) => rows,
+            res => panic!("{sql}: {res:?}"),
+        };
+        assert_eq!(
+            rows("SELECT name, tag FROM a AS l LEFT JOIN b ON id = a_id ORDER BY tag DESC"),
+            [
+                ["name", "tag", "_row_l", "_row_b"],
+                ["y", "r", "1", "2"],
+                ["x", "q", "0", "1"],
+                ["y", "p", "1", "0"],
+                ["z", "", "2", ""],
+            ]
+        );
+        // The rows of the subquery are not output rows
+        assert_eq!(
+            rows("SELECT name FROM a WHERE id IN (SELECT a_id FROM b) LIMIT 1 OFFSET 1"),
+            [["name", "_row_a"], ["y", "1"]]
+        );
+        assert_eq!(
+            rows("SELECT DISTINCT a_id FROM b ORDER BY a_id"),
+            [["a_id"], ["1"], ["2"]]
+        );
+        assert_eq!(rows("SELECT count(*) FROM b"), [["count(*)"], ["3"]]);
+    }
+
+    #[test]
+    fn test_max_cell_size() {
+        let (blob1, blob2) = (
+            format!("{}1", "x".repeat(30)),
+            format!("{}2", "x".repeat(30)),
+        );
+        let db = db_from_tables([table_from_rows(
+            "t",
+            &["id", "blob"],
+            &[&["1", &blob1], &["2", &blob2], &["3", "ééé"]],
+        )]);
+        let options = ExecOptions::DEFAULT.with_max_cell_size(Some(10));
+        let rows_with = |options: &ExecOptions, sql: &str| {
+            let mut session = crate::Session::new(&db);
+            session.options = options.clone();
+            match session.execute(sql) {
+                Ok(crate::StatementOutput::Rows(rows)) => rows,
+                res => panic!("{sql}: {res:?}"),
+            }
+        };
+        let rows = |sql: &str| rows_with(&options, sql);
+        let cut = "xxxxxxxxxx...[21 more bytes]";
+        assert_eq!(
+            rows("SELECT id, blob FROM t"),
+            [["id", "blob"], ["1", cut], ["2", cut], ["3", "ééé"]]
+        );
+        // Cut at a character boundary
+        assert_eq!(
+            rows_with(
+                &ExecOptions::DEFAULT.with_max_cell_size(Some(3)),
+                "SELECT blob FROM t WHERE id = 3"
+            ),
+            [["blob"], ["é...[4 more bytes]"]]
+        );
+        // The values are compared, sorted, grouped and deduplicated before they are truncated
+        assert_eq!(
+            rows(&format!("SELECT id FROM t WHERE blob = '{blob2}'")),
+            [["id"], ["2"]]
+        );
+        assert_eq!(
+            rows("SELECT id, length(blob) FROM t ORDER BY blob DESC LIMIT 2"),
+            [["id", "length(blob)"], ["3", "3"], ["2", "31"]]
+        );
+        assert_eq!(
+            rows("SELECT DISTINCT blob FROM t WHERE id < 3 ORDER BY blob"),
+            [["blob"], [cut], [cut]]
+        );
+        assert_eq!(
+            rows("SELECT blob, count(*) FROM t WHERE id < 3 GROUP BY blob"),
+            [["blob", "count(*)"], [cut, "1"], [cut, "1"]]
+        );
+        assert_eq!(
+            rows("SELECT blob FROM t UNION SELECT blob FROM t WHERE id = 1"),
+            [["blob"], [cut], [cut], ["ééé"]]
+        );
+        assert_eq!(
+            rows("SELECT id FROM t WHERE blob IN (SELECT blob FROM t WHERE id = 2)"),
+            [["id"], ["2"]]
+        );
+
+        let stmt = match crate::parse_statement("SELECT blob FROM t LIMIT 1").unwrap() {
+            crate::Statement::Select(stmt) => stmt,
+            stmt => panic!("{stmt:?}"),
+        };
+        let rows = crate::exec_select_iter_with(&db, &stmt, &options)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rows, [[cut]]);
+    }
+
+    #[test]
+    fn test_bind_columns() {
+        let db = db_from_tables([table_from_rows(
+            "t",
+            &["id", "name"],
+            &[&["1", "x"], &["2", "y"], &["3", "z"]],
+        )]);
+        let Statement::Select(stmt) = statement(
+            "SELECT name FROM t WHERE id > 1 AND name IN (SELECT name FROM t WHERE id < 3) ORDER BY t.id",
+        )
+        .unwrap()
+        .1
+        else {
+            panic!("Not a SELECT statement");
+        };
+        let ctx = QueryContext::new(&db, &stmt, &ExecOptions::DEFAULT, None).unwrap();
+        let (exprs, subqueries) = ctx.sql.own_exprs();
+        let ids: Vec<_> = exprs
+            .iter()
+            .filter_map(|ex| match ex {
+                Expr::Bound(_, id) => Some((id.joindex, id.col)),
+                Expr::Column(col) => panic!("{col} is not bound"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, [(0, 1), (0, 0), (0, 1), (0, 0)]);
+        // A subquery is bound to its own tables when it runs
+        assert!(
+            subqueries[0]
+                .own_exprs()
+                .0
+                .iter()
+                .any(|ex| matches!(ex, Expr::Column(_)))
+        );
+        assert_eq!(ctx.sql.to_string(), stmt.to_string());
+
+        // The columns of `*` are those of each joined table, even if they have the same name
+        assert_query_results!(
+            db,
+            "SELECT * FROM t AS a INNER JOIN t AS b ON a.id + 1 = b.id",
+            [
+                ["id", "name", "id", "name"],
+                ["1", "x", "2", "y"],
+                ["2", "y", "3", "z"],
+            ]
+        );
+        assert_query_results!(
+            db,
+            "SELECT name FROM t WHERE id > 1 AND name IN (SELECT name FROM t WHERE id < 3) ORDER BY t.id DESC",
+            [["name"], ["y"]]
+        );
     }
 }
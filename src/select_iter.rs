@@ -0,0 +1,211 @@
+//! Pull-based query results, for processing the rows lazily one at a time without implementing
+//! [`QueryOutput`](crate::select::QueryOutput).
+//! Scans with joins, WHERE, LIMIT and OFFSET yield each row as soon as it is found. Queries that need all the rows
+//! before yielding any, i.e. with ORDER BY, DISTINCT, aggregates or window functions, are buffered internally.
+
+use std::error::Error;
+
+use crate::{
+    db::Database,
+    eval::{AggregateResult, EvalError, eval_cell, find_aggregate_fn},
+    options::ExecOptions,
+    select::{
+        BufferOutput, Expr, QueryContext, RowScanner, SelectStmt, exec_select_with,
+        extend_colspecs, group_exprs, truncate_cell,
+    },
+    window::has_window_fn,
+};
+
+/// Iterator over the rows of a query result, returned by [`exec_select_iter`].
+/// Iteration stops after the first error.
+pub struct SelectIter<'a> {
+    header: Vec<String>,
+    rows: Rows<'a>,
+}
+
+enum Rows<'a> {
+    Buffered(std::vec::IntoIter<Vec<String>>),
+    Streamed(Box<Stream<'a>>),
+}
+
+struct Stream<'a> {
+    ctx: QueryContext<'a>,
+    cols: Vec<Expr>,
+    scanner: RowScanner<'a>,
+    /// Rows to skip before yielding any
+    offset: usize,
+    /// Rows left to yield
+    remaining: usize,
+}
+
+impl Stream<'_> {
+    fn next(&mut self) -> Option<Result<Vec<String>, Box<dyn Error>>> {
+        while self.remaining != 0 {
+            let row_cursor = match self.scanner.next(&self.ctx, &self.cols) {
+                Ok(Some(row_cursor)) => row_cursor,
+                Ok(None) => return None,
+                Err(e) => {
+                    self.remaining = 0;
+                    return Some(Err(e));
+                }
+            };
+            if self.offset != 0 {
+                self.offset -= 1;
+                continue;
+            }
+            self.remaining -= 1;
+            if let Err(e) = self.ctx.limits.output_row() {
+                self.remaining = 0;
+                return Some(Err(e));
+            }
+            let values = self
+                .cols
+                .iter()
+                .map(|ex| {
+                    match eval_cell(
+                        ex,
+                        &self.cols,
+                        &self.ctx,
+                        &row_cursor,
+                        &AggregateResult::default(),
+                    ) {
+                        Ok(res) => Ok(truncate_cell(res, self.ctx.max_cell_size)),
+                        Err(EvalError::CursorNone(_)) => Ok("".to_string()),
+                        Err(e) => Err(e),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>();
+            if values.is_err() {
+                self.remaining = 0;
+            }
+            return Some(values.map_err(|e| e.into()));
+        }
+        None
+    }
+}
+
+impl SelectIter<'_> {
+    /// Names of the columns, which is the first row given to a [`QueryOutput`](crate::select::QueryOutput).
+    pub fn header(&self) -> &[String] {
+        &self.header
+    }
+}
+
+impl Iterator for SelectIter<'_> {
+    type Item = Result<Vec<String>, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.rows {
+            Rows::Buffered(ref mut rows) => rows.next().map(Ok),
+            Rows::Streamed(ref mut stream) => stream.next(),
+        }
+    }
+}
+
+/// Execute a select statement, returning an iterator that yields the rows on demand.
+/// Errors in resolving the tables and the columns are returned here, and errors in evaluating rows are
+/// yielded by the iterator.
+pub fn exec_select_iter<'a>(
+    db: &'a Database,
+    sql: &'a SelectStmt,
+) -> Result<SelectIter<'a>, Box<dyn Error>> {
+    exec_select_iter_with(db, sql, &ExecOptions::DEFAULT)
+}
+
+/// Same as [`exec_select_iter`], but with the given options.
+pub fn exec_select_iter_with<'a>(
+    db: &'a Database,
+    sql: &'a SelectStmt,
+    options: &'a ExecOptions,
+) -> Result<SelectIter<'a>, Box<dyn Error>> {
+    // The tables of the CTEs live only as long as the call, so the rows are collected beforehand
+    if !sql.with.is_empty() {
+        let mut buf = BufferOutput::default();
+        exec_select_with(&mut buf, db, sql, options, None)?;
+        let mut rows = buf.0.into_iter();
+        return Ok(SelectIter {
+            header: rows.next().unwrap_or_default(),
+            rows: Rows::Buffered(rows),
+        });
+    }
+
+    let ctx = QueryContext::new(db, sql, options, None)?;
+    let (cols, header) = extend_colspecs(&ctx, &sql.cols)?;
+
+    // The rows are sorted in the deterministic mode
+    let buffered = !sql.ordering.is_empty()
+        || options.deterministic
+        || sql.distinct
+        || group_exprs(&ctx, &cols).is_some()
+        || cols.iter().any(|col| find_aggregate_fn(col).is_some())
+        || sql.qualify.is_some()
+        || has_window_fn(&cols, None);
+    if buffered {
+        let mut buf = BufferOutput::default();
+        exec_select_with(&mut buf, db, sql, options, None)?;
+        return Ok(SelectIter {
+            header,
+            rows: Rows::Buffered(buf.0.split_off(1.min(buf.0.len())).into_iter()),
+        });
+    }
+
+    let scanner = RowScanner::new(&ctx);
+    Ok(SelectIter {
+        header,
+        rows: Rows::Streamed(Box::new(Stream {
+            ctx,
+            cols,
+            scanner,
+            offset: sql.offset.unwrap_or(0),
+            remaining: sql.limit.unwrap_or(usize::MAX),
+        })),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Statement, make_table, parser::statement};
+
+    #[test]
+    fn test_exec_select_iter() {
+        let mut db = Database::new();
+        db.insert(
+            "t".to_string(),
+            make_table("t", "id,name\n1, a\n2, b\n3, c\n4, d\n").unwrap(),
+        );
+        let stmt = |sql| {
+            let Statement::Select(stmt) = statement(sql).unwrap().1 else {
+                panic!("Not a SELECT statement");
+            };
+            stmt
+        };
+
+        let sql = stmt("SELECT name FROM t WHERE id <> '2' LIMIT 2 OFFSET 1");
+        let iter = exec_select_iter(&db, &sql).unwrap();
+        assert_eq!(iter.header(), ["name"]);
+        let rows = iter.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows, vec![vec!["c"], vec!["d"]]);
+
+        // Rows are pulled only as far as they are consumed
+        let sql = stmt("SELECT id FROM t");
+        let mut iter = exec_select_iter(&db, &sql).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap(), vec!["1"]);
+
+        let sql = stmt("SELECT name FROM t ORDER BY id DESC LIMIT 1");
+        let rows = exec_select_iter(&db, &sql)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rows, vec![vec!["d"]]);
+
+        let sql = stmt("WITH u (n) AS (SELECT name FROM t WHERE id > 2) SELECT n FROM u");
+        let iter = exec_select_iter(&db, &sql).unwrap();
+        assert_eq!(iter.header(), ["n"]);
+        let rows = iter.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows, vec![vec!["c"], vec!["d"]]);
+
+        let sql = stmt("SELECT name FROM u");
+        assert!(exec_select_iter(&db, &sql).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! Semi-join and anti-join for `expr IN (SELECT ...)` and `expr NOT IN (SELECT ...)`.
+//! Subqueries cannot refer to the outer query, so the subquery is executed once and its values are collected
+//! in a hash set, which each row of the outer query probes in constant time. That keeps queries like
+//! "rows in A missing from B" linear in the sizes of both tables instead of quadratic.
+
+use std::collections::HashSet;
+
+use crate::eval::EvalError;
+
+pub(crate) struct SubqueryValues {
+    values: HashSet<String>,
+    /// Whether the subquery yielded a NULL, which makes `NOT IN` unknown for the values not in the set
+    has_null: bool,
+}
+
+impl SubqueryValues {
+    /// Collects the values from the result of the subquery, including the header row.
+    pub fn new(rows: Vec<Vec<String>>) -> Result<Self, EvalError> {
+        let mut rows = rows.into_iter();
+        if let Some(header) = rows.next()
+            && header.len() != 1
+        {
+            return Err(EvalError::Subquery(format!(
+                "subquery returned {} columns",
+                header.len()
+            )));
+        }
+        let mut values = HashSet::new();
+        let mut has_null = false;
+        for row in rows {
+            let val = row.into_iter().next().unwrap_or_default();
+            if val.is_empty() {
+                has_null = true;
+            } else {
+                values.insert(val);
+            }
+        }
+        Ok(Self { values, has_null })
+    }
+
+    /// Whether `val IN (subquery)`, or `val NOT IN (subquery)` if `negated`, is true.
+    /// A comparison with NULL is unknown, so it is not true in either way unless the subquery yields no rows.
+    pub fn test(&self, val: &str, negated: bool) -> bool {
+        if self.values.is_empty() && !self.has_null {
+            return negated;
+        }
+        if val.is_empty() {
+            return false;
+        }
+        let found = self.values.contains(val);
+        if negated {
+            !found && !self.has_null
+        } else {
+            found
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn values(vals: &[&str]) -> SubqueryValues {
+        let rows = std::iter::once("v")
+            .chain(vals.iter().copied())
+            .map(|val| vec![val.to_string()])
+            .collect();
+        SubqueryValues::new(rows).unwrap()
+    }
+
+    #[test]
+    fn test_subquery_values() {
+        let set = values(&["1", "2"]);
+        assert!(set.test("1", false));
+        assert!(!set.test("3", false));
+        assert!(set.test("3", true));
+        assert!(!set.test("", true));
+
+        let set = values(&["1", ""]);
+        assert!(set.test("1", false));
+        assert!(!set.test("3", true), "unknown with NULL in the subquery");
+
+        let set = values(&[]);
+        assert!(!set.test("", false));
+        assert!(set.test("", true));
+    }
+}
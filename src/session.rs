@@ -0,0 +1,450 @@
+//! Running scripts of multiple statements separated by `;`, collecting the result of each statement.
+
+use std::{collections::BTreeMap, error::Error, io::Write};
+
+use crate::{
+    compound::exec_compound,
+    db::{Database, Statement},
+    describe::{exec_describe, exec_show_tables},
+    explain::format_explain,
+    functions::exec_show_functions,
+    lint::{LintWarning, lint},
+    options::ExecOptions,
+    params::{Params, bind_params},
+    parse_error::ParseError,
+    parser::parse_statement,
+    schema_diff::exec_compare_schema,
+    search::exec_search,
+    select::{BufferOutput, Expr, exec_select_with},
+    trace::ExecTrace,
+};
+
+/// Settings shared by the statements run against a database.
+pub struct Session<'a> {
+    db: &'a Database,
+    pub options: ExecOptions,
+    /// Values bound to the placeholders in every statement
+    pub params: Params,
+    /// Columns to output instead of the select list of every statement, if not empty.
+    /// See [`SelectStmt::project_columns`](crate::SelectStmt::project_columns).
+    pub columns: Vec<String>,
+    /// Skip the rest of a script after the first statement that fails. Otherwise, the errors are recorded
+    /// and the rest is run.
+    pub stop_on_error: bool,
+    /// Queries saved by [`save_query`](Self::save_query), listed by `SHOW QUERIES`
+    pub saved: SavedQueries,
+}
+
+/// Named queries parsed once and run by name, e.g. reports offered by a REPL or a playground.
+#[derive(Debug, Clone, Default)]
+pub struct SavedQueries(BTreeMap<String, (String, Statement)>);
+
+impl SavedQueries {
+    /// Parses and saves the query under the name, replacing the query saved under the same name if any.
+    /// The query can have placeholders to be bound when it is run.
+    pub fn save(&mut self, name: &str, sql: &str) -> Result<(), ParseError> {
+        let stmt = parse_statement(sql)?;
+        self.0.insert(name.to_string(), (sql.to_string(), stmt));
+        Ok(())
+    }
+
+    /// Removes the query, returning its source text if it was saved.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.0.remove(name).map(|(sql, _)| sql)
+    }
+
+    /// The source text of the query saved under the name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(|(sql, _)| sql.as_str())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(|name| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The statement saved under the name with the values bound to its placeholders.
+    fn bind(&self, name: &str, params: &Params) -> Result<Statement, Box<dyn Error>> {
+        let (_, stmt) = self
+            .0
+            .get(name)
+            .ok_or_else(|| format!("No query saved as {name}"))?;
+        let mut stmt = stmt.clone();
+        bind_params(&mut stmt, params)?;
+        Ok(stmt)
+    }
+
+    /// Runs the query saved under the name with the values bound to its placeholders.
+    pub fn run(
+        &self,
+        db: &Database,
+        name: &str,
+        params: &Params,
+        options: &ExecOptions,
+    ) -> Result<StatementOutput, Box<dyn Error>> {
+        exec_statement(db, &self.bind(name, params)?, options, None)
+    }
+
+    /// The rows listed by `SHOW QUERIES` with the header, the name, the source text and the placeholders
+    /// separated by commas of each query in the order of the names.
+    pub fn rows(&self) -> Vec<Vec<String>> {
+        let header = ["name", "query", "params"]
+            .map(|col| col.to_string())
+            .to_vec();
+        let rows = self.0.iter().map(|(name, (sql, stmt))| {
+            let mut stmt = stmt.clone();
+            let mut params: Vec<String> = vec![];
+            for select in stmt.selects_mut() {
+                select.visit_exprs_mut(&mut |ex| {
+                    if matches!(ex, Expr::Param(_)) && !params.contains(&ex.to_string()) {
+                        params.push(ex.to_string());
+                    }
+                });
+            }
+            vec![name.clone(), sql.clone(), params.join(", ")]
+        });
+        std::iter::once(header).chain(rows).collect()
+    }
+}
+
+/// Result of a statement in a script, returned by [`Session::execute_script`].
+#[derive(Debug)]
+pub struct StatementResult {
+    /// The source text of the statement
+    pub sql: String,
+    pub result: Result<StatementOutput, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementOutput {
+    /// Result set of a query, with the header as the first row
+    Rows(Vec<Vec<String>>),
+    /// Query plan printed by `EXPLAIN`
+    Plan(String),
+}
+
+impl StatementOutput {
+    /// Formats the rows as an ASCII table, or writes the plan as it is.
+    pub fn format(&self, out: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            Self::Rows(rows) => BufferOutput(rows.clone()).format(out),
+            Self::Plan(plan) => write!(out, "{plan}"),
+        }
+    }
+}
+
+impl<'a> Session<'a> {
+    pub fn new(db: &'a Database) -> Self {
+        Self {
+            db,
+            options: ExecOptions::default(),
+            params: Params::new(),
+            columns: vec![],
+            stop_on_error: false,
+            saved: SavedQueries::default(),
+        }
+    }
+
+    /// The database that the statements run against
+    pub fn db(&self) -> &'a Database {
+        self.db
+    }
+
+    /// Parses and runs a single statement.
+    pub fn execute(&self, sql: &str) -> Result<StatementOutput, Box<dyn Error>> {
+        self.exec(self.parse(sql)?, None)
+    }
+
+    /// Same as [`execute`](Self::execute), but a SELECT records the combinations of rows it evaluates and its
+    /// statistics in the trace.
+    pub fn execute_traced(
+        &self,
+        sql: &str,
+        trace: &mut ExecTrace,
+    ) -> Result<StatementOutput, Box<dyn Error>> {
+        self.exec(self.parse(sql)?, Some(trace))
+    }
+
+    /// Parses a single statement and returns the warnings of [`lint`] on it as it would be run by
+    /// [`execute`](Self::execute), without running it.
+    pub fn lint(&self, sql: &str) -> Result<Vec<LintWarning>, Box<dyn Error>> {
+        let mut stmt = self.parse(sql)?;
+        self.project(&mut stmt);
+        Ok(lint(self.db, &stmt, &self.options))
+    }
+
+    /// Parses a single statement with the values of [`params`](Self::params) bound to its placeholders.
+    fn parse(&self, sql: &str) -> Result<Statement, Box<dyn Error>> {
+        let mut stmt = parse_statement(sql)?;
+        bind_params(&mut stmt, &self.params)?;
+        Ok(stmt)
+    }
+
+    /// Saves the query to be run by [`run_saved`](Self::run_saved). See [`SavedQueries::save`].
+    pub fn save_query(&mut self, name: &str, sql: &str) -> Result<(), ParseError> {
+        self.saved.save(name, sql)
+    }
+
+    /// Runs the query saved under the name with the values bound to its placeholders, in addition to
+    /// [`params`](Self::params) of the session. The values given here take precedence.
+    pub fn run_saved(
+        &self,
+        name: &str,
+        params: &Params,
+    ) -> Result<StatementOutput, Box<dyn Error>> {
+        let mut merged = self.params.clone();
+        merged.extend(params.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self.exec(self.saved.bind(name, &merged)?, None)
+    }
+
+    fn exec(
+        &self,
+        mut stmt: Statement,
+        trace: Option<&mut ExecTrace>,
+    ) -> Result<StatementOutput, Box<dyn Error>> {
+        if let Statement::ShowQueries = stmt {
+            return Ok(StatementOutput::Rows(self.saved.rows()));
+        }
+        self.project(&mut stmt);
+        exec_statement(self.db, &stmt, &self.options, trace)
+    }
+
+    /// Replaces the select lists with [`columns`](Self::columns) if any.
+    fn project(&self, stmt: &mut Statement) {
+        if !self.columns.is_empty() {
+            for select in stmt.selects_mut() {
+                select.project_columns(&self.columns);
+            }
+        }
+    }
+
+    /// Runs the statements in the script in order. The last statement does not need to be terminated by `;`.
+    /// If [`stop_on_error`](Self::stop_on_error) is set, the results end at the first error.
+    pub fn execute_script(&self, script: &str) -> Vec<StatementResult> {
+        let (mut stmts, rest) = split_statements(script);
+        if !rest.trim().is_empty() {
+            stmts.push(rest.trim().to_string());
+        }
+        let mut results = vec![];
+        for sql in stmts {
+            let result = self.execute(&sql).map_err(|e| e.to_string());
+            let failed = result.is_err();
+            results.push(StatementResult { sql, result });
+            if failed && self.stop_on_error {
+                break;
+            }
+        }
+        results
+    }
+}
+
+/// Runs the statement, recording a SELECT in the trace if any.
+pub(crate) fn exec_statement(
+    db: &Database,
+    stmt: &Statement,
+    options: &ExecOptions,
+    trace: Option<&mut ExecTrace>,
+) -> Result<StatementOutput, Box<dyn Error>> {
+    match stmt {
+        Statement::Select(select) => {
+            let mut buf = BufferOutput::default();
+            exec_select_with(&mut buf, db, select, options, trace)?;
+            Ok(StatementOutput::Rows(buf.0))
+        }
+        Statement::Compound(compound) => {
+            let mut buf = BufferOutput::default();
+            exec_compound(&mut buf, db, compound, options)?;
+            Ok(StatementOutput::Rows(buf.0))
+        }
+        Statement::Search(value) => {
+            let mut buf = BufferOutput::default();
+            exec_search(&mut buf, db, value)?;
+            Ok(StatementOutput::Rows(buf.0))
+        }
+        Statement::CompareSchema(old, new) => {
+            let mut buf = BufferOutput::default();
+            exec_compare_schema(&mut buf, db, old, new)?;
+            Ok(StatementOutput::Rows(buf.0))
+        }
+        Statement::Describe(table) => {
+            let mut buf = BufferOutput::default();
+            exec_describe(&mut buf, db, table)?;
+            Ok(StatementOutput::Rows(buf.0))
+        }
+        Statement::ShowTables => {
+            let mut buf = BufferOutput::default();
+            exec_show_tables(&mut buf, db)?;
+            Ok(StatementOutput::Rows(buf.0))
+        }
+        Statement::ShowFunctions => {
+            let mut buf = BufferOutput::default();
+            exec_show_functions(&mut buf)?;
+            Ok(StatementOutput::Rows(buf.0))
+        }
+        Statement::ShowQueries => {
+            Err("SHOW QUERIES lists the queries saved in a session, which runs it".into())
+        }
+        Statement::Explain(explain) => {
+            let mut buf = vec![];
+            format_explain(&mut buf, db, &explain.stmt, options, explain.format)?;
+            Ok(StatementOutput::Plan(String::from_utf8(buf)?))
+        }
+    }
+}
+
+/// Splits the input into the statements terminated by `;` and the rest, ignoring `;` in string literals.
+pub fn split_statements(input: &str) -> (Vec<String>, String) {
+    let mut stmts = vec![];
+    let mut start = 0;
+    let mut in_str = false;
+    for (i, c) in input.char_indices() {
+        match c {
+            '\'' => in_str = !in_str,
+            ';' if !in_str => {
+                let stmt = input[start..i].trim();
+                if !stmt.is_empty() {
+                    stmts.push(stmt.to_string());
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    (stmts, input[start..].to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::make_table;
+
+    #[test]
+    fn test_split_statements() {
+        let (stmts, rest) =
+            split_statements("SELECT * FROM a;\nSELECT ';' FROM b\n; SELECT *\nFROM");
+        assert_eq!(stmts, vec!["SELECT * FROM a", "SELECT ';' FROM b"]);
+        assert_eq!(rest, " SELECT *\nFROM");
+    }
+
+    #[test]
+    fn test_execute_script() {
+        let mut db = Database::new();
+        db.insert("a".to_string(), make_table("a", "id\n1\n2\n").unwrap());
+        let mut session = Session::new(&db);
+        let script = "SELECT id FROM a WHERE id = 2; SELECT x FROM b; SELECT count(*) FROM a";
+
+        let results = session.execute_script(script);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].sql, "SELECT id FROM a WHERE id = 2");
+        assert_eq!(
+            results[0].result,
+            Ok(StatementOutput::Rows(vec![
+                vec!["id".to_string()],
+                vec!["2".to_string()]
+            ]))
+        );
+        assert!(results[1].result.is_err());
+        assert_eq!(
+            results[2].result,
+            Ok(StatementOutput::Rows(vec![
+                vec!["count(*)".to_string()],
+                vec!["2".to_string()]
+            ]))
+        );
+
+        session.stop_on_error = true;
+        assert_eq!(session.execute_script(script).len(), 2);
+    }
+
+    #[test]
+    fn test_saved_queries() {
+        let mut db = Database::new();
+        db.insert(
+            "a".to_string(),
+            make_table("a", "id,name\n1,x\n2,y\n").unwrap(),
+        );
+        let mut session = Session::new(&db);
+        session
+            .save_query("by_id", "SELECT name FROM a WHERE id = :id")
+            .unwrap();
+        session.save_query("all", "SELECT count(*) FROM a").unwrap();
+        assert!(session.save_query("bad", "SELECT FROM").is_err());
+
+        let params = Params::from([("id".to_string(), "2".to_string())]);
+        assert_eq!(
+            session.run_saved("by_id", &params).unwrap(),
+            StatementOutput::Rows(vec![vec!["name".to_string()], vec!["y".to_string()]])
+        );
+        // The session parameters apply unless overridden
+        session.params.insert("id".to_string(), "1".to_string());
+        assert_eq!(
+            session.run_saved("by_id", &Params::new()).unwrap(),
+            StatementOutput::Rows(vec![vec!["name".to_string()], vec!["x".to_string()]])
+        );
+        let err = session.run_saved("by_name", &params).unwrap_err();
+        assert_eq!(err.to_string(), "No query saved as by_name");
+
+        assert_eq!(
+            session.execute("SHOW QUERIES").unwrap(),
+            StatementOutput::Rows(vec![
+                vec![
+                    "name".to_string(),
+                    "query".to_string(),
+                    "params".to_string()
+                ],
+                vec![
+                    "all".to_string(),
+                    "SELECT count(*) FROM a".to_string(),
+                    "".to_string()
+                ],
+                vec![
+                    "by_id".to_string(),
+                    "SELECT name FROM a WHERE id = :id".to_string(),
+                    ":id".to_string()
+                ],
+            ])
+        );
+        assert_eq!(
+            session.saved.remove("all").as_deref(),
+            Some("SELECT count(*) FROM a")
+        );
+        assert_eq!(session.saved.len(), 1);
+        assert!(exec_statement(&db, &Statement::ShowQueries, &ExecOptions::DEFAULT, None).is_err());
+    }
+    #[test]
+    fn test_execute_traced() {
+        let mut db = Database::new();
+        db.insert("a".to_string(), make_table("a", "id\n1\n2\n").unwrap());
+        let mut session = Session::new(&db);
+        session.params.insert("id".to_string(), "2".to_string());
+        session.columns = vec!["id".to_string()];
+
+        let mut trace = ExecTrace::new(0);
+        assert_eq!(
+            session
+                .execute_traced("SELECT id, id + 1 FROM a WHERE id = :id", &mut trace)
+                .unwrap(),
+            StatementOutput::Rows(vec![vec!["id".to_string()], vec!["2".to_string()]])
+        );
+        assert_eq!(trace.stats.tables, ["a"]);
+        assert_eq!(trace.stats.rows_emitted, 1);
+
+        // The statement is linted as it would be run, with the columns of the session
+        assert!(session.lint("SELECT naem FROM a").unwrap().is_empty());
+        session.columns.clear();
+        assert_eq!(
+            session.lint("SELECT naem FROM a WHERE id = :id").unwrap(),
+            [LintWarning::UnknownColumn {
+                column: "naem".to_string()
+            }]
+        );
+        assert!(session.lint("SELECT id FROM a WHERE id = :x").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Sketches for approximate aggregates, which take bounded memory regardless of the number of rows.
+//! `approx_count_distinct` uses HyperLogLog and `approx_quantile` uses a merging t-digest.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Number of bits of the hash used to select a register
+const HLL_PRECISION: u32 = 12;
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+/// HyperLogLog estimator of the number of distinct values, with a standard error of about 1.6%.
+#[derive(Debug, Clone)]
+pub(crate) struct HyperLogLog {
+    /// The maximum number of leading zeros (plus one) seen in the hashes that fell in each register
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; HLL_REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    pub fn add(&mut self, val: &str) {
+        // DefaultHasher::new() uses fixed keys, so the estimate is deterministic
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        let hash = hasher.finish();
+        let idx = (hash >> (64 - HLL_PRECISION)) as usize;
+        // Guard bit to bound the rank if the rest of the hash is all zeros
+        let rest = (hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[idx] = self.registers[idx].max(rank);
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = HLL_REGISTERS as f64;
+        let alpha = 0.7213 / (1. + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // Linear counting is more accurate for small cardinalities
+        let estimate = if estimate <= 2.5 * m && zeros != 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        };
+        estimate.round() as u64
+    }
+}
+
+/// Trade-off between the accuracy and the number of centroids, which is at most about this value.
+const TDIGEST_COMPRESSION: f64 = 100.;
+/// Number of values buffered before merging them into the centroids
+const TDIGEST_BUFFER: usize = 500;
+
+/// t-digest estimator of quantiles. Values are summarized in centroids that are small near the tails,
+/// so extreme quantiles are more accurate than the median.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TDigest {
+    /// Pairs of mean and weight, sorted by mean
+    centroids: Vec<(f64, f64)>,
+    buffer: Vec<f64>,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn add(&mut self, val: f64) {
+        if self.centroids.is_empty() && self.buffer.is_empty() {
+            (self.min, self.max) = (val, val);
+        }
+        self.min = self.min.min(val);
+        self.max = self.max.max(val);
+        self.buffer.push(val);
+        if TDIGEST_BUFFER <= self.buffer.len() {
+            self.centroids = self.merged();
+            self.buffer.clear();
+        }
+    }
+
+    /// Merges the buffered values into the centroids without modifying self.
+    fn merged(&self) -> Vec<(f64, f64)> {
+        let mut points = self.centroids.clone();
+        points.extend(self.buffer.iter().map(|&val| (val, 1.)));
+        points.sort_by(|lhs, rhs| lhs.0.total_cmp(&rhs.0));
+        let total: f64 = points.iter().map(|(_, weight)| weight).sum();
+
+        // The scale function k1, which limits each centroid to a unit of k
+        let k = |q: f64| TDIGEST_COMPRESSION / (2. * std::f64::consts::PI) * (2. * q - 1.).asin();
+        let k_inv =
+            |k: f64| ((k * 2. * std::f64::consts::PI / TDIGEST_COMPRESSION).sin() + 1.) / 2.;
+
+        let mut res: Vec<(f64, f64)> = vec![];
+        let mut points = points.into_iter();
+        let Some(mut cur) = points.next() else {
+            return res;
+        };
+        let mut weight_so_far = 0.;
+        let mut q_limit = k_inv(k(0.) + 1.);
+        for (mean, weight) in points {
+            if (weight_so_far + cur.1 + weight) / total <= q_limit {
+                cur.0 += (mean - cur.0) * weight / (cur.1 + weight);
+                cur.1 += weight;
+            } else {
+                weight_so_far += cur.1;
+                q_limit = k_inv(k(weight_so_far / total) + 1.);
+                res.push(cur);
+                cur = (mean, weight);
+            }
+        }
+        res.push(cur);
+        res
+    }
+
+    /// Estimates the value at the quantile `q` in [0, 1], or None if no values were added.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let centroids = self.merged();
+        let total: f64 = centroids.iter().map(|(_, weight)| weight).sum();
+        if centroids.is_empty() {
+            return None;
+        }
+        let target = q * total;
+
+        // Interpolate between the centers of adjacent centroids, and the extremes at both ends
+        let mut prev = (self.min, 0.);
+        let mut cum = 0.;
+        for &(mean, weight) in &centroids {
+            let center = cum + weight / 2.;
+            if target < center {
+                let ratio = (target - prev.1) / (center - prev.1);
+                return Some(prev.0 + (mean - prev.0) * ratio);
+            }
+            prev = (mean, center);
+            cum += weight;
+        }
+        let ratio = if total <= prev.1 {
+            1.
+        } else {
+            (target - prev.1) / (total - prev.1)
+        };
+        Some(prev.0 + (self.max - prev.0) * ratio)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hyper_log_log() {
+        let mut hll = HyperLogLog::default();
+        assert_eq!(hll.estimate(), 0);
+        for i in 0..10 {
+            hll.add(&i.to_string());
+            hll.add(&i.to_string());
+        }
+        assert_eq!(hll.estimate(), 10);
+
+        let mut hll = HyperLogLog::default();
+        for i in 0..100_000 {
+            hll.add(&(i % 50_000).to_string());
+        }
+        let estimate = hll.estimate() as f64;
+        assert!((estimate / 50_000. - 1.).abs() < 0.05, "{estimate}");
+    }
+
+    #[test]
+    fn test_tdigest() {
+        let mut digest = TDigest::default();
+        assert_eq!(digest.quantile(0.5), None);
+        for i in 1..=5 {
+            digest.add(i as f64);
+        }
+        assert_eq!(digest.quantile(0.), Some(1.));
+        assert_eq!(digest.quantile(0.5), Some(3.));
+        assert_eq!(digest.quantile(1.), Some(5.));
+
+        let mut digest = TDigest::default();
+        // Not in order, to exercise merging
+        for i in 0..10_000 {
+            digest.add(((i * 7919) % 10_000) as f64);
+        }
+        for q in [0.01, 0.5, 0.99] {
+            let estimate = digest.quantile(q).unwrap();
+            assert!((estimate - q * 10_000.).abs() < 50., "{q}: {estimate}");
+        }
+    }
+}
@@ -1,51 +1,303 @@
-use std::error::Error;
+use std::{collections::HashMap, error::Error, sync::OnceLock};
 
-#[derive(Debug, PartialEq)]
+use crate::{datetime::DateTime, merge_join::row_count, number::Number};
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Table {
     pub name: String,
     pub schema: Vec<RowSchema>,
+    /// The cells in row-major order. Call [`Table::invalidate_cache`] after modifying them directly, or use
+    /// [`Table::set`].
     pub data: Vec<String>,
+    /// All the column names in the header of the source file, if only some of them are loaded in `schema`
+    pub source_header: Option<Vec<String>>,
+    /// Column indices by name, built at load to avoid scanning the schema on every column resolution.
+    /// The first column wins if names collide.
+    col_index: HashMap<String, usize>,
+    /// Same as `col_index`, but keyed by lowercase names for case-insensitive resolution.
+    lowercase_col_index: HashMap<String, usize>,
+    numbers: NumberCache,
+}
+
+/// The numbers parsed from the cells of each column, without and with decimals enabled, computed on the first
+/// numeric use of the column.
+#[derive(Default, Clone)]
+struct NumberCache(Vec<[OnceLock<Vec<Option<Number>>>; 2]>);
+
+impl NumberCache {
+    fn new(cols: usize) -> Self {
+        Self((0..cols).map(|_| Default::default()).collect())
+    }
+}
+
+impl std::fmt::Debug for NumberCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NumberCache")
+    }
+}
+
+/// The cache is derived from the cells, so it does not make tables different
+impl PartialEq for NumberCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
 }
 
 impl Table {
+    pub fn new(name: String, schema: Vec<RowSchema>, data: Vec<String>) -> Self {
+        let cols = schema.len();
+        let mut col_index = HashMap::new();
+        let mut lowercase_col_index = HashMap::new();
+        for (i, col) in schema.iter().enumerate() {
+            col_index.entry(col.name.clone()).or_insert(i);
+            lowercase_col_index
+                .entry(col.name.to_ascii_lowercase())
+                .or_insert(i);
+        }
+        let mut table = Self {
+            name,
+            schema,
+            data,
+            source_header: None,
+            col_index,
+            lowercase_col_index,
+            numbers: NumberCache::new(cols),
+        };
+        table.infer_types();
+        table
+    }
+
+    fn infer_types(&mut self) {
+        for col in 0..self.schema.len() {
+            if !self.schema[col].declared {
+                self.schema[col].ty = self.infer_column_type(col);
+            }
+        }
+    }
+
+    /// Declares the type of the column, which replaces the inferred one and is kept when the cells change,
+    /// e.g. text for zip codes that are all written in digits. Returns `None` if the column is out of bounds.
+    pub fn declare_type(&mut self, col: usize, ty: ColumnType) -> Option<()> {
+        let schema = self.schema.get_mut(col)?;
+        schema.ty = ty;
+        schema.declared = true;
+        Some(())
+    }
+
+    /// Returns the index of the column with the given name, or if `case_insensitive` and no column has exactly
+    /// the name, the first column with the name in a different case.
+    pub fn find_col(&self, name: &str, case_insensitive: bool) -> Option<usize> {
+        self.col_index.get(name).copied().or_else(|| {
+            case_insensitive
+                .then(|| self.lowercase_col_index.get(&name.to_ascii_lowercase()))
+                .flatten()
+                .copied()
+        })
+    }
+
     pub fn get(&self, row: usize, col: usize) -> Option<&String> {
         let cols = self.schema.len();
         self.data.get(col + row * cols)
     }
+
+    /// Replaces the cell, dropping the cached numbers of the column and inferring its type again unless it is
+    /// declared.
+    pub fn set(&mut self, row: usize, col: usize, value: String) -> Option<String> {
+        let cols = self.schema.len();
+        let cell = self.data.get_mut(col + row * cols)?;
+        let prev = std::mem::replace(cell, value);
+        self.numbers.0[col] = Default::default();
+        if !self.schema[col].declared {
+            self.schema[col].ty = self.infer_column_type(col);
+        }
+        Some(prev)
+    }
+
+    /// Drops the cached numbers of all the columns and infers the types not declared again, which must be done after
+    /// modifying `data` directly.
+    pub fn invalidate_cache(&mut self) {
+        self.numbers = NumberCache::new(self.schema.len());
+        self.infer_types();
+    }
+
+    /// The cell parsed as a number like in arithmetic, or `None` if it is not a number or out of bounds.
+    /// The cells of a column are parsed at the first call for it, so that the arithmetic and aggregates on the
+    /// column do not parse the same strings for every row and every query.
+    pub(crate) fn get_number(&self, row: usize, col: usize, decimal: bool) -> Option<Number> {
+        let numbers = self.numbers.0.get(col)?[decimal as usize].get_or_init(|| {
+            (0..row_count(self))
+                .map(|row| {
+                    self.get(row, col)
+                        .and_then(|val| Number::parse(val, decimal))
+                })
+                .collect()
+        });
+        *numbers.get(row)?
+    }
+
+    /// The type of the column declared or inferred from its values when the table was made, or text if out of
+    /// bounds.
+    pub fn column_type(&self, col: usize) -> ColumnType {
+        self.schema
+            .get(col)
+            .map_or(ColumnType::Text, |schema| schema.ty)
+    }
+
+    /// Infers the type of the column from its values, since the cells are untyped strings. The narrowest type
+    /// that all the values are written in wins, ignoring NULLs (empty cells). A column without values is text.
+    fn infer_column_type(&self, col: usize) -> ColumnType {
+        let mut res: Option<ColumnType> = None;
+        for row in 0..row_count(self) {
+            let Some(val) = self.get(row, col).filter(|val| !val.is_empty()) else {
+                continue;
+            };
+            let ty = if val.parse::<i64>().is_ok() {
+                ColumnType::Integer
+            } else if Number::parse(val, false).is_some() {
+                ColumnType::Number
+            } else if let Some(date) = DateTime::parse(val, 0) {
+                if date.has_time {
+                    ColumnType::Timestamp
+                } else {
+                    ColumnType::Date
+                }
+            } else {
+                return ColumnType::Text;
+            };
+            res = Some(match res {
+                None => ty,
+                Some(prev) if prev == ty => ty,
+                Some(prev) if prev.is_numeric() && ty.is_numeric() => ColumnType::Number,
+                Some(prev) if prev.is_temporal() && ty.is_temporal() => ColumnType::Timestamp,
+                _ => return ColumnType::Text,
+            });
+        }
+        res.unwrap_or(ColumnType::Text)
+    }
+}
+
+/// The type of the values in a column, inferred by [`Table::column_type`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    /// Integers and decimals
+    Number,
+    /// Dates in ISO-8601 like `2024-01-31`
+    Date,
+    /// Timestamps in ISO-8601 like `2024-01-31 09:00:00`, or dates mixed with them
+    Timestamp,
+    Text,
+}
+
+impl ColumnType {
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Self::Integer | Self::Number)
+    }
+
+    pub fn is_temporal(self) -> bool {
+        matches!(self, Self::Date | Self::Timestamp)
+    }
+
+    /// Compares the values of this type, numbers numerically and dates chronologically, rather than as
+    /// strings where `10` is less than `9`. Values not written in the type like NULLs are compared as strings.
+    pub(crate) fn compare(self, lhs: &str, rhs: &str) -> std::cmp::Ordering {
+        if self.is_numeric()
+            && let (Some(lhs), Some(rhs)) = (Number::parse(lhs, false), Number::parse(rhs, false))
+        {
+            return match (lhs, rhs) {
+                (Number::Int(lhs), Number::Int(rhs)) => lhs.cmp(&rhs),
+                _ => lhs.to_f64().total_cmp(&rhs.to_f64()),
+            };
+        }
+        if self.is_temporal()
+            && let (Some(lhs), Some(rhs)) = (DateTime::parse(lhs, 0), DateTime::parse(rhs, 0))
+        {
+            return lhs.secs.cmp(&rhs.secs);
+        }
+        lhs.cmp(rhs)
+    }
+}
+
+impl std::str::FromStr for ColumnType {
+    type Err = String;
+
+    /// Parses the name of the type written by `Display`, like `integer`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "integer" => Ok(Self::Integer),
+            "number" => Ok(Self::Number),
+            "date" => Ok(Self::Date),
+            "timestamp" => Ok(Self::Timestamp),
+            "text" => Ok(Self::Text),
+            _ => Err(format!(
+                "Unknown column type {s}, expected integer, number, date, timestamp or text"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Integer => "integer",
+            Self::Number => "number",
+            Self::Date => "date",
+            Self::Timestamp => "timestamp",
+            Self::Text => "text",
+        })
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RowSchema {
     pub name: String,
+    /// Inferred from the values by [`Table::new`], unless declared by [`Table::declare_type`]
+    pub ty: ColumnType,
+    /// Whether `ty` is declared rather than inferred
+    pub declared: bool,
+}
+
+impl RowSchema {
+    /// A column of the name, whose type is to be inferred by [`Table::new`]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ty: ColumnType::Text,
+            declared: false,
+        }
+    }
 }
 
 pub fn make_table(name: &str, csv: &str) -> Result<Table, Box<dyn Error>> {
-    let csv = crate::csv::parse_csv(&csv)?;
+    make_table_with(name, csv, Some(','))
+}
+
+/// Same as [`make_table`], but the cells are separated by the delimiter like `'\t'` for TSV, or the one
+/// guessed from the header if `None`.
+pub fn make_table_with(
+    name: &str,
+    csv: &str,
+    delimiter: Option<char>,
+) -> Result<Table, Box<dyn Error>> {
+    let delimiter = delimiter.unwrap_or_else(|| crate::csv::detect_delimiter(csv));
+    let csv = crate::csv::parse_csv_with(csv, delimiter)?;
     let schema = csv
         .first()
         .ok_or_else(|| "CSV needs at least 1 line for the header".to_string())?
         .iter()
-        .map(|r| RowSchema {
-            name: r.trim().to_string(),
-        })
+        .map(|r| RowSchema::new(r.trim()))
         .collect::<Vec<_>>();
     let mut data = vec![];
     for record in &csv[1..] {
-        if record.len() == 0 {
+        if record.is_empty() {
             continue;
         }
         if record.len() != schema.len() {
             return Err("CSV needs the same number of columns as the header".into());
         }
-        for cell in record {
-            data.push(cell.trim().to_string());
-        }
+        data.extend(record.iter().cloned());
     }
-    Ok(Table {
-        name: name.to_string(),
-        schema,
-        data,
-    })
+    Ok(Table::new(name.to_string(), schema, data))
 }
 
 #[cfg(test)]
@@ -63,21 +315,69 @@ mod test {
         let table = make_table(table_name, csv).unwrap();
         assert_eq!(
             table,
-            Table {
-                name: table_name.to_string(),
-                schema: vec![
-                    RowSchema {
-                        name: "id".to_string()
-                    },
-                    RowSchema {
-                        name: "name".to_string()
-                    }
-                ],
-                data: ["1", "a", "2", "b", "3", "c"]
+            Table::new(
+                table_name.to_string(),
+                vec![RowSchema::new("id"), RowSchema::new("name")],
+                ["1", "a", "2", "b", "3", "c"]
                     .into_iter()
                     .map(|s| s.to_string())
                     .collect(),
-            }
-        )
+            )
+        );
+        assert_eq!(table.find_col("name", false), Some(1));
+        assert_eq!(table.find_col("NAME", false), None);
+        assert_eq!(table.find_col("NAME", true), Some(1));
+    }
+
+    #[test]
+    fn test_column_type() {
+        let csv = "id,price,day,at,name,empty\n\
+            1,1.5,2024-01-31,2024-01-31 09:00,a,\"\"\n\
+            2,2,2024-02-01,2024-02-01,3,\"\"\n\
+            \"\",\"\",\"\",\"\",\"\",\"\"\n";
+        let table = make_table("t", csv).unwrap();
+        let types: Vec<_> = (0..table.schema.len())
+            .map(|col| table.column_type(col))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ColumnType::Integer,
+                ColumnType::Number,
+                ColumnType::Date,
+                ColumnType::Timestamp,
+                ColumnType::Text,
+                ColumnType::Text,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_make_table_empty_cells() {
+        let table = make_table("a", "x,y,z\n1,,3\n,2,\n,,\n\"\",,\n").unwrap();
+        assert_eq!(
+            table.data,
+            ["1", "", "3", "", "2", "", "", "", "", "", "", ""]
+        );
+        assert_eq!(
+            make_table("a", "x,y\n1,2,\n").unwrap_err().to_string(),
+            "CSV needs the same number of columns as the header"
+        );
+    }
+
+    #[test]
+    fn test_get_number() {
+        let mut table = make_table("a", "id,price\n1,1.5\n2,x\n").unwrap();
+        assert_eq!(table.get_number(0, 1, false), Some(Number::Float(1.5)));
+        assert_eq!(table.get_number(1, 1, false), None);
+        assert_eq!(table.get_number(1, 0, false), Some(Number::Int(2)));
+        assert_eq!(table.get_number(2, 0, false), None);
+
+        // The cached numbers follow the writes
+        assert_eq!(table.set(1, 1, "3".to_string()), Some("x".to_string()));
+        assert_eq!(table.get_number(1, 1, false), Some(Number::Int(3)));
+        table.data[0] = "10".to_string();
+        table.invalidate_cache();
+        assert_eq!(table.get_number(0, 0, false), Some(Number::Int(10)));
     }
 }
@@ -0,0 +1,114 @@
+//! Helpers for the tests of applications embedding bogosql, to build tables in memory and check the results
+//! of queries on them concisely. Enabled by the `test-util` feature.
+//!
+//! ```
+//! use bogosql::{assert_query_results, test_util::{db_from_tables, table_from_rows}};
+//!
+//! let db = db_from_tables([table_from_rows("t", &["id", "name"], &[&["1", "x"], &["2", ""]])]);
+//! assert_query_results!(db, "SELECT name FROM t WHERE id = 1", [["name"], ["x"]]);
+//! ```
+
+use crate::{
+    db::Database,
+    session::{Session, StatementOutput},
+    table::{RowSchema, Table},
+};
+
+/// Makes a table from the column names and the rows of cells, where an empty cell is NULL.
+///
+/// # Panics
+///
+/// If a row does not have as many cells as the columns.
+pub fn table_from_rows(name: &str, headers: &[&str], rows: &[&[&str]]) -> Table {
+    let schema = headers
+        .iter()
+        .map(|header| RowSchema::new(*header))
+        .collect();
+    let mut data = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        assert_eq!(
+            row.len(),
+            headers.len(),
+            "Row {i} of table {name} has {} cells for {} columns",
+            row.len(),
+            headers.len()
+        );
+        data.extend(row.iter().map(|cell| cell.to_string()));
+    }
+    Table::new(name.to_string(), schema, data)
+}
+
+/// Makes a database of the tables, keyed by their names.
+pub fn db_from_tables(tables: impl IntoIterator<Item = Table>) -> Database {
+    tables
+        .into_iter()
+        .map(|table| (table.name.clone(), table))
+        .collect()
+}
+
+/// Makes a database of the tables in CSV with the header in the first line, like the files in `data`.
+///
+/// # Panics
+///
+/// If a CSV cannot be parsed.
+pub fn db_from_csv(tables: &[(&str, &str)]) -> Database {
+    db_from_tables(tables.iter().map(|(name, csv)| {
+        crate::make_table(name, csv).unwrap_or_else(|e| panic!("Table {name}: {e}"))
+    }))
+}
+
+/// Runs the statement with the default options and returns the rows with the header as the first row.
+///
+/// # Panics
+///
+/// If the statement fails, or is `EXPLAIN`, which has a plan instead of rows.
+pub fn query_rows(db: &Database, sql: &str) -> Vec<Vec<String>> {
+    match Session::new(db).execute(sql) {
+        Ok(StatementOutput::Rows(rows)) => rows,
+        Ok(StatementOutput::Plan(_)) => panic!("{sql} yields a plan rather than rows"),
+        Err(e) => panic!("{sql} failed: {e}"),
+    }
+}
+
+/// Asserts that the statement yields the rows, given as arrays of cells with the header as the first row.
+#[macro_export]
+macro_rules! assert_query_results {
+    ($db:expr, $sql:expr, $expected:expr $(,)?) => {{
+        let sql = $sql;
+        let expected: Vec<Vec<String>> = $expected
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect();
+        assert_eq!(
+            $crate::test_util::query_rows(&$db, sql),
+            expected,
+            "Results of {sql}"
+        );
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fixtures() {
+        let db = db_from_tables([
+            table_from_rows("a", &["id", "name"], &[&["1", "x"], &["2", ""]]),
+            table_from_rows("b", &["a_id"], &[&["2"]]),
+        ]);
+        assert_query_results!(
+            db,
+            "SELECT id, name FROM a INNER JOIN b ON id = a_id",
+            [["id", "name"], ["2", ""]]
+        );
+        let db = db_from_csv(&[("a", "id,name\n1,x\n")]);
+        assert_query_results!(db, "SELECT name FROM a", [["name"], ["x"]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Row 1 of table a has 1 cells for 2 columns")]
+    fn test_row_length() {
+        table_from_rows("a", &["id", "name"], &[&["1", "x"], &["2"]]);
+    }
+}
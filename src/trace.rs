@@ -0,0 +1,148 @@
+//! Step-by-step execution trace.
+//! The executor visits every combination of rows in the joined tables (the "cursor"),
+//! so recording what happened to each combination shows how a query is actually evaluated.
+
+use std::time::Duration;
+
+use crate::select::RowCursor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceOutcome {
+    /// The join condition of the given join clause (0-based) rejected the combination.
+    RejectedByJoin(usize),
+    /// The WHERE clause rejected the combination.
+    RejectedByWhere,
+    /// The QUALIFY clause rejected the combination.
+    RejectedByQualify,
+    /// One of the tables has no row left under the cursor.
+    NoRow,
+    /// The row passed the filters, but was skipped by OFFSET.
+    Skipped,
+    /// The row was sent to the output.
+    Emitted,
+    /// The row was accumulated into the aggregate functions.
+    Aggregated,
+}
+
+impl std::fmt::Display for TraceOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RejectedByJoin(i) => write!(f, "rejected by join #{i}"),
+            Self::RejectedByWhere => write!(f, "rejected by WHERE"),
+            Self::RejectedByQualify => write!(f, "rejected by QUALIFY"),
+            Self::NoRow => write!(f, "no row"),
+            Self::Skipped => write!(f, "skipped by OFFSET"),
+            Self::Emitted => write!(f, "emitted"),
+            Self::Aggregated => write!(f, "aggregated"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Row index of each joined table, in the order of FROM and JOIN clauses.
+    /// None means the table is exhausted, which shows up as NULLs in a left join.
+    pub cursor: Vec<Option<usize>>,
+    pub outcome: TraceOutcome,
+}
+
+impl std::fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        for (i, row) in self.cursor.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            match row {
+                Some(row) => write!(f, "{row}")?,
+                None => write!(f, "-")?,
+            }
+        }
+        write!(f, "] {}", self.outcome)
+    }
+}
+
+/// Counters of the work done by an execution, to tell why a query is slow.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecStats {
+    /// Names of the tables in the order of FROM and JOIN clauses
+    pub tables: Vec<String>,
+    /// Rows read from each table. A row of the inner table of a nested loop join is counted each time it is
+    /// combined with a row of the outer table.
+    pub rows_scanned: Vec<usize>,
+    /// Combinations of rows of the joined tables evaluated against the join conditions and WHERE
+    pub combinations: usize,
+    /// Rows written to the output, excluding the header
+    pub rows_emitted: usize,
+    /// Wall-clock time of the execution, or None if the clock is not available, e.g. in a wasm runtime
+    pub elapsed: Option<Duration>,
+    /// The rows of the last combination, to count a row of a table once while the combinations advance the
+    /// other tables
+    last_cursor: Vec<Option<usize>>,
+}
+
+impl ExecStats {
+    fn record(&mut self, row_cursor: &[RowCursor]) {
+        self.combinations += 1;
+        self.rows_scanned.resize(row_cursor.len(), 0);
+        self.last_cursor.resize(row_cursor.len(), None);
+        for (i, cursor) in row_cursor.iter().enumerate() {
+            if cursor.row.is_some() && cursor.row != self.last_cursor[i] {
+                self.rows_scanned[i] += 1;
+            }
+            self.last_cursor[i] = cursor.row;
+        }
+    }
+}
+
+impl std::fmt::Display for ExecStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Rows scanned:")?;
+        for (i, rows) in self.rows_scanned.iter().enumerate() {
+            let table = self.tables.get(i).map_or("?", |table| table.as_str());
+            write!(f, "{} {table} {rows}", if i == 0 { "" } else { "," })?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Combinations evaluated: {}", self.combinations)?;
+        write!(f, "Rows emitted: {}", self.rows_emitted)?;
+        if let Some(elapsed) = self.elapsed {
+            write!(f, "\nElapsed: {:.3} ms", elapsed.as_secs_f64() * 1000.)?;
+        }
+        Ok(())
+    }
+}
+
+/// A log of trace events with a cap on its length, since the number of combinations grows
+/// multiplicatively with joins.
+#[derive(Debug, Clone, Default)]
+pub struct ExecTrace {
+    pub events: Vec<TraceEvent>,
+    pub max_events: usize,
+    /// Set when some events were dropped because of `max_events`.
+    pub truncated: bool,
+    /// Counted for all the combinations, regardless of `max_events`
+    pub stats: ExecStats,
+}
+
+impl ExecTrace {
+    pub fn new(max_events: usize) -> Self {
+        Self {
+            events: vec![],
+            max_events,
+            truncated: false,
+            stats: ExecStats::default(),
+        }
+    }
+
+    pub(crate) fn record(&mut self, row_cursor: &[RowCursor], outcome: TraceOutcome) {
+        self.stats.record(row_cursor);
+        if self.max_events <= self.events.len() {
+            self.truncated = true;
+            return;
+        }
+        self.events.push(TraceEvent {
+            cursor: row_cursor.iter().map(|c| c.row).collect(),
+            outcome,
+        });
+    }
+}
@@ -0,0 +1,371 @@
+//! Values of the cells, and the text of the values written for other components or programs to read back:
+//! CSV, JSON and SQL string literals.
+//! Cells are strings, and a cell is a number, a boolean, a date or NULL only if it is written exactly as
+//! [`format`] writes that value, e.g. `1.5` but not `1.50` or `+1.5`, which are text. So
+//! `format(&parse(cell)) == cell` for every cell, and `parse(&format(&value)) == value` for every value other
+//! than a text that spells another value, like `'1'`. The ASCII tables of
+//! [`BufferOutput`](crate::BufferOutput) show the cells as they are, since they are for reading rather than
+//! re-reading.
+
+use std::io::Write;
+
+use crate::{datetime::DateTime, select::write_json_str};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Value<'a> {
+    /// An empty cell
+    Null,
+    Bool(bool),
+    Int(i64),
+    /// A finite number that is not an integer in i64, or written with a fraction like `2.0`
+    Float(f64),
+    /// A date or a timestamp without an offset
+    Date(DateTime),
+    Text(&'a str),
+}
+
+/// The value of the cell, which is text unless the cell is written as `format` writes another value.
+pub(crate) fn parse(cell: &str) -> Value<'_> {
+    let value = if cell.is_empty() {
+        Value::Null
+    } else if let Ok(val) = cell.parse() {
+        Value::Bool(val)
+    } else if let Ok(val) = cell.parse() {
+        Value::Int(val)
+    } else if let Ok(val) = cell.parse::<f64>()
+        && val.is_finite()
+    {
+        Value::Float(val)
+    } else if let Some(val) = DateTime::parse(cell, 0) {
+        Value::Date(val)
+    } else {
+        return Value::Text(cell);
+    };
+    // Another spelling of the value, like `007` or `1.50`, is kept as it is
+    if format(&value) == cell {
+        value
+    } else {
+        Value::Text(cell)
+    }
+}
+
+/// The cell of the value. A float is written in the shortest form that reads back as the same float, with
+/// a fraction or an exponent, e.g. `2.0` or `1e300`.
+pub(crate) fn format(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(val) => val.to_string(),
+        Value::Int(val) => val.to_string(),
+        Value::Float(val) => format!("{val:?}"),
+        Value::Date(val) => val.to_string(),
+        Value::Text(val) => val.to_string(),
+    }
+}
+
+/// Writes the cell as a JSON value: NULL as `null`, a boolean or a number as it is, and others as a string.
+pub(crate) fn write_json(out: &mut impl Write, cell: &str) -> std::io::Result<()> {
+    match parse(cell) {
+        Value::Null => write!(out, "null"),
+        Value::Bool(_) | Value::Int(_) | Value::Float(_) => write!(out, "{cell}"),
+        Value::Date(_) | Value::Text(_) => write_json_str(out, cell),
+    }
+}
+
+/// Writes the cell as a SQL string literal, which evaluates to the same cell, with a quote written as `''`.
+pub(crate) fn write_literal(f: &mut impl std::fmt::Write, cell: &str) -> std::fmt::Result {
+    write!(f, "'{}'", cell.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        csv::{parse_csv, write_csv_row},
+        error_json::format_error_json,
+        options::ExecOptions,
+        parse_error::format_error_text,
+        select::{BufferOutput, CsvOutput, JsonOutput, QueryOutput},
+        session::{Session, StatementOutput},
+        test_util::{db_from_csv, db_from_tables, table_from_rows},
+    };
+
+    /// A xorshift generator, so that the property tests are reproducible without dependencies
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn text(&mut self) -> String {
+            const CHARS: &[char] = &[
+                'a', 'Z', '0', '1', '9', '.', '-', '+', 'e', ' ', '\t', ',', ';', '"', '\'', '\n',
+                '\r', ':', 'T', 'é', '日',
+            ];
+            let len = self.below(12);
+            (0..len)
+                .map(|_| CHARS[self.below(CHARS.len() as u64) as usize])
+                .collect()
+        }
+
+        /// A text of the characters of more than 1 byte, combining marks, and the characters whose case mapping
+        /// changes the length, like `ß` to `SS`
+        fn unicode_text(&mut self) -> String {
+            const CHARS: &[&str] = &[
+                "a",
+                "Z",
+                " ",
+                "é",
+                "e\u{301}",
+                "\u{301}",
+                "日",
+                "本",
+                "😀",
+                "👩\u{200d}💻",
+                "ß",
+                "İ",
+                "ﬁ",
+                "Σ",
+                ",",
+                "\"",
+                "'",
+                "%",
+                "_",
+                "\t",
+            ];
+            let len = 1 + self.below(8);
+            (0..len)
+                .map(|_| CHARS[self.below(CHARS.len() as u64) as usize])
+                .collect()
+        }
+
+        fn value<'a>(&mut self, text: &'a str) -> Value<'a> {
+            match self.below(6) {
+                0 => Value::Null,
+                1 => Value::Bool(self.below(2) == 0),
+                2 => Value::Int(match self.below(3) {
+                    0 => self.below(1000) as i64 - 500,
+                    1 => i64::MIN,
+                    _ => self.next() as i64,
+                }),
+                3 => Value::Float(match self.below(3) {
+                    0 => (self.below(2000) as f64 - 1000.) / 8.,
+                    1 => self.below(1000) as f64,
+                    _ => {
+                        let val = f64::from_bits(self.next());
+                        if val.is_finite() { val } else { 0.1 }
+                    }
+                }),
+                4 => {
+                    let date = format!(
+                        "{:04}-{:02}-{:02}",
+                        1000 + self.below(9000),
+                        1 + self.below(12),
+                        1 + self.below(28)
+                    );
+                    let date = if self.below(2) == 0 {
+                        date
+                    } else {
+                        let (h, m, s) = (self.below(24), self.below(60), self.below(60));
+                        format!("{date} {h:02}:{m:02}:{s:02}")
+                    };
+                    Value::Date(DateTime::parse(&date, 0).unwrap())
+                }
+                _ => parse(text),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(""), Value::Null);
+        assert_eq!(parse("true"), Value::Bool(true));
+        assert_eq!(parse("-12"), Value::Int(-12));
+        assert_eq!(parse("2.5"), Value::Float(2.5));
+        assert_eq!(parse("2.0"), Value::Float(2.));
+        assert!(matches!(parse("2024-01-31"), Value::Date(_)));
+        for text in [
+            "TRUE", "007", "+1", "1.50", "1E5", "inf", "NaN", " 1", "2024-1-9", "abc",
+        ] {
+            assert_eq!(parse(text), Value::Text(text));
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..10000 {
+            let text = rng.text();
+            assert_eq!(format(&parse(&text)), text);
+            let value = rng.value(&text);
+            let cell = format(&value);
+            assert_eq!(parse(&cell), value, "{cell:?}");
+        }
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..1000 {
+            let texts: Vec<_> = (0..1 + rng.below(4)).map(|_| rng.text()).collect();
+            let row: Vec<_> = texts.iter().map(|text| format(&rng.value(text))).collect();
+            // A single empty cell is written as a blank line, which is an empty record
+            if row.len() == 1 && row[0].is_empty() {
+                continue;
+            }
+            let mut csv = vec![];
+            write_csv_row(&mut csv, &row).unwrap();
+            let csv = String::from_utf8(csv).unwrap();
+            assert_eq!(
+                parse_csv(csv.trim_end_matches('\n')),
+                Ok(vec![row]),
+                "{csv:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_literal_round_trip() {
+        let db = db_from_tables([table_from_rows("t", &["x"], &[&["1"]])]);
+        let session = Session::new(&db);
+        let mut rng = Rng(0xdead_beef_cafe_f00d);
+        for _ in 0..200 {
+            let text = rng.text();
+            let cell = format(&rng.value(&text));
+            let mut sql = "SELECT ".to_string();
+            write_literal(&mut sql, &cell).unwrap();
+            sql.push_str(" FROM t");
+            let Ok(StatementOutput::Rows(rows)) = session.execute(&sql) else {
+                panic!("{sql} failed");
+            };
+            assert_eq!(rows[1], [cell], "{sql}");
+        }
+    }
+
+    /// Texts of multibyte and combining characters go through CSV, the string functions, LIKE, the errors and
+    /// all the output formats without being split inside a character
+    #[test]
+    fn test_unicode_end_to_end() {
+        let mut rng = Rng(0x0123_4567_89ab_cdef);
+        for _ in 0..200 {
+            let texts: Vec<_> = (0..1 + rng.below(3)).map(|_| rng.unicode_text()).collect();
+            let mut csv = b"s\n".to_vec();
+            for text in &texts {
+                write_csv_row(&mut csv, std::slice::from_ref(text)).unwrap();
+            }
+            let db = db_from_csv(&[("t", std::str::from_utf8(&csv).unwrap())]);
+            let mut session = Session::new(&db);
+            let query = |session: &Session, sql: &str| match session.execute(sql) {
+                Ok(StatementOutput::Rows(rows)) => rows,
+                res => panic!("{sql}: {res:?}"),
+            };
+
+            let text = &texts[rng.below(texts.len() as u64) as usize];
+            let chars: Vec<_> = text.chars().collect();
+            let (start, len, width) = (rng.below(5) + 1, rng.below(5), rng.below(10));
+            let prefix: String = chars[..rng.below(chars.len() as u64) as usize]
+                .iter()
+                .flat_map(|&c| match c {
+                    '%' | '_' | '!' => vec!['!', c],
+                    c => vec![c],
+                })
+                .collect();
+            let at = rng.below(chars.len() as u64) as usize;
+            let needle: String = chars[at..].iter().take(2).collect();
+            let mut sql = format!(
+                "SELECT s, length(s), upper(s), lower(s), substr(s, {start}, {len}), \
+                    lpad(s, {width}, '·'), instr(s, "
+            );
+            write_literal(&mut sql, &needle).unwrap();
+            sql.push_str("), s LIKE ");
+            write_literal(&mut sql, &format!("{prefix}%")).unwrap();
+            sql.push_str(" ESCAPE '!', s LIKE ");
+            write_literal(&mut sql, &"_".repeat(chars.len())).unwrap();
+            sql.push_str(" FROM t WHERE s = ");
+            write_literal(&mut sql, text).unwrap();
+            let rows = query(&session, &sql);
+            let row = &rows[1];
+            let padded: String = match (width as usize).checked_sub(chars.len()) {
+                Some(pad) => "·".repeat(pad) + text,
+                None => chars[..width as usize].iter().collect(),
+            };
+            assert_eq!(
+                row[..6],
+                [
+                    text.clone(),
+                    chars.len().to_string(),
+                    text.to_uppercase(),
+                    text.to_lowercase(),
+                    chars
+                        .iter()
+                        .skip(start as usize - 1)
+                        .take(len as usize)
+                        .collect(),
+                    padded,
+                ],
+                "{sql}"
+            );
+            let pos: usize = row[6].parse().unwrap();
+            assert!(
+                chars[pos - 1..]
+                    .iter()
+                    .collect::<String>()
+                    .starts_with(&needle),
+                "{sql}"
+            );
+            assert_eq!(row[7..], ["true", "true"], "{sql}");
+
+            let max_cell_size = rng.below(8) as usize;
+            session.options = ExecOptions::DEFAULT.with_max_cell_size(Some(max_cell_size));
+            for row in &query(&session, "SELECT s FROM t")[1..] {
+                if texts.contains(&row[0]) {
+                    continue;
+                }
+                let (kept, _) = row[0].rsplit_once("...[").unwrap();
+                assert!(kept.len() <= max_cell_size, "{row:?}");
+                assert!(texts.iter().any(|text| text.starts_with(kept)), "{row:?}");
+            }
+
+            let rows = query(&Session::new(&db), "SELECT s, upper(s) FROM t");
+            let mut buf = BufferOutput::default();
+            let (mut csv, mut json) = (CsvOutput::default(), JsonOutput::default());
+            for row in &rows {
+                buf.output(row).unwrap();
+                csv.output(row).unwrap();
+                json.output(row).unwrap();
+            }
+            let csv = String::from_utf8(csv.0).unwrap();
+            assert_eq!(parse_csv(csv.trim_end_matches('\n')).unwrap(), rows);
+            String::from_utf8(json.finish()).unwrap();
+            buf.format(&mut vec![]).unwrap();
+            buf.format_vertical(&mut vec![]).unwrap();
+
+            // A query with the text as it is fails in parsing or finding the column somewhere in the text
+            let sql = format!("SELECT {text} FROM t");
+            if let Err(e) = Session::new(&db).execute(&sql) {
+                format_error_text(e.as_ref(), &sql);
+                format_error_json(e.as_ref(), &sql);
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut out = vec![];
+        for cell in ["", "false", "-3", "1e300", "1.50", "2024-01-31", "a\"b"] {
+            write_json(&mut out, cell).unwrap();
+            out.push(b' ');
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"null false -3 1e300 "1.50" "2024-01-31" "a\"b" "#
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Window functions like `row_number() OVER (PARTITION BY a ORDER BY b)` and the QUALIFY clause filtering on them.
+//! A window function needs all the rows that passed WHERE before yielding any value, so the rows are collected
+//! first, and the values of each window function are computed for all of them before evaluating the columns.
+
+use std::{cell::Cell, collections::HashMap, error::Error};
+
+use crate::{
+    eval::{
+        AggregateResult, EvalError, aggregate_expr, coerce_bool, eval_expr, find_aggregate_fn,
+        static_type,
+    },
+    select::{Expr, OrderKey, QueryContext, QueryOutput, RowCursor, scan_rows},
+    table::ColumnType,
+    trace::TraceOutcome,
+};
+
+pub(crate) struct WindowState {
+    /// Values of each window function for each row, keyed by the address of the AST node
+    values: HashMap<usize, Vec<String>>,
+    /// Index of the row being evaluated
+    row: Cell<usize>,
+}
+
+impl WindowState {
+    pub fn value(&self, expr: &Expr) -> Option<&String> {
+        self.values
+            .get(&(expr as *const _ as usize))?
+            .get(self.row.get())
+    }
+}
+
+/// Window function nodes in the output columns and the QUALIFY clause.
+fn find_windows<'a>(cols: &'a [Expr], qualify: Option<&'a Expr>) -> Vec<&'a Expr> {
+    let mut windows = vec![];
+    for ex in cols.iter().chain(qualify) {
+        ex.visit(&mut |ex| {
+            if matches!(ex, Expr::Window { .. }) {
+                windows.push(ex);
+            }
+        });
+    }
+    windows
+}
+
+pub(crate) fn has_window_fn(cols: &[Expr], qualify: Option<&Expr>) -> bool {
+    !find_windows(cols, qualify).is_empty()
+}
+
+pub(crate) fn exec_windowed(
+    out: &mut impl QueryOutput,
+    ctx: &QueryContext,
+    cols: &[Expr],
+) -> Result<(), Box<dyn Error>> {
+    let mut cursors = vec![];
+    scan_rows(ctx, cols, |row_cursor| {
+        cursors.push(row_cursor.to_vec());
+        Ok(true)
+    })?;
+
+    let mut values = HashMap::new();
+    for window in find_windows(cols, ctx.sql.qualify.as_ref()) {
+        values.insert(
+            window as *const _ as usize,
+            compute_window(ctx, cols, window, &cursors)?,
+        );
+    }
+    let state = WindowState {
+        values,
+        row: Cell::new(0),
+    };
+    let ctx = ctx.with_window(&state);
+
+    let eval = |ex: &Expr, row_cursor: &[RowCursor]| match eval_expr(
+        ex,
+        cols,
+        &ctx,
+        row_cursor,
+        &AggregateResult::default(),
+    ) {
+        Ok(res) => Ok(res),
+        Err(EvalError::CursorNone(_)) => Ok("".to_string()),
+        Err(e) => Err(e),
+    };
+
+    let offset = ctx.sql.offset.unwrap_or(0);
+    let limit = ctx.sql.limit.unwrap_or(usize::MAX);
+    let mut printed_rows = 0;
+    for (i, row_cursor) in cursors.iter().enumerate() {
+        if offset.saturating_add(limit) <= printed_rows {
+            break;
+        }
+        state.row.set(i);
+        if let Some(ref qualify) = ctx.sql.qualify
+            && !coerce_bool(&eval(qualify, row_cursor)?)
+        {
+            ctx.record(row_cursor, TraceOutcome::RejectedByQualify);
+            continue;
+        }
+        let values = cols
+            .iter()
+            .map(|ex| eval(ex, row_cursor))
+            .collect::<Result<Vec<_>, _>>()?;
+        if offset <= printed_rows {
+            ctx.record(row_cursor, TraceOutcome::Emitted);
+            out.output(&values)?;
+        } else {
+            ctx.record(row_cursor, TraceOutcome::Skipped);
+        }
+        printed_rows += 1;
+    }
+    Ok(())
+}
+
+/// Computes the value of a window function for each row. An aggregate function like `sum(x)` aggregates the
+/// rows of the partition, or with ORDER BY, the rows up to the row and its peers with the same sort keys, which
+/// is a running total.
+fn compute_window(
+    ctx: &QueryContext,
+    cols: &[Expr],
+    window: &Expr,
+    cursors: &[Vec<RowCursor>],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let Expr::Window {
+        func,
+        partition_by,
+        order_by,
+    } = window
+    else {
+        unreachable!()
+    };
+    let Expr::AggregateFn { name, .. } = &**func else {
+        return Err(EvalError::UnknownWindowFn(func.to_string()).into());
+    };
+    let aggregate = find_aggregate_fn(func).is_some();
+
+    let eval = |ex: &Expr, row_cursor: &[RowCursor]| match eval_expr(
+        ex,
+        cols,
+        ctx,
+        row_cursor,
+        &AggregateResult::default(),
+    ) {
+        Ok(res) => Ok(res),
+        Err(EvalError::CursorNone(_)) => Ok("".to_string()),
+        Err(e) => Err(e),
+    };
+
+    let mut partitions: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for (i, row_cursor) in cursors.iter().enumerate() {
+        let key = partition_by
+            .iter()
+            .map(|ex| eval(ex, row_cursor))
+            .collect::<Result<Vec<_>, _>>()?;
+        partitions.entry(key).or_default().push(i);
+    }
+
+    let sort_keys = cursors
+        .iter()
+        .map(|row_cursor| {
+            order_by
+                .iter()
+                .map(|order_by| match order_by.key {
+                    OrderKey::Expr(ref ex) => eval(ex, row_cursor),
+                    OrderKey::All => Err(EvalError::UnknownWindowFn(window.to_string())),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let types: Vec<_> = order_by
+        .iter()
+        .map(|order_by| match order_by.key {
+            OrderKey::Expr(ref ex) => static_type(ex, cols, ctx).unwrap_or(ColumnType::Text),
+            OrderKey::All => ColumnType::Text,
+        })
+        .collect();
+    let mut res = vec![String::new(); cursors.len()];
+    for rows in partitions.values_mut() {
+        rows.sort_by(|&lhs, &rhs| {
+            for (i, order_by) in order_by.iter().enumerate() {
+                let res = order_by.compare(types[i], &sort_keys[lhs][i], &sort_keys[rhs][i]);
+                if res.is_ne() {
+                    return res;
+                }
+            }
+            std::cmp::Ordering::Equal
+        });
+
+        if aggregate {
+            let mut results = AggregateResult {
+                active: true,
+                ..AggregateResult::default()
+            };
+            // Without ORDER BY, all the rows of the partition are peers
+            let peers = |lhs: usize, rhs: usize| sort_keys[lhs] == sort_keys[rhs];
+            for peers in rows.chunk_by(|&lhs, &rhs| peers(lhs, rhs)) {
+                for &row in peers {
+                    aggregate_expr(func, cols, ctx, &cursors[row], &mut results)?;
+                }
+                for &row in peers {
+                    res[row] = eval_expr(func, cols, ctx, &cursors[row], &results)?;
+                }
+            }
+            continue;
+        }
+
+        let (mut rank, mut dense_rank) = (0, 0);
+        for (pos, &row) in rows.iter().enumerate() {
+            // Peers with the same sort keys share the rank
+            if pos == 0 || sort_keys[rows[pos - 1]] != sort_keys[row] {
+                rank = pos + 1;
+                dense_rank += 1;
+            }
+            res[row] = match name.to_ascii_lowercase().as_str() {
+                "row_number" => pos + 1,
+                "rank" => rank,
+                "dense_rank" => dense_rank,
+                _ => return Err(EvalError::UnknownWindowFn(name.clone()).into()),
+            }
+            .to_string();
+        }
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        Database, Statement, make_table,
+        parser::statement,
+        select::{BufferOutput, exec_select},
+    };
+
+    #[test]
+    fn test_qualify() {
+        let csv = r#"email,updated,name
+a@x, 2024-01-01, Alice
+b@x, 2024-02-01, Bob
+a@x, 2024-03-01, Alicia
+b@x, 2024-01-15, Bobby
+"#;
+        let mut db = Database::new();
+        db.insert("t".to_string(), make_table("t", csv).unwrap());
+        let sql = "SELECT email, name FROM t \
+            QUALIFY row_number() OVER (PARTITION BY email ORDER BY updated DESC) = 1 ORDER BY email";
+        let (_, Statement::Select(stmt)) = statement(sql).unwrap() else {
+            panic!("Not a SELECT statement");
+        };
+        let mut buf = BufferOutput::default();
+        exec_select(&mut buf, &db, &stmt).unwrap();
+        assert_eq!(
+            buf.0,
+            vec![
+                vec!["email", "name"],
+                vec!["a@x", "Alicia"],
+                vec!["b@x", "Bob"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rank() {
+        let csv = r#"name,score
+a, 3
+b, 5
+c, 3
+d, 1
+"#;
+        let mut db = Database::new();
+        db.insert("t".to_string(), make_table("t", csv).unwrap());
+        let sql = "SELECT name, rank() OVER (ORDER BY score DESC), dense_rank() OVER (ORDER BY score DESC) \
+            FROM t ORDER BY name";
+        let (_, Statement::Select(stmt)) = statement(sql).unwrap() else {
+            panic!("Not a SELECT statement");
+        };
+        let mut buf = BufferOutput::default();
+        exec_select(&mut buf, &db, &stmt).unwrap();
+        assert_eq!(
+            buf.0[1..],
+            vec![
+                vec!["a", "2", "2"],
+                vec!["b", "1", "1"],
+                vec!["c", "2", "2"],
+                vec!["d", "4", "3"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aggregate_over() {
+        let db = crate::test_util::db_from_csv(&[("t", "k,v\na,1\nb,2\nb,3\nc,10\nc,20\nc,10\n")]);
+        crate::assert_query_results!(
+            db,
+            "SELECT k, v, sum(v) OVER (PARTITION BY k), count(*) OVER () FROM t ORDER BY k, v",
+            [
+                ["k", "v", "sum(v) OVER (PARTITION BY k)", "count(*) OVER ()"],
+                ["a", "1", "1", "6"],
+                ["b", "2", "5", "6"],
+                ["b", "3", "5", "6"],
+                ["c", "10", "40", "6"],
+                ["c", "10", "40", "6"],
+                ["c", "20", "40", "6"],
+            ]
+        );
+        // With ORDER BY, a running total up to the peers of the row
+        crate::assert_query_results!(
+            db,
+            "SELECT v, sum(v) OVER (PARTITION BY k ORDER BY v) FROM t WHERE k = 'c' ORDER BY v",
+            [
+                ["v", "sum(v) OVER (PARTITION BY k ORDER BY v ASC)"],
+                ["10", "20"],
+                ["10", "20"],
+                ["20", "40"],
+            ]
+        );
+        crate::assert_query_results!(
+            db,
+            "SELECT k, v FROM t QUALIFY v = max(v) OVER (PARTITION BY k) ORDER BY k",
+            [["k", "v"], ["a", "1"], ["b", "3"], ["c", "20"]]
+        );
+    }
+}
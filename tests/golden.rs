@@ -0,0 +1,114 @@
+//! Golden-file tests running the queries in `tests/golden/*.sql` against the bundled sample data in `data/`,
+//! which is also what the wasm demo shows.
+//! Each query is compared with the formatted result in the `.out` file of the same name.
+//! Run with `UPDATE_GOLDEN=1` to write the current results as the expected ones after an intended change.
+
+use std::{fs, path::Path};
+
+use bogosql::{
+    BufferOutput, Database, ExecOptions, SavedQueries, Statement, exec_compare_schema,
+    exec_describe, exec_search, exec_show_functions, exec_show_tables, format_compound,
+    format_explain, format_select_with, make_table, parse_statement,
+};
+
+fn load_data() -> Database {
+    let mut db = Database::new();
+    for entry in fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("data")).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_stem().unwrap().to_string_lossy().to_string();
+        let table = make_table(&name, &fs::read_to_string(&path).unwrap()).unwrap();
+        db.insert(name, table);
+    }
+    db
+}
+
+/// The results are reproducible in the deterministic mode, even of a query calling `now()`
+const OPTIONS: ExecOptions = ExecOptions::DEFAULT.with_deterministic(true);
+
+fn run_query(db: &Database, sql: &str) -> String {
+    let stmt = parse_statement(sql).unwrap();
+    let mut buf = vec![];
+    match stmt {
+        Statement::Select(stmt) => format_select_with(&mut buf, db, &stmt, &OPTIONS, None).unwrap(),
+        Statement::Explain(explain) => {
+            format_explain(&mut buf, db, &explain.stmt, &OPTIONS, explain.format).unwrap()
+        }
+        Statement::Compound(stmt) => format_compound(&mut buf, db, &stmt, &OPTIONS).unwrap(),
+        Statement::Search(value) => {
+            let mut out = BufferOutput::default();
+            exec_search(&mut out, db, &value).unwrap();
+            out.format(&mut buf).unwrap()
+        }
+        Statement::CompareSchema(old, new) => {
+            let mut out = BufferOutput::default();
+            exec_compare_schema(&mut out, db, &old, &new).unwrap();
+            out.format(&mut buf).unwrap()
+        }
+        Statement::Describe(table) => {
+            let mut out = BufferOutput::default();
+            exec_describe(&mut out, db, &table).unwrap();
+            out.format(&mut buf).unwrap()
+        }
+        Statement::ShowTables => {
+            let mut out = BufferOutput::default();
+            exec_show_tables(&mut out, db).unwrap();
+            out.format(&mut buf).unwrap()
+        }
+        Statement::ShowFunctions => {
+            let mut out = BufferOutput::default();
+            exec_show_functions(&mut out).unwrap();
+            out.format(&mut buf).unwrap()
+        }
+        Statement::ShowQueries => BufferOutput(SavedQueries::default().rows())
+            .format(&mut buf)
+            .unwrap(),
+    }
+    String::from_utf8(buf).unwrap()
+}
+
+/// Trailing spaces of the padded table cells are ignored, since editors tend to strip them.
+fn normalize(s: &str) -> String {
+    s.lines()
+        .map(|line| line.trim_end().to_string() + "\n")
+        .collect()
+}
+
+#[test]
+fn test_golden() {
+    let db = load_data();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut queries = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sql"))
+        .collect::<Vec<_>>();
+    queries.sort();
+    assert!(!queries.is_empty());
+
+    let mut failures = vec![];
+    for path in &queries {
+        let sql = fs::read_to_string(path).unwrap();
+        let actual = normalize(&run_query(&db, sql.trim()));
+        let out_path = path.with_extension("out");
+        if update {
+            fs::write(&out_path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&out_path)
+            .unwrap_or_else(|_| panic!("{out_path:?} not found, run with UPDATE_GOLDEN=1"));
+        if normalize(&expected) != actual {
+            failures.push(format!(
+                "{}\n--- expected\n{expected}--- actual\n{actual}",
+                path.display()
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "Golden tests failed:\n{}",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1 @@
+SELECT * FROM authors WHERE author_id NOT IN (SELECT author FROM books)
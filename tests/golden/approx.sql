@@ -0,0 +1 @@
+SELECT author, approx_count_distinct(title), approx_quantile(book_id, 0.5), approx_quantile(book_id, 1) FROM books GROUP BY author
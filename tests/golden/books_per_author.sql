@@ -0,0 +1 @@
+SELECT authors.name, count(book_id) FROM authors LEFT JOIN books ON author_id = author GROUP BY authors.name ORDER BY 2 DESC, 1
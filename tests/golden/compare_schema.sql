@@ -0,0 +1 @@
+COMPARE SCHEMA authors, books
@@ -0,0 +1 @@
+SELECT count(DISTINCT author), count(author) FROM books
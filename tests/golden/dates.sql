@@ -0,0 +1 @@
+SELECT date_add(DATE '2024-01-31', 1, 'month'), date_part('dow', DATE '2024-01-31'), date_trunc('year', TIMESTAMP '2024-05-17 12:34:56'), DATE '2024-01-31' < TIMESTAMP '2024-01-31 00:00:01' FROM authors LIMIT 1
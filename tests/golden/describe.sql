@@ -0,0 +1 @@
+SHOW COLUMNS FROM phonebook
@@ -0,0 +1 @@
+SELECT now(), current_date(), current_time(), date_add(now(), 1, 'day') FROM authors LIMIT 1
@@ -0,0 +1 @@
+EXPLAIN SELECT name, count(*) FROM authors LEFT JOIN books ON author_id = author WHERE title <> '' GROUP BY name ORDER BY 2 DESC LIMIT 2
@@ -0,0 +1 @@
+EXPLAIN SELECT book_id, title, name FROM books INNER JOIN characters ON book_id = book
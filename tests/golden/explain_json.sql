@@ -0,0 +1 @@
+EXPLAIN (FORMAT JSON) SELECT name, count(*) FROM authors INNER JOIN books ON author_id = author GROUP BY name
@@ -0,0 +1 @@
+EXPLAIN SELECT author_id, title, name FROM authors INNER JOIN books ON author_id = author
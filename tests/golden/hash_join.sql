@@ -0,0 +1 @@
+SELECT book_id, title, name FROM books INNER JOIN characters ON book_id = book
@@ -0,0 +1 @@
+SELECT * FROM authors LEFT JOIN books ON author_id = author
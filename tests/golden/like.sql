@@ -0,0 +1 @@
+SELECT name, name LIKE '%\_%' ESCAPE '\' FROM phonebook WHERE name LIKE 'A%' OR name NOT LIKE '%n%'
@@ -0,0 +1 @@
+SELECT name, title FROM authors LEFT JOIN books ON author_id = author ORDER BY title NULLS LAST
@@ -0,0 +1 @@
+SELECT id, round(id * 1.08, 1), mod(id, 3), abs(100 - id), ceil(id / 3), floor(id / 3) FROM phonebook
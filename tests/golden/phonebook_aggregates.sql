@@ -0,0 +1 @@
+SELECT count(*), min(id), max(id), avg(id) FROM phonebook
@@ -0,0 +1 @@
+SELECT DISTINCT name, phone FROM phonebook ORDER BY name DESC LIMIT 3 OFFSET 1
@@ -0,0 +1 @@
+SELECT * EXCLUDE (phone) FROM phonebook WHERE name = 'Alan Turing' OR id BETWEEN '103' AND '104'
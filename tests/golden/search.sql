@@ -0,0 +1 @@
+SEARCH '102'
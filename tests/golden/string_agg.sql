@@ -0,0 +1 @@
+SELECT author, string_agg(title, '; '), group_concat(book_id) FROM books GROUP BY author
@@ -0,0 +1 @@
+SELECT substr(name, 1, 5), lpad(author_id, 3, '0'), instr(name, 'A'), replace(name, '.', ''), trim(name, 'IAsk') FROM authors
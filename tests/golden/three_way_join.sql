@@ -0,0 +1 @@
+SELECT title, characters.name FROM authors INNER JOIN books ON author_id = books.author INNER JOIN characters ON book = book_id WHERE authors.name = 'Robert A. Heinlein'
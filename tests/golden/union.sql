@@ -0,0 +1 @@
+SELECT name FROM authors UNION SELECT name FROM characters
@@ -1,9 +1,16 @@
-import init, { run_query, list_table } from "./pkg/bogosql_wasm.js";
+import init, { run_query_with, query_csv, list_table, load_table, materialize, QueryOptions } from "./pkg/bogosql_wasm.js";
+import mermaid from "https://cdn.jsdelivr.net/npm/mermaid@11/dist/mermaid.esm.min.mjs";
 
-init().then(() => {
+mermaid.initialize({ startOnLoad: false });
+
+init().then(showTables);
+
+function showTables() {
+    const examples = document.getElementById("examples");
+    examples.innerHTML = "";
     const tables = list_table();
+    tables.sort();
     for (let table of tables) {
-        const examples = document.getElementById("examples");
         const link = document.createElement("a");
         link.href = "#";
         link.innerHTML = table;
@@ -14,21 +21,99 @@ init().then(() => {
         examples.appendChild(link);
         examples.appendChild(document.createTextNode(" "));
     }
-});
+}
+
+// The table is named after the file name without the extension, like the CSV files in the data directory.
+// Tab or semicolon separated files are loaded as well.
+async function onLoadCsv(event) {
+    for (const file of event.target.files) {
+        const name = file.name.replace(/\.[^.]*$/, "");
+        try {
+            load_table(name, await file.text());
+        }
+        catch(e) {
+            const resultElem = document.getElementById("result");
+            resultElem.value = `ERROR: ${e}`;
+        }
+    }
+    showTables();
+}
+
+function queryOptions() {
+    const deterministic = document.getElementById("deterministic").checked;
+    // A cross join of the larger tables would freeze the tab
+    return new QueryOptions()
+        .deterministic(deterministic)
+        .max_combinations(10_000_000)
+        .time_budget(5000);
+}
 
 function onQuery() {
     const query = document.getElementById("query");
     const queryValue = query.value;
     try {
-        const res = run_query(queryValue);
+        const res = run_query_with(queryValue, queryOptions());
         const resultElem = document.getElementById("result");
         resultElem.value = res;
+        renderDiagram(res);
+    }
+    catch(e) {
+        // The message starts with "Error: " and shows the query with a caret at a parse error
+        const resultElem = document.getElementById("result");
+        resultElem.value = `${e}`;
+    }
+}
+
+// The CSV is made by the wasm module with all the rows, however many the result view shows
+function onDownloadCsv() {
+    const query = document.getElementById("query");
+    try {
+        const res = query_csv(query.value, undefined, queryOptions());
+        const url = URL.createObjectURL(new Blob([res.csv()], { type: "text/csv" }));
+        const link = document.createElement("a");
+        link.href = url;
+        link.download = "results.csv";
+        link.click();
+        URL.revokeObjectURL(url);
     }
     catch(e) {
         const resultElem = document.getElementById("result");
-        resultElem.value = `ERROR: ${e}`;
+        resultElem.value = `${e}`;
+    }
+}
+
+// The results become a table that later queries can use, like a step of an analysis
+function onMaterialize() {
+    const query = document.getElementById("query");
+    const name = document.getElementById("tableName").value.trim();
+    const resultElem = document.getElementById("result");
+    if (!name) {
+        resultElem.value = "ERROR: Enter a name for the table";
+        return;
+    }
+    try {
+        materialize(name, query.value);
+        resultElem.value = `Saved the results as table ${name}`;
+        showTables();
+    }
+    catch(e) {
+        resultElem.value = `${e}`;
+    }
+}
+
+async function renderDiagram(res) {
+    const diagram = document.getElementById("diagram");
+    // Only the output of EXPLAIN (FORMAT MERMAID) is a diagram
+    if (!res.startsWith("graph TD")) {
+        diagram.innerHTML = "";
+        return;
     }
+    const { svg } = await mermaid.render("plan", res);
+    diagram.innerHTML = svg;
 }
 
 const button = document.getElementById("runQuery");
 button.addEventListener("click", onQuery);
+document.getElementById("downloadCsv").addEventListener("click", onDownloadCsv);
+document.getElementById("materialize").addEventListener("click", onMaterialize);
+document.getElementById("csvFile").addEventListener("change", onLoadCsv);
@@ -1,8 +1,19 @@
-use std::{error::Error, sync::LazyLock};
+use std::{
+    cell::RefCell,
+    error::Error,
+    sync::{LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::Duration,
+};
 
 use wasm_bindgen::prelude::*;
 
-use bogosql::{Database, format_select, make_table, statement};
+use bogosql::{
+    AggregateNulls, BufferOutput, Clock, CsvOutput, Database, ExecObserver, ExecOptions, ExecTrace,
+    ExplainFormat, Observer, PreparedStatement, RowSchema, SavedQueries, Session, Statement,
+    StatementOutput, Table, exec_describe, exec_select_iter_with, exec_select_traced,
+    format_error_json, format_error_text, format_explain, make_table_with, parse_statement,
+    parse_utc_offset, write_csv_row,
+};
 
 #[wasm_bindgen]
 extern "C" {
@@ -12,39 +23,616 @@ extern "C" {
 
 #[wasm_bindgen]
 pub fn run_query(src: &str) -> Result<String, JsValue> {
-    Ok(run_query_impl(src).map_err(|e| JsValue::from_str(&e.to_string()))?)
+    run_query_impl(src, &ExecOptions::DEFAULT)
+        .map_err(|e| JsValue::from_str(&format_error_text(&*e, src)))
 }
 
-static DB: LazyLock<Database> = LazyLock::new(|| {
+/// Same as `run_query`, but with the options like `--decimal` of the CLI
+#[wasm_bindgen]
+pub fn run_query_with(src: &str, options: &QueryOptions) -> Result<String, JsValue> {
+    let (options, _progress) = options.begin();
+    run_query_impl(src, &options).map_err(|e| JsValue::from_str(&format_error_text(&*e, src)))
+}
+
+/// Same as `run_query`, but returns the header and the rows instead of a formatted table, for the web UI to
+/// render them as it likes. The plan of `EXPLAIN` is a column `plan` with a row for each line.
+#[wasm_bindgen]
+pub fn run_query_rows(src: &str) -> Result<Rows, JsValue> {
+    run_query_rows_impl(src).map_err(|e| JsValue::from_str(&format_error_text(&*e, src)))
+}
+
+fn run_query_rows_impl(src: &str) -> Result<Rows, Box<dyn Error>> {
+    let db = db();
+    let mut rows = match session(&db).execute(src)? {
+        StatementOutput::Rows(rows) => rows,
+        StatementOutput::Plan(plan) => std::iter::once("plan")
+            .chain(plan.lines())
+            .map(|line| vec![line.to_string()])
+            .collect(),
+    };
+    let header = if rows.is_empty() {
+        vec![]
+    } else {
+        rows.remove(0)
+    };
+    Ok(Rows { header, rows })
+}
+
+/// Runs the query and writes the results as CSV with the header in the first line, stopping before the row
+/// that would make it longer than `max_bytes` if given. The UI can show the beginning of a large result, and
+/// offer to download all the rows by calling it again without the cap.
+#[wasm_bindgen]
+pub fn query_csv(
+    src: &str,
+    max_bytes: Option<usize>,
+    options: &QueryOptions,
+) -> Result<CsvResult, JsValue> {
+    let (options, _progress) = options.begin();
+    query_csv_impl(src, max_bytes.unwrap_or(usize::MAX), &options)
+        .map_err(|e| JsValue::from_str(&format_error_text(&*e, src)))
+}
+
+fn query_csv_impl(
+    src: &str,
+    max_bytes: usize,
+    options: &ExecOptions,
+) -> Result<CsvResult, Box<dyn Error>> {
+    let db = db();
+    let mut res = CsvResult {
+        csv: vec![],
+        truncated: false,
+    };
+    let mut line = vec![];
+    // Returns false if the row does not fit, but the header is always written
+    let mut write = |row: &[String]| -> std::io::Result<bool> {
+        line.clear();
+        write_csv_row(&mut line, row)?;
+        if !res.csv.is_empty() && max_bytes < res.csv.len() + line.len() {
+            res.truncated = true;
+            return Ok(false);
+        }
+        res.csv.extend_from_slice(&line);
+        Ok(true)
+    };
+    // A SELECT yields the rows one by one, so the rows after the cap are not even computed unless sorted
+    if let Statement::Select(query) = parse_statement(src)? {
+        let rows = exec_select_iter_with(&db, &query, options)?;
+        write(rows.header())?;
+        for row in rows {
+            if !write(&row?)? {
+                break;
+            }
+        }
+        return Ok(res);
+    }
+    let mut session = session(&db);
+    session.options = options.clone();
+    let rows = match session.execute(src)? {
+        StatementOutput::Rows(rows) => rows,
+        StatementOutput::Plan(plan) => std::iter::once("plan")
+            .chain(plan.lines())
+            .map(|line| vec![line.to_string()])
+            .collect(),
+    };
+    for row in &rows {
+        if !write(row)? {
+            break;
+        }
+    }
+    Ok(res)
+}
+
+/// The results of a query in CSV returned by `query_csv`
+#[wasm_bindgen]
+pub struct CsvResult {
+    csv: Vec<u8>,
+    truncated: bool,
+}
+
+#[wasm_bindgen]
+impl CsvResult {
+    pub fn csv(&self) -> String {
+        String::from_utf8_lossy(&self.csv).into_owned()
+    }
+
+    /// Whether rows were left out to keep the CSV within the cap
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+/// The columns of the table with their positions and types, as in `DESCRIBE table`, e.g. for autocompletion
+#[wasm_bindgen]
+pub fn table_schema(name: &str) -> Result<Rows, JsValue> {
+    let mut out = BufferOutput::default();
+    exec_describe(&mut out, &db(), name).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let mut rows = out.0;
+    let header = rows.remove(0);
+    Ok(Rows { header, rows })
+}
+
+/// The result set of a query returned by `run_query_rows`
+#[wasm_bindgen]
+pub struct Rows {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+#[wasm_bindgen]
+impl Rows {
+    /// The column names
+    pub fn header(&self) -> Vec<String> {
+        self.header.clone()
+    }
+
+    /// An array of the rows, each of which is an array of the cells. NULLs (empty cells) are null.
+    pub fn rows(&self) -> js_sys::Array {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| {
+                        if cell.is_empty() {
+                            JsValue::NULL
+                        } else {
+                            JsValue::from_str(cell)
+                        }
+                    })
+                    .collect::<js_sys::Array>()
+            })
+            .collect()
+    }
+}
+
+/// Options of running queries, built by chaining the setters, e.g. `new QueryOptions().decimal(true)`
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct QueryOptions(ExecOptions, Option<js_sys::Function>);
+
+#[wasm_bindgen]
+impl QueryOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat NULLs as 0 in aggregate functions and yield 0 over no values, like a spreadsheet
+    pub fn spreadsheet_nulls(self, spreadsheet_nulls: bool) -> Self {
+        Self(
+            self.0.with_aggregate_nulls(if spreadsheet_nulls {
+                AggregateNulls::Spreadsheet
+            } else {
+                AggregateNulls::Standard
+            }),
+            self.1,
+        )
+    }
+
+    /// Maximum number of decimal places in the results of aggregate functions, or undefined for the shortest
+    /// representation
+    pub fn precision(self, precision: Option<usize>) -> Self {
+        Self(self.0.with_float_precision(precision), self.1)
+    }
+
+    pub fn decimal(self, decimal: bool) -> Self {
+        Self(self.0.with_decimal(decimal), self.1)
+    }
+
+    /// Resolve column names ignoring case if none matches exactly, which is on by default
+    pub fn case_insensitive(self, case_insensitive: bool) -> Self {
+        Self(self.0.with_case_insensitive(case_insensitive), self.1)
+    }
+
+    /// Time zone as UTC or an offset like `+09:00`
+    pub fn timezone(self, timezone: &str) -> Result<QueryOptions, JsValue> {
+        let utc_offset = parse_utc_offset(timezone).map_err(|e| JsValue::from_str(&e))?;
+        Ok(Self(self.0.with_utc_offset(utc_offset), self.1))
+    }
+
+    /// Sort the rows of SELECT without ORDER BY and fix `now()` at 2000-01-01 00:00:00 UTC, so that the same
+    /// query always shows the same result
+    pub fn deterministic(self, deterministic: bool) -> Self {
+        Self(self.0.with_deterministic(deterministic), self.1)
+    }
+
+    /// Estimated memory in bytes for the groups of GROUP BY, beyond which the query fails instead of growing,
+    /// or undefined for no limit
+    pub fn memory_budget(self, memory_budget: Option<usize>) -> Self {
+        Self(self.0.with_memory_budget(memory_budget), self.1)
+    }
+
+    /// Add columns `_row_<table>` with the index of the row of each joined table that an output row is made of,
+    /// to trace an unexpected row of a join back to the source rows
+    pub fn provenance(self, provenance: bool) -> Self {
+        Self(self.0.with_provenance(provenance), self.1)
+    }
+
+    /// Size in bytes beyond which the cells of the results are cut and marked with the number of the bytes cut,
+    /// so that a column of large blobs does not flood the table, or undefined for no limit
+    pub fn max_cell_size(self, max_cell_size: Option<usize>) -> Self {
+        Self(self.0.with_max_cell_size(max_cell_size), self.1)
+    }
+
+    /// Fail a query that outputs more rows than this, or undefined for no limit
+    pub fn max_rows(self, max_rows: Option<usize>) -> Self {
+        Self(self.0.with_max_rows(max_rows), self.1)
+    }
+
+    /// Fail a query that evaluates more combinations of the rows of the joined tables than this, so that a
+    /// cross join of large tables does not freeze the tab, or undefined for no limit
+    pub fn max_combinations(self, max_combinations: Option<usize>) -> Self {
+        Self(self.0.with_max_combinations(max_combinations), self.1)
+    }
+
+    /// Call the function with the number of the combinations of rows scanned every 1024 of them while a query
+    /// runs with these options. The page is not redrawn until the query returns, so run the module in a Web
+    /// Worker and post the progress to the page from the function to show it.
+    pub fn on_progress(self, callback: js_sys::Function) -> Self {
+        Self(self.0, Some(callback))
+    }
+
+    /// Fail a query that runs longer than the milliseconds measured by `Date.now()`, or undefined for no limit
+    pub fn time_budget(self, millis: Option<f64>) -> Self {
+        let budget = millis.map(|millis| Duration::from_secs_f64(millis.max(0.) / 1000.));
+        Self(
+            self.0
+                .with_time_budget(budget)
+                .with_clock(Some(Clock(date_now))),
+            self.1,
+        )
+    }
+}
+
+impl QueryOptions {
+    /// The options of the library to run a query with, and the scope of the function given to `on_progress`,
+    /// which is told the progress until the scope is dropped after the query
+    fn begin(&self) -> (ExecOptions, ProgressScope) {
+        let options = match self.1 {
+            Some(_) => self
+                .0
+                .clone()
+                .with_observer(Some(Observer::new(JsObserver))),
+            None => self.0.clone(),
+        };
+        (options, ProgressScope::new(self.1.clone()))
+    }
+}
+
+thread_local! {
+    /// The function given to [`QueryOptions::on_progress`] of the query running, set only while it runs since an
+    /// observer must be `Send` for the threads of a parallel scan, which a JavaScript function is not
+    static PROGRESS_CALLBACK: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+}
+
+/// Sets [`PROGRESS_CALLBACK`] for a query, and restores the function of the query running before, if any, when
+/// dropped
+struct ProgressScope(Option<js_sys::Function>);
+
+impl ProgressScope {
+    fn new(callback: Option<js_sys::Function>) -> Self {
+        Self(PROGRESS_CALLBACK.replace(callback))
+    }
+}
+
+impl Drop for ProgressScope {
+    fn drop(&mut self) {
+        PROGRESS_CALLBACK.set(self.0.take());
+    }
+}
+
+/// Calls the function in [`PROGRESS_CALLBACK`] with the number of the combinations of rows scanned
+struct JsObserver;
+
+impl ExecObserver for JsObserver {
+    fn on_rows_scanned(&mut self, rows: usize) {
+        PROGRESS_CALLBACK.with_borrow(|progress| {
+            if let Some(callback) = progress {
+                // The progress is only informative, so an exception in the callback does not stop the query
+                let _ = callback.call1(&JsValue::NULL, &JsValue::from(rows));
+            }
+        });
+    }
+}
+
+/// The time since the epoch by `Date.now()`, since `std::time::Instant` is not available in a browser
+fn date_now() -> Duration {
+    Duration::from_secs_f64(js_sys::Date::now() / 1000.)
+}
+
+/// Same as `run_query`, but the error is a JSON object with the kind, the message, the span in the query and
+/// the suggestions, for the editor to underline the offending token
+#[wasm_bindgen]
+pub fn run_query_json_error(src: &str) -> Result<String, JsValue> {
+    run_query_impl(src, &ExecOptions::DEFAULT)
+        .map_err(|e| JsValue::from_str(&format_error_json(&*e, src)))
+}
+
+/// The sample tables unless built without the `sample-data` feature, and the tables loaded by the user with
+/// `load_table` or `init_with_tables`
+static DB: LazyLock<RwLock<Database>> = LazyLock::new(|| {
+    #[allow(unused_mut)]
     let mut db = Database::new();
 
+    #[cfg(feature = "sample-data")]
     for (file, csv) in [
         ("authors", include_str!("../../data/authors.csv")),
         ("books", include_str!("../../data/books.csv")),
         ("characters", include_str!("../../data/characters.csv")),
         ("phonebook", include_str!("../../data/phonebook.csv")),
     ] {
-        let table = make_table(file, csv).unwrap();
+        let table = bogosql::make_table(file, csv).unwrap();
 
         db.insert(file.to_string(), table);
     }
 
-    db
+    RwLock::new(db)
 });
 
-fn run_query_impl(src: &str) -> Result<String, Box<dyn Error>> {
-    let db = &*DB;
+// A panic while holding the lock cannot leave the database half-updated, since tables are inserted or removed
+// as a whole, so a poisoned lock is still usable.
+fn db() -> RwLockReadGuard<'static, Database> {
+    DB.read().unwrap_or_else(|e| e.into_inner())
+}
 
-    let (_, bogosql::Statement::Select(query)) = statement(src).map_err(|e| e.to_string())?;
+fn db_mut() -> RwLockWriteGuard<'static, Database> {
+    DB.write().unwrap_or_else(|e| e.into_inner())
+}
 
-    let mut buf = vec![0u8; 0];
-    format_select(&mut buf, &db, &query)?;
-    let res = String::from_utf8(buf)?;
+/// The queries saved by `save_query`, which outlive replacing the tables by `init_with_tables`
+static SAVED: LazyLock<RwLock<SavedQueries>> = LazyLock::new(Default::default);
 
-    Ok(res)
+fn saved() -> RwLockReadGuard<'static, SavedQueries> {
+    SAVED.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn saved_mut() -> RwLockWriteGuard<'static, SavedQueries> {
+    SAVED.write().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A session on the database with the saved queries, so that `SHOW QUERIES` lists them
+fn session(db: &Database) -> Session<'_> {
+    let mut session = Session::new(db);
+    session.saved = saved().clone();
+    session
+}
+
+fn run_query_impl(src: &str, options: &ExecOptions) -> Result<String, Box<dyn Error>> {
+    let db = db();
+    let mut session = session(&db);
+    session.options = options.clone();
+    let mut buf = vec![];
+    session.execute(src)?.format(&mut buf)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// A query parsed once and run with different values bound to its placeholders
+#[wasm_bindgen]
+pub struct PreparedQuery(PreparedStatement);
+
+#[wasm_bindgen]
+impl PreparedQuery {
+    #[wasm_bindgen(constructor)]
+    pub fn new(src: &str) -> Result<PreparedQuery, JsValue> {
+        Ok(Self(
+            PreparedStatement::new(src).map_err(|e| JsValue::from_str(&e.to_string()))?,
+        ))
+    }
+
+    /// Runs the query with the values bound to the positional placeholders in order
+    pub fn run(&mut self, values: Vec<String>) -> Result<String, JsValue> {
+        self.run_impl(&values, &ExecOptions::DEFAULT)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Same as `run`, but with the options
+    pub fn run_with(
+        &mut self,
+        values: Vec<String>,
+        options: &QueryOptions,
+    ) -> Result<String, JsValue> {
+        let (options, _progress) = options.begin();
+        self.run_impl(&values, &options)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    fn run_impl(
+        &mut self,
+        values: &[String],
+        options: &ExecOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        let output = self
+            .0
+            .clear_bindings()
+            .bind(values)
+            .execute_with(&db(), options)?;
+        let mut buf = vec![0u8; 0];
+        output.format(&mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+}
+
+/// Returns the execution trace of a SELECT query, one event per line
+#[wasm_bindgen]
+pub fn trace_query(src: &str, max_events: usize) -> Result<Vec<String>, JsValue> {
+    trace_query_impl(src, max_events).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+fn trace_query_impl(src: &str, max_events: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    let Statement::Select(query) = parse_statement(src)? else {
+        return Err("Only SELECT can be traced".into());
+    };
+
+    let mut trace = ExecTrace::new(max_events);
+    exec_select_traced(&mut CsvOutput::default(), &db(), &query, &mut trace)?;
+
+    Ok(trace.events.iter().map(|ev| ev.to_string()).collect())
+}
+
+/// Returns the plan of a SELECT query, or of the query of an EXPLAIN statement, as a tree of objects with the
+/// `label` and the `children` of each node, for the web UI to show it as it likes, e.g. as an expandable tree
+#[wasm_bindgen]
+pub fn explain_query(src: &str) -> Result<JsValue, JsValue> {
+    let json = explain_query_impl(src).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    js_sys::JSON::parse(&json)
+}
+
+fn explain_query_impl(src: &str) -> Result<String, Box<dyn Error>> {
+    let query = match parse_statement(src)? {
+        Statement::Select(query) => query,
+        Statement::Explain(explain) => explain.stmt,
+        _ => return Err("Only SELECT has a plan".into()),
+    };
+
+    let mut buf = vec![];
+    format_explain(
+        &mut buf,
+        &db(),
+        &query,
+        &ExecOptions::DEFAULT,
+        ExplainFormat::Json,
+    )?;
+    Ok(String::from_utf8(buf)?)
 }
 
 #[wasm_bindgen]
 pub fn list_table() -> Vec<String> {
-    DB.iter().map(|(k, _)| k.clone()).collect()
+    db().names().cloned().collect()
+}
+
+/// Parses the CSV with the header in the first line and adds it as a table, replacing the table with the
+/// same name if any. The delimiter like tab or `;` is guessed from the header.
+#[wasm_bindgen]
+pub fn load_table(name: &str, csv: &str) -> Result<(), JsValue> {
+    let table = make_table_with(name, csv, None).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    db_mut().insert(name.to_string(), table);
+    Ok(())
+}
+
+/// Replaces all the tables, including the sample tables, with the tables in the `Map` from the names to the
+/// CSVs, e.g. `init_with_tables(new Map([["books", "id,title\n1,Dune\n"]]))`. The delimiter of each CSV is
+/// guessed from the header. If a CSV cannot be parsed, the tables are left as they were.
+#[wasm_bindgen]
+pub fn init_with_tables(tables: &js_sys::Map) -> Result<(), JsValue> {
+    let mut db = Database::new();
+    let mut res = Ok(());
+    tables.for_each(&mut |csv, name| {
+        if res.is_err() {
+            return;
+        }
+        let (Some(name), Some(csv)) = (name.as_string(), csv.as_string()) else {
+            res = Err(JsValue::from_str(
+                "Tables must be a Map from names to CSV strings",
+            ));
+            return;
+        };
+        match make_table_with(&name, &csv, None) {
+            Ok(table) => {
+                db.insert(name, table);
+            }
+            Err(e) => res = Err(JsValue::from_str(&format!("Table {name}: {e}"))),
+        }
+    });
+    res?;
+    *db_mut() = db;
+    Ok(())
+}
+
+/// Runs the query and adds its results as a table, replacing the table with the same name if any, so that
+/// the later steps of an analysis can query the results without running the query again. The columns are
+/// named after the header of the results, and their types are inferred from the cells as in `load_table`.
+#[wasm_bindgen]
+pub fn materialize(name: &str, src: &str) -> Result<(), JsValue> {
+    let table =
+        materialize_impl(name, src).map_err(|e| JsValue::from_str(&format_error_text(&*e, src)))?;
+    db_mut().insert(name.to_string(), table);
+    Ok(())
+}
+
+fn materialize_impl(name: &str, src: &str) -> Result<Table, Box<dyn Error>> {
+    let db = db();
+    let StatementOutput::Rows(rows) = session(&db).execute(src)? else {
+        return Err("EXPLAIN has a plan rather than rows to make a table of".into());
+    };
+    let mut rows = rows.into_iter();
+    let schema = rows
+        .next()
+        .unwrap_or_default()
+        .into_iter()
+        .map(RowSchema::new)
+        .collect();
+    Ok(Table::new(
+        name.to_string(),
+        schema,
+        rows.flatten().collect(),
+    ))
+}
+
+/// Removes the table, which can be one of the sample tables
+#[wasm_bindgen]
+pub fn drop_table(name: &str) -> Result<(), JsValue> {
+    db_mut()
+        .remove(name)
+        .map(|_| ())
+        .ok_or_else(|| JsValue::from_str(&format!("Table {name} not found")))
+}
+
+/// Saves the query under the name for a "saved reports" list, replacing the query of the same name if any.
+/// The query can have placeholders like `:id`, bound by `run_saved`.
+#[wasm_bindgen]
+pub fn save_query(name: &str, src: &str) -> Result<(), JsValue> {
+    saved_mut()
+        .save(name, src)
+        .map_err(|e| JsValue::from_str(&format_error_text(&e, src)))
+}
+
+/// Removes the saved query
+#[wasm_bindgen]
+pub fn remove_saved_query(name: &str) -> Result<(), JsValue> {
+    saved_mut()
+        .remove(name)
+        .map(|_| ())
+        .ok_or_else(|| JsValue::from_str(&format!("No query saved as {name}")))
+}
+
+/// The names of the saved queries in order
+#[wasm_bindgen]
+pub fn list_saved_queries() -> Vec<String> {
+    saved().names().map(|name| name.to_string()).collect()
+}
+
+/// Runs the saved query formatted as a table, with the values in the `Map` from the placeholder names like
+/// `id` for `:id` or `1` for `?1` to the strings, e.g. `run_saved("by_author", new Map([["id", "2"]]), options)`
+#[wasm_bindgen]
+pub fn run_saved(
+    name: &str,
+    params: &js_sys::Map,
+    options: &QueryOptions,
+) -> Result<String, JsValue> {
+    let mut values = bogosql::Params::new();
+    let mut res = Ok(());
+    params.for_each(
+        &mut |value, key| match (key.as_string(), value.as_string()) {
+            (Some(key), Some(value)) => {
+                values.insert(key, value);
+            }
+            _ => {
+                res = Err(JsValue::from_str(
+                    "Parameters must be a Map from names to strings",
+                ))
+            }
+        },
+    );
+    res?;
+    let db = db();
+    let mut session = session(&db);
+    let (exec_options, _progress) = options.begin();
+    session.options = exec_options;
+    let output = session
+        .run_saved(name, &values)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let mut buf = vec![];
+    output
+        .format(&mut buf)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    String::from_utf8(buf).map_err(|e| JsValue::from_str(&e.to_string()))
 }